@@ -1,5 +1,5 @@
 use unia::{
-    model::{Message, Part},
+    model::{Extensions, Message, Part, TextAnnotations},
     providers::{openai::OpenAI, Provider},
     Client,
 };
@@ -21,12 +21,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Step 2: Construct the Message
     // ============================================================================================
     // Messages in unia are structured to support multimodal content (text, images, files).
-    // A `Message` is an enum representing the role (User, Assistant, System, Tool).
-    // Each message contains a vector of `Part`s.
+    // A `Message` is an enum representing the role (User or Assistant).
+    // Each message contains a vector of `Part`s. The system prompt is not a message but
+    // a model option (`ModelOptions::with_system`), and tool results are sent as
+    // `Part::FunctionResponse` parts of a User message.
     //
     // Here, we create a simple User message with a single Text part.
+    // `Message::user("...")` is a shorthand for exactly this.
     let messages = vec![Message::User(vec![Part::Text {
-        content: "Explain quantum computing in one sentence.".to_string(),
+        content: "Explain quantum computing in one sentence.".into(),
+        signature: None,
+        extensions: Extensions::new(),
+        annotations: TextAnnotations::default(),
         finished: true, // `finished` indicates if the part is complete (relevant for streaming)
     }])];
 
@@ -1,7 +1,7 @@
 use futures::StreamExt;
 use std::io::{self, Write};
 use unia::{
-    model::{Message, Part},
+    model::{Extensions, Message, Part, TextAnnotations},
     providers::{openai::OpenAI, Provider},
     StreamingClient,
 };
@@ -19,7 +19,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = OpenAI::create(api_key, "gpt-5".to_string());
 
     let messages = vec![Message::User(vec![Part::Text {
-        content: "Write a haiku about Rust programming.".to_string(),
+        content: "Write a haiku about Rust programming.".into(),
+        signature: None,
+        extensions: Extensions::new(),
+        annotations: TextAnnotations::default(),
         finished: true,
     }])];
 
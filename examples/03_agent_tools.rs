@@ -5,7 +5,7 @@ use rmcp::{
     schemars, tool, tool_handler, tool_router, ServerHandler,
 };
 use serde::Deserialize;
-use unia::model::{Message, Part};
+use unia::model::{Extensions, Message, Part, TextAnnotations};
 use unia::providers::{OpenAI, Provider};
 use unia::Agent;
 
@@ -34,6 +34,12 @@ pub struct WeatherTools {
     tool_router: ToolRouter<Self>,
 }
 
+impl Default for WeatherTools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[tool_router]
 impl WeatherTools {
     pub fn new() -> Self {
@@ -126,7 +132,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 5. Return the final response.
     let response = agent
         .chat(vec![Message::User(vec![Part::Text {
-            content: "What is the weather in Tokyo in celsius?".to_string(),
+            content: "What is the weather in Tokyo in celsius?".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
             finished: true,
         }])])
         .await?;
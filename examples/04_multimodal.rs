@@ -1,6 +1,5 @@
-use base64::{engine::general_purpose, Engine as _};
 use unia::{
-    model::{MediaType, Message, Part},
+    model::{Extensions, MediaType, Message, Part, TextAnnotations},
     providers::{openai::OpenAI, Provider},
     Client,
 };
@@ -19,14 +18,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Step 2: Fetch Image Data
     // ============================================================================================
     // Most LLM APIs require images to be sent as base64-encoded strings or via a URL.
-    // unia handles the formatting, but you need to provide the raw data or the URI.
+    // unia handles the encoding, so you only need to provide the raw bytes or the URI.
     //
-    // Here, we download an image from the web and encode it as base64.
+    // Here, we download an image from the web and keep its raw bytes.
     let image_url = "https://picsum.photos/id/13/2500/1667";
     println!("Fetching image from {}...", image_url);
 
     let image_bytes = reqwest::get(image_url).await?.bytes().await?;
-    let base64_image = general_purpose::STANDARD.encode(&image_bytes);
 
     // ============================================================================================
     // Step 3: Create a Multimodal Message
@@ -34,17 +32,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // A `Message` can contain multiple `Part`s. To send an image, we use `Part::Media`.
     //
     // - `media_type`: The type of media (Image, Audio, Video).
-    // - `data`: The base64 encoded data string.
+    // - `data`: The raw bytes of the media, base64 encoded only when the request is sent.
     // - `mime_type`: The MIME type of the file (e.g., "image/jpeg", "image/png").
     // - `uri`: Optional URI to tell the model where the media is located or came from.
     let message = Message::User(vec![
         Part::Text {
-            content: "What is in this image?".to_string(),
+            content: "What is in this image?".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
             finished: true,
         },
         Part::Media {
             media_type: MediaType::Image,
-            data: base64_image,
+            data: image_bytes,
             mime_type: "image/jpeg".to_string(),
             uri: Some(image_url.to_string()), // We provide the URI for context
             finished: true,
@@ -1,13 +1,78 @@
 //! Agent struct for automatic tool execution with LLM providers.
 
+use crate::audit::AuditLogger;
 use crate::client::{Client, ClientError};
+use crate::compress::{self, Compressor};
+use crate::conversation::Conversation;
+use crate::history::push_merged;
+use crate::instructions::Instructions;
 use crate::model::{FinishReason, Message, Part, Response, Usage};
-use serde_json::json;
+use crate::scratchpad::Scratchpad;
+use crate::structured::parse_partial;
+use crate::tools::{ToolConfig, ToolError, ToolErrorKind, ToolRetryPolicy};
+use crate::trace::{AgentDecision, TraceEvent, TraceRecorder};
+use crate::validate::{
+    correction, validate_all, SchemaValidator, Validator, DEFAULT_VALIDATION_ATTEMPTS,
+};
+use async_trait::async_trait;
+use rmcp::model::Tool;
+use serde_json::Value;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Semaphore};
 use tracing::{debug, info, warn};
 
 use crate::mcp::MCPServer;
 
+/// Callbacks run at the boundaries of the iterations of an [`Agent`]'s tool loop.
+///
+/// Iterations are numbered from 0. Returning an error from a hook ends the loop with
+/// that error, which lets applications enforce their own policies.
+#[async_trait]
+pub trait IterationHooks: Send + Sync {
+    /// Called before each request with the messages about to be sent.
+    ///
+    /// Changes only affect this request, not the conversation, so context that goes
+    /// stale (e.g. the current time or fresh memory recalls) can be injected anew on
+    /// every iteration.
+    async fn on_iteration_start(
+        &self,
+        _iteration: usize,
+        _request: &mut Vec<Message>,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    /// Called once the response of an iteration has been received and its tool calls
+    /// executed, with the usage of all iterations so far.
+    async fn on_iteration_end(&self, _iteration: usize, _usage: &Usage) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// Source of the tools offered to the model in each iteration of an [`Agent`]'s
+/// tool loop.
+///
+/// Implemented for async closures taking the conversation so far, so tools can be
+/// exposed depending on context (e.g. `deploy` only once `plan` succeeded).
+#[async_trait]
+pub trait ToolProvider: Send + Sync {
+    /// Tools to offer for the next request of the conversation `history`.
+    async fn tools(&self, history: Vec<Message>) -> Result<Vec<Tool>, ClientError>;
+}
+
+#[async_trait]
+impl<F, Fut> ToolProvider for F
+where
+    F: Fn(Vec<Message>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Tool>, ClientError>> + Send,
+{
+    async fn tools(&self, history: Vec<Message>) -> Result<Vec<Tool>, ClientError> {
+        self(history).await
+    }
+}
+
 /// Agent that automatically executes tools in a loop.
 ///
 /// Unlike the raw `Client`, an `Agent` handles tool execution automatically:
@@ -29,7 +94,7 @@ use crate::mcp::MCPServer;
 ///
 /// let messages = vec![
 ///     Message::User(vec![
-///         Part::Text { content: "What's the weather?".into(), finished: true }
+///         Part::Text { content: "What's the weather?".into(), signature: None, finished: true }
 ///     ])
 /// ];
 ///
@@ -39,6 +104,20 @@ pub struct Agent<C: Client> {
     client: C,
     max_iterations: usize,
     server: Option<Box<dyn MCPServer>>,
+    tool_retry: ToolRetryPolicy,
+    tool_configs: HashMap<String, ToolConfig>,
+    concurrency_classes: HashMap<String, Arc<Semaphore>>,
+    execute_repaired: bool,
+    compressor: Option<Box<dyn Compressor>>,
+    audit: Option<AuditLogger>,
+    validators: Vec<Arc<dyn Validator>>,
+    validation_attempts: usize,
+    hooks: Vec<Arc<dyn IterationHooks>>,
+    tool_provider: Option<Arc<dyn ToolProvider>>,
+    instructions: Instructions,
+    trace: Option<TraceRecorder>,
+    validate_arguments: bool,
+    scratchpad: Option<Scratchpad>,
 }
 
 impl<C: Client> Agent<C> {
@@ -53,6 +132,20 @@ impl<C: Client> Agent<C> {
             client,
             max_iterations: 10,
             server: None,
+            tool_retry: ToolRetryPolicy::default(),
+            tool_configs: HashMap::new(),
+            concurrency_classes: HashMap::new(),
+            execute_repaired: false,
+            compressor: None,
+            audit: None,
+            validators: Vec::new(),
+            validation_attempts: DEFAULT_VALIDATION_ATTEMPTS,
+            hooks: Vec::new(),
+            tool_provider: None,
+            instructions: Instructions::new(),
+            trace: None,
+            validate_arguments: true,
+            scratchpad: None,
         }
     }
 
@@ -68,6 +161,132 @@ impl<C: Client> Agent<C> {
         self
     }
 
+    /// Set when failed tool calls are retried before the failure is reported to the model.
+    pub fn with_tool_retry(mut self, policy: ToolRetryPolicy) -> Self {
+        self.tool_retry = policy;
+        self
+    }
+
+    /// Configure the execution of the tool called `name`.
+    pub fn with_tool_config(mut self, name: impl Into<String>, config: ToolConfig) -> Self {
+        self.tool_configs.insert(name.into(), config);
+        self
+    }
+
+    /// Allow at most `limit` concurrent calls to the tools of a concurrency class.
+    ///
+    /// The limit applies across all conversations running on this agent. Tools are
+    /// assigned to classes with [`ToolConfig::with_concurrency_class`]; classes
+    /// without a limit are unrestricted.
+    pub fn with_concurrency_limit(mut self, class: impl Into<String>, limit: usize) -> Self {
+        self.concurrency_classes
+            .insert(class.into(), Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// Execute calls whose arguments were truncated and repaired (see
+    /// [`Part::FunctionCall`]). Defaults to `false`: such calls are answered with an
+    /// invalid arguments error instead, so the model can issue them again.
+    pub fn with_execute_repaired(mut self, execute: bool) -> Self {
+        self.execute_repaired = execute;
+        self
+    }
+
+    /// Compress the history with `compressor` before each request.
+    ///
+    /// Only what is sent is compressed: the messages returned in responses are
+    /// unaffected. The estimated savings are logged at info level.
+    pub fn with_compressor<P: Compressor + 'static>(mut self, compressor: P) -> Self {
+        self.compressor = Some(Box::new(compressor));
+        self
+    }
+
+    /// Record tool calls and their results in `logger`.
+    ///
+    /// Requests and responses are recorded by wrapping the client in
+    /// [`Audited`](crate::audit::Audited).
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit = Some(logger);
+        self
+    }
+
+    /// Check the arguments of tool calls against the input schema of the tool before
+    /// executing them. Defaults to `true`.
+    ///
+    /// Invalid calls are answered with a [`ToolErrorKind::InvalidArguments`] error
+    /// describing the problem, so that the model can call the tool again.
+    pub fn with_validate_arguments(mut self, validate: bool) -> Self {
+        self.validate_arguments = validate;
+        self
+    }
+
+    /// Require final answers of [`Agent::chat`] to pass `validator`.
+    ///
+    /// Rejected answers are followed by a message describing the problem and the model
+    /// is asked again, up to the number of validation attempts. Streaming chats are not
+    /// validated.
+    pub fn with_validator<V: Validator + 'static>(mut self, validator: V) -> Self {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    /// Number of attempts at a valid final answer, including the first. Defaults to 3.
+    pub fn with_validation_attempts(mut self, attempts: usize) -> Self {
+        self.validation_attempts = attempts.max(1);
+        self
+    }
+
+    /// Run `hooks` at the start and end of every iteration of the tool loop.
+    pub fn with_hooks<H: IterationHooks + 'static>(mut self, hooks: H) -> Self {
+        self.hooks.push(Arc::new(hooks));
+        self
+    }
+
+    /// Compute the tools offered to the model before every request with `provider`,
+    /// instead of offering every tool of the MCP server throughout the loop.
+    ///
+    /// Calls are still executed by the MCP server.
+    pub fn with_tool_provider<P: ToolProvider + 'static>(mut self, provider: P) -> Self {
+        self.tool_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Send `instructions` with every request, after the system prompt of the client.
+    ///
+    /// Use the [`InstructionLayer::App`](crate::instructions::InstructionLayer::App)
+    /// layer for instructions of the application. Calling this again adds to the
+    /// instructions set before.
+    pub fn with_instructions(mut self, instructions: Instructions) -> Self {
+        self.instructions = self.instructions.overlay(&instructions);
+        self
+    }
+
+    /// Record requests, responses, tool calls and loop decisions in `recorder`.
+    pub fn with_trace(mut self, recorder: TraceRecorder) -> Self {
+        self.trace = Some(recorder);
+        self
+    }
+
+    /// Share `scratchpad` with the tools, which hold clones of it.
+    ///
+    /// While it holds entries, its [`summary`](Scratchpad::summary) is appended to every
+    /// request, after the last message. Like iteration hook changes, the summary is not
+    /// kept in the conversation.
+    pub fn with_scratchpad(mut self, scratchpad: Scratchpad) -> Self {
+        self.scratchpad = Some(scratchpad);
+        self
+    }
+
+    /// The scratchpad shared with the tools, if any.
+    pub fn scratchpad(&self) -> Option<&Scratchpad> {
+        self.scratchpad.as_ref()
+    }
+
+    /// Get a reference to the underlying client.
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
     /// Send a chat request with automatic tool execution.
     ///
     /// This method automatically handles the tool execution loop:
@@ -80,7 +299,19 @@ impl<C: Client> Agent<C> {
     ///
     /// # Returns
     /// The response containing all new messages generated during the execution (including tool calls and results)
-    pub async fn chat(&self, mut messages: Vec<Message>) -> Result<Response, ClientError> {
+    pub async fn chat(&self, messages: Vec<Message>) -> Result<Response, ClientError> {
+        self.chat_with_instructions(messages, &Instructions::new())
+            .await
+    }
+
+    /// Like [`Agent::chat`], with `call` layered over the instructions of the agent
+    /// for this call only.
+    pub async fn chat_with_instructions(
+        &self,
+        mut messages: Vec<Message>,
+        call: &Instructions,
+    ) -> Result<Response, ClientError> {
+        let instructions = self.instructions.overlay(call);
         debug!(
             "Starting agent chat loop with {} initial messages",
             messages.len()
@@ -90,6 +321,8 @@ impl<C: Client> Agent<C> {
             data: Vec::new(),
             usage: Usage::default(),
             finish: FinishReason::Unfinished,
+            stop_sequence: None,
+            service_tier: None,
         };
 
         let (tools, tool_map) = if let Some(server) = &self.server {
@@ -112,12 +345,28 @@ impl<C: Client> Agent<C> {
             (Vec::new(), HashMap::new())
         };
 
+        let mut attempts = 0;
         for iteration in 0..self.max_iterations {
             debug!("Agent iteration {}/{}", iteration + 1, self.max_iterations);
 
-            let response = self.client.request(messages.clone(), tools.clone()).await?;
+            let request = self
+                .prepare(iteration, messages.clone(), &instructions)
+                .await?;
+            let tools = self.iteration_tools(&messages, &tools).await?;
+            let schemas = self.input_schemas(&tools);
+            self.trace_request(iteration, &request, &tools);
+            let response = self.client.request(request, tools).await;
+            self.trace_result(iteration, &response);
+            let response = response?;
+            self.trace_decision(iteration, || match function_calls(&response.data) {
+                0 => AgentDecision::Finish,
+                calls => AgentDecision::CallTools { calls },
+            });
+            let answer = response.clone();
             current_response.usage += response.usage;
             current_response.finish = response.finish.clone();
+            current_response.stop_sequence = response.stop_sequence.clone();
+            current_response.service_tier = response.service_tier;
 
             let mut tool_calls_executed = false;
 
@@ -130,6 +379,7 @@ impl<C: Client> Agent<C> {
                         id,
                         name,
                         arguments,
+                        repaired,
                         ..
                     } = part
                     {
@@ -141,44 +391,46 @@ impl<C: Client> Agent<C> {
                             ClientError::Config("No MCP server configured".to_string())
                         })?;
                         let server_id = tool_map.get(name).cloned().flatten();
-                        let result = server
-                            .call_tool(name.clone(), arguments.clone(), server_id)
+                        let response_part = self
+                            .execute_call(
+                                server.as_ref(),
+                                id,
+                                name,
+                                arguments,
+                                *repaired,
+                                server_id,
+                                schemas.get(name),
+                            )
                             .await;
 
-                        let response_part = match result {
-                            Ok(mut part) => {
-                                info!("Tool {} executed successfully", name);
-                                debug!("Tool result: {:?}", part);
-                                if let Part::FunctionResponse {
-                                    id: ref mut pid, ..
-                                } = part
-                                {
-                                    *pid = id.clone();
-                                }
-                                part
-                            }
-                            Err(e) => {
-                                warn!("Tool {} execution failed: {}", name, e);
-                                Part::FunctionResponse {
-                                    id: id.clone(),
-                                    name: name.clone(),
-                                    response: json!({ "error": format!("Error: {}", e) }),
-                                    parts: vec![],
-                                    finished: true,
-                                }
-                            }
-                        };
-
                         let response_msg = Message::User(vec![response_part]);
                         messages.push(response_msg.clone());
                         current_response.data.push(response_msg);
                     }
                 }
             }
+            self.end_iteration(iteration, &current_response.usage)
+                .await?;
 
             if !tool_calls_executed {
-                debug!("No more function calls, agent loop complete");
-                return Ok(current_response);
+                let Err(error) = validate_all(&self.validators, &answer) else {
+                    debug!("No more function calls, agent loop complete");
+                    return Ok(current_response);
+                };
+                attempts += 1;
+                if attempts >= self.validation_attempts {
+                    return Err(ClientError::ValidationFailed {
+                        attempts,
+                        message: error,
+                    });
+                }
+                debug!("Answer failed validation (attempt {}): {}", attempts, error);
+                self.trace_decision(iteration, || AgentDecision::Retry {
+                    reason: error.clone(),
+                });
+                let correction = correction(&error);
+                messages.push(correction.clone());
+                current_response.data.push(correction);
             }
         }
 
@@ -186,11 +438,28 @@ impl<C: Client> Agent<C> {
             "Max iterations ({}) reached in agent loop",
             self.max_iterations
         );
+        self.trace_decision(self.max_iterations, || AgentDecision::MaxIterations);
         Err(ClientError::Config(
             "Max iterations reached in agent loop".to_string(),
         ))
     }
 
+    /// Continue the current branch of `conversation`, appending the new messages.
+    ///
+    /// Instructions are layered in order: the agent's, the conversation's, then `call`.
+    pub async fn chat_conversation(
+        &self,
+        conversation: &mut Conversation,
+        call: &Instructions,
+    ) -> Result<Response, ClientError> {
+        let instructions = conversation.instructions().overlay(call);
+        let response = self
+            .chat_with_instructions(conversation.messages().to_vec(), &instructions)
+            .await?;
+        conversation.push_response(&response);
+        Ok(response)
+    }
+
     /// Send a streaming chat request with automatic tool execution.
     ///
     /// This method automatically handles the tool execution loop with streaming:
@@ -198,14 +467,71 @@ impl<C: Client> Agent<C> {
     /// - Executes any tool calls
     /// - Continues until no more tool calls or max iterations reached
     ///
+    /// Dropping the stream cancels the tool call in flight, if any. Use
+    /// [`Agent::chat_stream_until`] to stop gracefully and learn which calls were aborted.
+    ///
     /// # Arguments
     /// - `messages`: Conversation messages
     ///
     /// # Returns
     /// A stream of chunks for the final response after all tool executions complete
     pub fn chat_stream<'a>(
+        &'a self,
+        messages: Vec<Message>,
+    ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        C: crate::client::StreamingClient,
+    {
+        self.chat_stream_until(messages, StopSignal::new())
+    }
+
+    /// Like [`Agent::chat_stream`], but writes the text of the responses to `writer` as
+    /// it arrives (see [`ResponseStreamExt::write_to`](crate::stream::ResponseStreamExt::write_to))
+    /// and returns the final response.
+    ///
+    /// Pass a mutable reference to keep using the writer, e.g. `&mut tokio::io::stdout()`.
+    pub async fn chat_stream_to<W>(
+        &self,
+        messages: Vec<Message>,
+        mut writer: W,
+    ) -> Result<Response, ClientError>
+    where
+        C: crate::client::StreamingClient,
+        W: tokio::io::AsyncWrite + Unpin + Send,
+    {
+        use crate::stream::ResponseStreamExt;
+        self.chat_stream(messages).write_to(&mut writer).await
+    }
+
+    /// Like [`Agent::chat_stream`], but stops once `stop` is triggered.
+    ///
+    /// Stopping while the model is generating ends the stream after the last snapshot.
+    /// Stopping while tools run aborts the calls in flight and skips the remaining ones;
+    /// a final response is emitted in which they are answered with
+    /// [`ToolErrorKind::Cancelled`](crate::tools::ToolErrorKind::Cancelled) errors.
+    pub fn chat_stream_until<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        stop: StopSignal,
+    ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        C: crate::client::StreamingClient,
+    {
+        self.chat_stream_steered(messages, stop, Interjections::new())
+    }
+
+    /// Like [`Agent::chat_stream_until`], but also applies the messages pushed to
+    /// `interjections` while the run is in progress.
+    ///
+    /// Interjected messages are added at the end of the iteration in which they arrive,
+    /// after the tool results if any, and are part of the emitted responses. If the
+    /// model answered without calling tools, the run continues with another iteration
+    /// instead of finishing, so users can steer the agent without restarting it.
+    pub fn chat_stream_steered<'a>(
         &'a self,
         mut messages: Vec<Message>,
+        stop: StopSignal,
+        interjections: Interjections,
     ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Response, ClientError>> + Send + 'a>>
     where
         C: crate::client::StreamingClient,
@@ -218,6 +544,8 @@ impl<C: Client> Agent<C> {
                 data: Vec::new(),
                 usage: Usage::default(),
                 finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
             };
 
             let (tools, tool_map) = if let Some(server) = &self.server {
@@ -245,14 +573,63 @@ impl<C: Client> Agent<C> {
                     self.max_iterations
                 );
 
-                let mut stream = self.client.request_stream(messages.clone(), tools.clone()).await?;
+                let request = self
+                    .prepare(iteration, messages.clone(), &self.instructions)
+                    .await?;
+                let tools = self.iteration_tools(&messages, &tools).await?;
+                let schemas = self.input_schemas(&tools);
+                self.trace_request(iteration, &request, &tools);
+                let stream = self.client.request_stream(request, tools).await;
+                if let Err(error) = &stream {
+                    self.trace_error(iteration, error);
+                }
+                let mut stream = stream?;
+                let mut last = None;
 
                 // Snapshot of state before this turn
                 let base_data_len = current_response.data.len();
                 let base_usage = current_response.usage.clone();
+                let mut streamed_arguments: HashMap<usize, Value> = HashMap::new();
 
-                while let Some(response_result) = stream.next().await {
+                loop {
+                    let response_result = tokio::select! {
+                        next = stream.next() => next,
+                        _ = stop.stopped() => {
+                            debug!("Agent stream stopped during generation");
+                            return;
+                        }
+                    };
+                    let Some(response_result) = response_result else {
+                        break;
+                    };
+                    if let Err(error) = &response_result {
+                        self.trace_error(iteration, error);
+                    }
                     let response = response_result?;
+                    if self.trace.is_some() {
+                        last = Some(response.clone());
+                    }
+
+                    // Forward arguments of calls still being generated to tools that opted in
+                    if let Some(server) = &self.server {
+                        let parts = response.data.iter().flat_map(|m| m.parts()).enumerate();
+                        for (index, part) in parts {
+                            if let Part::FunctionCall { id, name, arguments, finished: false, .. } = part {
+                                let server_id = tool_map.get(name).cloned().flatten();
+                                if !server.streams_arguments(name, server_id.as_deref()) {
+                                    continue;
+                                }
+                                let Some(partial) = parse_partial_arguments(arguments) else {
+                                    continue;
+                                };
+                                if streamed_arguments.get(&index) == Some(&partial) {
+                                    continue;
+                                }
+                                streamed_arguments.insert(index, partial.clone());
+                                server.partial_arguments(name.clone(), id.clone(), partial, server_id).await;
+                            }
+                        }
+                    }
 
                     // Update current_response
                     // Truncate to base length to remove previous partials of this turn
@@ -262,10 +639,16 @@ impl<C: Client> Agent<C> {
                     current_response.usage = base_usage.clone();
                     current_response.usage += response.usage;
                     current_response.finish = response.finish;
+                    current_response.stop_sequence = response.stop_sequence;
+                    current_response.service_tier = response.service_tier;
 
                     yield current_response.clone();
                 }
 
+                if let Some(response) = last {
+                    self.trace_result(iteration, &Ok(response));
+                }
+
                 // After stream, current_response contains the full assistant message for this turn.
                 // Update messages history
                 if current_response.data.len() > base_data_len {
@@ -275,6 +658,11 @@ impl<C: Client> Agent<C> {
                      }
                 }
 
+                self.trace_decision(iteration, || match function_calls(current_response.data.last()) {
+                    0 => AgentDecision::Finish,
+                    calls => AgentDecision::CallTools { calls },
+                });
+
                 // Check for tool calls
                 let mut tool_calls_executed = false;
                 let mut tool_responses = Vec::new();
@@ -282,33 +670,24 @@ impl<C: Client> Agent<C> {
                 // We only check the LAST message for tool calls, which should be the assistant's message
                 if let Some(msg) = current_response.data.last() {
                     for part in msg.parts() {
-                        if let Part::FunctionCall { id, name, arguments, finished, .. } = part {
+                        if let Part::FunctionCall { id, name, arguments, repaired, finished, .. } = part {
                             if *finished {
                                 tool_calls_executed = true;
                                 info!("Executing tool: {}", name);
 
                                 let server = self.server.as_ref().ok_or_else(|| ClientError::Config("No MCP server configured".to_string()))?;
                                 let server_id = tool_map.get(name).cloned().flatten();
-                                let result = server
-                                    .call_tool(name.clone(), arguments.clone(), server_id)
-                                    .await;
-
-                                let response_part = match result {
-                                    Ok(mut part) => {
-                                        if let Part::FunctionResponse { id: ref mut pid, .. } = part {
-                                            *pid = id.clone();
+                                let cancelled = || Part::function_error(id.clone(), name, ToolError::cancelled());
+                                let response_part = if stop.is_stopped() {
+                                    cancelled()
+                                } else {
+                                    tokio::select! {
+                                        part = self.execute_call(server.as_ref(), id, name, arguments, *repaired, server_id, schemas.get(name)) => part,
+                                        _ = stop.stopped() => {
+                                            warn!("Tool {} aborted", name);
+                                            cancelled()
                                         }
-                                        part
                                     }
-                                    Err(e) => {
-                                        Part::FunctionResponse {
-                                            id: id.clone(),
-                                            name: name.clone(),
-                                            response: json!({ "error": format!("Error: {}", e) }),
-                                            parts: vec![],
-                                            finished: true,
-                                        }
-                                    },
                                 };
                                 tool_responses.push(response_part);
                             }
@@ -320,12 +699,34 @@ impl<C: Client> Agent<C> {
                     let tool_msg = Message::User(tool_responses);
                     messages.push(tool_msg.clone());
                     current_response.data.push(tool_msg);
-
-                    yield current_response.clone();
+                }
+                // Interjections are kept queued if the run was stopped
+                let interjected = if stop.is_stopped() {
+                    Vec::new()
                 } else {
+                    interjections.take()
+                };
+                if !interjected.is_empty() {
+                    let count = interjected.len();
+                    debug!("Applying {} interjected messages", count);
+                    self.trace_decision(iteration, || AgentDecision::Interjection { messages: count });
+                    for message in interjected {
+                        push_merged(&mut messages, message.clone());
+                        push_merged(&mut current_response.data, message);
+                    }
+                } else if !tool_calls_executed {
+                    self.end_iteration(iteration, &current_response.usage).await?;
                     // No tool calls, we are done
                     return;
                 }
+
+                yield current_response.clone();
+                self.end_iteration(iteration, &current_response.usage).await?;
+
+                if stop.is_stopped() {
+                    debug!("Agent stream stopped during tool execution");
+                    return;
+                }
             }
 
             warn!(
@@ -338,3 +739,329 @@ impl<C: Client> Agent<C> {
         })
     }
 }
+
+impl<C: Client> Agent<C> {
+    /// Messages to send in `iteration`: compressed, preceded by `instructions`,
+    /// followed by the scratchpad summary, then passed to the start hooks.
+    async fn prepare(
+        &self,
+        iteration: usize,
+        messages: Vec<Message>,
+        instructions: &Instructions,
+    ) -> Result<Vec<Message>, ClientError> {
+        let mut request = self.compress(messages).await?;
+        if !instructions.is_empty() {
+            let system = self.client.model_options().system.as_deref();
+            request = instructions.apply(system, request);
+        }
+        if let Some(summary) = self.scratchpad.as_ref().and_then(Scratchpad::summary) {
+            push_merged(&mut request, Message::User(vec![Part::text(summary)]));
+        }
+        for hooks in &self.hooks {
+            hooks.on_iteration_start(iteration, &mut request).await?;
+        }
+        Ok(request)
+    }
+
+    /// Tools to offer in the next request: those of the tool provider, if any, or
+    /// `server_tools`.
+    async fn iteration_tools(
+        &self,
+        messages: &[Message],
+        server_tools: &[Tool],
+    ) -> Result<Vec<Tool>, ClientError> {
+        match &self.tool_provider {
+            Some(provider) => provider.tools(messages.to_vec()).await,
+            None => Ok(server_tools.to_vec()),
+        }
+    }
+
+    /// Run the end hooks of `iteration`.
+    async fn end_iteration(&self, iteration: usize, usage: &Usage) -> Result<(), ClientError> {
+        for hooks in &self.hooks {
+            hooks.on_iteration_end(iteration, usage).await?;
+        }
+        Ok(())
+    }
+
+    fn trace_request(&self, iteration: usize, messages: &[Message], tools: &[Tool]) {
+        if let Some(trace) = &self.trace {
+            trace.record(TraceEvent::Request {
+                iteration,
+                model: self.client.model_options().model.clone(),
+                messages: messages.to_vec(),
+                tools: tools.to_vec(),
+            });
+        }
+    }
+
+    fn trace_result(&self, iteration: usize, result: &Result<Response, ClientError>) {
+        match result {
+            Ok(response) => {
+                if let Some(trace) = &self.trace {
+                    trace.record(TraceEvent::Response {
+                        iteration,
+                        response: response.clone(),
+                    });
+                }
+            }
+            Err(error) => self.trace_error(iteration, error),
+        }
+    }
+
+    fn trace_error(&self, iteration: usize, error: &ClientError) {
+        if let Some(trace) = &self.trace {
+            trace.record(TraceEvent::Error {
+                iteration,
+                message: error.to_string(),
+            });
+        }
+    }
+
+    fn trace_decision(&self, iteration: usize, decision: impl FnOnce() -> AgentDecision) {
+        if let Some(trace) = &self.trace {
+            trace.record(TraceEvent::Decision {
+                iteration,
+                decision: decision(),
+            });
+        }
+    }
+
+    /// Apply the configured compressor, if any, to the messages about to be sent.
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>, ClientError> {
+        let Some(compressor) = &self.compressor else {
+            return Ok(messages);
+        };
+        let (messages, report) = compress::compress(compressor.as_ref(), messages).await?;
+        info!(
+            "Compressed history from ~{} to ~{} tokens",
+            report.tokens_before, report.tokens_after
+        );
+        Ok(messages)
+    }
+
+    /// Input schemas of `tools` by name, if arguments are validated.
+    fn input_schemas(&self, tools: &[Tool]) -> HashMap<String, SchemaValidator> {
+        if !self.validate_arguments {
+            return HashMap::new();
+        }
+        tools
+            .iter()
+            .map(|tool| {
+                let schema = Value::Object(tool.input_schema.as_ref().clone());
+                (tool.name.to_string(), SchemaValidator::new(schema))
+            })
+            .collect()
+    }
+
+    /// Execute a function call requested by the model.
+    ///
+    /// Calls with repaired arguments are rejected unless configured otherwise, and
+    /// calls whose arguments do not match `schema` are answered with the problem
+    /// instead of being executed.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_call(
+        &self,
+        server: &dyn MCPServer,
+        id: &Option<String>,
+        name: &str,
+        arguments: &Value,
+        repaired: bool,
+        server_id: Option<String>,
+        schema: Option<&SchemaValidator>,
+    ) -> Part {
+        if let Some(audit) = &self.audit {
+            audit.tool_call(id.as_deref(), name, arguments);
+        }
+        if let Some(trace) = &self.trace {
+            trace.record(TraceEvent::ToolCall {
+                id: id.clone(),
+                name: name.to_string(),
+                arguments: arguments.clone(),
+            });
+        }
+
+        let invalid = schema.and_then(|schema| schema.check(arguments).err());
+        let part = if repaired && !self.execute_repaired {
+            warn!("Not executing tool {} with truncated arguments", name);
+            let error = ToolError::invalid_arguments(
+                "The arguments were truncated. Call the tool again with complete arguments.",
+            );
+            Part::function_error(id.clone(), name, error)
+        } else if let Some(problem) = invalid {
+            warn!(
+                "Not executing tool {} with invalid arguments: {}",
+                name, problem
+            );
+            let error = ToolError::invalid_arguments(format!(
+                "The arguments do not match the input schema: {}. Call the tool again with valid arguments.",
+                problem
+            ));
+            Part::function_error(id.clone(), name, error)
+        } else {
+            self.execute_tool(server, id, name, arguments, server_id)
+                .await
+        };
+
+        if let Some(audit) = &self.audit {
+            audit.tool_result(&part);
+        }
+        if let Some(trace) = &self.trace {
+            trace.record(TraceEvent::ToolResult {
+                result: part.clone(),
+            });
+        }
+        part
+    }
+
+    /// Call a tool, retrying according to the retry policy and the tool configuration.
+    ///
+    /// Failures are returned as function responses carrying a [`ToolError`], so that
+    /// the model can react to them.
+    async fn execute_tool(
+        &self,
+        server: &dyn MCPServer,
+        id: &Option<String>,
+        name: &str,
+        arguments: &Value,
+        server_id: Option<String>,
+    ) -> Part {
+        let config = self.tool_configs.get(name);
+        let max_retries = config.and_then(|c| c.max_retries).unwrap_or(1);
+        let class = config
+            .and_then(|c| c.concurrency_class.as_ref())
+            .and_then(|class| self.concurrency_classes.get(class));
+
+        let mut retries = 0;
+        loop {
+            // The semaphore is never closed.
+            let _permit = match class {
+                Some(semaphore) => semaphore.acquire().await.ok(),
+                None => None,
+            };
+            let call = server.call_tool(name.to_string(), arguments.clone(), server_id.clone());
+            let result = match config.and_then(|c| c.timeout) {
+                Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| {
+                    ToolError::new(
+                        ToolErrorKind::Timeout,
+                        format!("Tool did not answer within {:?}", timeout),
+                    )
+                }),
+                None => Ok(call.await),
+            };
+
+            let part = match result {
+                Ok(Ok(mut part)) => {
+                    debug!("Tool result: {:?}", part);
+                    if let Part::FunctionResponse {
+                        id: ref mut pid, ..
+                    } = part
+                    {
+                        *pid = id.clone();
+                    }
+                    part
+                }
+                Ok(Err(e)) => Part::function_error(id.clone(), name, ToolError::from(e)),
+                Err(error) => Part::function_error(id.clone(), name, error),
+            };
+
+            match &part {
+                Part::FunctionResponse {
+                    error: Some(error), ..
+                } => {
+                    if retries < max_retries && self.tool_retry.should_retry(error) {
+                        warn!("Tool {} failed, retrying: {}", name, error);
+                        retries += 1;
+                        continue;
+                    }
+                    warn!("Tool {} execution failed: {}", name, error);
+                }
+                _ => info!("Tool {} executed successfully", name),
+            }
+            return part;
+        }
+    }
+}
+
+/// Signal to stop an [`Agent::chat_stream_until`] stream from outside.
+///
+/// Clones share the same state, so one clone can be handed to the stream while
+/// another is kept to trigger it.
+#[derive(Debug, Clone)]
+pub struct StopSignal(Arc<watch::Sender<bool>>);
+
+impl Default for StopSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+
+    /// Trigger the signal.
+    pub fn stop(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Whether the signal was triggered.
+    pub fn is_stopped(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Wait until the signal is triggered.
+    pub async fn stopped(&self) {
+        let mut receiver = self.0.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail.
+        let _ = receiver.wait_for(|stopped| *stopped).await;
+    }
+}
+
+/// Messages pushed into an [`Agent::chat_stream_steered`] run from outside.
+///
+/// Clones share the same queue, so one clone can be handed to the stream while
+/// another is kept to push messages.
+#[derive(Debug, Clone, Default)]
+pub struct Interjections(Arc<Mutex<Vec<Message>>>);
+
+impl Interjections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `message`, usually a [`Message::user`], for the next iteration boundary.
+    pub fn push(&self, message: Message) {
+        self.0.lock().unwrap().push(message);
+    }
+
+    /// Number of messages not applied yet.
+    pub fn pending(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    fn take(&self) -> Vec<Message> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Number of finished function calls in `messages`.
+fn function_calls<'a>(messages: impl IntoIterator<Item = &'a Message>) -> usize {
+    messages
+        .into_iter()
+        .flat_map(|message| message.parts())
+        .filter(|part| matches!(part, Part::FunctionCall { finished: true, .. }))
+        .count()
+}
+
+/// Best-effort parse of the arguments of a function call that is still streaming.
+///
+/// Providers expose in-progress arguments as the raw JSON generated so far.
+fn parse_partial_arguments(arguments: &Value) -> Option<Value> {
+    match arguments {
+        Value::String(raw) => parse_partial(raw),
+        Value::Null => None,
+        other => Some(other.clone()),
+    }
+}
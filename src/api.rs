@@ -1,3 +1,9 @@
+#[cfg(feature = "anthropic")]
 pub mod anthropic;
+pub mod completion;
+pub mod embed;
+#[cfg(feature = "gemini")]
 pub mod gemini;
+#[cfg(feature = "openai")]
 pub mod openai;
+pub mod rerank;
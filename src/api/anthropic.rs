@@ -1,22 +1,69 @@
 //! Anthropic API client implementation.
 
 use async_trait::async_trait;
-use base64::prelude::*;
+use bytes::Bytes;
 use futures::{Stream, StreamExt};
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use serde_with::skip_serializing_none;
 use std::collections::HashMap;
 use std::pin::Pin;
 
-use crate::client::{Client, ClientError, StreamingClient};
-use crate::http::{add_extra_headers, build_http_client, RequestBuilderExt, ResponseExt};
-use crate::model::{FinishReason, MediaType, Message, Part, Response, Usage};
-use crate::options::{ModelOptions, TransportOptions};
+use crate::client::{BatchItemOutcome, Client, ClientError, RequestPreview, StreamingClient};
+use crate::constraints::PromptStyle;
+use crate::eventstream::EventStreamResponseExt;
+use crate::history::{normalize_history, push_merged};
+use crate::http::{
+    add_extra_headers, add_idempotency_key, build_http_client, check_request_size,
+    estimate_request_tokens, preview_request, request_id, RequestBuilderExt, ResponseExt,
+};
+use crate::model::{
+    base64_data, Extensions, FinishReason, MediaType, Message, Part, Response, TextAnnotations,
+    Usage,
+};
+use crate::options::{
+    self, ModelOptions, OptionViolation, ProviderOptions, SafetyLevel, SamplingPreset,
+    TransportOptions,
+};
+use crate::region::VertexLocation;
 use crate::sse::SSEResponseExt;
+use crate::stream::{shape_stream, FirstTokenDeadline};
+use crate::structured::parse_arguments;
+use crate::tools::ToolError;
 
 const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// API version sent in the body of requests to Claude on Vertex AI.
+const VERTEX_ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+/// API version sent in the body of requests to Claude on Amazon Bedrock.
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// Smallest thinking budget accepted by the API, used when none is configured.
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Output token limits of known model families, matched by prefix in order.
+const MAX_OUTPUT_TOKENS: &[(&str, u32)] = &[
+    ("claude-opus-4-5", 64_000),
+    ("claude-opus-4", 32_000),
+    ("claude-sonnet-4", 64_000),
+    ("claude-haiku-4", 64_000),
+    ("claude-3-7-sonnet", 64_000),
+    ("claude-3-5", 8_192),
+];
+
+/// Output token limit assumed for models missing from [`MAX_OUTPUT_TOKENS`].
+const FALLBACK_MAX_TOKENS: u32 = 4_096;
+
+/// `max_tokens` sent when the options do not set one.
+///
+/// Anthropic requires `max_tokens` on every request, so the full output limit of the
+/// model is used rather than an arbitrary small value that would truncate long answers.
+pub fn default_max_tokens(model: &str) -> u32 {
+    MAX_OUTPUT_TOKENS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(FALLBACK_MAX_TOKENS, |(_, limit)| *limit)
+}
 
 /// Anthropic model options.
 #[skip_serializing_none]
@@ -28,6 +75,208 @@ pub struct AnthropicModel {
     pub service_tier: Option<ServiceTier>,
     pub thinking_budget: Option<u32>,
     pub tool_choice: Option<AnthropicToolChoice>,
+    /// Beta features enabled through the `anthropic-beta` header.
+    pub betas: Option<Vec<AnthropicBeta>>,
+    /// Mark the tool definitions with `cache_control`, so that requests sharing them
+    /// read them from the prompt cache. Tools come first in the prompt, so they are
+    /// cached even when the system prompt changes. Cache reads are reported in
+    /// [`Usage::cached_prompt_tokens`]. Defaults to `false`.
+    pub cache_tools: Option<bool>,
+    /// Mark the system prompt with `cache_control`, caching the tools along with it.
+    /// Defaults to `false`.
+    pub cache_system: Option<bool>,
+    /// Mark the last block of the last `n` user messages with `cache_control`, so that
+    /// each request reads the conversation so far from the cache. One or two suffice
+    /// for a growing conversation.
+    pub cache_messages: Option<usize>,
+    /// Lifetime of the cache entries written by the breakpoints above. Defaults to
+    /// five minutes.
+    pub cache_ttl: Option<AnthropicCacheTtl>,
+}
+
+/// Number of `cache_control` breakpoints a request may hold.
+pub const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+impl AnthropicModel {
+    /// Number of `cache_control` breakpoints the options put in a request, at most.
+    pub fn cache_breakpoints(&self) -> usize {
+        usize::from(self.cache_tools.unwrap_or(false))
+            + usize::from(self.cache_system.unwrap_or(false))
+            + self.cache_messages.unwrap_or(0)
+    }
+
+    /// Description of the breakpoints exceeding [`MAX_CACHE_BREAKPOINTS`], if any.
+    fn excess_breakpoints(&self) -> Option<String> {
+        let breakpoints = self.cache_breakpoints();
+        (breakpoints > MAX_CACHE_BREAKPOINTS).then(|| {
+            format!(
+                "requests {} cache breakpoints (tools, system prompt and {} messages), but \
+                 Anthropic allows at most {}; cache fewer messages",
+                breakpoints,
+                self.cache_messages.unwrap_or(0),
+                MAX_CACHE_BREAKPOINTS
+            )
+        })
+    }
+
+    fn cache_control(&self) -> AnthropicCacheControl {
+        AnthropicCacheControl::Ephemeral {
+            ttl: self.cache_ttl,
+        }
+    }
+}
+
+/// Lifetime of prompt cache entries.
+///
+/// The one hour TTL is billed at a higher write price. Accounts without general
+/// access to it also need [`AnthropicBeta::ExtendedCacheTtl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnthropicCacheTtl {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+/// Anthropic beta feature flags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum AnthropicBeta {
+    /// Token-efficient tool use (`token-efficient-tools-2025-02-19`).
+    TokenEfficientTools,
+    /// One hour prompt cache TTL (`extended-cache-ttl-2025-04-11`).
+    ExtendedCacheTtl,
+    /// One million token context window (`context-1m-2025-08-07`).
+    Context1M,
+    /// Thinking between tool calls (`interleaved-thinking-2025-05-14`).
+    InterleavedThinking,
+    /// 128k output tokens (`output-128k-2025-02-19`).
+    Output128K,
+    /// Files API (`files-api-2025-04-14`).
+    FilesApi,
+    /// Any other beta flag, sent verbatim.
+    Other(String),
+}
+
+impl AnthropicBeta {
+    /// Get the header value for this beta.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AnthropicBeta::TokenEfficientTools => "token-efficient-tools-2025-02-19",
+            AnthropicBeta::ExtendedCacheTtl => "extended-cache-ttl-2025-04-11",
+            AnthropicBeta::Context1M => "context-1m-2025-08-07",
+            AnthropicBeta::InterleavedThinking => "interleaved-thinking-2025-05-14",
+            AnthropicBeta::Output128K => "output-128k-2025-02-19",
+            AnthropicBeta::FilesApi => "files-api-2025-04-14",
+            AnthropicBeta::Other(beta) => beta,
+        }
+    }
+}
+
+impl From<String> for AnthropicBeta {
+    fn from(beta: String) -> Self {
+        [
+            AnthropicBeta::TokenEfficientTools,
+            AnthropicBeta::ExtendedCacheTtl,
+            AnthropicBeta::Context1M,
+            AnthropicBeta::InterleavedThinking,
+            AnthropicBeta::Output128K,
+            AnthropicBeta::FilesApi,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == beta)
+        .unwrap_or(AnthropicBeta::Other(beta))
+    }
+}
+
+impl From<AnthropicBeta> for String {
+    fn from(beta: AnthropicBeta) -> Self {
+        beta.as_str().to_string()
+    }
+}
+
+impl ProviderOptions for AnthropicModel {
+    /// Temperatures stay within Anthropic's 0.0 to 1.0 range; top-p and top-k are
+    /// left unset as Anthropic recommends for most uses.
+    fn apply_preset(options: &mut ModelOptions<Self>, preset: SamplingPreset) {
+        options.temperature = Some(match preset {
+            SamplingPreset::Precise => 0.2,
+            SamplingPreset::Balanced => 0.6,
+            SamplingPreset::Creative => 1.0,
+        });
+        options.top_p = None;
+        options.provider.top_k = None;
+    }
+
+    fn validate(options: &ModelOptions<Self>, violations: &mut Vec<OptionViolation>) {
+        if options.temperature.is_some_and(|t| t > 1.0) {
+            violations.push(OptionViolation::new(
+                "temperature",
+                "must be between 0.0 and 1.0 for Anthropic",
+            ));
+        }
+        if let Some(tier) = options.service_tier {
+            if ServiceTier::from_tier(tier).is_none() {
+                violations.push(OptionViolation::new(
+                    "service_tier",
+                    format!("{:?} is not offered by Anthropic", tier),
+                ));
+            }
+        }
+        if options
+            .safety
+            .is_some_and(|level| level != SafetyLevel::Default)
+        {
+            violations.push(OptionViolation::new(
+                "safety",
+                "content filters are not configurable on Anthropic",
+            ));
+        }
+        if options.output.is_some() {
+            violations.push(OptionViolation::new(
+                "output",
+                "constrained output is not supported by Anthropic",
+            ));
+        }
+        if let Some(message) = options.provider.excess_breakpoints() {
+            violations.push(OptionViolation::new("provider.cache_messages", message));
+        }
+        if !options.reasoning.unwrap_or(false) {
+            return;
+        }
+
+        let budget = options
+            .provider
+            .thinking_budget
+            .unwrap_or(MIN_THINKING_BUDGET);
+        if budget < MIN_THINKING_BUDGET {
+            violations.push(OptionViolation::new(
+                "provider.thinking_budget",
+                format!("must be at least {}", MIN_THINKING_BUDGET),
+            ));
+        }
+        let max_tokens = options
+            .max_tokens
+            .unwrap_or_else(|| default_max_tokens(&options.model));
+        if max_tokens <= budget {
+            violations.push(OptionViolation::new(
+                "max_tokens",
+                format!("must be greater than the thinking budget ({})", budget),
+            ));
+        }
+        if options.temperature.is_some() {
+            violations.push(OptionViolation::new(
+                "temperature",
+                "cannot be set when reasoning is enabled",
+            ));
+        }
+        if options.provider.top_k.is_some() {
+            violations.push(OptionViolation::new(
+                "provider.top_k",
+                "cannot be set when reasoning is enabled",
+            ));
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +291,19 @@ pub enum ServiceTier {
     StandardOnly,
 }
 
+impl ServiceTier {
+    /// Anthropic tier requesting the provider-agnostic `tier`, if offered.
+    ///
+    /// Priority capacity is used by `auto` on accounts that have it.
+    pub fn from_tier(tier: options::ServiceTier) -> Option<Self> {
+        match tier {
+            options::ServiceTier::Auto | options::ServiceTier::Priority => Some(Self::Auto),
+            options::ServiceTier::Standard => Some(Self::StandardOnly),
+            options::ServiceTier::Flex | options::ServiceTier::Batch => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AnthropicToolChoice {
@@ -58,6 +320,44 @@ pub enum AnthropicToolChoice {
     None,
 }
 
+/// Channel through which Claude is reached.
+///
+/// The message format is shared by all of them; they differ in authentication, in
+/// where the model and API version are given, and in the streaming format.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AnthropicBackend {
+    /// The Anthropic API, authenticated with an Anthropic API key.
+    #[default]
+    Anthropic,
+    /// Vertex AI, authenticated with a Google Cloud access token.
+    Vertex(VertexLocation),
+    /// Amazon Bedrock in an AWS region such as `us-east-1`, authenticated with a
+    /// Bedrock API key. The model is a Bedrock model or inference profile id, e.g.
+    /// `us.anthropic.claude-sonnet-4-20250514-v1:0`.
+    Bedrock { region: String },
+}
+
+impl AnthropicBackend {
+    /// Base URL of the backend, for the Anthropic API the public endpoint.
+    pub fn base_url(&self) -> String {
+        match self {
+            AnthropicBackend::Anthropic => "https://api.anthropic.com/v1".to_string(),
+            AnthropicBackend::Vertex(location) => location.publisher_url("anthropic"),
+            AnthropicBackend::Bedrock { region } => {
+                format!("https://bedrock-runtime.{}.amazonaws.com", region)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AnthropicBackend::Anthropic => "Anthropic",
+            AnthropicBackend::Vertex(_) => "Anthropic on Vertex AI",
+            AnthropicBackend::Bedrock { .. } => "Anthropic on Amazon Bedrock",
+        }
+    }
+}
+
 /// Anthropic client.
 #[derive(Debug, Clone)]
 pub struct AnthropicClient {
@@ -65,6 +365,7 @@ pub struct AnthropicClient {
     base_url: String,
     model_options: ModelOptions<AnthropicModel>,
     transport_options: TransportOptions,
+    backend: AnthropicBackend,
 }
 
 impl AnthropicClient {
@@ -79,54 +380,276 @@ impl AnthropicClient {
             base_url,
             model_options,
             transport_options,
+            backend: AnthropicBackend::Anthropic,
         }
     }
 
-    fn handle_error_response(status: reqwest::StatusCode, body: &str) -> ClientError {
-        if let Ok(error_resp) = serde_json::from_str::<AnthropicErrorResponse>(body) {
-            ClientError::ProviderError(format!(
+    /// Reach Claude through `backend`, using its default base URL.
+    ///
+    /// The API key must be a credential of that backend. Message batches are only
+    /// available through the Anthropic API.
+    pub fn with_backend(mut self, backend: AnthropicBackend) -> Self {
+        self.base_url = backend.base_url();
+        self.backend = backend;
+        self
+    }
+
+    /// Reach Claude through Vertex AI in the given project and region.
+    pub fn with_region(self, location: VertexLocation) -> Self {
+        self.with_backend(AnthropicBackend::Vertex(location))
+    }
+
+    pub fn backend(&self) -> &AnthropicBackend {
+        &self.backend
+    }
+
+    fn handle_error_response(
+        status: reqwest::StatusCode,
+        request_id: Option<String>,
+        body: &str,
+    ) -> ClientError {
+        let message = if let Ok(error_resp) = serde_json::from_str::<AnthropicErrorResponse>(body) {
+            format!(
                 "Anthropic error ({}): {}",
                 error_resp.error.error_type, error_resp.error.message
-            ))
+            )
         } else {
-            ClientError::ProviderError(format!("HTTP {}: {}", status, body))
+            format!("HTTP {}: {}", status, body)
+        };
+        ClientError::Api {
+            status: Some(status.as_u16()),
+            request_id,
+            message,
         }
     }
 
+    fn headers(&self) -> Result<HeaderMap, ClientError> {
+        let mut headers = HeaderMap::new();
+        if self.backend != AnthropicBackend::Anthropic {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+                    .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
+            );
+        } else {
+            headers.insert(
+                "x-api-key",
+                HeaderValue::from_str(&self.api_key)
+                    .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
+            );
+            headers.insert(
+                "anthropic-version",
+                HeaderValue::from_static(ANTHROPIC_VERSION),
+            );
+        }
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        // Bedrock takes betas in the body instead.
+        let betas = self.model_options.provider.betas.as_ref();
+        if let Some(betas) =
+            betas.filter(|_| !matches!(self.backend, AnthropicBackend::Bedrock { .. }))
+        {
+            if !betas.is_empty() {
+                let value = betas
+                    .iter()
+                    .map(AnthropicBeta::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                headers.insert(
+                    "anthropic-beta",
+                    HeaderValue::from_str(&value)
+                        .map_err(|_| ClientError::Config("Invalid beta flag".to_string()))?,
+                );
+            }
+        }
+        Ok(headers)
+    }
+
     fn build_request(
         &self,
         messages: Vec<Message>,
         tools: Vec<rmcp::model::Tool>,
         stream: bool,
     ) -> Result<reqwest::RequestBuilder, ClientError> {
-        let url = format!("{}/messages", self.base_url);
+        let model = self.model_options.request_model();
+        let url = match &self.backend {
+            AnthropicBackend::Anthropic => format!("{}/messages", self.base_url),
+            AnthropicBackend::Vertex(_) => {
+                let method = if stream {
+                    "streamRawPredict"
+                } else {
+                    "rawPredict"
+                };
+                format!("{}/models/{}:{}", self.base_url, model, method)
+            }
+            AnthropicBackend::Bedrock { .. } => {
+                let method = if stream {
+                    "invoke-with-response-stream"
+                } else {
+                    "invoke"
+                };
+                format!("{}/model/{}/{}", self.base_url, model, method)
+            }
+        };
 
-        let model = self.model_options.model.clone();
+        let messages = if self.model_options.normalize_history.unwrap_or(false) {
+            normalize_history(messages)
+        } else {
+            messages
+        };
 
-        let request_body =
-            AnthropicRequest::new(messages, &self.model_options, model, tools, stream);
+        check_request_size(&self.transport_options, &messages)?;
 
-        let http_client = build_http_client(&self.transport_options)?;
+        let mut request_body =
+            AnthropicRequest::new(messages, &self.model_options, model, tools, stream)?;
+        // Vertex AI and Bedrock take the model from the URL and the version from the body.
+        match &self.backend {
+            AnthropicBackend::Anthropic => {}
+            AnthropicBackend::Vertex(_) => {
+                request_body.model = None;
+                request_body.anthropic_version = Some(VERTEX_ANTHROPIC_VERSION);
+            }
+            AnthropicBackend::Bedrock { .. } => {
+                // Streaming is selected by the endpoint.
+                request_body.model = None;
+                request_body.stream = None;
+                request_body.anthropic_version = Some(BEDROCK_ANTHROPIC_VERSION);
+                request_body.anthropic_beta = self
+                    .model_options
+                    .provider
+                    .betas
+                    .clone()
+                    .filter(|betas| !betas.is_empty());
+            }
+        }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            "x-api-key",
-            HeaderValue::from_str(&self.api_key)
-                .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
-        );
-        headers.insert(
-            "anthropic-version",
-            HeaderValue::from_static(ANTHROPIC_VERSION),
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let http_client = build_http_client(&self.transport_options)?;
 
-        let mut req = http_client.post(&url).headers(headers);
+        let mut req = http_client.post(&url).headers(self.headers()?);
         req = add_extra_headers(req, &self.transport_options);
+        req = add_idempotency_key(req, &self.transport_options);
 
         Ok(req.json_logged(&request_body))
     }
 }
 
+/// Result of a single request within a message batch.
+pub type AnthropicBatchResult = (String, Result<Response, ClientError>);
+
+impl AnthropicClient {
+    /// Stream the results of a message batch.
+    ///
+    /// The `.jsonl` results file is read incrementally and every line is decoded
+    /// into a `(custom_id, result)` pair as soon as it arrives, so pipelines can
+    /// start processing before the whole file has been downloaded. Requests that
+    /// errored yield a [`ClientError::Api`] in the pair, and requests that expired or
+    /// were canceled a [`ClientError::BatchItem`]; transport failures end the stream
+    /// with an outer `Err`.
+    ///
+    /// Fails if the batch has not finished processing yet.
+    pub async fn results_stream(
+        &self,
+        batch_id: &str,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<AnthropicBatchResult, ClientError>> + Send>>,
+        ClientError,
+    > {
+        if self.backend != AnthropicBackend::Anthropic {
+            return Err(ClientError::Unsupported {
+                provider: self.backend.name().to_string(),
+                capability: "message batches".to_string(),
+            });
+        }
+        let http_client = build_http_client(&self.transport_options)?;
+
+        let url = format!("{}/messages/batches/{}", self.base_url, batch_id);
+        let req = http_client.get(&url).headers(self.headers()?);
+        let response = add_extra_headers(req, &self.transport_options)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let batch: AnthropicBatch = response.json_logged().await?;
+        let results_url = batch.results_url.ok_or_else(|| {
+            ClientError::ProviderError(format!(
+                "Batch {} has no results yet (status: {})",
+                batch.id, batch.processing_status
+            ))
+        })?;
+
+        let req = http_client.get(&results_url).headers(self.headers()?);
+        let response = add_extra_headers(req, &self.transport_options)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let byte_stream = response.bytes_stream();
+
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut byte_stream = Box::pin(byte_stream);
+            let mut buffer: Vec<u8> = Vec::new();
+
+            while let Some(chunk) = byte_stream.next().await {
+                buffer.extend_from_slice(&chunk?);
+
+                while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    if let Some(result) = decode_batch_line(&line)? {
+                        yield result;
+                    }
+                }
+            }
+
+            if let Some(result) = decode_batch_line(&buffer)? {
+                yield result;
+            }
+        }))
+    }
+}
+
+/// Decode a single line of a batch results file. Blank lines yield `None`.
+fn decode_batch_line(line: &[u8]) -> Result<Option<AnthropicBatchResult>, ClientError> {
+    let line = std::str::from_utf8(line)
+        .map_err(|e| ClientError::ProviderError(format!("Invalid UTF-8 in batch results: {}", e)))?
+        .trim();
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    let entry: AnthropicBatchEntry = serde_json::from_str(line)?;
+    let result = match entry.result {
+        AnthropicBatchOutcome::Succeeded { message } => Ok(message.into()),
+        AnthropicBatchOutcome::Errored { error } => Err(ClientError::Api {
+            status: None,
+            request_id: None,
+            message: format!(
+                "Anthropic error ({}): {}",
+                error.error.error_type, error.error.message
+            ),
+        }),
+        AnthropicBatchOutcome::Canceled => Err(ClientError::BatchItem {
+            outcome: BatchItemOutcome::Canceled,
+        }),
+        AnthropicBatchOutcome::Expired => Err(ClientError::BatchItem {
+            outcome: BatchItemOutcome::Expired,
+        }),
+    };
+
+    Ok(Some((entry.custom_id, result)))
+}
+
 #[async_trait]
 impl Client for AnthropicClient {
     type ModelProvider = AnthropicModel;
@@ -142,8 +665,9 @@ impl Client for AnthropicClient {
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
         let anthropic_response: AnthropicResponse = response.json_logged().await?;
@@ -157,6 +681,19 @@ impl Client for AnthropicClient {
     fn transport_options(&self) -> &TransportOptions {
         &self.transport_options
     }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        let system = self.model_options.system_prompt(PromptStyle::Xml);
+        let estimated_tokens = estimate_request_tokens(system.as_deref(), &messages, &tools);
+        preview_request(
+            self.build_request(messages, tools, false)?,
+            estimated_tokens,
+        )
+    }
 }
 
 #[async_trait]
@@ -168,15 +705,29 @@ impl StreamingClient for AnthropicClient {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
     {
         let req = self.build_request(messages, tools, true)?;
-        let response = req.send().await?;
+        let retry = req.try_clone();
+        let deadline = FirstTokenDeadline::start(&self.transport_options);
+        let response = deadline.send(req).await?;
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
-        Ok(Box::pin(AnthropicStream::create_stream(response)))
+        // Bedrock wraps the same events in an AWS event stream.
+        if let AnthropicBackend::Bedrock { .. } = self.backend {
+            return Ok(shape_stream(
+                &self.transport_options,
+                deadline.guard(AnthropicStream::create_stream(response.chunks())),
+            ));
+        }
+        let events = response.sse_with_transport(retry, &self.transport_options);
+        Ok(shape_stream(
+            &self.transport_options,
+            deadline.guard(AnthropicStream::create_stream(events)),
+        ))
     }
 }
 
@@ -186,19 +737,22 @@ struct AnthropicStream;
 
 impl AnthropicStream {
     fn create_stream(
-        response: reqwest::Response,
+        sse_stream: impl Stream<Item = Result<String, ClientError>> + Send,
     ) -> impl Stream<Item = Result<Response, ClientError>> + Send {
-        let sse_stream = response.sse();
-
         Box::pin(async_stream::try_stream! {
             let mut stream = Box::pin(sse_stream);
             let mut current_response = Response {
                 data: vec![Message::Assistant(vec![])],
                 usage: Usage::default(),
                 finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
             };
 
             let mut tool_buffers: HashMap<u32, (String, String, String)> = HashMap::new();
+            // Position of the part of each content block. Blocks without a part (e.g.
+            // redacted thinking) are skipped, so positions can lag behind block indices.
+            let mut block_parts: HashMap<u32, usize> = HashMap::new();
 
             while let Some(event_result) = stream.next().await {
                 let event_str = event_result?;
@@ -208,16 +762,21 @@ impl AnthropicStream {
 
                 match chunk_result {
                     AnthropicStreamEvent::MessageStart { message } => {
-                        current_response.usage.prompt_tokens = Some(message.usage.input_tokens);
-                        current_response.usage.completion_tokens = Some(message.usage.output_tokens);
+                        current_response.usage = Usage::from(&message.usage);
+                        current_response.service_tier = message
+                            .usage
+                            .service_tier
+                            .as_deref()
+                            .and_then(options::ServiceTier::from_reported);
                         yield current_response.clone();
                     },
                     AnthropicStreamEvent::ContentBlockStart { index, content_block } => {
                         let parts = current_response.data[0].parts_mut();
+                        let count = parts.len();
 
                         match content_block {
-                            AnthropicContentBlock::Text { text, .. } => {
-                                parts.push(Part::Text { content: text, finished: false });
+                            AnthropicContentBlock::Text { text, citations, .. } => {
+                                parts.push(text_part(text, citations, false));
                             },
                             AnthropicContentBlock::ToolUse { id, name, .. } => {
                                 tool_buffers.insert(index, (id.clone(), name.clone(), String::new()));
@@ -226,33 +785,53 @@ impl AnthropicStream {
                                     name,
                                     arguments: Value::Null,
                                     signature: None,
+                                    repaired: false,
+                                    extensions: Extensions::new(),
                                     finished: false,
                                 });
                             },
                             AnthropicContentBlock::Thinking { thinking, signature } => {
                                 parts.push(Part::Reasoning {
-                                    content: thinking,
+                                    content: thinking.into(),
                                     summary: None,
                                     signature: Some(signature),
+                                    extensions: Extensions::new(),
                                     finished: false,
                                 });
                             },
                             _ => {},
                         }
+                        if parts.len() > count {
+                            block_parts.insert(index, count);
+                        }
                         yield current_response.clone();
                     },
                     AnthropicStreamEvent::ContentBlockDelta { index, delta } => {
                         let parts = current_response.data[0].parts_mut();
-                        if let Some(part) = parts.get_mut(index as usize) {
+                        if let Some(part) = block_parts.get(&index).and_then(|&i| parts.get_mut(i)) {
                             match delta {
                                 AnthropicDelta::Text { text } => {
                                     if let Part::Text { content: current_text, .. } = part {
                                         current_text.push_str(&text);
                                     }
                                 },
+                                AnthropicDelta::Citations { citation } => {
+                                    if let Part::Text { extensions, .. } = part {
+                                        let citations = extensions
+                                            .entry("citations")
+                                            .or_insert_with(|| Value::Array(Vec::new()));
+                                        if let Value::Array(citations) = citations {
+                                            citations.push(citation);
+                                        }
+                                    }
+                                },
                                 AnthropicDelta::InputJson { partial_json } => {
                                     if let Some(buffer) = tool_buffers.get_mut(&index) {
                                         buffer.2.push_str(&partial_json);
+                                        // Expose the raw partial JSON while the call is generated.
+                                        if let Part::FunctionCall { arguments, .. } = part {
+                                            *arguments = Value::String(buffer.2.clone());
+                                        }
                                     }
                                 },
                                 AnthropicDelta::Thinking { thinking } => {
@@ -271,21 +850,25 @@ impl AnthropicStream {
                     },
                     AnthropicStreamEvent::ContentBlockStop { index } => {
                         let parts = current_response.data[0].parts_mut();
-                        if let Some(part) = parts.get_mut(index as usize) {
+                        if let Some(part) = block_parts.get(&index).and_then(|&i| parts.get_mut(i)) {
                             match part {
                                 Part::Text { finished, .. } => *finished = true,
                                 Part::Reasoning { finished, .. } => *finished = true,
-                                Part::FunctionCall { finished, arguments, .. } => {
+                                Part::FunctionCall { finished, arguments, repaired, .. } => {
                                     *finished = true;
                                     if let Some((_, _, json_str)) = tool_buffers.remove(&index) {
-                                        if let Ok(json_val) = serde_json::from_str(&json_str) {
-                                            *arguments = json_val;
+                                        // Calls without input stream no JSON at all.
+                                        if !json_str.is_empty() {
+                                            (*arguments, *repaired) = parse_arguments(&json_str);
+                                        } else {
+                                            *arguments = json!({});
                                         }
                                     }
                                 },
                                 Part::FunctionResponse { finished, .. } => *finished = true,
                                 Part::Media { finished, .. } => *finished = true,
                             }
+                            part.annotate();
                         }
                         yield current_response.clone();
                     },
@@ -299,6 +882,7 @@ impl AnthropicStream {
                                 _ => FinishReason::Stop,
                             };
                         }
+                        current_response.stop_sequence = delta.stop_sequence;
                         if let Some(usage_delta) = usage {
                             current_response.usage.completion_tokens = Some(usage_delta.output_tokens);
                         }
@@ -309,7 +893,18 @@ impl AnthropicStream {
                     },
                     AnthropicStreamEvent::Ping => {},
                     AnthropicStreamEvent::Error { error } => {
-                        Err(ClientError::ProviderError(format!("Stream error ({}): {}", error.error_type, error.message)))?;
+                        // Errors inside an event stream carry no HTTP status; map the documented types.
+                        let status = match error.error_type.as_str() {
+                            "rate_limit_error" => Some(429),
+                            "api_error" => Some(500),
+                            "overloaded_error" => Some(529),
+                            _ => None,
+                        };
+                        Err(ClientError::Api {
+                            status,
+                            request_id: None,
+                            message: format!("Stream error ({}): {}", error.error_type, error.message),
+                        })?;
                     }
                 }
             }
@@ -317,12 +912,133 @@ impl AnthropicStream {
     }
 }
 
+/// Render a conversation in the Messages API training layout (`system`, `messages`, `tools`).
+pub(crate) fn training_example(
+    messages: Vec<Message>,
+    model_options: &ModelOptions<AnthropicModel>,
+    tools: Vec<rmcp::model::Tool>,
+) -> Result<Value, ClientError> {
+    let request = AnthropicRequest::new(messages, model_options, String::new(), tools, false)?;
+    let mut example = serde_json::to_value(request)?;
+    if let Value::Object(fields) = &mut example {
+        fields.retain(|key, _| key == "system" || key == "messages" || key == "tools");
+    }
+    Ok(example)
+}
+
+/// Parse a Messages API `messages` array (or a single message).
+pub(crate) fn messages_from_json(value: Value) -> Result<Vec<Message>, ClientError> {
+    let wire: Vec<AnthropicInputMessage> = match value {
+        Value::Array(_) => serde_json::from_value(value)?,
+        other => vec![serde_json::from_value(other)?],
+    };
+
+    let mut call_names = HashMap::new();
+    let mut messages = Vec::new();
+
+    for message in wire {
+        let blocks = match message.content {
+            AnthropicInputContent::Text(text) => vec![AnthropicContentBlock::Text {
+                text,
+                citations: None,
+                cache_control: None,
+            }],
+            AnthropicInputContent::Blocks(blocks) => blocks,
+        };
+
+        let parts = blocks
+            .into_iter()
+            .filter_map(|block| match block {
+                AnthropicContentBlock::Text {
+                    text, citations, ..
+                } => Some(text_part(text, citations, true)),
+                AnthropicContentBlock::Image { source, .. } => Some(source.into_part()),
+                AnthropicContentBlock::Document { source, .. } => Some(source.into_part()),
+                AnthropicContentBlock::ToolUse {
+                    id, name, input, ..
+                } => {
+                    call_names.insert(id.clone(), name.clone());
+                    Some(Part::FunctionCall {
+                        id: Some(id),
+                        name,
+                        arguments: input,
+                        signature: None,
+                        repaired: false,
+                        extensions: Extensions::new(),
+                        finished: true,
+                    })
+                }
+                AnthropicContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                    ..
+                } => {
+                    let (text, parts) = match content {
+                        AnthropicToolResultContent::Text(text) => (text, Vec::new()),
+                        AnthropicToolResultContent::Blocks(blocks) => {
+                            let mut text = String::new();
+                            let mut parts = Vec::new();
+                            for block in blocks {
+                                match block {
+                                    AnthropicToolResultBlock::Text { text: t } => text.push_str(&t),
+                                    AnthropicToolResultBlock::Image { source } => {
+                                        parts.push(source.into_part())
+                                    }
+                                }
+                            }
+                            (text, parts)
+                        }
+                    };
+                    let error = is_error
+                        .unwrap_or(false)
+                        .then(|| ToolError::execution(text.clone()));
+                    let response = if text.is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&text).unwrap_or_else(|_| json!({ "response": text }))
+                    };
+                    Some(Part::FunctionResponse {
+                        name: call_names.get(&tool_use_id).cloned().unwrap_or_default(),
+                        id: Some(tool_use_id),
+                        response,
+                        parts,
+                        error,
+                        finished: true,
+                    })
+                }
+                AnthropicContentBlock::Thinking {
+                    thinking,
+                    signature,
+                } => Some(Part::Reasoning {
+                    content: thinking.into(),
+                    summary: None,
+                    signature: Some(signature),
+                    extensions: Extensions::new(),
+                    finished: true,
+                }),
+                AnthropicContentBlock::RedactedThinking { .. } => None,
+            })
+            .collect();
+
+        let converted = match message.role.as_str() {
+            "assistant" => Message::Assistant(parts),
+            _ => Message::User(parts),
+        };
+        push_merged(&mut messages, converted);
+    }
+
+    Ok(messages)
+}
+
 // --- Request Types ---
 
 #[skip_serializing_none]
 #[derive(Debug, Serialize)]
 struct AnthropicRequest {
-    model: String,
+    model: Option<String>,
+    anthropic_version: Option<&'static str>,
+    anthropic_beta: Option<Vec<AnthropicBeta>>,
     messages: Vec<AnthropicMessage>,
     max_tokens: u32,
     system: Option<Vec<AnthropicSystemBlock>>,
@@ -362,6 +1078,20 @@ struct AnthropicMessage {
     content: Vec<AnthropicContentBlock>,
 }
 
+/// A message as accepted by the Messages API, where content may be a plain string.
+#[derive(Debug, Deserialize)]
+struct AnthropicInputMessage {
+    role: String,
+    content: AnthropicInputContent,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AnthropicInputContent {
+    Text(String),
+    Blocks(Vec<AnthropicContentBlock>),
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum AnthropicSystemBlock {
@@ -375,7 +1105,10 @@ enum AnthropicSystemBlock {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum AnthropicCacheControl {
-    Ephemeral,
+    Ephemeral {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl: Option<AnthropicCacheTtl>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -397,6 +1130,8 @@ enum AnthropicToolResultContent {
 enum AnthropicContentBlock {
     Text {
         text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<Vec<Value>>,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<AnthropicCacheControl>,
     },
@@ -434,20 +1169,94 @@ enum AnthropicContentBlock {
     },
 }
 
+impl AnthropicContentBlock {
+    /// Breakpoint slot of the block, for blocks that can be cached.
+    fn cache_control_mut(&mut self) -> Option<&mut Option<AnthropicCacheControl>> {
+        match self {
+            Self::Text { cache_control, .. }
+            | Self::Image { cache_control, .. }
+            | Self::Document { cache_control, .. }
+            | Self::ToolUse { cache_control, .. }
+            | Self::ToolResult { cache_control, .. } => Some(cache_control),
+            Self::Thinking { .. } | Self::RedactedThinking { .. } => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-struct AnthropicImageSource {
-    #[serde(rename = "type")]
-    source_type: String,
-    media_type: String,
-    data: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicImageSource {
+    Base64 {
+        media_type: String,
+        #[serde(with = "base64_data")]
+        data: Bytes,
+    },
+    Url {
+        url: String,
+    },
+}
+
+impl AnthropicImageSource {
+    fn from_media(data: &Bytes, mime_type: &str, uri: &Option<String>) -> Self {
+        match uri {
+            Some(url) if data.is_empty() => Self::Url { url: url.clone() },
+            _ => Self::Base64 {
+                media_type: mime_type.to_string(),
+                data: data.clone(),
+            },
+        }
+    }
+
+    fn into_part(self) -> Part {
+        match self {
+            Self::Base64 { media_type, data } => Part::Media {
+                media_type: MediaType::Image,
+                data,
+                mime_type: media_type,
+                uri: None,
+                finished: true,
+            },
+            Self::Url { url } => Part::remote_media(MediaType::Image, "image/*", url),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct AnthropicDocumentSource {
-    #[serde(rename = "type")]
-    pub source_type: String,
-    pub media_type: String,
-    pub data: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicDocumentSource {
+    Base64 {
+        media_type: String,
+        #[serde(with = "base64_data")]
+        data: Bytes,
+    },
+    Url {
+        url: String,
+    },
+}
+
+impl AnthropicDocumentSource {
+    fn from_media(data: &Bytes, mime_type: &str, uri: &Option<String>) -> Self {
+        match uri {
+            Some(url) if data.is_empty() => Self::Url { url: url.clone() },
+            _ => Self::Base64 {
+                media_type: mime_type.to_string(),
+                data: data.clone(),
+            },
+        }
+    }
+
+    fn into_part(self) -> Part {
+        match self {
+            Self::Base64 { media_type, data } => Part::Media {
+                media_type: MediaType::Document,
+                data,
+                mime_type: media_type,
+                uri: None,
+                finished: true,
+            },
+            Self::Url { url } => Part::remote_media(MediaType::Document, "application/pdf", url),
+        }
+    }
 }
 
 impl AnthropicRequest {
@@ -457,7 +1266,13 @@ impl AnthropicRequest {
         model: String,
         tool_defs: Vec<rmcp::model::Tool>,
         stream: bool,
-    ) -> Self {
+    ) -> Result<Self, ClientError> {
+        if let Some(message) = model_options.provider.excess_breakpoints() {
+            return Err(ClientError::Config(format!(
+                "Anthropic options {}",
+                message
+            )));
+        }
         let mut messages = Vec::new();
 
         for msg in messages_in {
@@ -469,51 +1284,53 @@ impl AnthropicRequest {
             let mut content_blocks = Vec::new();
             for part in msg.parts() {
                 match part {
-                    Part::Text { content: t, .. } => {
-                        content_blocks.push(AnthropicContentBlock::Text {
-                            text: t.clone(),
-                            cache_control: None,
-                        })
-                    }
+                    Part::Text {
+                        content: t,
+                        extensions,
+                        ..
+                    } => content_blocks.push(AnthropicContentBlock::Text {
+                        text: t.to_string(),
+                        citations: extensions
+                            .get("citations")
+                            .and_then(Value::as_array)
+                            .cloned(),
+                        cache_control: None,
+                    }),
                     Part::Media {
                         media_type,
                         data,
                         mime_type,
+                        uri,
                         ..
                     } => {
-                        content_blocks.push(AnthropicContentBlock::Text {
-                            text: part.anchor_media(),
-                            cache_control: None,
-                        });
+                        if model_options.anchors_media() {
+                            content_blocks.push(AnthropicContentBlock::Text {
+                                text: part.anchor_media(),
+                                citations: None,
+                                cache_control: None,
+                            });
+                        }
 
                         match media_type {
                             MediaType::Image => {
                                 content_blocks.push(AnthropicContentBlock::Image {
-                                    source: AnthropicImageSource {
-                                        source_type: "base64".to_string(),
-                                        media_type: mime_type.clone(),
-                                        data: data.clone(),
-                                    },
+                                    source: AnthropicImageSource::from_media(data, mime_type, uri),
                                     cache_control: None,
                                 });
                             }
                             MediaType::Document => {
                                 content_blocks.push(AnthropicContentBlock::Document {
-                                    source: AnthropicDocumentSource {
-                                        source_type: "base64".to_string(),
-                                        media_type: mime_type.clone(),
-                                        data: data.clone(),
-                                    },
+                                    source: AnthropicDocumentSource::from_media(
+                                        data, mime_type, uri,
+                                    ),
                                     cache_control: None,
                                 });
                             }
+                            MediaType::Video => return Err(Self::unsupported_video()),
                             MediaType::Text | MediaType::Binary => {
-                                let content = match BASE64_STANDARD.decode(data) {
-                                    Ok(bytes) => String::from_utf8(bytes).unwrap_or(data.clone()),
-                                    Err(_) => data.clone(),
-                                };
                                 content_blocks.push(AnthropicContentBlock::Text {
-                                    text: content,
+                                    text: base64_data::to_text(data),
+                                    citations: None,
                                     cache_control: None,
                                 });
                             }
@@ -538,48 +1355,55 @@ impl AnthropicRequest {
                         id,
                         response,
                         parts,
+                        error,
                         ..
                     } => {
                         if let Some(call_id) = id {
                             let mut blocks = Vec::new();
 
-                            if response.clone() != json!({}) {
+                            if let Some(error) = error {
+                                blocks.push(AnthropicToolResultBlock::Text {
+                                    text: error.message.clone(),
+                                });
+                            } else if response.clone() != json!({}) {
                                 blocks.push(AnthropicToolResultBlock::Text {
                                     text: serde_json::to_string(&response).unwrap_or_default(),
                                 });
                             }
 
                             for part in parts {
+                                // The error message already holds the text of failed calls.
+                                if let (Part::Text { content, .. }, None) = (part, error) {
+                                    blocks.push(AnthropicToolResultBlock::Text {
+                                        text: content.to_string(),
+                                    });
+                                }
                                 if let Part::Media {
                                     media_type,
                                     data,
                                     mime_type,
+                                    uri,
                                     ..
                                 } = part
                                 {
-                                    blocks.push(AnthropicToolResultBlock::Text {
-                                        text: part.anchor_media(),
-                                    });
+                                    if model_options.anchors_media() {
+                                        blocks.push(AnthropicToolResultBlock::Text {
+                                            text: part.anchor_media(),
+                                        });
+                                    }
 
                                     match media_type {
                                         MediaType::Image => {
                                             blocks.push(AnthropicToolResultBlock::Image {
-                                                source: AnthropicImageSource {
-                                                    source_type: "base64".to_string(),
-                                                    media_type: mime_type.clone(),
-                                                    data: data.clone(),
-                                                },
+                                                source: AnthropicImageSource::from_media(
+                                                    data, mime_type, uri,
+                                                ),
                                             });
                                         }
+                                        MediaType::Video => return Err(Self::unsupported_video()),
                                         _ => {
-                                            let content = match BASE64_STANDARD.decode(data) {
-                                                Ok(bytes) => {
-                                                    String::from_utf8(bytes).unwrap_or(data.clone())
-                                                }
-                                                Err(_) => data.clone(),
-                                            };
                                             blocks.push(AnthropicToolResultBlock::Text {
-                                                text: content,
+                                                text: base64_data::to_text(data),
                                             });
                                         }
                                     }
@@ -589,7 +1413,7 @@ impl AnthropicRequest {
                             content_blocks.push(AnthropicContentBlock::ToolResult {
                                 tool_use_id: call_id.clone(),
                                 content: AnthropicToolResultContent::Blocks(blocks),
-                                is_error: None,
+                                is_error: error.is_some().then_some(true),
                                 cache_control: None,
                             });
                         }
@@ -598,7 +1422,7 @@ impl AnthropicRequest {
                         content, signature, ..
                     } => {
                         content_blocks.push(AnthropicContentBlock::Thinking {
-                            thinking: content.clone(),
+                            thinking: content.to_string(),
                             signature: signature.clone().unwrap_or_default(),
                         });
                     }
@@ -613,7 +1437,24 @@ impl AnthropicRequest {
             }
         }
 
-        let tools = tool_defs
+        let cached_messages = model_options.provider.cache_messages.unwrap_or(0);
+        for message in messages
+            .iter_mut()
+            .rev()
+            .filter(|message| message.role == "user")
+            .take(cached_messages)
+        {
+            if let Some(cache_control) = message
+                .content
+                .iter_mut()
+                .rev()
+                .find_map(AnthropicContentBlock::cache_control_mut)
+            {
+                *cache_control = Some(model_options.provider.cache_control());
+            }
+        }
+
+        let mut tools: Vec<AnthropicTool> = tool_defs
             .into_iter()
             .map(|t| AnthropicTool {
                 name: t.name.into_owned(),
@@ -622,32 +1463,51 @@ impl AnthropicRequest {
                 cache_control: None,
             })
             .collect();
+        // A breakpoint on the last tool caches the whole tools array.
+        if model_options.provider.cache_tools.unwrap_or(false) {
+            if let Some(tool) = tools.last_mut() {
+                tool.cache_control = Some(model_options.provider.cache_control());
+            }
+        }
 
         let thinking = if model_options.reasoning.unwrap_or(false) {
-            if let Some(budget) = model_options.provider.thinking_budget {
-                Some(AnthropicThinkingConfig::Enabled {
-                    budget_tokens: budget,
-                })
-            } else {
-                Some(AnthropicThinkingConfig::Enabled {
-                    budget_tokens: 1024,
-                })
-            }
+            Some(AnthropicThinkingConfig::Enabled {
+                budget_tokens: model_options
+                    .provider
+                    .thinking_budget
+                    .unwrap_or(MIN_THINKING_BUDGET),
+            })
         } else {
             None
         };
 
-        let system = model_options.system.as_ref().map(|s| {
+        let system = model_options.system_prompt(PromptStyle::Xml).map(|text| {
             vec![AnthropicSystemBlock::Text {
-                text: s.clone(),
-                cache_control: None,
+                text,
+                cache_control: model_options
+                    .provider
+                    .cache_system
+                    .unwrap_or(false)
+                    .then(|| model_options.provider.cache_control()),
             }]
         });
 
-        AnthropicRequest {
-            model,
+        let max_tokens = model_options.max_tokens.unwrap_or_else(|| {
+            let max_tokens = default_max_tokens(&model);
+            tracing::warn!(
+                "max_tokens not set for {}, defaulting to {}",
+                model,
+                max_tokens
+            );
+            max_tokens
+        });
+
+        Ok(AnthropicRequest {
+            model: Some(model),
+            anthropic_version: None,
+            anthropic_beta: None,
             messages,
-            max_tokens: model_options.max_tokens.unwrap_or(1024),
+            max_tokens,
             system,
             temperature: model_options.temperature,
             top_p: model_options.top_p,
@@ -657,8 +1517,19 @@ impl AnthropicRequest {
             tool_choice: model_options.provider.tool_choice.clone(),
             metadata: model_options.provider.metadata.clone(),
             stop_sequences: model_options.provider.stop_sequences.clone(),
-            service_tier: model_options.provider.service_tier.clone(),
+            service_tier: model_options
+                .provider
+                .service_tier
+                .clone()
+                .or_else(|| model_options.service_tier.and_then(ServiceTier::from_tier)),
             thinking,
+        })
+    }
+
+    fn unsupported_video() -> ClientError {
+        ClientError::Unsupported {
+            provider: "Anthropic".to_string(),
+            capability: "video input".to_string(),
         }
     }
 }
@@ -688,6 +1559,23 @@ struct AnthropicUsage {
     cache_creation_input_tokens: Option<u32>,
     #[serde(default)]
     cache_read_input_tokens: Option<u32>,
+    #[serde(default)]
+    service_tier: Option<String>,
+}
+
+impl From<&AnthropicUsage> for Usage {
+    /// Anthropic counts cached tokens apart from `input_tokens`; they are added back
+    /// so that `prompt_tokens` is the size of the whole prompt.
+    fn from(usage: &AnthropicUsage) -> Self {
+        let cache_read = usage.cache_read_input_tokens.unwrap_or(0);
+        let cache_creation = usage.cache_creation_input_tokens.unwrap_or(0);
+        Usage {
+            prompt_tokens: Some(usage.input_tokens + cache_read + cache_creation),
+            completion_tokens: Some(usage.output_tokens),
+            cached_prompt_tokens: usage.cache_read_input_tokens,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -702,18 +1590,34 @@ struct AnthropicError {
     message: String,
 }
 
+/// Text part for a text block, keeping its citations as an extension.
+fn text_part(text: String, citations: Option<Vec<Value>>, finished: bool) -> Part {
+    let mut extensions = Extensions::new();
+    if let Some(citations) = citations.filter(|citations| !citations.is_empty()) {
+        extensions.insert("citations".to_string(), Value::Array(citations));
+    }
+    let mut part = Part::Text {
+        content: text.into(),
+        signature: None,
+        extensions,
+        annotations: TextAnnotations::default(),
+        finished,
+    };
+    if finished {
+        part.annotate();
+    }
+    part
+}
+
 impl From<AnthropicResponse> for Response {
     fn from(resp: AnthropicResponse) -> Self {
         let mut parts = Vec::new();
 
         for content in resp.content {
             match content {
-                AnthropicContentBlock::Text { text, .. } => {
-                    parts.push(Part::Text {
-                        content: text,
-                        finished: true,
-                    });
-                }
+                AnthropicContentBlock::Text {
+                    text, citations, ..
+                } => parts.push(text_part(text, citations, true)),
                 AnthropicContentBlock::ToolUse {
                     id, name, input, ..
                 } => {
@@ -722,6 +1626,8 @@ impl From<AnthropicResponse> for Response {
                         name,
                         arguments: input,
                         signature: None,
+                        repaired: false,
+                        extensions: Extensions::new(),
                         finished: true,
                     });
                 }
@@ -730,9 +1636,10 @@ impl From<AnthropicResponse> for Response {
                     signature,
                 } => {
                     parts.push(Part::Reasoning {
-                        content: thinking,
+                        content: thinking.into(),
                         summary: None,
                         signature: Some(signature),
+                        extensions: Extensions::new(),
                         finished: true,
                     });
                 }
@@ -751,15 +1658,42 @@ impl From<AnthropicResponse> for Response {
 
         Response {
             data: vec![Message::Assistant(parts)],
-            usage: Usage {
-                prompt_tokens: Some(resp.usage.input_tokens),
-                completion_tokens: Some(resp.usage.output_tokens),
-            },
+            usage: Usage::from(&resp.usage),
             finish: finish_reason,
+            stop_sequence: resp.stop_sequence,
+            service_tier: resp
+                .usage
+                .service_tier
+                .as_deref()
+                .and_then(options::ServiceTier::from_reported),
         }
     }
 }
 
+// --- Batch Types ---
+
+#[derive(Debug, Deserialize)]
+struct AnthropicBatch {
+    id: String,
+    processing_status: String,
+    results_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicBatchEntry {
+    custom_id: String,
+    result: AnthropicBatchOutcome,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBatchOutcome {
+    Succeeded { message: AnthropicResponse },
+    Errored { error: AnthropicErrorResponse },
+    Canceled,
+    Expired,
+}
+
 // --- SSE Event Types ---
 
 #[derive(Debug, Deserialize)]
@@ -801,6 +1735,8 @@ enum AnthropicDelta {
     Thinking { thinking: String },
     #[serde(rename = "signature_delta")]
     Signature { signature: String },
+    #[serde(rename = "citations_delta")]
+    Citations { citation: Value },
 }
 
 #[derive(Debug, Deserialize)]
@@ -809,3 +1745,480 @@ struct AnthropicMessageDelta {
     stop_reason: Option<String>,
     stop_sequence: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::PartIndex;
+    use crate::test_fixtures::image;
+    use rmcp::model::Tool;
+    use std::sync::Arc;
+
+    fn block_types(options: &ModelOptions<AnthropicModel>) -> Vec<String> {
+        let messages = vec![Message::User(vec![
+            Part::text("first"),
+            image("a"),
+            Part::text("second"),
+            image("b"),
+        ])];
+        let request =
+            AnthropicRequest::new(messages, options, "claude".to_string(), vec![], false).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        body["messages"][0]["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| match block["type"].as_str().unwrap() {
+                "text" => format!("text:{}", block["text"].as_str().unwrap()),
+                "image" => format!("image:{}", block["source"]["data"].as_str().unwrap()),
+                other => other.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_beta_header() {
+        let mut options = ModelOptions::<AnthropicModel>::new("claude");
+        options.provider.betas = Some(vec![
+            AnthropicBeta::TokenEfficientTools,
+            AnthropicBeta::Other("custom-2025-01-01".to_string()),
+        ]);
+        let client = AnthropicClient::new(
+            "key".to_string(),
+            "https://api.anthropic.com/v1".to_string(),
+            options,
+            TransportOptions::default(),
+        );
+
+        let headers = client.headers().unwrap();
+        assert_eq!(
+            headers["anthropic-beta"],
+            "token-efficient-tools-2025-02-19,custom-2025-01-01"
+        );
+    }
+
+    #[test]
+    fn test_backend_request_shapes() {
+        let mut options = ModelOptions::<AnthropicModel>::new("anthropic.claude-sonnet-4");
+        options.provider.betas = Some(vec![AnthropicBeta::TokenEfficientTools]);
+        let client = AnthropicClient::new(
+            "key".to_string(),
+            AnthropicBackend::Anthropic.base_url(),
+            options,
+            TransportOptions::default(),
+        );
+
+        let build = |client: &AnthropicClient, stream: bool| {
+            let request = client
+                .build_request(vec![Message::user("Hi")], vec![], stream)
+                .unwrap()
+                .build()
+                .unwrap();
+            let body: Value =
+                serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+            (request, body)
+        };
+
+        let (request, body) = build(&client, true);
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(request.headers()["x-api-key"], "key");
+        assert_eq!(body["model"], "anthropic.claude-sonnet-4");
+
+        let bedrock = client.clone().with_backend(AnthropicBackend::Bedrock {
+            region: "eu-west-1".to_string(),
+        });
+        let (request, body) = build(&bedrock, true);
+        assert_eq!(
+            request.url().as_str(),
+            "https://bedrock-runtime.eu-west-1.amazonaws.com/model/anthropic.claude-sonnet-4/invoke-with-response-stream"
+        );
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer key");
+        assert!(request.headers().get("anthropic-beta").is_none());
+        assert_eq!(body["anthropic_version"], BEDROCK_ANTHROPIC_VERSION);
+        assert_eq!(
+            body["anthropic_beta"],
+            json!(["token-efficient-tools-2025-02-19"])
+        );
+        assert!(body.get("model").is_none() && body.get("stream").is_none());
+
+        let vertex = client.with_region(VertexLocation::new("acme", "us-east5"));
+        let (request, body) = build(&vertex, false);
+        assert!(request
+            .url()
+            .as_str()
+            .ends_with("/models/anthropic.claude-sonnet-4:rawPredict"));
+        assert!(request.headers().get("x-api-key").is_none());
+        assert_eq!(body["anthropic_version"], VERTEX_ANTHROPIC_VERSION);
+    }
+
+    #[test]
+    fn test_beta_serde_round_trip() {
+        let betas = vec![
+            AnthropicBeta::Context1M,
+            AnthropicBeta::Other("custom".to_string()),
+        ];
+        let json = serde_json::to_value(&betas).unwrap();
+        assert_eq!(json, json!(["context-1m-2025-08-07", "custom"]));
+
+        let parsed: Vec<AnthropicBeta> = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, betas);
+    }
+
+    #[test]
+    fn test_decode_batch_line() {
+        let succeeded = br#"{"custom_id":"req-1","result":{"type":"succeeded","message":{"id":"msg_1","type":"message","role":"assistant","content":[{"type":"text","text":"Hi"}],"model":"claude","stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":3,"output_tokens":1}}}}"#;
+        let (custom_id, result) = decode_batch_line(succeeded).unwrap().unwrap();
+        assert_eq!(custom_id, "req-1");
+        let response = result.unwrap();
+        assert_eq!(response.data[0].content().as_deref(), Some("Hi"));
+        assert_eq!(response.finish, FinishReason::Stop);
+
+        let errored = br#"{"custom_id":"req-2","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"bad"}}}}"#;
+        let (custom_id, result) = decode_batch_line(errored).unwrap().unwrap();
+        assert_eq!(custom_id, "req-2");
+        assert!(matches!(
+            result,
+            Err(ClientError::Api { status: None, message, .. })
+                if message == "Anthropic error (invalid_request_error): bad"
+        ));
+
+        let canceled = br#"{"custom_id":"req-3","result":{"type":"canceled"}}"#;
+        let (custom_id, result) = decode_batch_line(canceled).unwrap().unwrap();
+        assert_eq!(custom_id, "req-3");
+        assert!(matches!(
+            result,
+            Err(ClientError::BatchItem {
+                outcome: BatchItemOutcome::Canceled
+            })
+        ));
+
+        let expired = br#"{"custom_id":"req-4","result":{"type":"expired"}}"#;
+        let (custom_id, result) = decode_batch_line(expired).unwrap().unwrap();
+        assert_eq!(custom_id, "req-4");
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error,
+            ClientError::BatchItem {
+                outcome: BatchItemOutcome::Expired
+            }
+        ));
+        assert_eq!(error.code(), "batch_item");
+
+        assert!(decode_batch_line(b"  \n").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_stop_sequence_is_reported() {
+        let response: AnthropicResponse = serde_json::from_value(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": "Hi" }],
+            "model": "claude",
+            "stop_reason": "stop_sequence",
+            "stop_sequence": "END",
+            "usage": { "input_tokens": 3, "output_tokens": 1 }
+        }))
+        .unwrap();
+        let response = Response::from(response);
+
+        assert_eq!(response.finish, FinishReason::Stop);
+        assert_eq!(response.stop_sequence.as_deref(), Some("END"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_parts_follow_block_order() {
+        let events = [
+            json!({ "type": "message_start", "message": {
+                "id": "msg_1", "type": "message", "role": "assistant", "content": [],
+                "model": "claude", "usage": { "input_tokens": 3, "output_tokens": 0 }
+            }}),
+            json!({ "type": "content_block_start", "index": 0,
+                "content_block": { "type": "redacted_thinking", "data": "abc" } }),
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({ "type": "content_block_start", "index": 1,
+                "content_block": { "type": "text", "text": "" } }),
+            json!({ "type": "content_block_delta", "index": 1,
+                "delta": { "type": "text_delta", "text": "Hi" } }),
+            json!({ "type": "content_block_stop", "index": 1 }),
+            json!({ "type": "content_block_start", "index": 2,
+                "content_block": { "type": "tool_use", "id": "call_1", "name": "lookup", "input": {} } }),
+            json!({ "type": "content_block_delta", "index": 2,
+                "delta": { "type": "input_json_delta", "partial_json": "{\"q\":1}" } }),
+            json!({ "type": "content_block_stop", "index": 2 }),
+        ];
+        let events = futures::stream::iter(events.map(|event| Ok(event.to_string())));
+        let snapshots: Vec<Response> = AnthropicStream::create_stream(events)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let last = snapshots.last().unwrap();
+        let parts: Vec<_> = last.indexed_parts().collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            parts[1].0,
+            PartIndex {
+                message: 0,
+                part: 1
+            }
+        );
+        assert!(
+            matches!(parts[0].1, Part::Text { content, finished: true, .. } if content == "Hi")
+        );
+        assert!(matches!(
+            parts[1].1,
+            Part::FunctionCall { arguments, finished: true, .. } if arguments == &json!({ "q": 1 })
+        ));
+    }
+
+    #[test]
+    fn test_citations_are_kept_and_replayed() {
+        let citation = json!({
+            "type": "char_location",
+            "cited_text": "The sky is blue.",
+            "document_index": 0,
+            "start_char_index": 0,
+            "end_char_index": 16
+        });
+        let response: AnthropicResponse = serde_json::from_value(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": "It is blue.", "citations": [citation] }],
+            "model": "claude",
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 3, "output_tokens": 1 }
+        }))
+        .unwrap();
+        let response = Response::from(response);
+
+        let part = &response.data[0].parts()[0];
+        assert_eq!(part.extensions().unwrap()["citations"], json!([citation]));
+        let Part::Text { annotations, .. } = part else {
+            panic!("expected text");
+        };
+        assert_eq!(
+            annotations.citations[0].cited_text.as_deref(),
+            Some("The sky is blue.")
+        );
+
+        let request = AnthropicRequest::new(
+            response.data,
+            &ModelOptions::new("claude"),
+            "claude".to_string(),
+            vec![],
+            false,
+        )
+        .unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["messages"][0]["content"][0]["citations"],
+            json!([citation])
+        );
+    }
+
+    #[test]
+    fn test_interleaved_media_keeps_order() {
+        assert_eq!(
+            block_types(&ModelOptions::new("claude")),
+            vec![
+                "text:first",
+                "text:File (image/png) at a.png:",
+                "image:YQ==",
+                "text:second",
+                "text:File (image/png) at b.png:",
+                "image:Yg==",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_media_anchors_can_be_disabled() {
+        let mut options = ModelOptions::new("claude");
+        options.anchor_media = Some(false);
+
+        assert_eq!(
+            block_types(&options),
+            vec!["text:first", "image:YQ==", "text:second", "image:Yg=="]
+        );
+    }
+
+    #[test]
+    fn test_remote_image_uses_url_source() {
+        let messages = vec![Message::User(vec![Part::remote_media(
+            MediaType::Image,
+            "image/png",
+            "https://example.com/cat.png",
+        )])];
+        let mut options = ModelOptions::new("claude");
+        options.anchor_media = Some(false);
+
+        let request =
+            AnthropicRequest::new(messages, &options, "claude".to_string(), vec![], false).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["messages"][0]["content"][0]["source"],
+            json!({ "type": "url", "url": "https://example.com/cat.png" })
+        );
+    }
+
+    #[test]
+    fn test_default_max_tokens_uses_model_limit() {
+        assert_eq!(default_max_tokens("claude-sonnet-4-5-20250929"), 64_000);
+        assert_eq!(default_max_tokens("claude-opus-4-1"), 32_000);
+        assert_eq!(default_max_tokens("claude-3-5-haiku-latest"), 8_192);
+        assert_eq!(default_max_tokens("claude-custom"), FALLBACK_MAX_TOKENS);
+
+        let messages = vec![Message::User(vec![Part::text("Hi")])];
+        let options = ModelOptions::new("claude-haiku-4-5");
+        let request = AnthropicRequest::new(
+            messages,
+            &options,
+            "claude-haiku-4-5".to_string(),
+            vec![],
+            false,
+        )
+        .unwrap();
+        assert_eq!(request.max_tokens, 64_000);
+    }
+
+    #[test]
+    fn test_video_is_unsupported() {
+        let messages = vec![Message::User(vec![Part::Media {
+            media_type: MediaType::Video,
+            data: Bytes::from_static(&[0; 3]),
+            mime_type: "video/mp4".to_string(),
+            uri: None,
+            finished: true,
+        }])];
+        let options = ModelOptions::new("claude");
+
+        let result = AnthropicRequest::new(messages, &options, "claude".to_string(), vec![], false);
+
+        assert!(matches!(result, Err(ClientError::Unsupported { .. })));
+    }
+
+    #[test]
+    fn test_tool_error_sets_is_error() {
+        let messages = vec![
+            Message::Assistant(vec![Part::FunctionCall {
+                id: Some("toolu_1".to_string()),
+                name: "weather".to_string(),
+                arguments: json!({}),
+                signature: None,
+                repaired: false,
+                extensions: Extensions::new(),
+                finished: true,
+            }]),
+            Message::User(vec![Part::function_error(
+                Some("toolu_1".to_string()),
+                "weather",
+                ToolError::execution("City not found"),
+            )]),
+        ];
+        let options = ModelOptions::new("claude");
+
+        let request =
+            AnthropicRequest::new(messages, &options, "claude".to_string(), vec![], false).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        let result = &body["messages"][1]["content"][0];
+        assert_eq!(result["is_error"], json!(true));
+        assert_eq!(result["content"][0]["text"], json!("City not found"));
+
+        let imported = messages_from_json(body["messages"].clone()).unwrap();
+        assert!(matches!(
+            &imported[1].parts()[0],
+            Part::FunctionResponse { error: Some(error), .. } if error.message == "City not found"
+        ));
+    }
+
+    #[test]
+    fn test_cache_breakpoints() {
+        let options = ModelOptions::<AnthropicModel>::new("claude")
+            .with_system("Be brief.")
+            .with_provider(AnthropicModel {
+                cache_system: Some(true),
+                cache_messages: Some(1),
+                cache_ttl: Some(AnthropicCacheTtl::OneHour),
+                ..Default::default()
+            });
+        let messages = vec![
+            Message::user("First"),
+            Message::assistant("Answer"),
+            Message::User(vec![Part::text("Second"), Part::text("Third")]),
+        ];
+
+        let request = AnthropicRequest::new(
+            messages.clone(),
+            &options,
+            "claude".to_string(),
+            vec![],
+            false,
+        )
+        .unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        let breakpoint = json!({ "type": "ephemeral", "ttl": "1h" });
+        assert_eq!(body["system"][0]["cache_control"], breakpoint);
+        assert!(body["messages"][0]["content"][0]
+            .get("cache_control")
+            .is_none());
+        assert!(body["messages"][2]["content"][0]
+            .get("cache_control")
+            .is_none());
+        assert_eq!(
+            body["messages"][2]["content"][1]["cache_control"],
+            breakpoint
+        );
+
+        let mut options = options;
+        options.provider.cache_tools = Some(true);
+        options.provider.cache_messages = Some(3);
+        let error = options.validate().unwrap_err();
+        assert_eq!(error.violations[0].field, "provider.cache_messages");
+        assert!(matches!(
+            AnthropicRequest::new(messages, &options, "claude".to_string(), vec![], false),
+            Err(ClientError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_tools_are_cached() {
+        let tool = |name: &'static str| Tool::new(name, "A tool", Arc::new(Default::default()));
+        let options = ModelOptions::<AnthropicModel>::new("claude").with_provider(AnthropicModel {
+            cache_tools: Some(true),
+            ..Default::default()
+        });
+
+        let request = AnthropicRequest::new(
+            vec![Message::user("Hi")],
+            &options,
+            "claude".to_string(),
+            vec![tool("first"), tool("last")],
+            false,
+        )
+        .unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(
+            body["tools"][1]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+
+        let usage: AnthropicUsage = serde_json::from_value(json!({
+            "input_tokens": 20,
+            "output_tokens": 5,
+            "cache_read_input_tokens": 1500
+        }))
+        .unwrap();
+        let usage = Usage::from(&usage);
+        assert_eq!(usage.prompt_tokens, Some(1520));
+        assert_eq!(usage.cached_prompt_tokens, Some(1500));
+    }
+}
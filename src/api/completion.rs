@@ -0,0 +1,658 @@
+//! Client for raw text completion endpoints driven by a chat template.
+//!
+//! Local backends such as llama.cpp, vLLM and Ollama expose an OpenAI-style
+//! `/completions` endpoint that takes a single prompt string. [`CompletionClient`]
+//! renders the conversation with a [`ChatTemplate`] and reads the completion back as
+//! assistant text, so such models can be used wherever a [`Client`] is expected.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::constraints::PromptStyle;
+use crate::http::{
+    add_extra_headers, build_http_client, estimate_request_tokens, preview_request, request_id,
+    RequestBuilderExt, ResponseExt,
+};
+use crate::model::{Extensions, FinishReason, Message, Part, Response, TextAnnotations, Usage};
+use crate::options::{ModelOptions, OptionViolation, ProviderOptions, TransportOptions};
+use crate::sse::SSEResponseExt;
+use crate::stream::{shape_stream, FirstTokenDeadline};
+use crate::template::{ChatTemplate, FimFormat};
+
+/// Completion model options.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CompletionModel {
+    /// Template the conversation is rendered with. Defaults to ChatML.
+    pub template: ChatTemplate,
+    /// Stop sequences sent in addition to those of the template.
+    pub stop_sequences: Option<Vec<String>>,
+    /// Fill-in-the-middle convention used by [`CompletionClient::fim`].
+    /// Defaults to [`FimFormat::Suffix`].
+    pub fim: FimFormat,
+}
+
+impl ProviderOptions for CompletionModel {
+    fn validate(options: &ModelOptions<Self>, violations: &mut Vec<OptionViolation>) {
+        if options.output.is_some() {
+            violations.push(OptionViolation::new(
+                "output",
+                "constrained output is not supported by completion endpoints",
+            ));
+        }
+    }
+}
+
+/// Options of a raw [`CompletionClient::complete`] request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionOptions {
+    /// Text that comes after the completion, for fill-in-the-middle.
+    pub suffix: Option<String>,
+    /// Return the prompt in addition to the completion.
+    pub echo: Option<bool>,
+    /// Number of most likely tokens to return log probabilities for at each position.
+    pub logprobs: Option<u32>,
+}
+
+impl CompletionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the text following the completion.
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// Echo the prompt back in the completion text.
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = Some(echo);
+        self
+    }
+
+    /// Request log probabilities for the top `count` tokens at each position.
+    pub fn with_logprobs(mut self, count: u32) -> Self {
+        self.logprobs = Some(count);
+        self
+    }
+}
+
+/// Result of a raw [`CompletionClient::complete`] request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// Generated text, preceded by the prompt when echo was requested.
+    pub text: String,
+    /// Token log probabilities, when requested.
+    pub logprobs: Option<CompletionLogprobs>,
+    pub finish: FinishReason,
+    pub usage: Usage,
+}
+
+/// Token log probabilities of a completion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct CompletionLogprobs {
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Log probability of each token; `None` for the first echoed token.
+    #[serde(default)]
+    pub token_logprobs: Vec<Option<f64>>,
+    /// Most likely alternatives at each position, by token.
+    #[serde(default)]
+    pub top_logprobs: Vec<Option<HashMap<String, f64>>>,
+    /// Character offset of each token in the text.
+    #[serde(default)]
+    pub text_offset: Vec<usize>,
+}
+
+/// Client for OpenAI-style `/completions` endpoints.
+#[derive(Debug, Clone)]
+pub struct CompletionClient {
+    api_key: Option<String>,
+    base_url: String,
+    model_options: ModelOptions<CompletionModel>,
+    transport_options: TransportOptions,
+}
+
+impl CompletionClient {
+    /// Create a client for the API rooted at `base_url`, e.g. `http://localhost:8080/v1`.
+    pub fn new(
+        base_url: String,
+        model_options: ModelOptions<CompletionModel>,
+        transport_options: TransportOptions,
+    ) -> Self {
+        Self {
+            api_key: None,
+            base_url,
+            model_options,
+            transport_options,
+        }
+    }
+
+    /// Set the key sent as a bearer token, for servers that require one.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, ClientError> {
+        if !tools.is_empty() {
+            return Err(ClientError::Unsupported {
+                provider: "Completion".to_string(),
+                capability: "tools".to_string(),
+            });
+        }
+
+        let options = &self.model_options;
+        let template = &options.provider.template;
+        let mut stop = template.stop.clone();
+        stop.extend(options.provider.stop_sequences.iter().flatten().cloned());
+
+        let request_body = CompletionRequest {
+            prompt: template.render(
+                options.system_prompt(PromptStyle::Plain).as_deref(),
+                &messages,
+            )?,
+            stop: (!stop.is_empty()).then_some(stop),
+            stream: stream.then_some(true),
+            ..self.request_body()
+        };
+
+        self.post("completions", &request_body)
+    }
+
+    /// Request body with the model and sampling options set.
+    fn request_body(&self) -> CompletionRequest {
+        let options = &self.model_options;
+        CompletionRequest {
+            model: options.request_model(),
+            prompt: String::new(),
+            suffix: None,
+            max_tokens: options.max_tokens,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            stop: options.provider.stop_sequences.clone(),
+            echo: None,
+            logprobs: None,
+            stream: None,
+        }
+    }
+
+    fn post(
+        &self,
+        path: &str,
+        request_body: &CompletionRequest,
+    ) -> Result<reqwest::RequestBuilder, ClientError> {
+        let http_client = build_http_client(&self.transport_options)?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if let Some(api_key) = &self.api_key {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", api_key))
+                    .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
+            );
+        }
+
+        let url = format!("{}/{}", self.base_url, path);
+        let req = http_client.post(&url).headers(headers);
+        let req = add_extra_headers(req, &self.transport_options);
+
+        Ok(req.json_logged(request_body))
+    }
+
+    /// Complete a raw prompt, without applying the chat template.
+    ///
+    /// This is the surface for prompts that are not conversations, such as
+    /// fill-in-the-middle code completion through [`CompletionOptions::with_suffix`].
+    /// Sampling options and extra stop sequences come from the client's model options.
+    pub async fn complete(
+        &self,
+        prompt: impl Into<String>,
+        options: CompletionOptions,
+    ) -> Result<Completion, ClientError> {
+        let request_body = CompletionRequest {
+            prompt: prompt.into(),
+            suffix: options.suffix,
+            echo: options.echo,
+            logprobs: options.logprobs,
+            ..self.request_body()
+        };
+
+        self.send("completions", &request_body).await
+    }
+
+    /// Generate the code between `prefix` and `suffix`.
+    ///
+    /// The request follows the model's [`FimFormat`], so callers do not have to know
+    /// its special tokens or endpoints.
+    pub async fn fim(
+        &self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+    ) -> Result<Completion, ClientError> {
+        let (prefix, suffix) = (prefix.into(), suffix.into());
+        match &self.model_options.provider.fim {
+            FimFormat::Suffix => {
+                self.complete(prefix, CompletionOptions::new().with_suffix(suffix))
+                    .await
+            }
+            FimFormat::Tokens(tokens) => {
+                self.complete(tokens.render(&prefix, &suffix), CompletionOptions::new())
+                    .await
+            }
+            FimFormat::Endpoint => {
+                let request_body = CompletionRequest {
+                    prompt: prefix,
+                    suffix: Some(suffix),
+                    ..self.request_body()
+                };
+                self.send("fim/completions", &request_body).await
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        path: &str,
+        request_body: &CompletionRequest,
+    ) -> Result<Completion, ClientError> {
+        let response = self.post(path, request_body)?.send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let completion: CompletionResponse = response.json_logged().await?;
+        let choice = completion.choices.into_iter().next().ok_or_else(|| {
+            ClientError::ProviderError("Completion response has no choices".to_string())
+        })?;
+
+        Ok(Completion {
+            text: choice.text().to_string(),
+            logprobs: choice.logprobs,
+            finish: finish_reason(choice.finish_reason.as_deref()),
+            usage: completion.usage.map(Usage::from).unwrap_or_default(),
+        })
+    }
+
+    fn handle_error_response(
+        status: reqwest::StatusCode,
+        request_id: Option<String>,
+        body: &str,
+    ) -> ClientError {
+        ClientError::Api {
+            status: Some(status.as_u16()),
+            request_id,
+            message: format!("HTTP {}: {}", status, body),
+        }
+    }
+}
+
+#[async_trait]
+impl Client for CompletionClient {
+    type ModelProvider = CompletionModel;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<Response, ClientError> {
+        let req = self.build_request(messages, tools, false)?;
+
+        let response = req.send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let completion: CompletionResponse = response.json_logged().await?;
+        let choice = completion.choices.into_iter().next().ok_or_else(|| {
+            ClientError::ProviderError("Completion response has no choices".to_string())
+        })?;
+        let usage = completion.usage.map(Usage::from).unwrap_or_default();
+
+        Ok(snapshot(
+            &self.model_options.provider.template,
+            &choice.text,
+            finish_reason(choice.finish_reason.as_deref()),
+            usage,
+        ))
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        &self.model_options
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        &self.transport_options
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        let system = self.model_options.system_prompt(PromptStyle::Plain);
+        let estimated_tokens = estimate_request_tokens(system.as_deref(), &messages, &tools);
+        preview_request(
+            self.build_request(messages, tools, false)?,
+            estimated_tokens,
+        )
+    }
+}
+
+#[async_trait]
+impl StreamingClient for CompletionClient {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let req = self.build_request(messages, tools, true)?;
+        let retry = req.try_clone();
+        let deadline = FirstTokenDeadline::start(&self.transport_options);
+        let response = deadline.send(req).await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let template = self.model_options.provider.template.clone();
+        let sse_stream = response.sse_with_transport(retry, &self.transport_options);
+        let stream = async_stream::try_stream! {
+            let mut stream = Box::pin(sse_stream);
+            let mut completion = String::new();
+            let mut finish = FinishReason::Unfinished;
+            let mut usage = Usage::default();
+
+            while let Some(event_result) = stream.next().await {
+                let event_str = event_result?;
+                let chunk: CompletionResponse = serde_json::from_str(&event_str)
+                    .map_err(|e| ClientError::ProviderError(format!("JSON parse error: {}", e)))?;
+
+                if let Some(chunk_usage) = chunk.usage {
+                    usage = chunk_usage.into();
+                }
+                for choice in chunk.choices {
+                    completion.push_str(&choice.text);
+                    if choice.finish_reason.is_some() {
+                        finish = finish_reason(choice.finish_reason.as_deref());
+                    }
+                }
+
+                yield snapshot(&template, &completion, finish.clone(), usage.clone());
+            }
+        };
+
+        Ok(shape_stream(
+            &self.transport_options,
+            deadline.guard(Box::pin(stream)),
+        ))
+    }
+}
+
+/// Snapshot of the response for the completion generated so far.
+fn snapshot(
+    template: &ChatTemplate,
+    completion: &str,
+    finish: FinishReason,
+    usage: Usage,
+) -> Response {
+    let mut part = Part::Text {
+        content: template.parse(completion).into(),
+        signature: None,
+        extensions: Extensions::new(),
+        annotations: TextAnnotations::default(),
+        finished: finish != FinishReason::Unfinished,
+    };
+    if finish != FinishReason::Unfinished {
+        part.annotate();
+    }
+    Response {
+        data: vec![Message::Assistant(vec![part])],
+        usage,
+        finish,
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
+
+/// Map a completion finish reason.
+fn finish_reason(reason: Option<&str>) -> FinishReason {
+    match reason {
+        Some("length") => FinishReason::OutputTokens,
+        Some("content_filter") => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+// --- Wire Types ---
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+struct CompletionRequest {
+    model: String,
+    prompt: String,
+    suffix: Option<String>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    stop: Option<Vec<String>>,
+    echo: Option<bool>,
+    logprobs: Option<u32>,
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    #[serde(default)]
+    choices: Vec<CompletionChoice>,
+    usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChoice {
+    #[serde(default)]
+    text: String,
+    /// Chat-style message returned by FIM endpoints instead of `text`.
+    message: Option<CompletionMessage>,
+    finish_reason: Option<String>,
+    logprobs: Option<CompletionLogprobs>,
+}
+
+impl CompletionChoice {
+    fn text(&self) -> &str {
+        match &self.message {
+            Some(CompletionMessage {
+                content: Some(content),
+            }) => content,
+            _ => &self.text,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+impl From<CompletionUsage> for Usage {
+    fn from(usage: CompletionUsage) -> Self {
+        Usage {
+            prompt_tokens: Some(usage.prompt_tokens),
+            completion_tokens: Some(usage.completion_tokens),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_renders_template() {
+        let mut options = ModelOptions::<CompletionModel>::new("local").with_system("Be brief.");
+        options.provider.template = ChatTemplate::mistral();
+        options.provider.stop_sequences = Some(vec!["\nUser:".to_string()]);
+        let client = CompletionClient::new(
+            "http://localhost:8080/v1".to_string(),
+            options,
+            TransportOptions::default(),
+        );
+
+        let messages = vec![Message::User(vec![Part::Text {
+            content: "Hi".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }])];
+        let request = client
+            .build_request(messages, vec![], false)
+            .unwrap()
+            .build()
+            .unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:8080/v1/completions"
+        );
+        assert_eq!(body["prompt"], "<s>[INST] Be brief.\n\nHi [/INST]");
+        assert_eq!(body["stop"], serde_json::json!(["</s>", "\nUser:"]));
+    }
+
+    /// Answer a single request with `body`, sending the received request body back
+    /// through the returned channel.
+    async fn serve_once(
+        body: &'static str,
+    ) -> (String, tokio::sync::oneshot::Receiver<serde_json::Value>) {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(socket);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut request = vec![0; content_length];
+            reader.read_exact(&mut request).await.unwrap();
+            let _ = tx.send(serde_json::from_slice(&request).unwrap());
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            reader
+                .into_inner()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        });
+        (format!("http://{}/v1", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn test_complete_fill_in_the_middle() {
+        let (url, request) = serve_once(
+            r#"{"choices":[{"text":"a + b","finish_reason":"stop","logprobs":{"tokens":["a"," +"," b"],"token_logprobs":[-0.1,-0.2,-0.3]}}],"usage":{"prompt_tokens":7,"completion_tokens":3}}"#,
+        )
+        .await;
+        let client = CompletionClient::new(
+            url,
+            ModelOptions::new("local").with_max_tokens(16),
+            TransportOptions::default(),
+        );
+
+        let completion = client
+            .complete(
+                "def add(a, b):\n    return ",
+                CompletionOptions::new().with_suffix("\n").with_logprobs(1),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(completion.text, "a + b");
+        assert_eq!(completion.finish, FinishReason::Stop);
+        assert_eq!(completion.usage.completion_tokens, Some(3));
+        assert_eq!(completion.logprobs.unwrap().tokens, vec!["a", " +", " b"]);
+
+        let request = request.await.unwrap();
+        assert_eq!(request["prompt"], "def add(a, b):\n    return ");
+        assert_eq!(request["suffix"], "\n");
+        assert_eq!(request["logprobs"], 1);
+        assert_eq!(request["max_tokens"], 16);
+        assert!(request.get("stop").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fim_endpoint() {
+        let (url, request) = serve_once(
+            r#"{"choices":[{"message":{"role":"assistant","content":"a + b"},"finish_reason":"stop"}]}"#,
+        )
+        .await;
+        let client = CompletionClient::new(
+            url,
+            ModelOptions::new("codestral-latest").with_provider(CompletionModel {
+                fim: FimFormat::Endpoint,
+                ..Default::default()
+            }),
+            TransportOptions::default(),
+        );
+
+        let completion = client
+            .fim("def add(a, b):\n    return ", "\n")
+            .await
+            .unwrap();
+
+        assert_eq!(completion.text, "a + b");
+        let request = request.await.unwrap();
+        assert_eq!(request["prompt"], "def add(a, b):\n    return ");
+        assert_eq!(request["suffix"], "\n");
+    }
+}
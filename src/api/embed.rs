@@ -0,0 +1,157 @@
+//! Client for OpenAI-compatible embeddings endpoints.
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::client::ClientError;
+use crate::embed::Embedder;
+use crate::http::{
+    add_extra_headers, build_http_client, request_id, RequestBuilderExt, ResponseExt,
+};
+use crate::options::TransportOptions;
+
+/// Embedder backed by an OpenAI-compatible `/embeddings` endpoint.
+#[derive(Debug, Clone)]
+pub struct OpenAIEmbedder {
+    api_key: String,
+    base_url: String,
+    model: String,
+    dimensions: Option<u32>,
+    transport_options: TransportOptions,
+}
+
+impl OpenAIEmbedder {
+    /// Create an embedder for `model`, e.g. `text-embedding-3-small`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            model: model.into(),
+            dimensions: None,
+            transport_options: TransportOptions::default(),
+        }
+    }
+
+    /// Shorten embeddings to `dimensions`, for models that support it.
+    pub fn with_dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Use another OpenAI-compatible API, e.g. `https://api.mistral.ai/v1`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_transport_options(mut self, transport_options: TransportOptions) -> Self {
+        self.transport_options = transport_options;
+        self
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAIEmbedder {
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ClientError> {
+        let count = inputs.len();
+        let request_body = EmbeddingRequest {
+            model: self.model.clone(),
+            input: inputs,
+            dimensions: self.dimensions,
+        };
+
+        let http_client = build_http_client(&self.transport_options)?;
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+                .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
+        );
+
+        let url = format!("{}/embeddings", self.base_url);
+        let req = http_client.post(&url).headers(headers);
+        let req = add_extra_headers(req, &self.transport_options);
+        let response = req.json_logged(&request_body).send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(ClientError::Api {
+                status: Some(status.as_u16()),
+                request_id,
+                message: format!("HTTP {}: {}", status, body),
+            });
+        }
+
+        let response: EmbeddingResponse = response.json_logged().await?;
+        embeddings(response, count)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+    dimensions: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Order the returned embeddings like the inputs.
+fn embeddings(response: EmbeddingResponse, count: usize) -> Result<Vec<Vec<f32>>, ClientError> {
+    let mut embeddings = vec![None; count];
+    for data in response.data {
+        let slot = embeddings.get_mut(data.index).ok_or_else(|| {
+            ClientError::ProviderError(format!("Embedding refers to unknown input {}", data.index))
+        })?;
+        *slot = Some(data.embedding);
+    }
+    embeddings
+        .into_iter()
+        .enumerate()
+        .map(|(index, embedding)| {
+            embedding.ok_or_else(|| {
+                ClientError::ProviderError(format!("No embedding returned for input {}", index))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embeddings_follow_input_order() {
+        let response: EmbeddingResponse = serde_json::from_value(serde_json::json!({
+            "data": [
+                { "index": 1, "embedding": [0.0, 1.0] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            embeddings(response, 2).unwrap(),
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+
+        let response: EmbeddingResponse =
+            serde_json::from_value(serde_json::json!({ "data": [] })).unwrap();
+        assert!(embeddings(response, 1).is_err());
+    }
+}
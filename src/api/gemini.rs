@@ -1,18 +1,36 @@
 //! Google Gemini API client implementation.
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::skip_serializing_none;
+use std::collections::HashMap;
 use std::pin::Pin;
-
-use crate::client::{Client, ClientError, StreamingClient};
-use crate::http::{add_extra_headers, build_http_client, RequestBuilderExt, ResponseExt};
-use crate::model::{FinishReason, MediaType, Message, Part, Response, Usage};
-use crate::options::{ModelOptions, TransportOptions};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client::{Client, ClientError, FilterStage, RequestPreview, StreamingClient};
+use crate::constraints::PromptStyle;
+use crate::history::{first_rewritten, normalize_history, push_merged};
+use crate::http::{
+    add_extra_headers, build_http_client, check_request_size, estimate_request_tokens,
+    fingerprint_bytes, preview_request, request_id, RequestBuilderExt, ResponseExt,
+};
+use crate::model::{
+    base64_data, Extensions, FinishReason, MediaType, Message, Part, Response, TextAnnotations,
+    Usage,
+};
+use crate::options::{
+    ModelOptions, OptionViolation, OutputFormat, ProviderOptions, SafetyLevel, SamplingPreset,
+    TransportOptions,
+};
+use crate::region::VertexLocation;
+use crate::schema::{sanitize_schema, SchemaDialect};
 use crate::sse::SSEResponseExt;
+use crate::stream::{shape_stream, FirstTokenDeadline};
 
 /// Gemini model options.
 #[skip_serializing_none]
@@ -21,10 +39,232 @@ pub struct GeminiModel {
     pub top_k: Option<u32>,
     pub safety_settings: Option<Vec<GeminiSafetySetting>>,
     pub stop_sequences: Option<Vec<String>>,
+    /// MIME type of the answer: `text/plain`, `application/json`, or `text/x.enum`
+    /// for a single value of the enum in `response_schema`. Takes precedence over
+    /// [`ModelOptions::output`] along with the response schemas.
     pub response_mime_type: Option<String>,
+    /// JSON Schema the response must follow. Requires `response_mime_type` to be
+    /// `application/json`; the schema is sanitized for the Gemini dialect.
+    pub response_json_schema: Option<Value>,
+    /// Schema in the OpenAPI subset of Gemini's `responseSchema`, e.g.
+    /// `{"type": "STRING", "enum": [...]}` for enum output. Requires
+    /// `response_mime_type` to be `application/json` or `text/x.enum`, and cannot be
+    /// combined with `response_json_schema`.
+    pub response_schema: Option<Value>,
     pub thinking_budget: Option<u32>,
     pub thinking_level: Option<GeminiThinkingLevel>,
     pub include_thoughts: Option<bool>,
+    /// Number of candidates to generate. Each candidate is returned as its own
+    /// assistant message in [`Response::data`], ordered by candidate index.
+    pub candidate_count: Option<u32>,
+    /// Largest base64 payload (in bytes) sent inline. Bigger media parts are uploaded
+    /// through the Files API and referenced by URI instead.
+    /// Defaults to [`DEFAULT_INLINE_DATA_LIMIT`].
+    pub inline_data_limit: Option<usize>,
+    /// Sampling and clipping applied to every video part.
+    pub video_metadata: Option<GeminiVideoMetadata>,
+    /// Keep the request prefix stable across turns to maximize implicit cache hits.
+    ///
+    /// Tools are sent sorted by name, and a warning is logged when
+    /// [`normalize_history`](crate::history::normalize_history) rewrites earlier turns,
+    /// since Gemini only reuses a cached prefix that is sent unchanged. Cache hits are
+    /// reported in [`Usage::cached_prompt_tokens`].
+    pub stable_prefix: Option<bool>,
+}
+
+/// Video processing options for Gemini.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiVideoMetadata {
+    /// Frames sampled per second (the API defaults to 1).
+    pub fps: Option<f64>,
+    /// Start of the clip as a duration string, e.g. `"10s"`.
+    pub start_offset: Option<String>,
+    /// End of the clip as a duration string, e.g. `"1.5s"`.
+    pub end_offset: Option<String>,
+}
+
+impl ProviderOptions for GeminiModel {
+    /// Gemini samples with temperature, top-p and top-k together, so all three are set.
+    fn apply_preset(options: &mut ModelOptions<Self>, preset: SamplingPreset) {
+        let (temperature, top_p, top_k) = match preset {
+            SamplingPreset::Precise => (0.2, 0.8, 20),
+            SamplingPreset::Balanced => (0.7, 0.95, 40),
+            SamplingPreset::Creative => (1.2, 0.95, 64),
+        };
+        options.temperature = Some(temperature);
+        options.top_p = Some(top_p);
+        options.provider.top_k = Some(top_k);
+    }
+
+    fn validate(options: &ModelOptions<Self>, violations: &mut Vec<OptionViolation>) {
+        if options.service_tier.is_some() {
+            violations.push(OptionViolation::new(
+                "service_tier",
+                "is not offered by Gemini",
+            ));
+        }
+        let provider = &options.provider;
+        if provider.thinking_budget.is_some() && provider.thinking_level.is_some() {
+            violations.push(OptionViolation::new(
+                "provider.thinking_level",
+                "cannot be combined with provider.thinking_budget",
+            ));
+        }
+        if provider.response_json_schema.is_some()
+            && provider.response_mime_type.as_deref() != Some("application/json")
+        {
+            violations.push(OptionViolation::new(
+                "provider.response_json_schema",
+                "requires provider.response_mime_type to be application/json",
+            ));
+        }
+        if provider.response_schema.is_some() {
+            if provider.response_json_schema.is_some() {
+                violations.push(OptionViolation::new(
+                    "provider.response_schema",
+                    "cannot be combined with provider.response_json_schema",
+                ));
+            }
+            if !matches!(
+                provider.response_mime_type.as_deref(),
+                Some(JSON_MIME_TYPE | ENUM_MIME_TYPE)
+            ) {
+                violations.push(OptionViolation::new(
+                    "provider.response_schema",
+                    "requires provider.response_mime_type to be application/json or text/x.enum",
+                ));
+            }
+        }
+    }
+}
+
+const JSON_MIME_TYPE: &str = "application/json";
+const ENUM_MIME_TYPE: &str = "text/x.enum";
+
+/// `response` of a Gemini function response, which must be an object: text blocks
+/// of the result are added under `content`, or `response` if there is no structured
+/// result.
+fn function_response(response: &Value, parts: &[Part]) -> Value {
+    let text: Vec<&str> = parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .collect();
+    if text.is_empty() {
+        return response.clone();
+    }
+    let text = Value::String(text.join("\n"));
+    match response {
+        Value::Object(map) if map.is_empty() => serde_json::json!({ "response": text }),
+        Value::Object(map) if !map.contains_key("content") => {
+            let mut map = map.clone();
+            map.insert("content".to_string(), text);
+            Value::Object(map)
+        }
+        _ => serde_json::json!({ "response": response, "content": text }),
+    }
+}
+
+/// `responseMimeType`, `responseJsonSchema` and `responseSchema` of a request, from
+/// the raw provider settings if a MIME type is set and from [`ModelOptions::output`]
+/// otherwise.
+fn response_format(
+    model_options: &ModelOptions<GeminiModel>,
+) -> (Option<String>, Option<Value>, Option<Value>) {
+    let provider = &model_options.provider;
+    let (mime_type, json_schema, schema) =
+        match (&provider.response_mime_type, &model_options.output) {
+            (Some(_), _) | (None, None) => (
+                provider.response_mime_type.clone(),
+                provider.response_json_schema.clone(),
+                provider.response_schema.clone(),
+            ),
+            (None, Some(OutputFormat::Json)) => (Some(JSON_MIME_TYPE.to_string()), None, None),
+            (None, Some(OutputFormat::JsonSchema(schema))) => {
+                (Some(JSON_MIME_TYPE.to_string()), Some(schema.clone()), None)
+            }
+            (None, Some(OutputFormat::Enum(values))) => (
+                Some(ENUM_MIME_TYPE.to_string()),
+                None,
+                Some(serde_json::json!({ "type": "STRING", "enum": values })),
+            ),
+        };
+    (
+        mime_type,
+        json_schema.map(|schema| sanitize_schema(schema, SchemaDialect::GeminiJsonSchema)),
+        schema.map(|schema| sanitize_schema(schema, SchemaDialect::Gemini)),
+    )
+}
+
+/// Default value for [`GeminiModel::inline_data_limit`].
+///
+/// Gemini rejects requests larger than 20 MB, so anything close to that is uploaded.
+pub const DEFAULT_INLINE_DATA_LIMIT: usize = 15 * 1024 * 1024;
+
+/// Interval between file state checks while waiting for an upload to be processed.
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest time to wait for an uploaded file to be processed.
+pub const FILE_PROCESSING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How long an uploaded file is reused for the same media. The Files API keeps files
+/// for 48 hours; the margin keeps a request from referencing a file about to expire.
+const UPLOAD_REUSE_TTL: Duration = Duration::from_secs(47 * 60 * 60);
+
+/// URIs of files uploaded for inline media, by content fingerprint and MIME type.
+///
+/// Agent loops and conversations send the same history again on every request, so
+/// without it large media would be uploaded again each time.
+#[derive(Debug, Clone, Default)]
+struct UploadCache(Arc<Mutex<HashMap<String, (String, Instant)>>>);
+
+impl UploadCache {
+    fn key(data: &[u8], mime_type: &str) -> String {
+        format!("{}:{}", fingerprint_bytes(data), mime_type)
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let mut uploads = self.0.lock().unwrap();
+        match uploads.get(key) {
+            Some((uri, uploaded)) if uploaded.elapsed() < UPLOAD_REUSE_TTL => Some(uri.clone()),
+            Some(_) => {
+                uploads.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: String, uri: String) {
+        self.0.lock().unwrap().insert(key, (uri, Instant::now()));
+    }
+}
+
+/// A file stored through the Gemini Files API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFile {
+    /// Resource name, e.g. `files/abc-123`.
+    pub name: String,
+    /// URI used to reference the file in requests.
+    pub uri: String,
+    pub mime_type: String,
+    pub display_name: Option<String>,
+    pub size_bytes: Option<String>,
+    pub state: Option<GeminiFileState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiFileState {
+    StateUnspecified,
+    Processing,
+    Active,
+    Failed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +280,35 @@ pub struct GeminiSafetySetting {
     pub threshold: String,
 }
 
+impl GeminiSafetySetting {
+    /// Harm categories a [`SafetyLevel`] applies to.
+    const CATEGORIES: [&'static str; 4] = [
+        "HARM_CATEGORY_HARASSMENT",
+        "HARM_CATEGORY_HATE_SPEECH",
+        "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+        "HARM_CATEGORY_DANGEROUS_CONTENT",
+    ];
+
+    /// Settings applying `level` to every harm category, or `None` to keep the
+    /// defaults of the API.
+    pub fn for_level(level: SafetyLevel) -> Option<Vec<Self>> {
+        let threshold = match level {
+            SafetyLevel::Strict => "BLOCK_LOW_AND_ABOVE",
+            SafetyLevel::Default => return None,
+            SafetyLevel::Permissive => "BLOCK_NONE",
+        };
+        Some(
+            Self::CATEGORIES
+                .iter()
+                .map(|category| Self {
+                    category: category.to_string(),
+                    threshold: threshold.to_string(),
+                })
+                .collect(),
+        )
+    }
+}
+
 /// Gemini client.
 #[derive(Debug, Clone)]
 pub struct GeminiClient {
@@ -47,6 +316,7 @@ pub struct GeminiClient {
     base_url: String,
     model_options: ModelOptions<GeminiModel>,
     transport_options: TransportOptions,
+    uploads: UploadCache,
 }
 
 impl GeminiClient {
@@ -61,17 +331,36 @@ impl GeminiClient {
             base_url,
             model_options,
             transport_options,
+            uploads: UploadCache::default(),
         }
     }
 
-    fn handle_error_response(status: reqwest::StatusCode, body: &str) -> ClientError {
-        if let Ok(error_resp) = serde_json::from_str::<GeminiErrorResponse>(body) {
-            ClientError::ProviderError(format!(
+    /// Reach Gemini through Vertex AI in the given project and region.
+    ///
+    /// The API key must be a Vertex AI key. The Files API is not available on Vertex
+    /// AI, so media above the inline size limit must be passed by URI.
+    pub fn with_region(mut self, location: VertexLocation) -> Self {
+        self.base_url = location.publisher_url("google");
+        self
+    }
+
+    fn handle_error_response(
+        status: reqwest::StatusCode,
+        request_id: Option<String>,
+        body: &str,
+    ) -> ClientError {
+        let message = if let Ok(error_resp) = serde_json::from_str::<GeminiErrorResponse>(body) {
+            format!(
                 "Gemini error ({}): {}",
                 error_resp.error.code, error_resp.error.message
-            ))
+            )
         } else {
-            ClientError::ProviderError(format!("HTTP {}: {}", status, body))
+            format!("HTTP {}: {}", status, body)
+        };
+        ClientError::Api {
+            status: Some(status.as_u16()),
+            request_id,
+            message,
         }
     }
 
@@ -81,7 +370,7 @@ impl GeminiClient {
         tools: Vec<rmcp::model::Tool>,
         stream: bool,
     ) -> Result<reqwest::RequestBuilder, ClientError> {
-        let model = self.model_options.model.clone();
+        let model = self.model_options.request_model();
 
         let method = if stream {
             "streamGenerateContent?alt=sse&"
@@ -93,6 +382,34 @@ impl GeminiClient {
             self.base_url, model, method, self.api_key
         );
 
+        let stable_prefix = self.model_options.provider.stable_prefix.unwrap_or(false);
+        let messages = if self.model_options.normalize_history.unwrap_or(false) {
+            if stable_prefix {
+                let normalized = normalize_history(messages.clone());
+                if let Some(index) = first_rewritten(&messages, &normalized)
+                    .filter(|index| index + 1 < messages.len())
+                {
+                    tracing::warn!(
+                        "History normalization rewrote message {} of {}, which prevents implicit cache hits on the rest of the prefix",
+                        index,
+                        messages.len()
+                    );
+                }
+                normalized
+            } else {
+                normalize_history(messages)
+            }
+        } else {
+            messages
+        };
+
+        check_request_size(&self.transport_options, &messages)?;
+
+        let mut tools = tools;
+        if stable_prefix {
+            tools.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
         let request_body = GeminiRequest::new(messages, &self.model_options, tools)?;
 
         let http_client = build_http_client(&self.transport_options)?;
@@ -107,6 +424,181 @@ impl GeminiClient {
     }
 }
 
+impl GeminiClient {
+    /// Upload a file through the Files API and wait until it can be used in requests.
+    ///
+    /// Uses the resumable upload protocol and polls the file state until it becomes
+    /// `ACTIVE`. Videos in particular can take a while to be processed.
+    pub async fn upload_file(
+        &self,
+        data: impl Into<Bytes>,
+        mime_type: &str,
+        display_name: Option<String>,
+    ) -> Result<GeminiFile, ClientError> {
+        let data = data.into();
+        let http_client = build_http_client(&self.transport_options)?;
+
+        let start = http_client
+            .post(format!("{}?key={}", self.upload_url(), self.api_key))
+            .header("X-Goog-Upload-Protocol", "resumable")
+            .header("X-Goog-Upload-Command", "start")
+            .header("X-Goog-Upload-Header-Content-Length", data.len())
+            .header("X-Goog-Upload-Header-Content-Type", mime_type)
+            .json_logged(&serde_json::json!({ "file": { "display_name": display_name } }));
+        let response = add_extra_headers(start, &self.transport_options)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let upload_url = response
+            .headers()
+            .get("x-goog-upload-url")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| ClientError::ProviderError("Missing upload URL".to_string()))?
+            .to_string();
+
+        let response = http_client
+            .post(&upload_url)
+            .header("X-Goog-Upload-Offset", 0)
+            .header("X-Goog-Upload-Command", "upload, finalize")
+            .body(data)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        let uploaded: GeminiFileResponse = response.json_logged().await?;
+        self.wait_for_file(&uploaded.file.name).await
+    }
+
+    /// Get the metadata of an uploaded file.
+    pub async fn get_file(&self, name: &str) -> Result<GeminiFile, ClientError> {
+        let http_client = build_http_client(&self.transport_options)?;
+        let req = http_client.get(format!("{}/{}?key={}", self.base_url, name, self.api_key));
+        let response = add_extra_headers(req, &self.transport_options)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let request_id = request_id(response.headers());
+            let body = response.text_logged().await.unwrap_or_default();
+            return Err(Self::handle_error_response(status, request_id, &body));
+        }
+
+        response.json_logged().await
+    }
+
+    /// Poll an uploaded file until it is `ACTIVE`, for at most
+    /// [`FILE_PROCESSING_TIMEOUT`].
+    pub async fn wait_for_file(&self, name: &str) -> Result<GeminiFile, ClientError> {
+        let deadline = Instant::now() + FILE_PROCESSING_TIMEOUT;
+        loop {
+            let file = self.get_file(name).await?;
+            match file.state {
+                Some(GeminiFileState::Processing) if Instant::now() >= deadline => {
+                    return Err(ClientError::Timeout(format!(
+                        "File {} still processing after {:?}",
+                        name, FILE_PROCESSING_TIMEOUT
+                    )));
+                }
+                Some(GeminiFileState::Processing) => {
+                    tracing::debug!("Waiting for file {} to be processed", name);
+                    tokio::time::sleep(FILE_POLL_INTERVAL).await;
+                }
+                Some(GeminiFileState::Failed) => {
+                    return Err(ClientError::ProviderError(format!(
+                        "File {} failed processing",
+                        name
+                    )));
+                }
+                _ => return Ok(file),
+            }
+        }
+    }
+
+    /// Upload media parts whose inline payload exceeds the configured limit.
+    ///
+    /// Uploaded parts are replaced with data-less media parts pointing at the file URI,
+    /// which the request builder serializes as `fileData`. Media uploaded before by
+    /// this client (or a clone) is not uploaded again.
+    async fn upload_large_media(
+        &self,
+        mut messages: Vec<Message>,
+    ) -> Result<Vec<Message>, ClientError> {
+        let limit = self
+            .model_options
+            .provider
+            .inline_data_limit
+            .unwrap_or(DEFAULT_INLINE_DATA_LIMIT);
+
+        for message in messages.iter_mut() {
+            for part in message.parts_mut().iter_mut() {
+                if let Part::Media {
+                    data,
+                    mime_type,
+                    uri,
+                    ..
+                } = part
+                {
+                    // The limit applies to the base64 encoded payload.
+                    if data.len().div_ceil(3) * 4 <= limit {
+                        continue;
+                    }
+
+                    let key = UploadCache::key(data, mime_type);
+                    let file_uri = match self.uploads.get(&key) {
+                        Some(file_uri) => file_uri,
+                        None => {
+                            tracing::info!(
+                                "Uploading {} bytes of {} through the Files API",
+                                data.len(),
+                                mime_type
+                            );
+                            let file = self
+                                .upload_file(data.clone(), mime_type, uri.clone())
+                                .await?;
+                            self.uploads.insert(key, file.uri.clone());
+                            file.uri
+                        }
+                    };
+
+                    *data = Bytes::new();
+                    *uri = Some(file_uri);
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Files API upload endpoint, derived from the base URL (`{origin}/upload{path}/files`).
+    fn upload_url(&self) -> String {
+        let path_start = self
+            .base_url
+            .find("://")
+            .and_then(|scheme_end| {
+                self.base_url[scheme_end + 3..]
+                    .find('/')
+                    .map(|i| scheme_end + 3 + i)
+            })
+            .unwrap_or(self.base_url.len());
+        let (origin, path) = self.base_url.split_at(path_start);
+        format!("{}/upload{}/files", origin, path)
+    }
+}
+
 #[async_trait]
 impl Client for GeminiClient {
     type ModelProvider = GeminiModel;
@@ -116,17 +608,22 @@ impl Client for GeminiClient {
         messages: Vec<Message>,
         tools: Vec<rmcp::model::Tool>,
     ) -> Result<Response, ClientError> {
+        let messages = self.upload_large_media(messages).await?;
         let req = self.build_request(messages, tools, false)?;
 
         let response = req.send().await?;
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
         let gemini_response: GeminiResponse = response.json_logged().await?;
+        if let Some(error) = gemini_response.prompt_blocked() {
+            return Err(error);
+        }
         Ok(gemini_response.into())
     }
 
@@ -137,6 +634,19 @@ impl Client for GeminiClient {
     fn transport_options(&self) -> &TransportOptions {
         &self.transport_options
     }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        let system = self.model_options.system_prompt(PromptStyle::Plain);
+        let estimated_tokens = estimate_request_tokens(system.as_deref(), &messages, &tools);
+        preview_request(
+            self.build_request(messages, tools, false)?,
+            estimated_tokens,
+        )
+    }
 }
 
 #[async_trait]
@@ -147,16 +657,24 @@ impl StreamingClient for GeminiClient {
         tools: Vec<rmcp::model::Tool>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
     {
+        let messages = self.upload_large_media(messages).await?;
         let req = self.build_request(messages, tools, true)?;
-        let response = req.send().await?;
+        let retry = req.try_clone();
+        let deadline = FirstTokenDeadline::start(&self.transport_options);
+        let response = deadline.send(req).await?;
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
-        Ok(Box::pin(GeminiStream::create(response)))
+        let events = response.sse_with_transport(retry, &self.transport_options);
+        Ok(shape_stream(
+            &self.transport_options,
+            deadline.guard(GeminiStream::from_events(events)),
+        ))
     }
 }
 
@@ -165,150 +683,209 @@ impl StreamingClient for GeminiClient {
 struct GeminiStream;
 
 impl GeminiStream {
-    fn create(
-        response: reqwest::Response,
+    /// Accumulate SSE data payloads into cumulative response snapshots.
+    fn from_events(
+        sse_stream: impl Stream<Item = Result<String, ClientError>> + Send,
     ) -> impl Stream<Item = Result<Response, ClientError>> + Send {
-        let sse_stream = response.sse();
-
         Box::pin(async_stream::try_stream! {
             let mut stream = Box::pin(sse_stream);
             let mut current_response = Response {
                 data: vec![Message::Assistant(vec![])],
                 usage: Usage::default(),
                 finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
             };
 
             #[derive(PartialEq)]
             enum PartType { Text, Reasoning, FunctionCall }
-            let mut last_part_type: Option<PartType> = None;
+            // Type of the last part and finish reason of every candidate, by index.
+            let mut last_part_types: Vec<Option<PartType>> = vec![None];
+            let mut finishes: Vec<Option<FinishReason>> = vec![None];
 
             while let Some(event_result) = stream.next().await {
                 let event_str = event_result?;
 
                 let chunk_result: GeminiResponse = serde_json::from_str(&event_str)
                     .map_err(|e| ClientError::ProviderError(format!("JSON parse error: {}", e)))?;
+                if let Some(error) = chunk_result.prompt_blocked() {
+                    Err(error)?;
+                }
 
                 if let Some(usage_meta) = chunk_result.usage_metadata {
                     current_response.usage.prompt_tokens = Some(usage_meta.prompt_token_count);
                     current_response.usage.completion_tokens = Some(usage_meta.candidates_token_count.unwrap_or(0) + usage_meta.thoughts_token_count.unwrap_or(0));
+                    current_response.usage.cached_prompt_tokens = usage_meta.cached_content_token_count;
                 }
 
-                if let Some(candidates) = chunk_result.candidates {
-                    if let Some(candidate) = candidates.first() {
-                        if let Some(content) = &candidate.content {
-                            let parts = current_response.data[0].parts_mut();
-
-                            for part in &content.parts {
-                                match part {
-                                    GeminiPart::Text { text, thought } => {
-                                        let is_thought = thought.unwrap_or(false);
-                                        let current_type = if is_thought { PartType::Reasoning } else { PartType::Text };
-
-                                        if last_part_type
-                                            .as_ref()
-                                            .is_some_and(|last_type| *last_type != current_type)
-                                        {
-                                            if let Some(last_part) = parts.last_mut() {
-                                                match last_part {
-                                                    Part::Text { finished, .. } => *finished = true,
-                                                    Part::Reasoning { finished, .. } => *finished = true,
-                                                    Part::FunctionCall { finished, .. } => {
-                                                        *finished = true
-                                                    }
-                                                    _ => {}
-                                                }
-                                            }
-                                        }
-                                        last_part_type = Some(current_type);
-
-                                        let should_append = if let Some(last_part) = parts.last_mut() {
-                                            matches!(
-                                                (last_part, is_thought),
-                                                (Part::Text { finished: false, .. }, false)
-                                                    | (Part::Reasoning { finished: false, .. }, true)
-                                            )
-                                        } else {
-                                            false
-                                        };
-
-                                        if should_append {
-                                            if let Some(last_part) = parts.last_mut() {
-                                                match last_part {
-                                                    Part::Text { content: t, .. } => t.push_str(text),
-                                                    Part::Reasoning { content: c, .. } => c.push_str(text),
-                                                    _ => {}
+                for candidate in chunk_result.candidates.unwrap_or_default() {
+                    let index = candidate.index.unwrap_or(0) as usize;
+                    if current_response.data.len() <= index {
+                        current_response.data.resize_with(index + 1, || Message::Assistant(vec![]));
+                        last_part_types.resize_with(index + 1, || None);
+                        finishes.resize_with(index + 1, || None);
+                    }
+                    let last_part_type = &mut last_part_types[index];
+
+                    if let Some(content) = &candidate.content {
+                        let parts = current_response.data[index].parts_mut();
+
+                        for part in &content.parts {
+                            match part {
+                                GeminiPart::Text { text, thought, thought_signature } => {
+                                    let is_thought = thought.unwrap_or(false);
+                                    let current_type = if is_thought { PartType::Reasoning } else { PartType::Text };
+
+                                    if last_part_type
+                                        .as_ref()
+                                        .is_some_and(|last_type| *last_type != current_type)
+                                    {
+                                        if let Some(last_part) = parts.last_mut() {
+                                            match last_part {
+                                                Part::Text { finished, .. } => *finished = true,
+                                                Part::Reasoning { finished, .. } => *finished = true,
+                                                Part::FunctionCall { finished, .. } => {
+                                                    *finished = true
                                                 }
+                                                _ => {}
                                             }
-                                        } else if is_thought {
-                                            parts.push(Part::Reasoning {
-                                                content: text.clone(),
-                                                summary: None,
-                                                signature: None,
-                                                finished: false,
-                                            });
-                                        } else {
-                                            parts.push(Part::Text {
-                                                content: text.clone(),
-                                                finished: false,
-                                            });
+                                            last_part.annotate();
                                         }
-                                    },
-                                    GeminiPart::FunctionCall { function_call, thought_signature } => {
-                                        if last_part_type
-                                            .as_ref()
-                                            .is_some_and(|last_type| *last_type != PartType::FunctionCall)
-                                        {
-                                            if let Some(last_part) = parts.last_mut() {
-                                                match last_part {
-                                                    Part::Text { finished, .. } => *finished = true,
-                                                    Part::Reasoning { finished, .. } => *finished = true,
-                                                    _ => {}
-                                                }
+                                    }
+                                    *last_part_type = Some(current_type);
+
+                                    // Signed parts are kept as received, so that they are replayed
+                                    // exactly where Gemini placed the signature.
+                                    let should_append = if let Some(last_part) = parts.last_mut() {
+                                        matches!(
+                                            (last_part, is_thought),
+                                            (Part::Text { finished: false, signature: None, .. }, false)
+                                                | (Part::Reasoning { finished: false, signature: None, .. }, true)
+                                        )
+                                    } else {
+                                        false
+                                    };
+
+                                    if should_append {
+                                        if let Some(last_part) = parts.last_mut() {
+                                            match last_part {
+                                                Part::Text { content: t, .. } => t.push_str(text),
+                                                Part::Reasoning { content: c, .. } => c.push_str(text),
+                                                _ => {}
                                             }
                                         }
-                                        last_part_type = Some(PartType::FunctionCall);
-
-                                        parts.push(Part::FunctionCall {
-                                            id: None,
-                                            name: function_call.name.clone(),
-                                            arguments: function_call.args.clone(),
-                                            signature: thought_signature.clone(),
+                                    } else if is_thought {
+                                        parts.push(Part::Reasoning {
+                                            content: text.clone().into(),
+                                            summary: None,
+                                            signature: None,
+                                            extensions: Extensions::new(),
                                             finished: false,
                                         });
-                                    },
-                                    _ => {}
-                                }
+                                    } else {
+                                        parts.push(Part::Text {
+                                            content: text.clone().into(),
+                                            signature: None,
+                                            extensions: Extensions::new(),
+                                            annotations: TextAnnotations::default(),
+                                            finished: false,
+                                        });
+                                    }
+
+                                    if let Some(thought_signature) = thought_signature {
+                                        match parts.last_mut() {
+                                            Some(Part::Text { signature, .. })
+                                            | Some(Part::Reasoning { signature, .. }) => {
+                                                *signature = Some(thought_signature.clone());
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                },
+                                GeminiPart::FunctionCall { function_call, thought_signature } => {
+                                    if last_part_type
+                                        .as_ref()
+                                        .is_some_and(|last_type| *last_type != PartType::FunctionCall)
+                                    {
+                                        if let Some(last_part) = parts.last_mut() {
+                                            match last_part {
+                                                Part::Text { finished, .. } => *finished = true,
+                                                Part::Reasoning { finished, .. } => *finished = true,
+                                                _ => {}
+                                            }
+                                            last_part.annotate();
+                                        }
+                                    }
+                                    *last_part_type = Some(PartType::FunctionCall);
+
+                                    parts.push(Part::FunctionCall {
+                                        id: None,
+                                        name: function_call.name.clone(),
+                                        arguments: function_call.args.clone(),
+                                        signature: thought_signature.clone(),
+                                        repaired: false,
+                                        extensions: Extensions::new(),
+                                        finished: false,
+                                    });
+                                },
+                                _ => {}
                             }
                         }
+                    }
 
-                        if let Some(finish_reason) = &candidate.finish_reason {
-                            for part in current_response.data[0].parts_mut() {
-                                match part {
-                                    Part::Text { finished, .. } => *finished = true,
-                                    Part::Reasoning { finished, .. } => *finished = true,
-                                    Part::FunctionCall { finished, .. } => *finished = true,
-                                    Part::FunctionResponse { finished, .. } => *finished = true,
-                                    Part::Media { finished, .. } => *finished = true,
-                                }
-                            }
+                    if let Some(metadata) = candidate.grounding_metadata {
+                        attach_grounding(current_response.data[index].parts_mut(), metadata);
+                    }
 
-                            current_response.finish = match finish_reason.as_str() {
-                                "STOP" => FinishReason::Stop,
-                                "MAX_TOKENS" => FinishReason::OutputTokens,
-                                "SAFETY" => FinishReason::ContentFilter,
-                                "RECITATION" => FinishReason::ContentFilter,
-                                _ => FinishReason::Stop,
-                            };
+                    if let Some(finish_reason) = &candidate.finish_reason {
+                        for part in current_response.data[index].parts_mut() {
+                            match part {
+                                Part::Text { finished, .. } => *finished = true,
+                                Part::Reasoning { finished, .. } => *finished = true,
+                                Part::FunctionCall { finished, .. } => *finished = true,
+                                Part::FunctionResponse { finished, .. } => *finished = true,
+                                Part::Media { finished, .. } => *finished = true,
+                            }
+                            part.annotate();
                         }
+
+                        finishes[index] = Some(finish_reason_from(finish_reason));
                     }
                 }
 
+                // The response finishes with the first candidate, once every candidate is done.
+                if finishes.iter().all(Option::is_some) {
+                    current_response.finish = finishes[0].clone().unwrap_or(FinishReason::Unfinished);
+                }
+
                 yield current_response.clone();
             }
         })
     }
 }
 
+/// Parse a Gemini `contents` array (or a single content).
+///
+/// Function responses carry no call ids, as in the Gemini API itself.
+pub(crate) fn messages_from_json(value: Value) -> Result<Vec<Message>, ClientError> {
+    let wire: Vec<GeminiContent> = match value {
+        Value::Array(_) => serde_json::from_value(value)?,
+        other => vec![serde_json::from_value(other)?],
+    };
+
+    let mut messages = Vec::new();
+    for content in wire {
+        let parts = content.parts.into_iter().map(Part::from).collect();
+        let converted = match content.role.as_str() {
+            "model" => Message::Assistant(parts),
+            _ => Message::User(parts),
+        };
+        push_merged(&mut messages, converted);
+    }
+
+    Ok(messages)
+}
+
 // --- Request Types ---
 
 #[skip_serializing_none]
@@ -325,6 +902,7 @@ struct GeminiRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GeminiContent {
+    #[serde(default)]
     role: String,
     parts: Vec<GeminiPart>,
 }
@@ -337,6 +915,8 @@ enum GeminiPart {
         text: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         thought: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<String>,
     },
     FunctionCall {
         function_call: GeminiFunctionCall,
@@ -348,6 +928,13 @@ enum GeminiPart {
     },
     InlineData {
         inline_data: GeminiInlineData,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        video_metadata: Option<GeminiVideoMetadata>,
+    },
+    FileData {
+        file_data: GeminiFileData,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        video_metadata: Option<GeminiVideoMetadata>,
     },
 }
 
@@ -375,13 +962,26 @@ struct GeminiFunctionResponsePart {
 struct GeminiFunctionResponseBlob {
     #[serde(rename = "mimeType")]
     mime_type: String,
-    data: String,
+    #[serde(with = "base64_data")]
+    data: Bytes,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GeminiInlineData {
+    #[serde(alias = "mimeType")]
     mime_type: String,
-    data: String,
+    #[serde(with = "base64_data")]
+    data: Bytes,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiFileData {
+    /// Omitted for wildcard types such as `video/*`, letting Gemini detect the type
+    /// (required for YouTube URLs).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<String>,
+    file_uri: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -404,9 +1004,14 @@ struct GeminiGenerationConfig {
     top_p: Option<f32>,
     top_k: Option<u32>,
     max_output_tokens: Option<u32>,
+    candidate_count: Option<u32>,
     stop_sequences: Option<Vec<String>>,
     response_mime_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    response_json_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     thinking_config: Option<GeminiThinkingConfig>,
 }
 
@@ -437,29 +1042,64 @@ impl GeminiRequest {
             let mut parts = Vec::new();
             for part in msg.parts() {
                 match part {
-                    Part::Text { content: t, .. } => parts.push(GeminiPart::Text {
-                        text: t.clone(),
+                    Part::Text {
+                        content: t,
+                        signature,
+                        ..
+                    } => parts.push(GeminiPart::Text {
+                        text: t.to_string(),
                         thought: None,
+                        thought_signature: signature.clone(),
                     }),
-                    Part::Reasoning { content, .. } => parts.push(GeminiPart::Text {
-                        text: content.clone(),
+                    Part::Reasoning {
+                        content, signature, ..
+                    } => parts.push(GeminiPart::Text {
+                        text: content.to_string(),
                         thought: Some(true),
+                        thought_signature: signature.clone(),
                     }),
                     Part::Media {
-                        data, mime_type, ..
+                        media_type,
+                        data,
+                        mime_type,
+                        uri,
+                        ..
                     } => {
-                        let anchor_text = part.anchor_media();
-                        parts.push(GeminiPart::Text {
-                            text: anchor_text,
-                            thought: None,
-                        });
+                        if model_options.anchors_media() {
+                            let anchor_text = part.anchor_media();
+                            parts.push(GeminiPart::Text {
+                                text: anchor_text,
+                                thought: None,
+                                thought_signature: None,
+                            });
+                        }
 
-                        parts.push(GeminiPart::InlineData {
-                            inline_data: GeminiInlineData {
-                                mime_type: mime_type.clone(),
-                                data: data.clone(),
-                            },
-                        });
+                        let video_metadata = match media_type {
+                            MediaType::Video => model_options.provider.video_metadata.clone(),
+                            _ => None,
+                        };
+
+                        match uri {
+                            Some(file_uri) if part.is_remote_media() => {
+                                parts.push(GeminiPart::FileData {
+                                    file_data: GeminiFileData {
+                                        mime_type: Some(mime_type.clone())
+                                            .filter(|mime| !mime.ends_with("/*")),
+                                        file_uri: file_uri.clone(),
+                                    },
+                                    video_metadata,
+                                });
+                            }
+                            _ => {
+                                parts.push(GeminiPart::InlineData {
+                                    inline_data: GeminiInlineData {
+                                        mime_type: mime_type.clone(),
+                                        data: data.clone(),
+                                    },
+                                    video_metadata,
+                                });
+                            }
+                        }
                     }
                     Part::FunctionCall {
                         name,
@@ -479,6 +1119,7 @@ impl GeminiRequest {
                         name,
                         response,
                         parts: inner_parts,
+                        error,
                         ..
                     } => {
                         let mut parts_vec = Vec::new();
@@ -506,7 +1147,11 @@ impl GeminiRequest {
                         parts.push(GeminiPart::FunctionResponse {
                             function_response: GeminiFunctionResponse {
                                 name: name.clone(),
-                                response: response.clone(),
+                                // Gemini expects failures under an `error` key.
+                                response: match error {
+                                    Some(error) => error.to_response(),
+                                    None => function_response(response, inner_parts),
+                                },
                                 parts: function_response_parts,
                             },
                         });
@@ -529,7 +1174,10 @@ impl GeminiRequest {
                     .map(|t| GeminiFunctionDeclaration {
                         name: t.name.into_owned(),
                         description: t.description.map(|d| d.into_owned()).unwrap_or_default(),
-                        parameters_json_schema: Some(Value::Object((*t.input_schema).clone())),
+                        parameters_json_schema: Some(sanitize_schema(
+                            Value::Object((*t.input_schema).clone()),
+                            SchemaDialect::GeminiJsonSchema,
+                        )),
                     })
                     .collect(),
             }]
@@ -537,13 +1185,20 @@ impl GeminiRequest {
             Vec::new()
         };
 
-        let system_instruction = model_options.system.as_ref().map(|s| GeminiContent {
-            role: "user".to_string(),
-            parts: vec![GeminiPart::Text {
-                text: s.clone(),
-                thought: None,
-            }],
-        });
+        let system_instruction =
+            model_options
+                .system_prompt(PromptStyle::Plain)
+                .map(|text| GeminiContent {
+                    role: "user".to_string(),
+                    parts: vec![GeminiPart::Text {
+                        text,
+                        thought: None,
+                        thought_signature: None,
+                    }],
+                });
+
+        let (response_mime_type, response_json_schema, response_schema) =
+            response_format(model_options);
 
         Ok(GeminiRequest {
             contents,
@@ -554,8 +1209,11 @@ impl GeminiRequest {
                 top_p: model_options.top_p,
                 top_k: model_options.provider.top_k,
                 max_output_tokens: model_options.max_tokens,
+                candidate_count: model_options.provider.candidate_count,
                 stop_sequences: model_options.provider.stop_sequences.clone(),
-                response_mime_type: model_options.provider.response_mime_type.clone(),
+                response_mime_type,
+                response_json_schema,
+                response_schema,
                 thinking_config: if model_options.reasoning.unwrap_or(false)
                     || model_options.provider.include_thoughts.unwrap_or(false)
                 {
@@ -568,7 +1226,11 @@ impl GeminiRequest {
                     None
                 },
             },
-            safety_settings: model_options.provider.safety_settings.clone(),
+            safety_settings: model_options.provider.safety_settings.clone().or_else(|| {
+                model_options
+                    .safety
+                    .and_then(GeminiSafetySetting::for_level)
+            }),
         })
     }
 }
@@ -580,15 +1242,52 @@ impl GeminiRequest {
 struct GeminiResponse {
     candidates: Option<Vec<GeminiCandidate>>,
     usage_metadata: Option<GeminiUsageMetadata>,
+    prompt_feedback: Option<GeminiPromptFeedback>,
+}
+
+impl GeminiResponse {
+    /// Error for a prompt that was blocked before generation, which otherwise comes
+    /// back as a response without candidates.
+    fn prompt_blocked(&self) -> Option<ClientError> {
+        let feedback = self.prompt_feedback.as_ref()?;
+        let reason = feedback.block_reason.as_ref()?;
+        let mut categories: Vec<String> = feedback
+            .safety_ratings
+            .iter()
+            .filter(|rating| rating.blocked == Some(true))
+            .map(|rating| rating.category.clone())
+            .collect();
+        if categories.is_empty() {
+            categories.push(reason.clone());
+        }
+        Some(ClientError::ContentFiltered {
+            stage: FilterStage::Prompt,
+            categories,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiSafetyRating {
+    category: String,
+    blocked: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[allow(dead_code)]
 struct GeminiCandidate {
     content: Option<GeminiContent>,
     finish_reason: Option<String>,
     index: Option<u32>,
+    grounding_metadata: Option<Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -599,6 +1298,12 @@ struct GeminiUsageMetadata {
     candidates_token_count: Option<u32>,
     total_token_count: u32,
     thoughts_token_count: Option<u32>,
+    cached_content_token_count: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFileResponse {
+    file: GeminiFile,
 }
 
 #[derive(Debug, Deserialize)]
@@ -614,81 +1319,130 @@ struct GeminiError {
     status: String,
 }
 
+impl From<GeminiPart> for Part {
+    fn from(part: GeminiPart) -> Self {
+        match part {
+            GeminiPart::Text {
+                text,
+                thought: Some(true),
+                thought_signature,
+            } => Part::Reasoning {
+                content: text.into(),
+                summary: None,
+                signature: thought_signature,
+                extensions: Extensions::new(),
+                finished: true,
+            },
+            GeminiPart::Text {
+                text,
+                thought_signature,
+                ..
+            } => Part::Text {
+                content: text.into(),
+                signature: thought_signature,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            },
+            GeminiPart::FunctionCall {
+                function_call,
+                thought_signature,
+            } => Part::FunctionCall {
+                id: None,
+                name: function_call.name,
+                arguments: function_call.args,
+                signature: thought_signature,
+                repaired: false,
+                extensions: Extensions::new(),
+                finished: true,
+            },
+            GeminiPart::FunctionResponse { function_response } => Part::FunctionResponse {
+                id: None,
+                name: function_response.name,
+                response: function_response.response,
+                parts: function_response
+                    .parts
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|p| Part::Media {
+                        media_type: MediaType::Binary,
+                        data: p.inline_data.data,
+                        mime_type: p.inline_data.mime_type,
+                        uri: None,
+                        finished: true,
+                    })
+                    .collect(),
+                error: None,
+                finished: true,
+            },
+            GeminiPart::InlineData { inline_data, .. } => Part::Media {
+                media_type: MediaType::from_mime_type(&inline_data.mime_type),
+                data: inline_data.data,
+                mime_type: inline_data.mime_type,
+                uri: None,
+                finished: true,
+            },
+            GeminiPart::FileData { file_data, .. } => {
+                let mime_type = file_data
+                    .mime_type
+                    .unwrap_or_else(|| "application/octet-stream".to_string());
+                Part::remote_media(
+                    MediaType::from_mime_type(&mime_type),
+                    mime_type,
+                    file_data.file_uri,
+                )
+            }
+        }
+    }
+}
+
+/// Keep the grounding metadata of a candidate as an extension of its last text part.
+fn attach_grounding(parts: &mut [Part], metadata: Value) {
+    let last_text = parts.iter_mut().rev().find_map(|part| match part {
+        Part::Text { extensions, .. } => Some(extensions),
+        _ => None,
+    });
+    if let Some(extensions) = last_text {
+        extensions.insert("groundingMetadata".to_string(), metadata);
+    }
+}
+
+/// Map a Gemini candidate finish reason.
+fn finish_reason_from(reason: &str) -> FinishReason {
+    match reason {
+        "STOP" => FinishReason::Stop,
+        "MAX_TOKENS" => FinishReason::OutputTokens,
+        "SAFETY" => FinishReason::ContentFilter,
+        "RECITATION" => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
 impl From<GeminiResponse> for Response {
     fn from(resp: GeminiResponse) -> Self {
-        let mut parts = Vec::new();
-        let mut finish_reason = FinishReason::Unfinished;
-
-        if let Some(mut candidates) = resp.candidates {
-            if !candidates.is_empty() {
-                let candidate = candidates.remove(0);
-                if let Some(content) = candidate.content {
-                    for part in content.parts {
-                        match part {
-                            GeminiPart::Text { text, thought } => {
-                                if thought.unwrap_or(false) {
-                                    parts.push(Part::Reasoning {
-                                        content: text,
-                                        summary: None,
-                                        signature: None,
-                                        finished: true,
-                                    });
-                                } else {
-                                    parts.push(Part::Text {
-                                        content: text,
-                                        finished: true,
-                                    });
-                                }
-                            }
-                            GeminiPart::FunctionCall {
-                                function_call,
-                                thought_signature,
-                            } => {
-                                parts.push(Part::FunctionCall {
-                                    id: None,
-                                    name: function_call.name,
-                                    arguments: function_call.args,
-                                    signature: thought_signature,
-                                    finished: true,
-                                });
-                            }
-                            GeminiPart::FunctionResponse { function_response } => {
-                                let mut inner_parts = Vec::new();
-                                if let Some(gemini_parts) = function_response.parts {
-                                    for p in gemini_parts {
-                                        inner_parts.push(Part::Media {
-                                            media_type: MediaType::Binary,
-                                            data: p.inline_data.data,
-                                            mime_type: p.inline_data.mime_type,
-                                            uri: None,
-                                            finished: true,
-                                        });
-                                    }
-                                }
-
-                                parts.push(Part::FunctionResponse {
-                                    id: None,
-                                    name: function_response.name,
-                                    response: function_response.response,
-                                    parts: inner_parts,
-                                    finished: true,
-                                });
-                            }
-                            _ => {}
-                        }
-                    }
+        let mut candidates = resp.candidates.unwrap_or_default();
+        candidates.sort_by_key(|candidate| candidate.index.unwrap_or(0));
+
+        let finish_reason = candidates
+            .first()
+            .and_then(|candidate| candidate.finish_reason.as_deref())
+            .map_or(FinishReason::Unfinished, finish_reason_from);
+        let mut data: Vec<Message> = candidates
+            .into_iter()
+            .map(|candidate| {
+                let mut parts: Vec<Part> = candidate
+                    .content
+                    .map(|content| content.parts.into_iter().map(Part::from).collect())
+                    .unwrap_or_default();
+                if let Some(metadata) = candidate.grounding_metadata {
+                    attach_grounding(&mut parts, metadata);
                 }
-
-                if let Some(reason) = candidate.finish_reason {
-                    finish_reason = match reason.as_str() {
-                        "STOP" => FinishReason::Stop,
-                        "MAX_TOKENS" => FinishReason::OutputTokens,
-                        "SAFETY" => FinishReason::ContentFilter,
-                        "RECITATION" => FinishReason::ContentFilter,
-                        _ => FinishReason::Stop,
-                    };
-                }
-            }
+                parts.iter_mut().for_each(Part::annotate);
+                Message::Assistant(parts)
+            })
+            .collect();
+        if data.is_empty() {
+            data.push(Message::Assistant(vec![]));
         }
 
         let usage = resp
@@ -698,13 +1452,397 @@ impl From<GeminiResponse> for Response {
                 completion_tokens: Some(
                     u.candidates_token_count.unwrap_or(0) + u.thoughts_token_count.unwrap_or(0),
                 ),
+                cached_prompt_tokens: u.cached_content_token_count,
+                ..Default::default()
             })
             .unwrap_or_default();
 
         Response {
-            data: vec![Message::Assistant(parts)],
+            data,
             usage,
             finish: finish_reason,
+            stop_sequence: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::image;
+
+    fn part_kinds(options: &ModelOptions<GeminiModel>) -> Vec<String> {
+        let messages = vec![Message::User(vec![
+            Part::text("first"),
+            image("a"),
+            Part::text("second"),
+            image("b"),
+        ])];
+        let request = GeminiRequest::new(messages, options, vec![]).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        body["contents"][0]["parts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|part| {
+                if let Some(text) = part.get("text") {
+                    format!("text:{}", text.as_str().unwrap())
+                } else {
+                    format!("inline:{}", part["inlineData"]["data"].as_str().unwrap())
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_upload_cache_reuses_files() {
+        let cache = UploadCache::default();
+        let key = UploadCache::key(b"%PDF-1.7", "application/pdf");
+        assert_eq!(cache.get(&key), None);
+        cache.insert(key.clone(), "https://files/abc".to_string());
+
+        let clone = cache.clone();
+        assert_eq!(clone.get(&key).as_deref(), Some("https://files/abc"));
+        assert_eq!(
+            clone.get(&UploadCache::key(b"%PDF-1.7", "text/plain")),
+            None
+        );
+
+        // Instants cannot go further back than the start of the clock.
+        if let Some(expired) = Instant::now().checked_sub(UPLOAD_REUSE_TTL) {
+            cache.0.lock().unwrap().get_mut(&key).unwrap().1 = expired;
+            assert_eq!(cache.get(&key), None);
+        }
+    }
+
+    #[test]
+    fn test_upload_url() {
+        let client = GeminiClient::new(
+            "key".to_string(),
+            "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            ModelOptions::new("gemini"),
+            TransportOptions::default(),
+        );
+
+        assert_eq!(
+            client.upload_url(),
+            "https://generativelanguage.googleapis.com/upload/v1beta/files"
+        );
+    }
+
+    #[test]
+    fn test_uploaded_media_uses_file_data() {
+        let messages = vec![Message::User(vec![Part::Media {
+            media_type: MediaType::Document,
+            data: Bytes::new(),
+            mime_type: "application/pdf".to_string(),
+            uri: Some("https://generativelanguage.googleapis.com/v1beta/files/abc".to_string()),
+            finished: true,
+        }])];
+        let mut options = ModelOptions::new("gemini");
+        options.anchor_media = Some(false);
+
+        let request = GeminiRequest::new(messages, &options, vec![]).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["contents"][0]["parts"][0],
+            serde_json::json!({
+                "fileData": {
+                    "mimeType": "application/pdf",
+                    "fileUri": "https://generativelanguage.googleapis.com/v1beta/files/abc"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_youtube_part_uses_file_data() {
+        let messages = vec![Message::User(vec![Part::youtube(
+            "https://www.youtube.com/watch?v=9hE5-98ZeCg",
+        )])];
+        let mut options = ModelOptions::new("gemini");
+        options.anchor_media = Some(false);
+
+        let request = GeminiRequest::new(messages, &options, vec![]).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["contents"][0]["parts"][0],
+            serde_json::json!({
+                "fileData": { "fileUri": "https://www.youtube.com/watch?v=9hE5-98ZeCg" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_video_metadata() {
+        let messages = vec![Message::User(vec![Part::Media {
+            media_type: MediaType::Video,
+            data: Bytes::from_static(&[0; 3]),
+            mime_type: "video/mp4".to_string(),
+            uri: None,
+            finished: true,
+        }])];
+        let mut options: ModelOptions<GeminiModel> = ModelOptions::new("gemini");
+        options.anchor_media = Some(false);
+        options.provider.video_metadata = Some(GeminiVideoMetadata {
+            fps: Some(5.0),
+            start_offset: Some("10s".to_string()),
+            end_offset: None,
+        });
+
+        let request = GeminiRequest::new(messages, &options, vec![]).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["contents"][0]["parts"][0]["videoMetadata"],
+            serde_json::json!({ "fps": 5.0, "startOffset": "10s" })
+        );
+    }
+
+    #[test]
+    fn test_interleaved_media_keeps_order() {
+        assert_eq!(
+            part_kinds(&ModelOptions::new("gemini")),
+            vec![
+                "text:first",
+                "text:File (image/png) at a.png:",
+                "inline:YQ==",
+                "text:second",
+                "text:File (image/png) at b.png:",
+                "inline:Yg==",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_media_anchors_can_be_disabled() {
+        let mut options = ModelOptions::new("gemini");
+        options.anchor_media = Some(false);
+
+        assert_eq!(
+            part_kinds(&options),
+            vec!["text:first", "inline:YQ==", "text:second", "inline:Yg=="]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_accumulates_candidates_by_index() {
+        let events = [
+            serde_json::json!({ "candidates": [
+                { "index": 0, "content": { "role": "model", "parts": [{ "text": "Hel" }] } },
+                { "index": 1, "content": { "role": "model", "parts": [{ "text": "Bon" }] } }
+            ] }),
+            serde_json::json!({ "candidates": [
+                { "index": 1, "content": { "role": "model", "parts": [{ "text": "jour" }] }, "finishReason": "STOP" },
+                { "index": 0, "content": { "role": "model", "parts": [{ "text": "lo" }] } }
+            ] }),
+            serde_json::json!({ "candidates": [
+                { "index": 0, "content": { "role": "model", "parts": [] }, "finishReason": "MAX_TOKENS" }
+            ] }),
+        ];
+        let stream = GeminiStream::from_events(futures::stream::iter(
+            events.iter().map(|event| Ok(event.to_string())),
+        ));
+        let snapshots: Vec<Response> = stream.map(Result::unwrap).collect().await;
+
+        assert_eq!(snapshots[1].finish, FinishReason::Unfinished);
+        let last = snapshots.last().unwrap();
+        assert_eq!(last.finish, FinishReason::OutputTokens);
+        let texts: Vec<_> = last.data.iter().map(|m| m.content().unwrap()).collect();
+        assert_eq!(texts, vec!["Hello", "Bonjour"]);
+    }
+
+    #[test]
+    fn test_grounding_metadata_is_kept_on_text() {
+        let metadata = serde_json::json!({ "webSearchQueries": ["weather paris"] });
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "Sunny" }] },
+                "finishReason": "STOP",
+                "groundingMetadata": metadata
+            }]
+        }))
+        .unwrap();
+        let response = Response::from(response);
+
+        let part = &response.data[0].parts()[0];
+        assert_eq!(part.extensions().unwrap()["groundingMetadata"], metadata);
+    }
+
+    #[test]
+    fn test_response_keeps_candidates_apart() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [
+                { "index": 1, "content": { "role": "model", "parts": [{ "text": "B" }] }, "finishReason": "STOP" },
+                { "index": 0, "content": { "role": "model", "parts": [{ "text": "A" }] }, "finishReason": "MAX_TOKENS" }
+            ]
+        }))
+        .unwrap();
+        let response = Response::from(response);
+
+        assert_eq!(response.data.len(), 2);
+        assert_eq!(response.data[0].content().as_deref(), Some("A"));
+        assert_eq!(response.data[1].content().as_deref(), Some("B"));
+        assert_eq!(response.finish, FinishReason::OutputTokens);
+    }
+
+    #[test]
+    fn test_usage_reports_cached_tokens() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{ "content": { "role": "model", "parts": [{ "text": "Hi" }] } }],
+            "usageMetadata": {
+                "promptTokenCount": 2048,
+                "candidatesTokenCount": 3,
+                "totalTokenCount": 2051,
+                "cachedContentTokenCount": 1024
+            }
+        }))
+        .unwrap();
+        let usage = Response::from(response).usage;
+
+        assert_eq!(usage.prompt_tokens, Some(2048));
+        assert_eq!(usage.cached_prompt_tokens, Some(1024));
+    }
+
+    #[test]
+    fn test_blocked_prompt_is_an_error() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "promptFeedback": {
+                "blockReason": "SAFETY",
+                "safetyRatings": [
+                    { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" },
+                    { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true }
+                ]
+            },
+            "usageMetadata": { "promptTokenCount": 8, "totalTokenCount": 8 }
+        }))
+        .unwrap();
+
+        match response.prompt_blocked() {
+            Some(ClientError::ContentFiltered { stage, categories }) => {
+                assert_eq!(stage, FilterStage::Prompt);
+                assert_eq!(categories, vec!["HARM_CATEGORY_DANGEROUS_CONTENT"]);
+            }
+            other => panic!("unexpected {:?}", other),
         }
     }
+
+    #[test]
+    fn test_function_response_text_is_kept_apart() {
+        let text = [Part::text("Sunny in Paris.")];
+        assert_eq!(
+            function_response(&serde_json::json!({}), &text),
+            serde_json::json!({ "response": "Sunny in Paris." })
+        );
+        assert_eq!(
+            function_response(&serde_json::json!({ "temp": 21 }), &text),
+            serde_json::json!({ "temp": 21, "content": "Sunny in Paris." })
+        );
+        assert_eq!(
+            function_response(&serde_json::json!({ "temp": 21 }), &[]),
+            serde_json::json!({ "temp": 21 })
+        );
+    }
+
+    #[test]
+    fn test_text_thought_signatures_are_replayed() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        { "text": "Thinking", "thought": true, "thoughtSignature": "sig_a" },
+                        { "text": "Answer", "thoughtSignature": "sig_b" }
+                    ]
+                },
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+        let response = Response::from(response);
+
+        let request =
+            GeminiRequest::new(response.data, &ModelOptions::new("gemini"), vec![]).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["contents"][0]["parts"],
+            serde_json::json!([
+                { "text": "Thinking", "thought": true, "thoughtSignature": "sig_a" },
+                { "text": "Answer", "thoughtSignature": "sig_b" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_safety_level_maps_to_settings() {
+        let messages = vec![Message::user("Hi")];
+        let options = ModelOptions::<GeminiModel>::new("gemini").with_safety(SafetyLevel::Strict);
+        let request = GeminiRequest::new(messages.clone(), &options, vec![]).unwrap();
+        let settings = request.safety_settings.unwrap();
+        assert_eq!(settings.len(), 4);
+        assert!(settings
+            .iter()
+            .all(|setting| setting.threshold == "BLOCK_LOW_AND_ABOVE"));
+
+        let options = options.with_provider(GeminiModel {
+            safety_settings: Some(vec![GeminiSafetySetting {
+                category: "HARM_CATEGORY_HARASSMENT".to_string(),
+                threshold: "BLOCK_ONLY_HIGH".to_string(),
+            }]),
+            ..Default::default()
+        });
+        let request = GeminiRequest::new(messages.clone(), &options, vec![]).unwrap();
+        assert_eq!(request.safety_settings.unwrap().len(), 1);
+
+        let options = ModelOptions::<GeminiModel>::new("gemini").with_safety(SafetyLevel::Default);
+        let request = GeminiRequest::new(messages, &options, vec![]).unwrap();
+        assert!(request.safety_settings.is_none());
+    }
+
+    #[test]
+    fn test_output_format_maps_to_response_schemas() {
+        let messages = vec![Message::user("Is this review positive?")];
+        let options = ModelOptions::<GeminiModel>::new("gemini")
+            .with_output(OutputFormat::one_of(["positive", "negative"]));
+        let request = GeminiRequest::new(messages.clone(), &options, vec![]).unwrap();
+        let config = serde_json::to_value(&request.generation_config).unwrap();
+        assert_eq!(config["responseMimeType"], "text/x.enum");
+        assert_eq!(
+            config["responseSchema"],
+            serde_json::json!({ "type": "STRING", "enum": ["positive", "negative"] })
+        );
+        assert!(config.get("responseJsonSchema").is_none());
+
+        let schema =
+            serde_json::json!({ "type": "object", "properties": { "ok": { "type": "boolean" } } });
+        let options = options.with_output(OutputFormat::JsonSchema(schema.clone()));
+        let request = GeminiRequest::new(messages.clone(), &options, vec![]).unwrap();
+        let config = serde_json::to_value(&request.generation_config).unwrap();
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(config["responseJsonSchema"], schema);
+
+        // Raw provider settings take precedence.
+        let options = options.with_provider(GeminiModel {
+            response_mime_type: Some("text/plain".to_string()),
+            ..Default::default()
+        });
+        let request = GeminiRequest::new(messages, &options, vec![]).unwrap();
+        let config = serde_json::to_value(&request.generation_config).unwrap();
+        assert_eq!(config["responseMimeType"], "text/plain");
+        assert!(config.get("responseJsonSchema").is_none());
+
+        let invalid = ModelOptions::<GeminiModel>::new("gemini").with_provider(GeminiModel {
+            response_schema: Some(serde_json::json!({ "type": "STRING" })),
+            ..Default::default()
+        });
+        assert!(invalid.validate().is_err());
+        assert!(ModelOptions::<GeminiModel>::new("gemini")
+            .with_output(OutputFormat::Enum(vec![]))
+            .validate()
+            .is_err());
+    }
 }
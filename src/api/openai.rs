@@ -1,24 +1,94 @@
 //! OpenAI Chat Completions API client implementation.
 
 use async_trait::async_trait;
+use base64::prelude::*;
+use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use serde_with::skip_serializing_none;
+use serde_with::{serde_as, skip_serializing_none, DefaultOnNull};
 use std::collections::HashMap;
 use std::pin::Pin;
 
-use crate::client::{Client, ClientError, StreamingClient};
-use crate::http::{add_extra_headers, build_http_client, RequestBuilderExt, ResponseExt};
-use crate::model::{FinishReason, MediaType, Message, Part, Response, Usage};
-use crate::options::{ModelOptions, TransportOptions};
+use crate::client::{Client, ClientError, FilterStage, RequestPreview, StreamingClient};
+use crate::constraints::PromptStyle;
+use crate::history::{normalize_history, push_merged};
+use crate::http::{
+    add_extra_headers, add_idempotency_key, build_http_client, check_request_size,
+    estimate_request_tokens, preview_request, request_id, RequestBuilderExt, ResponseExt,
+};
+use crate::model::{
+    base64_data, Extensions, FinishReason, MediaType, Message, Part, Response, TextAnnotations,
+    Usage,
+};
+use crate::options::{
+    ModelOptions, OptionViolation, ProviderOptions, SafetyLevel, ServiceTier, TransportOptions,
+};
+use crate::schema::{sanitize_schema, SchemaDialect};
 use crate::sse::SSEResponseExt;
+use crate::stream::{shape_stream, FirstTokenDeadline};
+use crate::structured::parse_arguments;
+use crate::tools::ToolExt;
 
 /// Trait for models compatible with OpenAI's Chat Completions API.
 pub trait OpenAICompatibleModel:
     Send + Sync + Default + Serialize + for<'de> Deserialize<'de> + Clone
 {
+    /// Value of the `service_tier` field requesting `tier`, or `None` if the API does
+    /// not offer it. No API offers tiers unless it overrides this.
+    fn service_tier(_tier: ServiceTier) -> Option<&'static str> {
+        None
+    }
+}
+
+impl<M: OpenAICompatibleModel> ProviderOptions for M {
+    fn validate(options: &ModelOptions<Self>, violations: &mut Vec<OptionViolation>) {
+        if let Some(tier) = options.service_tier {
+            if M::service_tier(tier).is_none() {
+                violations.push(OptionViolation::new(
+                    "service_tier",
+                    format!("{:?} is not offered by this provider", tier),
+                ));
+            }
+        }
+        if options
+            .safety
+            .is_some_and(|level| level != SafetyLevel::Default)
+        {
+            violations.push(OptionViolation::new(
+                "safety",
+                "content filters are not configurable on this provider",
+            ));
+        }
+        if options.output.is_some() {
+            violations.push(OptionViolation::new(
+                "output",
+                "constrained output is not supported by this provider",
+            ));
+        }
+        if !is_reasoning_model(&options.model) {
+            return;
+        }
+        if options.temperature.is_some() {
+            violations.push(OptionViolation::new(
+                "temperature",
+                "is not supported by reasoning models",
+            ));
+        }
+        if options.top_p.is_some() {
+            violations.push(OptionViolation::new(
+                "top_p",
+                "is not supported by reasoning models",
+            ));
+        }
+    }
+}
+
+/// Whether the model is a reasoning model, which takes `max_completion_tokens` and only
+/// supports the default sampling parameters.
+fn is_reasoning_model(model: &str) -> bool {
+    model.starts_with("o1") || model.starts_with("o3")
 }
 
 /// Generic client for OpenAI-compatible Chat Completions APIs.
@@ -45,14 +115,33 @@ impl<M: OpenAICompatibleModel> OpenAIClient<M> {
         }
     }
 
-    fn handle_error_response(status: reqwest::StatusCode, body: &str) -> ClientError {
-        if let Ok(error_resp) = serde_json::from_str::<OpenAIErrorResponse>(body) {
-            ClientError::ProviderError(format!(
+    /// Send requests to the API rooted at `base_url` instead.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    fn handle_error_response(
+        status: reqwest::StatusCode,
+        request_id: Option<String>,
+        body: &str,
+    ) -> ClientError {
+        let message = if let Ok(error_resp) = serde_json::from_str::<OpenAIErrorResponse>(body) {
+            if let Some(error) = error_resp.error.prompt_blocked() {
+                return error;
+            }
+            format!(
                 "OpenAI error ({}): {}",
-                error_resp.error.error_type, error_resp.error.message
-            ))
+                error_resp.error.error_type.as_deref().unwrap_or("unknown"),
+                error_resp.error.message
+            )
         } else {
-            ClientError::ProviderError(format!("HTTP {}: {}", status, body))
+            format!("HTTP {}: {}", status, body)
+        };
+        ClientError::Api {
+            status: Some(status.as_u16()),
+            request_id,
+            message,
         }
     }
 
@@ -64,9 +153,17 @@ impl<M: OpenAICompatibleModel> OpenAIClient<M> {
     ) -> Result<reqwest::RequestBuilder, ClientError> {
         let url = format!("{}/chat/completions", self.base_url);
 
-        let model = self.model_options.model.clone();
+        let model = self.model_options.request_model();
+
+        let messages = if self.model_options.normalize_history.unwrap_or(false) {
+            normalize_history(messages)
+        } else {
+            messages
+        };
 
-        let request_body = OpenAIRequest::new(messages, &self.model_options, model, tools, stream);
+        check_request_size(&self.transport_options, &messages)?;
+
+        let request_body = OpenAIRequest::new(messages, &self.model_options, model, tools, stream)?;
 
         let http_client = build_http_client(&self.transport_options)?;
 
@@ -77,9 +174,24 @@ impl<M: OpenAICompatibleModel> OpenAIClient<M> {
             HeaderValue::from_str(&format!("Bearer {}", self.api_key))
                 .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
         );
+        if let Some(organization) = self.transport_options.organization() {
+            headers.insert(
+                "OpenAI-Organization",
+                HeaderValue::from_str(organization)
+                    .map_err(|_| ClientError::Config("Invalid organization".to_string()))?,
+            );
+        }
+        if let Some(project) = self.transport_options.project() {
+            headers.insert(
+                "OpenAI-Project",
+                HeaderValue::from_str(project)
+                    .map_err(|_| ClientError::Config("Invalid project".to_string()))?,
+            );
+        }
 
         let mut req = http_client.post(&url).headers(headers);
         req = add_extra_headers(req, &self.transport_options);
+        req = add_idempotency_key(req, &self.transport_options);
 
         Ok(req.json_logged(&request_body))
     }
@@ -100,8 +212,9 @@ impl<M: OpenAICompatibleModel> Client for OpenAIClient<M> {
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
         let openai_response: OpenAIResponse = response.json_logged().await?;
@@ -115,6 +228,19 @@ impl<M: OpenAICompatibleModel> Client for OpenAIClient<M> {
     fn transport_options(&self) -> &TransportOptions {
         &self.transport_options
     }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<rmcp::model::Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        let system = self.model_options.system_prompt(PromptStyle::Plain);
+        let estimated_tokens = estimate_request_tokens(system.as_deref(), &messages, &tools);
+        preview_request(
+            self.build_request(messages, tools, false)?,
+            estimated_tokens,
+        )
+    }
 }
 
 #[async_trait]
@@ -126,15 +252,22 @@ impl<M: OpenAICompatibleModel> StreamingClient for OpenAIClient<M> {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
     {
         let req = self.build_request(messages, tools, true)?;
-        let response = req.send().await?;
+        let retry = req.try_clone();
+        let deadline = FirstTokenDeadline::start(&self.transport_options);
+        let response = deadline.send(req).await?;
         let status = response.status();
 
         if !status.is_success() {
+            let request_id = request_id(response.headers());
             let body = response.text_logged().await.unwrap_or_default();
-            return Err(Self::handle_error_response(status, &body));
+            return Err(Self::handle_error_response(status, request_id, &body));
         }
 
-        Ok(Box::pin(OpenAIStream::create(response)))
+        let events = response.sse_with_transport(retry, &self.transport_options);
+        Ok(shape_stream(
+            &self.transport_options,
+            deadline.guard(OpenAIStream::create(events)),
+        ))
     }
 }
 
@@ -144,16 +277,16 @@ struct OpenAIStream;
 
 impl OpenAIStream {
     fn create(
-        response: reqwest::Response,
+        sse_stream: impl Stream<Item = Result<String, ClientError>> + Send,
     ) -> impl Stream<Item = Result<Response, ClientError>> + Send {
-        let sse_stream = response.sse();
-
         Box::pin(async_stream::try_stream! {
             let mut stream = Box::pin(sse_stream);
             let mut current_response = Response {
                 data: vec![Message::Assistant(vec![])],
                 usage: Usage::default(),
                 finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
             };
 
             let mut tool_index_map: HashMap<u32, usize> = HashMap::new();
@@ -166,8 +299,10 @@ impl OpenAIStream {
                     .map_err(|e| ClientError::ProviderError(format!("JSON parse error: {} | Input: {}", e, event_str)))?;
 
                 if let Some(usage) = chunk_result.usage {
-                    current_response.usage.prompt_tokens = Some(usage.prompt_tokens);
-                    current_response.usage.completion_tokens = Some(usage.completion_tokens);
+                    current_response.usage = usage.into();
+                }
+                if let Some(tier) = chunk_result.service_tier.as_deref() {
+                    current_response.service_tier = ServiceTier::from_reported(tier);
                 }
 
                 for choice in chunk_result.choices {
@@ -180,11 +315,22 @@ impl OpenAIStream {
                                     content.push_str(&delta_content);
                                 }
                             } else {
-                                parts.push(Part::Text { content: delta_content, finished: false });
+                                parts.push(Part::Text { content: delta_content.into(), signature: None, extensions: Extensions::new(), annotations: TextAnnotations::default(), finished: false });
                                 current_text_part_index = Some(parts.len() - 1);
                             }
                         }
 
+                        if let Some(annotations) = delta.annotations {
+                            if let Some(Part::Text { extensions, .. }) = current_text_part_index.and_then(|idx| parts.get_mut(idx)) {
+                                let entry = extensions
+                                    .entry("annotations")
+                                    .or_insert_with(|| Value::Array(Vec::new()));
+                                if let Value::Array(entry) = entry {
+                                    entry.extend(annotations);
+                                }
+                            }
+                        }
+
                         if let Some(tool_calls) = delta.tool_calls {
                             for tool_call in tool_calls {
                                 let idx = *tool_index_map.entry(tool_call.index).or_insert_with(|| {
@@ -193,6 +339,8 @@ impl OpenAIStream {
                                         name: String::new(),
                                         arguments: Value::String(String::new()),
                                         signature: None,
+                                        repaired: false,
+                                        extensions: Extensions::new(),
                                         finished: false,
                                     });
                                     parts.len() - 1
@@ -222,19 +370,16 @@ impl OpenAIStream {
                             match part {
                                 Part::Text { finished, .. } => *finished = true,
                                 Part::Reasoning { finished, .. } => *finished = true,
-                                Part::FunctionCall { finished, arguments, .. } => {
+                                Part::FunctionCall { finished, arguments, repaired, .. } => {
                                     *finished = true;
                                     if let Value::String(json_str) = arguments {
-                                        if let Ok(json_val) = serde_json::from_str(json_str) {
-                                            *arguments = json_val;
-                                        } else {
-                                            *arguments = json!({});
-                                        }
+                                        (*arguments, *repaired) = parse_arguments(json_str);
                                     }
                                 },
                                 Part::FunctionResponse { finished, .. } => *finished = true,
                                 Part::Media { finished, .. } => *finished = true,
                             }
+                            part.annotate();
                         }
 
                         current_response.finish = match finish_reason.as_str() {
@@ -244,6 +389,7 @@ impl OpenAIStream {
                             "content_filter" => FinishReason::ContentFilter,
                             _ => FinishReason::Stop,
                         };
+                        current_response.stop_sequence = matched_stop_sequence(&choice.stop_reason);
                     }
                 }
 
@@ -253,6 +399,110 @@ impl OpenAIStream {
     }
 }
 
+/// Render a conversation as an OpenAI fine-tuning example (`messages` and `tools`).
+pub(crate) fn training_example<M: OpenAICompatibleModel>(
+    messages: Vec<Message>,
+    model_options: &ModelOptions<M>,
+    tools: Vec<rmcp::model::Tool>,
+) -> Result<Value, ClientError> {
+    let request = OpenAIRequest::new(messages, model_options, String::new(), tools, false)?;
+    let mut example = serde_json::to_value(request)?;
+    if let Value::Object(fields) = &mut example {
+        fields.retain(|key, _| key == "messages" || key == "tools");
+    }
+    Ok(example)
+}
+
+/// Text of the system and developer messages of an OpenAI `messages` array, separated
+/// by blank lines, or `None` if there are none.
+#[cfg(feature = "server")]
+pub(crate) fn system_from_json(value: &Value) -> Result<Option<String>, ClientError> {
+    let wire: Vec<OpenAIMessage> = match value {
+        Value::Array(_) => serde_json::from_value(value.clone())?,
+        other => vec![serde_json::from_value(other.clone())?],
+    };
+    let prompts: Vec<String> = wire
+        .into_iter()
+        .filter(|message| matches!(message.role.as_str(), "system" | "developer"))
+        .map(|message| message.content.into_text())
+        .filter(|text| !text.is_empty())
+        .collect();
+    Ok((!prompts.is_empty()).then(|| prompts.join("\n\n")))
+}
+
+/// Parse an OpenAI Chat Completions `messages` array (or a single message).
+///
+/// System and developer messages are skipped, since the system prompt lives in
+/// [`ModelOptions::system`]. Tool results are attached to the following user turn.
+pub(crate) fn messages_from_json(value: Value) -> Result<Vec<Message>, ClientError> {
+    let wire: Vec<OpenAIMessage> = match value {
+        Value::Array(_) => serde_json::from_value(value)?,
+        other => vec![serde_json::from_value(other)?],
+    };
+
+    let mut call_names = HashMap::new();
+    let mut messages = Vec::new();
+    let mut after_tool = false;
+
+    for message in wire {
+        let is_tool = message.role == "tool";
+        let converted = match message.role.as_str() {
+            "user" => Message::User(message.content.into_parts()),
+            "assistant" => {
+                let mut parts = message.content.into_parts();
+                for call in message.tool_calls {
+                    call_names.insert(call.id.clone(), call.function.name.clone());
+                    let arguments = serde_json::from_str(&call.function.arguments)
+                        .unwrap_or(Value::String(call.function.arguments));
+                    parts.push(Part::FunctionCall {
+                        id: Some(call.id),
+                        name: call.function.name,
+                        arguments,
+                        signature: None,
+                        repaired: false,
+                        extensions: Extensions::new(),
+                        finished: true,
+                    });
+                }
+                Message::Assistant(parts)
+            }
+            "tool" => {
+                let name = message
+                    .name
+                    .or_else(|| {
+                        let id = message.tool_call_id.as_ref()?;
+                        call_names.get(id).cloned()
+                    })
+                    .unwrap_or_default();
+                let text = message.content.into_text();
+                let response =
+                    serde_json::from_str(&text).unwrap_or_else(|_| json!({ "response": text }));
+                Message::User(vec![Part::FunctionResponse {
+                    id: message.tool_call_id,
+                    name,
+                    response,
+                    parts: Vec::new(),
+                    error: None,
+                    finished: true,
+                }])
+            }
+            role => {
+                tracing::debug!("Skipping {} message", role);
+                continue;
+            }
+        };
+
+        if is_tool || after_tool {
+            push_merged(&mut messages, converted);
+        } else {
+            messages.push(converted);
+        }
+        after_tool = is_tool;
+    }
+
+    Ok(messages)
+}
+
 // --- Request Types ---
 
 #[skip_serializing_none]
@@ -268,30 +518,69 @@ struct OpenAIRequest<M> {
     stream: Option<bool>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<OpenAITool>,
+    service_tier: Option<&'static str>,
     #[serde(flatten)]
     provider_options: M,
 }
 
-#[derive(Debug, Serialize)]
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
 struct OpenAIMessage {
     role: String,
+    #[serde_as(as = "DefaultOnNull")]
+    #[serde(default)]
     content: OpenAIContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     tool_calls: Vec<OpenAIToolCall>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum OpenAIContent {
     Text(String),
     Parts(Vec<OpenAIContentPart>),
 }
 
-#[derive(Debug, Serialize)]
+impl Default for OpenAIContent {
+    fn default() -> Self {
+        OpenAIContent::Text(String::new())
+    }
+}
+
+impl OpenAIContent {
+    fn into_text(self) -> String {
+        match self {
+            OpenAIContent::Text(text) => text,
+            OpenAIContent::Parts(parts) => parts
+                .into_iter()
+                .filter_map(|part| match part {
+                    OpenAIContentPart::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn into_parts(self) -> Vec<Part> {
+        match self {
+            OpenAIContent::Text(text) if text.is_empty() => Vec::new(),
+            OpenAIContent::Text(text) => vec![Part::Text {
+                content: text.into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }],
+            OpenAIContent::Parts(parts) => parts.into_iter().map(Part::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum OpenAIContentPart {
     Text { text: String },
@@ -299,7 +588,80 @@ enum OpenAIContentPart {
     File { file: OpenAIFileContent },
 }
 
-#[derive(Debug, Serialize)]
+impl From<OpenAIContentPart> for Part {
+    fn from(part: OpenAIContentPart) -> Self {
+        match part {
+            OpenAIContentPart::Text { text } => Part::Text {
+                content: text.into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            },
+            OpenAIContentPart::ImageUrl { image_url } => match parse_data_url(&image_url.url) {
+                Some((mime_type, data)) => Part::Media {
+                    media_type: MediaType::Image,
+                    data,
+                    mime_type,
+                    uri: None,
+                    finished: true,
+                },
+                None => Part::remote_media(MediaType::Image, "image/*", image_url.url),
+            },
+            OpenAIContentPart::File { file } => {
+                let data = file.file_data.unwrap_or_default();
+                let (mime_type, data) = parse_data_url(&data).unwrap_or_else(|| {
+                    (
+                        "application/octet-stream".to_string(),
+                        base64_data::decode_lenient(data),
+                    )
+                });
+                let media_type = if mime_type == "application/pdf" {
+                    MediaType::Document
+                } else {
+                    MediaType::Binary
+                };
+                Part::Media {
+                    media_type,
+                    data,
+                    mime_type,
+                    uri: file.filename.or(file.file_id),
+                    finished: true,
+                }
+            }
+        }
+    }
+}
+
+/// Split a `data:<mime>;base64,<data>` URL into its MIME type and decoded payload.
+fn parse_data_url(url: &str) -> Option<(String, Bytes)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    let data = BASE64_STANDARD.decode(data).ok()?;
+    Some((mime_type.to_string(), data.into()))
+}
+
+/// Image content part for a media part, by URL if it is remote and inline otherwise.
+fn image_part(part: &Part) -> Option<OpenAIContentPart> {
+    let Part::Media {
+        data,
+        mime_type,
+        uri,
+        ..
+    } = part
+    else {
+        return None;
+    };
+    let url = match uri {
+        Some(uri) if part.is_remote_media() => uri.clone(),
+        _ => format!("data:{};base64,{}", mime_type, BASE64_STANDARD.encode(data)),
+    };
+    Some(OpenAIContentPart::ImageUrl {
+        image_url: OpenAIImageUrl { url },
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct OpenAIFileContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     file_data: Option<String>,
@@ -309,7 +671,7 @@ struct OpenAIFileContent {
     filename: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct OpenAIImageUrl {
     url: String,
 }
@@ -326,6 +688,8 @@ struct OpenAIFunction {
     name: String,
     description: Option<String>,
     parameters: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strict: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -349,19 +713,20 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
         model: String,
         tool_defs: Vec<rmcp::model::Tool>,
         stream: bool,
-    ) -> Self {
+    ) -> Result<Self, ClientError> {
         let mut messages = Vec::new();
 
-        if let Some(system) = &model_options.system {
+        if let Some(system) = model_options.system_prompt(PromptStyle::Plain) {
             messages.push(OpenAIMessage {
                 role: "system".to_string(),
-                content: OpenAIContent::Text(system.clone()),
+                content: OpenAIContent::Text(system),
                 name: None,
                 tool_call_id: None,
                 tool_calls: Vec::new(),
             });
         }
 
+        let forward_media = model_options.forward_tool_media.unwrap_or(true);
         for msg in messages_in {
             let role = match msg {
                 Message::User(_) => "user",
@@ -370,34 +735,48 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
 
             let mut content_parts = Vec::new();
             let mut tool_calls = Vec::new();
-            let mut tool_call_id = None;
-            let name = None;
+            let mut tool_messages = Vec::new();
+            // Images of tool results, sent with the user content after the tool messages.
+            let mut forwarded_parts = Vec::new();
 
             for part in msg.parts() {
                 match part {
-                    Part::Text { content: t, .. } => {
-                        content_parts.push(OpenAIContentPart::Text { text: t.clone() })
-                    }
+                    Part::Text { content: t, .. } => content_parts.push(OpenAIContentPart::Text {
+                        text: t.to_string(),
+                    }),
                     Part::Media {
                         media_type: MediaType::Image,
-                        data,
-                        mime_type,
                         ..
                     } => {
-                        let anchor_text = part.anchor_media();
-                        content_parts.push(OpenAIContentPart::Text { text: anchor_text });
-                        content_parts.push(OpenAIContentPart::ImageUrl {
-                            image_url: OpenAIImageUrl {
-                                url: format!("data:{};base64,{}", mime_type, data),
-                            },
+                        if model_options.anchors_media() {
+                            let anchor_text = part.anchor_media();
+                            content_parts.push(OpenAIContentPart::Text { text: anchor_text });
+                        }
+                        content_parts.extend(image_part(part));
+                    }
+                    Part::Media {
+                        media_type: MediaType::Video,
+                        ..
+                    } => {
+                        return Err(ClientError::Unsupported {
+                            provider: "OpenAI".to_string(),
+                            capability: "video input".to_string(),
+                        });
+                    }
+                    Part::Media { .. } if part.is_remote_media() => {
+                        return Err(ClientError::Unsupported {
+                            provider: "OpenAI".to_string(),
+                            capability: "remote file URIs".to_string(),
                         });
                     }
                     Part::Media { data, uri, .. } => {
-                        let anchor_text = part.anchor_media();
-                        content_parts.push(OpenAIContentPart::Text { text: anchor_text });
+                        if model_options.anchors_media() {
+                            let anchor_text = part.anchor_media();
+                            content_parts.push(OpenAIContentPart::Text { text: anchor_text });
+                        }
                         content_parts.push(OpenAIContentPart::File {
                             file: OpenAIFileContent {
-                                file_data: Some(data.clone()),
+                                file_data: Some(BASE64_STANDARD.encode(data)),
                                 file_id: None,
                                 filename: uri.clone(),
                             },
@@ -422,17 +801,25 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
                         id: Some(call_id),
                         response,
                         parts,
+                        error,
                         ..
                     } => {
-                        tool_call_id = Some(call_id.clone());
-
                         let mut content_str = String::new();
 
-                        if response != &serde_json::json!({}) {
+                        if let Some(error) = error {
+                            content_str.push_str(&format!("Error: {}", error.message));
+                        } else if response != &serde_json::json!({}) {
                             content_str.push_str(&response.to_string());
                         }
 
                         for part in parts {
+                            // The error message already holds the text of failed calls.
+                            if let (Part::Text { content, .. }, None) = (part, error) {
+                                if !content_str.is_empty() {
+                                    content_str.push('\n');
+                                }
+                                content_str.push_str(content);
+                            }
                             if let Part::Media {
                                 media_type,
                                 mime_type,
@@ -440,22 +827,47 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
                             } = part
                             {
                                 let anchor_text = part.anchor_media();
-                                content_str.push_str(&format!("\n{}", anchor_text));
+                                if model_options.anchors_media() {
+                                    content_str.push_str(&format!("\n{}", anchor_text));
+                                }
 
                                 match media_type {
+                                    MediaType::Image if forward_media => {
+                                        content_str.push_str("\n[Image sent in the next message]");
+                                        if model_options.anchors_media() {
+                                            forwarded_parts.push(OpenAIContentPart::Text {
+                                                text: anchor_text,
+                                            });
+                                        }
+                                        forwarded_parts.extend(image_part(part));
+                                    }
                                     MediaType::Image => content_str.push_str("\n[Image Content]"),
                                     _ => content_str.push_str(&format!("\n[File: {}]", mime_type)),
                                 }
                             }
                         }
 
-                        content_parts.push(OpenAIContentPart::Text { text: content_str });
+                        // Every result is a separate `tool` message answering its call.
+                        tool_messages.push(OpenAIMessage {
+                            role: "tool".to_string(),
+                            content: OpenAIContent::Text(content_str),
+                            name: None,
+                            tool_call_id: Some(call_id.clone()),
+                            tool_calls: Vec::new(),
+                        });
                     }
                     _ => {}
                 }
             }
 
-            let final_role = if tool_call_id.is_some() { "tool" } else { role };
+            // Tool results have to directly follow the assistant turn that requested them,
+            // so they go before any other content of the same user turn.
+            let answers_tools = !tool_messages.is_empty();
+            messages.extend(tool_messages);
+            content_parts.splice(0..0, forwarded_parts);
+            if answers_tools && content_parts.is_empty() {
+                continue;
+            }
 
             let content = if content_parts.len() == 1 {
                 if let OpenAIContentPart::Text { text } = &content_parts[0] {
@@ -470,34 +882,43 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
             };
 
             messages.push(OpenAIMessage {
-                role: final_role.to_string(),
+                role: role.to_string(),
                 content,
-                name,
-                tool_call_id,
+                name: None,
+                tool_call_id: None,
                 tool_calls,
             });
         }
 
+        let strict_tools = model_options.strict_tools.unwrap_or(false);
         let tools = tool_defs
             .into_iter()
-            .map(|t| OpenAITool {
-                tool_type: "function".to_string(),
-                function: OpenAIFunction {
-                    name: t.name.into_owned(),
-                    description: t.description.map(|d| d.into_owned()),
-                    parameters: Value::Object((*t.input_schema).clone()),
-                },
+            .map(|t| {
+                let strict = t.strict().unwrap_or(strict_tools);
+                let parameters = Value::Object((*t.input_schema).clone());
+                OpenAITool {
+                    tool_type: "function".to_string(),
+                    function: OpenAIFunction {
+                        name: t.name.into_owned(),
+                        description: t.description.map(|d| d.into_owned()),
+                        parameters: if strict {
+                            sanitize_schema(parameters, SchemaDialect::OpenAIStrict)
+                        } else {
+                            parameters
+                        },
+                        strict: strict.then_some(true),
+                    },
+                }
             })
             .collect();
 
-        let is_reasoning_model = model.starts_with("o1") || model.starts_with("o3");
-        let (max_tokens, max_completion_tokens) = if is_reasoning_model {
+        let (max_tokens, max_completion_tokens) = if is_reasoning_model(&model) {
             (None, model_options.max_tokens)
         } else {
             (model_options.max_tokens, None)
         };
 
-        OpenAIRequest {
+        Ok(OpenAIRequest {
             model,
             messages,
             max_tokens,
@@ -506,8 +927,9 @@ impl<M: OpenAICompatibleModel> OpenAIRequest<M> {
             top_p: model_options.top_p,
             stream: if stream { Some(true) } else { None },
             tools,
+            service_tier: model_options.service_tier.and_then(M::service_tier),
             provider_options: model_options.provider.clone(),
-        }
+        })
     }
 }
 
@@ -519,12 +941,17 @@ struct OpenAIResponse {
     id: String,
     choices: Vec<OpenAIChoice>,
     usage: Option<OpenAIUsage>,
+    service_tier: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIChoice {
     message: OpenAIResponseMessage,
     finish_reason: Option<String>,
+    /// Matched stop sequence, reported by OpenAI-compatible servers such as vLLM.
+    /// Stop token ids are reported as numbers and ignored.
+    #[serde(default)]
+    stop_reason: Option<Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -533,12 +960,34 @@ struct OpenAIResponseMessage {
     role: String,
     content: Option<String>,
     tool_calls: Option<Vec<OpenAIToolCall>>,
+    #[serde(default)]
+    annotations: Vec<Value>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIUsage {
     prompt_tokens: u32,
     completion_tokens: u32,
+    completion_tokens_details: Option<OpenAICompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAICompletionTokensDetails {
+    accepted_prediction_tokens: Option<u32>,
+    rejected_prediction_tokens: Option<u32>,
+}
+
+impl From<OpenAIUsage> for Usage {
+    fn from(usage: OpenAIUsage) -> Self {
+        let details = usage.completion_tokens_details;
+        Usage {
+            prompt_tokens: Some(usage.prompt_tokens),
+            completion_tokens: Some(usage.completion_tokens),
+            accepted_prediction_tokens: details.as_ref().and_then(|d| d.accepted_prediction_tokens),
+            rejected_prediction_tokens: details.and_then(|d| d.rejected_prediction_tokens),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -549,30 +998,74 @@ struct OpenAIErrorResponse {
 #[derive(Debug, Deserialize)]
 struct OpenAIError {
     #[serde(rename = "type")]
-    error_type: String,
+    error_type: Option<String>,
     message: String,
+    code: Option<String>,
+    /// Azure OpenAI details, with the `content_filter_result` of blocked prompts.
+    innererror: Option<Value>,
+}
+
+impl OpenAIError {
+    /// Error for a prompt rejected by the Azure OpenAI content filter.
+    fn prompt_blocked(&self) -> Option<ClientError> {
+        if self.code.as_deref() != Some("content_filter") {
+            return None;
+        }
+        let mut categories: Vec<String> = self
+            .innererror
+            .as_ref()
+            .and_then(|inner| inner["content_filter_result"].as_object())
+            .into_iter()
+            .flatten()
+            .filter(|(_, result)| result["filtered"] == Value::Bool(true))
+            .map(|(category, _)| category.clone())
+            .collect();
+        if categories.is_empty() {
+            categories.push("content_filter".to_string());
+        }
+        Some(ClientError::ContentFiltered {
+            stage: FilterStage::Prompt,
+            categories,
+        })
+    }
 }
 
 impl From<OpenAIResponse> for Response {
     fn from(resp: OpenAIResponse) -> Self {
         let mut parts = Vec::new();
         let mut finish_reason = FinishReason::Stop;
+        let mut stop_sequence = None;
 
         if let Some(choice) = resp.choices.first() {
+            stop_sequence = matched_stop_sequence(&choice.stop_reason);
             if let Some(content) = &choice.message.content {
-                parts.push(Part::Text {
-                    content: content.clone(),
+                let mut extensions = Extensions::new();
+                if !choice.message.annotations.is_empty() {
+                    extensions.insert(
+                        "annotations".to_string(),
+                        Value::Array(choice.message.annotations.clone()),
+                    );
+                }
+                let mut part = Part::Text {
+                    content: content.clone().into(),
+                    signature: None,
+                    extensions,
+                    annotations: TextAnnotations::default(),
                     finished: true,
-                });
+                };
+                part.annotate();
+                parts.push(part);
             }
             if let Some(tool_calls) = &choice.message.tool_calls {
                 for tool_call in tool_calls {
+                    let (arguments, repaired) = parse_arguments(&tool_call.function.arguments);
                     parts.push(Part::FunctionCall {
                         id: Some(tool_call.id.clone()),
                         name: tool_call.function.name.clone(),
-                        arguments: serde_json::from_str(&tool_call.function.arguments)
-                            .unwrap_or(Value::Null),
+                        arguments,
                         signature: None,
+                        repaired,
+                        extensions: Extensions::new(),
                         finished: true,
                     });
                 }
@@ -589,22 +1082,28 @@ impl From<OpenAIResponse> for Response {
             }
         }
 
-        let usage = resp
-            .usage
-            .map(|u| Usage {
-                prompt_tokens: Some(u.prompt_tokens),
-                completion_tokens: Some(u.completion_tokens),
-            })
-            .unwrap_or_default();
+        let usage = resp.usage.map(Usage::from).unwrap_or_default();
 
         Response {
             data: vec![Message::Assistant(parts)],
             usage,
             finish: finish_reason,
+            stop_sequence,
+            service_tier: resp
+                .service_tier
+                .as_deref()
+                .and_then(ServiceTier::from_reported),
         }
     }
 }
 
+fn matched_stop_sequence(stop_reason: &Option<Value>) -> Option<String> {
+    match stop_reason {
+        Some(Value::String(sequence)) => Some(sequence.clone()),
+        _ => None,
+    }
+}
+
 // --- Stream Types ---
 
 #[derive(Debug, Deserialize)]
@@ -613,18 +1112,22 @@ struct OpenAIStreamChunk {
     id: String,
     choices: Vec<OpenAIStreamChoice>,
     usage: Option<OpenAIUsage>,
+    service_tier: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIStreamChoice {
     delta: Option<OpenAIDelta>,
     finish_reason: Option<String>,
+    #[serde(default)]
+    stop_reason: Option<Value>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIDelta {
     content: Option<String>,
     tool_calls: Option<Vec<OpenAIStreamToolCall>>,
+    annotations: Option<Vec<Value>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -639,3 +1142,310 @@ struct OpenAIStreamFunction {
     name: Option<String>,
     arguments: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::image;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    struct TestModel;
+
+    impl OpenAICompatibleModel for TestModel {}
+
+    fn content_of(options: &ModelOptions<TestModel>) -> Value {
+        let messages = vec![Message::User(vec![
+            Part::text("first"),
+            image("a"),
+            Part::text("second"),
+            image("b"),
+        ])];
+        let request =
+            OpenAIRequest::new(messages, options, "gpt-5".to_string(), vec![], false).unwrap();
+        serde_json::to_value(&request).unwrap()["messages"][0]["content"].clone()
+    }
+
+    #[test]
+    fn test_organization_and_project_headers() {
+        let client = OpenAIClient::<TestModel>::new(
+            "key".to_string(),
+            "https://api.openai.com".to_string(),
+            ModelOptions::new("gpt-5"),
+            TransportOptions::new()
+                .with_organization("org-123")
+                .with_project("proj_456"),
+        );
+
+        let request = client
+            .build_request(vec![], vec![], false)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()["OpenAI-Organization"], "org-123");
+        assert_eq!(request.headers()["OpenAI-Project"], "proj_456");
+    }
+
+    #[test]
+    fn test_interleaved_media_keeps_order() {
+        let content = content_of(&ModelOptions::new("gpt-5"));
+
+        assert_eq!(
+            content,
+            json!([
+                { "type": "text", "text": "first" },
+                { "type": "text", "text": "File (image/png) at a.png:" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,YQ==" } },
+                { "type": "text", "text": "second" },
+                { "type": "text", "text": "File (image/png) at b.png:" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,Yg==" } },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_inbound_data_url_is_decoded() {
+        let part = Part::from(OpenAIContentPart::ImageUrl {
+            image_url: OpenAIImageUrl {
+                url: "data:image/png;base64,iVBORw==".to_string(),
+            },
+        });
+
+        assert_eq!(
+            part,
+            Part::media(
+                MediaType::Image,
+                "image/png",
+                Bytes::from_static(b"\x89PNG")
+            )
+        );
+    }
+
+    #[test]
+    fn test_media_anchors_can_be_disabled() {
+        let mut options = ModelOptions::new("gpt-5");
+        options.anchor_media = Some(false);
+        let content = content_of(&options);
+
+        assert_eq!(
+            content,
+            json!([
+                { "type": "text", "text": "first" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,YQ==" } },
+                { "type": "text", "text": "second" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,Yg==" } },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tool_result_keeps_structured_content_and_text() {
+        let messages = vec![Message::User(vec![Part::FunctionResponse {
+            id: Some("call_1".to_string()),
+            name: "weather".to_string(),
+            response: json!({ "temp": 21 }),
+            parts: vec![Part::text("Sunny in Paris.")],
+            error: None,
+            finished: true,
+        }])];
+        let request = OpenAIRequest::new(
+            messages,
+            &ModelOptions::<TestModel>::new("gpt-5"),
+            "gpt-5".to_string(),
+            vec![],
+            false,
+        )
+        .unwrap();
+        let messages = serde_json::to_value(&request).unwrap()["messages"].clone();
+        assert_eq!(messages[0]["content"], "{\"temp\":21}\nSunny in Paris.");
+    }
+
+    #[test]
+    fn test_tool_result_images_are_forwarded() {
+        let messages = vec![Message::User(vec![Part::FunctionResponse {
+            id: Some("call_1".to_string()),
+            name: "screenshot".to_string(),
+            response: json!({}),
+            parts: vec![image("a")],
+            error: None,
+            finished: true,
+        }])];
+        let render = |options: &ModelOptions<TestModel>| {
+            let request = OpenAIRequest::new(
+                messages.clone(),
+                options,
+                "gpt-5".to_string(),
+                vec![],
+                false,
+            )
+            .unwrap();
+            serde_json::to_value(&request).unwrap()["messages"].clone()
+        };
+
+        let forwarded = render(&ModelOptions::new("gpt-5"));
+        assert_eq!(
+            forwarded,
+            json!([
+                {
+                    "role": "tool",
+                    "content": "\nFile (image/png) at a.png:\n[Image sent in the next message]",
+                    "tool_call_id": "call_1"
+                },
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": "File (image/png) at a.png:" },
+                        { "type": "image_url", "image_url": { "url": "data:image/png;base64,YQ==" } },
+                    ]
+                },
+            ])
+        );
+
+        let mut options = ModelOptions::new("gpt-5");
+        options.forward_tool_media = Some(false);
+        let placeholder = render(&options);
+        assert_eq!(placeholder.as_array().unwrap().len(), 1);
+        assert_eq!(
+            placeholder[0]["content"],
+            json!("\nFile (image/png) at a.png:\n[Image Content]")
+        );
+    }
+
+    #[test]
+    fn test_content_filter_error_is_typed() {
+        let body = serde_json::json!({
+            "error": {
+                "message": "The response was filtered",
+                "type": null,
+                "code": "content_filter",
+                "status": 400,
+                "innererror": {
+                    "code": "ResponsibleAIPolicyViolation",
+                    "content_filter_result": {
+                        "hate": { "filtered": false, "severity": "safe" },
+                        "violence": { "filtered": true, "severity": "high" }
+                    }
+                }
+            }
+        });
+        let error = OpenAIClient::<TestModel>::handle_error_response(
+            reqwest::StatusCode::BAD_REQUEST,
+            None,
+            &body.to_string(),
+        );
+
+        match error {
+            ClientError::ContentFiltered { stage, categories } => {
+                assert_eq!(stage, FilterStage::Prompt);
+                assert_eq!(categories, vec!["violence"]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_service_tier_is_sent_and_reported() {
+        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+        struct TieredModel;
+
+        impl OpenAICompatibleModel for TieredModel {
+            fn service_tier(tier: ServiceTier) -> Option<&'static str> {
+                (tier == ServiceTier::Flex).then_some("flex")
+            }
+        }
+
+        let options =
+            ModelOptions::<TieredModel>::new("gpt-5").with_service_tier(ServiceTier::Flex);
+        let request =
+            OpenAIRequest::new(vec![], &options, "gpt-5".to_string(), vec![], false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["service_tier"],
+            "flex"
+        );
+
+        let options = options.with_service_tier(ServiceTier::Priority);
+        let request =
+            OpenAIRequest::new(vec![], &options, "gpt-5".to_string(), vec![], false).unwrap();
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("service_tier")
+            .is_none());
+
+        let response: OpenAIResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "choices": [{ "message": { "role": "assistant", "content": "Hi" }, "finish_reason": "stop" }],
+            "service_tier": "default"
+        }))
+        .unwrap();
+        assert_eq!(
+            Response::from(response).service_tier,
+            Some(ServiceTier::Standard)
+        );
+    }
+
+    #[test]
+    fn test_prediction_is_sent_and_usage_reported() {
+        use crate::providers::{OpenAIModel, OpenAIPrediction};
+
+        let mut options = ModelOptions::<OpenAIModel>::new("gpt-4o");
+        options.provider.prediction = Some(OpenAIPrediction::content("fn main() {}"));
+        let request =
+            OpenAIRequest::new(vec![], &options, "gpt-4o".to_string(), vec![], false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["prediction"],
+            json!({ "type": "content", "content": "fn main() {}" })
+        );
+
+        let usage: OpenAIUsage = serde_json::from_value(json!({
+            "prompt_tokens": 20,
+            "completion_tokens": 12,
+            "completion_tokens_details": {
+                "accepted_prediction_tokens": 8,
+                "rejected_prediction_tokens": 2
+            }
+        }))
+        .unwrap();
+        let usage = Usage::from(usage);
+        assert_eq!(usage.accepted_prediction_tokens, Some(8));
+        assert_eq!(usage.rejected_prediction_tokens, Some(2));
+    }
+
+    #[test]
+    fn test_strict_mode_is_set_per_tool() {
+        use crate::providers::OpenAIModel;
+        use crate::tools::Tool;
+        use std::sync::Arc;
+
+        let schema = json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" } }
+        });
+        let tool = |name: &'static str| {
+            Tool::new(
+                name,
+                "A tool",
+                Arc::new(schema.as_object().unwrap().clone()),
+            )
+        };
+        let tools = vec![tool("strict").with_strict(true), tool("loose")];
+
+        let options = ModelOptions::<OpenAIModel>::new("gpt-4o");
+        let request =
+            OpenAIRequest::new(vec![], &options, "gpt-4o".to_string(), tools.clone(), false)
+                .unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        let strict = &body["tools"][0]["function"];
+        assert_eq!(strict["strict"], json!(true));
+        assert_eq!(strict["parameters"]["additionalProperties"], json!(false));
+        assert_eq!(strict["parameters"]["required"], json!(["city"]));
+        assert!(body["tools"][1]["function"].get("strict").is_none());
+
+        let mut options = options;
+        options.strict_tools = Some(true);
+        let tools = vec![tool("loose").with_strict(false)];
+        let request =
+            OpenAIRequest::new(vec![], &options, "gpt-4o".to_string(), tools, false).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert!(body["tools"][0]["function"].get("strict").is_none());
+    }
+}
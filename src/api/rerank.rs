@@ -0,0 +1,261 @@
+//! Clients for the Cohere and Voyage rerank endpoints.
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::client::ClientError;
+use crate::http::{
+    add_extra_headers, build_http_client, request_id, RequestBuilderExt, ResponseExt,
+};
+use crate::options::TransportOptions;
+use crate::rerank::{Reranker, ScoredDocument};
+
+/// Reranker backed by Cohere's `/v2/rerank` endpoint.
+#[derive(Debug, Clone)]
+pub struct CohereReranker {
+    api_key: String,
+    base_url: String,
+    model: String,
+    top_n: Option<usize>,
+    transport_options: TransportOptions,
+}
+
+impl CohereReranker {
+    /// Create a reranker for `model`, e.g. `rerank-v3.5`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.cohere.com".to_string(),
+            model: model.into(),
+            top_n: None,
+            transport_options: TransportOptions::default(),
+        }
+    }
+
+    /// Keep only the `top_n` most relevant documents.
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = Some(top_n);
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_transport_options(mut self, transport_options: TransportOptions) -> Self {
+        self.transport_options = transport_options;
+        self
+    }
+}
+
+#[async_trait]
+impl Reranker for CohereReranker {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+    ) -> Result<Vec<ScoredDocument>, ClientError> {
+        let mut body = json!({
+            "model": self.model,
+            "query": query,
+            "documents": documents,
+        });
+        if let Some(top_n) = self.top_n {
+            body["top_n"] = json!(top_n);
+        }
+
+        let url = format!("{}/v2/rerank", self.base_url);
+        let response = post(&url, &self.api_key, &self.transport_options, &body).await?;
+        scored(response, documents)
+    }
+}
+
+/// Reranker backed by Voyage AI's `/v1/rerank` endpoint.
+#[derive(Debug, Clone)]
+pub struct VoyageReranker {
+    api_key: String,
+    base_url: String,
+    model: String,
+    top_k: Option<usize>,
+    truncation: Option<bool>,
+    transport_options: TransportOptions,
+}
+
+impl VoyageReranker {
+    /// Create a reranker for `model`, e.g. `rerank-2`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.voyageai.com".to_string(),
+            model: model.into(),
+            top_k: None,
+            truncation: None,
+            transport_options: TransportOptions::default(),
+        }
+    }
+
+    /// Keep only the `top_k` most relevant documents.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Whether inputs over the context length are truncated (the default) or rejected.
+    pub fn with_truncation(mut self, truncation: bool) -> Self {
+        self.truncation = Some(truncation);
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_transport_options(mut self, transport_options: TransportOptions) -> Self {
+        self.transport_options = transport_options;
+        self
+    }
+}
+
+#[async_trait]
+impl Reranker for VoyageReranker {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+    ) -> Result<Vec<ScoredDocument>, ClientError> {
+        let mut body = json!({
+            "model": self.model,
+            "query": query,
+            "documents": documents,
+        });
+        if let Some(top_k) = self.top_k {
+            body["top_k"] = json!(top_k);
+        }
+        if let Some(truncation) = self.truncation {
+            body["truncation"] = json!(truncation);
+        }
+
+        let url = format!("{}/v1/rerank", self.base_url);
+        let response = post(&url, &self.api_key, &self.transport_options, &body).await?;
+        scored(response, documents)
+    }
+}
+
+/// Rerank response shared by both APIs; Cohere names the list `results`, Voyage `data`.
+#[derive(Debug, Deserialize)]
+struct RerankResponse {
+    #[serde(alias = "data")]
+    results: Vec<RerankResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f64,
+}
+
+async fn post(
+    url: &str,
+    api_key: &str,
+    transport_options: &TransportOptions,
+    body: &Value,
+) -> Result<RerankResponse, ClientError> {
+    let http_client = build_http_client(transport_options)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", api_key))
+            .map_err(|_| ClientError::Config("Invalid API key".to_string()))?,
+    );
+
+    let req = http_client.post(url).headers(headers);
+    let req = add_extra_headers(req, transport_options);
+    let response = req.json_logged(body).send().await?;
+    let status = response.status();
+
+    if !status.is_success() {
+        let request_id = request_id(response.headers());
+        let body = response.text_logged().await.unwrap_or_default();
+        return Err(ClientError::Api {
+            status: Some(status.as_u16()),
+            request_id,
+            message: format!("HTTP {}: {}", status, body),
+        });
+    }
+
+    response.json_logged().await
+}
+
+/// Attach the input documents to the scores, most relevant first.
+fn scored(
+    response: RerankResponse,
+    documents: Vec<String>,
+) -> Result<Vec<ScoredDocument>, ClientError> {
+    let mut documents: Vec<Option<String>> = documents.into_iter().map(Some).collect();
+    let mut scored = response
+        .results
+        .into_iter()
+        .map(|result| {
+            let document = documents
+                .get_mut(result.index)
+                .and_then(Option::take)
+                .ok_or_else(|| {
+                    ClientError::ProviderError(format!(
+                        "Rerank result refers to unknown document {}",
+                        result.index
+                    ))
+                })?;
+            Ok(ScoredDocument {
+                index: result.index,
+                score: result.relevance_score,
+                document,
+            })
+        })
+        .collect::<Result<Vec<_>, ClientError>>()?;
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(scored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: Value) -> RerankResponse {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_scored_orders_documents_by_relevance() {
+        let documents = vec!["cats".to_string(), "rust".to_string(), "tokio".to_string()];
+        let scored = scored(
+            response(json!({
+                "data": [
+                    { "index": 0, "relevance_score": 0.1 },
+                    { "index": 2, "relevance_score": 0.7 },
+                    { "index": 1, "relevance_score": 0.9 }
+                ]
+            })),
+            documents,
+        )
+        .unwrap();
+
+        let order: Vec<_> = scored.iter().map(|d| d.document.as_str()).collect();
+        assert_eq!(order, vec!["rust", "tokio", "cats"]);
+        assert_eq!(scored[0].index, 1);
+    }
+
+    #[test]
+    fn test_scored_rejects_unknown_index() {
+        let result = scored(
+            response(json!({ "results": [{ "index": 3, "relevance_score": 0.5 }] })),
+            vec!["only".to_string()],
+        );
+        assert!(matches!(result, Err(ClientError::ProviderError(_))));
+    }
+}
@@ -0,0 +1,348 @@
+//! Files attached to a prompt.
+//!
+//! An [`Attachment`] names files on disk, a glob pattern or a URL. [`attach`] expands
+//! them into the parts of a user message: text files are inlined as text, everything
+//! else becomes a [`Part::Media`] with its MIME type guessed from the file extension,
+//! and URLs are sent by reference. Sizes are checked against [`AttachmentLimits`]
+//! before anything is sent, so oversized files fail early instead of as a provider
+//! `400`.
+
+use bytes::Bytes;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::catalog::ModelVendor;
+use crate::model::{MediaType, Message, Part};
+
+/// Errors returned while expanding attachments.
+#[derive(Error, Debug)]
+pub enum AttachmentError {
+    #[error("Cannot read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid glob pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+
+    #[error("{path} is {size} bytes, more than the limit of {limit}")]
+    FileTooLarge {
+        path: String,
+        size: usize,
+        limit: usize,
+    },
+
+    #[error("Attachments total {size} bytes, more than the limit of {limit}")]
+    TotalTooLarge { size: usize, limit: usize },
+}
+
+/// A file, set of files or remote resource to attach to a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attachment {
+    /// A file, or a directory whose files are attached recursively.
+    Path(PathBuf),
+    /// Files matching a glob pattern such as `src/**/*.rs`.
+    Glob(String),
+    /// A remote resource, sent by reference.
+    Url(String),
+}
+
+impl Attachment {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self::Path(path.into())
+    }
+
+    pub fn glob(pattern: impl Into<String>) -> Self {
+        Self::Glob(pattern.into())
+    }
+
+    pub fn url(url: impl Into<String>) -> Self {
+        Self::Url(url.into())
+    }
+
+    /// Expand the attachment into message parts, one per file, in path order.
+    pub async fn parts(&self) -> Result<Vec<Part>, AttachmentError> {
+        let paths = match self {
+            Self::Url(url) => {
+                let mime_type =
+                    mime_type_for(Path::new(url.split(['?', '#']).next().unwrap_or(url)))
+                        .unwrap_or("application/octet-stream");
+                return Ok(vec![Part::remote_media(
+                    MediaType::from_mime_type(mime_type),
+                    mime_type,
+                    url.clone(),
+                )]);
+            }
+            Self::Path(path) => files_in(path)?,
+            Self::Glob(pattern) => {
+                let mut paths = Vec::new();
+                for entry in glob::glob(pattern)? {
+                    let path = entry.map_err(|e| AttachmentError::Io {
+                        path: e.path().to_path_buf(),
+                        source: e.into(),
+                    })?;
+                    if path.is_file() {
+                        paths.push(path);
+                    }
+                }
+                paths
+            }
+        };
+
+        let mut parts = Vec::with_capacity(paths.len());
+        for path in paths {
+            let data = tokio::fs::read(&path)
+                .await
+                .map_err(|source| AttachmentError::Io {
+                    path: path.clone(),
+                    source,
+                })?;
+            parts.push(file_part(&path, data));
+        }
+        Ok(parts)
+    }
+}
+
+/// Size limits checked by [`attach`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AttachmentLimits {
+    /// Largest single file, in bytes.
+    pub max_file_bytes: Option<usize>,
+    /// Largest total of all attachments, in bytes.
+    pub max_total_bytes: Option<usize>,
+}
+
+impl AttachmentLimits {
+    /// No limits.
+    pub const UNLIMITED: Self = Self {
+        max_file_bytes: None,
+        max_total_bytes: None,
+    };
+
+    /// Limits of the vendor's API for inline data: 20 MB images and 50 MB requests
+    /// for OpenAI, 5 MB images and 32 MB requests for Anthropic, and 20 MB requests
+    /// for Gemini. Base64 encoding adds a third on top of the raw sizes checked here.
+    pub fn for_vendor(vendor: ModelVendor) -> Self {
+        const MB: usize = 1024 * 1024;
+        match vendor {
+            ModelVendor::OpenAI => Self {
+                max_file_bytes: Some(20 * MB),
+                max_total_bytes: Some(50 * MB),
+            },
+            ModelVendor::Anthropic => Self {
+                max_file_bytes: Some(5 * MB),
+                max_total_bytes: Some(32 * MB),
+            },
+            ModelVendor::Google => Self {
+                max_file_bytes: Some(20 * MB),
+                max_total_bytes: Some(20 * MB),
+            },
+        }
+    }
+
+    /// Check the size of every part and of all parts together, returning the total.
+    pub fn check(&self, parts: &[Part]) -> Result<usize, AttachmentError> {
+        let mut total = 0;
+        for part in parts {
+            let size = part_size(part);
+            if let Some(limit) = self.max_file_bytes.filter(|limit| size > *limit) {
+                return Err(AttachmentError::FileTooLarge {
+                    path: part_name(part),
+                    size,
+                    limit,
+                });
+            }
+            total += size;
+        }
+        if let Some(limit) = self.max_total_bytes.filter(|limit| total > *limit) {
+            return Err(AttachmentError::TotalTooLarge { size: total, limit });
+        }
+        Ok(total)
+    }
+}
+
+/// Build a user message with `prompt` followed by the expanded `attachments`.
+pub async fn attach(
+    prompt: impl Into<String>,
+    attachments: &[Attachment],
+    limits: &AttachmentLimits,
+) -> Result<Message, AttachmentError> {
+    let mut files = Vec::new();
+    for attachment in attachments {
+        files.extend(attachment.parts().await?);
+    }
+    limits.check(&files)?;
+
+    let mut parts = vec![Part::text(prompt.into())];
+    parts.extend(files);
+    Ok(Message::User(parts))
+}
+
+/// Files of `path`, recursively and sorted if it is a directory.
+fn files_in(path: &Path) -> Result<Vec<PathBuf>, AttachmentError> {
+    let io_error = |source| AttachmentError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut entries = std::fs::read_dir(path)
+        .map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error)?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for entry in entries {
+        files.extend(files_in(&entry)?);
+    }
+    Ok(files)
+}
+
+/// Part for a file: inline text for text files, media otherwise.
+fn file_part(path: &Path, mut data: Vec<u8>) -> Part {
+    let uri = path.display().to_string();
+    let mime_type = mime_type_for(path);
+
+    let is_text =
+        mime_type.is_none_or(|mime| mime.starts_with("text/") || mime == "application/json");
+    if is_text {
+        match String::from_utf8(data) {
+            Ok(text) => {
+                let mime_type = mime_type.unwrap_or("text/plain");
+                return Part::text(format!("File ({}) at {}:\n{}", mime_type, uri, text));
+            }
+            Err(e) => data = e.into_bytes(),
+        }
+    }
+
+    let mime_type = mime_type.unwrap_or("application/octet-stream");
+    Part::Media {
+        media_type: MediaType::from_mime_type(mime_type),
+        data: Bytes::from(data),
+        mime_type: mime_type.to_string(),
+        uri: Some(uri),
+        finished: true,
+    }
+}
+
+/// MIME type of a file from its extension.
+fn mime_type_for(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+fn part_size(part: &Part) -> usize {
+    match part {
+        Part::Text { content, .. } => content.len(),
+        Part::Media { data, .. } => data.len(),
+        _ => 0,
+    }
+}
+
+/// Name of an attached part for error messages.
+fn part_name(part: &Part) -> String {
+    match part {
+        Part::Media { uri: Some(uri), .. } => uri.clone(),
+        Part::Text { content, .. } => content
+            .lines()
+            .next()
+            .and_then(|line| line.split_once(" at "))
+            .map_or_else(String::new, |(_, name)| {
+                name.trim_end_matches(':').to_string()
+            }),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unia-attach-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("notes.md"), "# Notes").unwrap();
+        std::fs::write(dir.join("nested/logo.png"), b"\x89PNG").unwrap();
+        std::fs::write(dir.join("nested/data.bin"), [0xff, 0xfe]).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_directory_expands_to_typed_parts() {
+        let dir = temp_dir("dir");
+        let parts = Attachment::path(&dir).parts().await.unwrap();
+
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(
+            &parts[0],
+            Part::Media { media_type: MediaType::Binary, mime_type, .. } if mime_type == "application/octet-stream"
+        ));
+        assert!(matches!(
+            &parts[1],
+            Part::Media { media_type: MediaType::Image, mime_type, .. } if mime_type == "image/png"
+        ));
+        assert!(matches!(
+            &parts[2],
+            Part::Text { content, .. } if content.starts_with("File (text/markdown) at ") && content.ends_with(":\n# Notes")
+        ));
+
+        let pattern = format!("{}/**/*.png", dir.display());
+        assert_eq!(Attachment::glob(pattern).parts().await.unwrap().len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_limits_are_checked() {
+        let dir = temp_dir("limits");
+        let attachments = [
+            Attachment::path(&dir),
+            Attachment::url("https://example.com/a.jpg?s=1"),
+        ];
+
+        let message = attach("Describe these", &attachments, &AttachmentLimits::UNLIMITED)
+            .await
+            .unwrap();
+        assert_eq!(message.parts().len(), 5);
+        assert!(message.parts()[4].is_remote_media());
+
+        let limits = AttachmentLimits {
+            max_file_bytes: Some(3),
+            max_total_bytes: None,
+        };
+        match attach("Describe these", &attachments, &limits).await {
+            Err(AttachmentError::FileTooLarge { path, size, .. }) => {
+                assert!(path.ends_with("logo.png"));
+                assert_eq!(size, 4);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+//! Structured audit logging.
+//!
+//! An [`AuditLogger`] records requests, responses, tool calls and errors as JSON
+//! objects, one per line, in an [`AuditSink`]: a file, a channel or any writer.
+//! Wrap a client in [`Audited`] to record its traffic, and pass the logger to
+//! [`Agent::with_audit_logger`](crate::agent::Agent::with_audit_logger) to record
+//! tool calls as well. Secrets are redacted before records reach the sink.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use serde_json::{json, Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{Message, Part, Response};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Replacement for redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Object keys whose values are always redacted, compared case-insensitively and
+/// ignoring `-` and `_`.
+const SECRET_KEYS: &[&str] = &[
+    "apikey",
+    "xapikey",
+    "xgoogapikey",
+    "authorization",
+    "password",
+    "secret",
+    "clientsecret",
+    "token",
+    "accesstoken",
+    "refreshtoken",
+    "privatekey",
+];
+
+/// Prefixes of well-known API key formats, redacted wherever they appear in text.
+const SECRET_PREFIXES: &[&str] = &["sk-", "sk_live_", "sk_test_", "AIza", "ghp_", "xoxb-"];
+
+/// Destination of audit records.
+pub trait AuditSink: Send + Sync {
+    /// Write a single record.
+    fn write(&self, record: &Value) -> io::Result<()>;
+}
+
+/// Sink appending records as JSON lines to a writer.
+pub struct JsonlWriter<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl JsonlWriter<File> {
+    /// Append to the file at `path`, creating it if needed.
+    pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write + Send> AuditSink for JsonlWriter<W> {
+    fn write(&self, record: &Value) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+impl AuditSink for UnboundedSender<Value> {
+    fn write(&self, record: &Value) -> io::Result<()> {
+        self.send(record.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "audit channel closed"))
+    }
+}
+
+/// Records audit events in a sink.
+///
+/// Cloning a logger yields a handle writing to the same sink.
+#[derive(Clone)]
+pub struct AuditLogger {
+    sink: Arc<dyn AuditSink>,
+    session: Option<String>,
+    secret_keys: Vec<String>,
+}
+
+impl AuditLogger {
+    pub fn new<S: AuditSink + 'static>(sink: S) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            session: None,
+            secret_keys: Vec::new(),
+        }
+    }
+
+    /// Tag every record with a session id.
+    pub fn with_session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Additionally redact the values of object keys named `key`.
+    pub fn with_secret_key(mut self, key: impl Into<String>) -> Self {
+        self.secret_keys.push(normalize_key(&key.into()));
+        self
+    }
+
+    /// Record a request about to be sent.
+    pub fn request(&self, model: &str, messages: &[Message], tools: &[Tool]) {
+        let tools: Vec<&str> = tools.iter().map(|tool| tool.name.as_ref()).collect();
+        self.log(
+            "request",
+            json!({ "model": model, "messages": messages, "tools": tools }),
+        );
+    }
+
+    /// Record a response.
+    pub fn response(&self, response: &Response) {
+        self.log("response", json!({ "response": response }));
+    }
+
+    /// Record a tool call requested by the model.
+    pub fn tool_call(&self, id: Option<&str>, name: &str, arguments: &Value) {
+        self.log(
+            "tool_call",
+            json!({ "id": id, "name": name, "arguments": arguments }),
+        );
+    }
+
+    /// Record the result of a tool call.
+    pub fn tool_result(&self, result: &Part) {
+        self.log("tool_result", json!({ "result": result }));
+    }
+
+    /// Record an error.
+    pub fn error(&self, error: &ClientError) {
+        self.log("error", json!({ "message": error.to_string() }));
+    }
+
+    /// Record an event of the given kind. `data` must be a JSON object.
+    pub fn log(&self, event: &str, data: Value) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        let mut record = Map::new();
+        record.insert("timestamp".to_string(), json!(timestamp));
+        if let Some(session) = &self.session {
+            record.insert("session".to_string(), json!(session));
+        }
+        record.insert("event".to_string(), json!(event));
+        if let Value::Object(data) = data {
+            record.extend(data);
+        }
+
+        let mut record = Value::Object(record);
+        redact(&mut record, &self.secret_keys);
+        if let Err(e) = self.sink.write(&record) {
+            warn!("Failed to write audit record: {}", e);
+        }
+    }
+}
+
+/// Redact secrets in a JSON value.
+///
+/// Values of keys that usually hold credentials are replaced entirely, and strings
+/// are scanned for words shaped like well-known API keys. `extra_keys` must be
+/// normalized (lowercase, without `-` and `_`).
+pub fn redact(value: &mut Value, extra_keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = normalize_key(key);
+                if SECRET_KEYS.contains(&key.as_str()) || extra_keys.contains(&key) {
+                    *value = json!(REDACTED);
+                } else {
+                    redact(value, extra_keys);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| redact(v, extra_keys)),
+        Value::String(text) => {
+            if let Some(redacted) = redact_text(text) {
+                *text = redacted;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether values of `key` usually hold credentials.
+pub(crate) fn is_secret_key(key: &str) -> bool {
+    SECRET_KEYS.contains(&normalize_key(key).as_str())
+}
+
+/// Text with key-shaped words redacted.
+pub(crate) fn redact_str(text: &str) -> String {
+    redact_text(text).unwrap_or_else(|| text.to_string())
+}
+
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Text with key-shaped words redacted, or `None` if there were none.
+fn redact_text(text: &str) -> Option<String> {
+    let is_secret = |word: &str| {
+        word.len() >= 20
+            && SECRET_PREFIXES
+                .iter()
+                .any(|prefix| word.starts_with(prefix))
+    };
+    if !text.split_whitespace().any(is_secret) {
+        return None;
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        redacted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        redacted.push_str(if is_secret(word) { REDACTED } else { word });
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    Some(redacted)
+}
+
+/// Client wrapper recording every request, response and error in an [`AuditLogger`].
+///
+/// Streaming requests are recorded with their final snapshot.
+pub struct Audited<C> {
+    client: C,
+    logger: AuditLogger,
+}
+
+impl<C> Audited<C> {
+    pub fn new(client: C, logger: AuditLogger) -> Self {
+        Self { client, logger }
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Audited<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        self.logger
+            .request(&self.client.model_options().model, &messages, &tools);
+        let result = self.client.request(messages, tools).await;
+        match &result {
+            Ok(response) => self.logger.response(response),
+            Err(error) => self.logger.error(error),
+        }
+        result
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<C: StreamingClient> StreamingClient for Audited<C> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        self.logger
+            .request(&self.client.model_options().model, &messages, &tools);
+        let mut stream = match self.client.request_stream(messages, tools).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                self.logger.error(&error);
+                return Err(error);
+            }
+        };
+
+        let logger = self.logger.clone();
+        Ok(Box::pin(async_stream::stream! {
+            let mut last = None;
+            while let Some(item) = stream.next().await {
+                match &item {
+                    Ok(response) => last = Some(response.clone()),
+                    Err(error) => logger.error(error),
+                }
+                yield item;
+            }
+            if let Some(response) = last {
+                logger.response(&response);
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        let mut value = json!({
+            "headers": { "Authorization": "Bearer abc", "X-Api-Key": "abc" },
+            "text": "my key is sk-proj-0123456789abcdefghij, keep it safe",
+            "session_token": "custom",
+            "tokens": 12
+        });
+        redact(&mut value, &["sessiontoken".to_string()]);
+
+        assert_eq!(
+            value,
+            json!({
+                "headers": { "Authorization": REDACTED, "X-Api-Key": REDACTED },
+                "text": "my key is [REDACTED] keep it safe",
+                "session_token": REDACTED,
+                "tokens": 12
+            })
+        );
+    }
+
+    #[test]
+    fn test_logger_writes_json_lines() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let logger = AuditLogger::new(tx).with_session("s-1");
+
+        logger.tool_call(Some("call_1"), "login", &json!({ "password": "hunter2" }));
+        logger.error(&ClientError::Timeout("slow".to_string()));
+
+        let call = rx.try_recv().unwrap();
+        assert_eq!(call["event"], "tool_call");
+        assert_eq!(call["session"], "s-1");
+        assert_eq!(call["arguments"]["password"], REDACTED);
+        assert!(call["timestamp"].is_u64());
+        assert_eq!(rx.try_recv().unwrap()["event"], "error");
+    }
+}
@@ -0,0 +1,500 @@
+//! Token and spend budgets.
+//!
+//! A [`Budget`] tracks the tokens used, and what they cost, by every client sharing
+//! it, typically all clients of one API key or provider. Clients wrapped in
+//! [`Budgeted`] fail with [`ClientError::BudgetExhausted`] once a limit is reached,
+//! and callbacks registered with [`Budget::on_threshold`] are invoked as usage
+//! crosses fractions of the limits, e.g. to send an alert at 80%.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{Message, Response, Usage};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Price of a model in dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+impl Pricing {
+    pub fn new(prompt_per_million: f64, completion_per_million: f64) -> Self {
+        Self {
+            prompt_per_million,
+            completion_per_million,
+        }
+    }
+
+    /// Cost of `usage` in dollars.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let prompt = f64::from(usage.prompt_tokens.unwrap_or(0));
+        let completion = f64::from(usage.completion_tokens.unwrap_or(0));
+        (prompt * self.prompt_per_million + completion * self.completion_per_million) / 1e6
+    }
+}
+
+/// Window after which a budget starts over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BudgetPeriod {
+    /// Usage is reset at midnight UTC.
+    #[default]
+    Daily,
+    /// Usage is never reset automatically.
+    Lifetime,
+}
+
+/// Usage accumulated in the current period.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Spend {
+    pub tokens: u64,
+    /// Cost in dollars. Zero unless pricing is configured.
+    pub cost: f64,
+}
+
+/// Notification that usage crossed a threshold of the budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetAlert {
+    /// The crossed fraction of the limit, e.g. `0.8`.
+    pub threshold: f64,
+    pub spend: Spend,
+}
+
+type AlertCallback = Arc<dyn Fn(&BudgetAlert) + Send + Sync>;
+
+struct BudgetState {
+    spend: Spend,
+    day: u64,
+    /// Thresholds already reported in the current period, by index.
+    alerted: Vec<bool>,
+}
+
+/// Shared token and spend limits.
+///
+/// Cloning a budget yields a handle to the same usage. Limits are checked before
+/// each request, so requests running concurrently may overshoot them slightly.
+#[derive(Clone)]
+pub struct Budget {
+    state: Arc<Mutex<BudgetState>>,
+    max_tokens: Option<u64>,
+    max_cost: Option<f64>,
+    pricing: Option<Pricing>,
+    period: BudgetPeriod,
+    thresholds: Vec<(f64, AlertCallback)>,
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Budget")
+            .field("spend", &self.spend())
+            .field("max_tokens", &self.max_tokens)
+            .field("max_cost", &self.max_cost)
+            .field("period", &self.period)
+            .finish()
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Budget {
+    /// Create a budget without limits, resetting daily.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BudgetState {
+                spend: Spend::default(),
+                day: today(),
+                alerted: Vec::new(),
+            })),
+            max_tokens: None,
+            max_cost: None,
+            pricing: None,
+            period: BudgetPeriod::default(),
+            thresholds: Vec::new(),
+        }
+    }
+
+    /// Stop after `max_tokens` prompt and completion tokens per period.
+    pub fn with_max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Stop after spending `max_cost` dollars per period.
+    ///
+    /// Requires [`with_pricing`](Self::with_pricing): without it, [`check`](Self::check)
+    /// fails with [`ClientError::Config`].
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Price used to compute the cost of usage.
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    pub fn with_period(mut self, period: BudgetPeriod) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Call `callback` once per period when usage reaches `threshold` (a fraction
+    /// such as `0.8`) of any limit.
+    pub fn on_threshold<F>(mut self, threshold: f64, callback: F) -> Self
+    where
+        F: Fn(&BudgetAlert) + Send + Sync + 'static,
+    {
+        self.thresholds.push((threshold, Arc::new(callback)));
+        self
+    }
+
+    /// Usage in the current period.
+    pub fn spend(&self) -> Spend {
+        self.state().spend
+    }
+
+    /// Fail with [`ClientError::BudgetExhausted`] if a limit has been reached, or with
+    /// [`ClientError::Config`] if a cost limit is set without pricing.
+    pub fn check(&self) -> Result<(), ClientError> {
+        if self.max_cost.is_some() && self.pricing.is_none() {
+            return Err(ClientError::Config(
+                "Budget has a cost limit but no pricing".to_string(),
+            ));
+        }
+        let spend = self.spend();
+        if let Some(max_tokens) = self.max_tokens.filter(|max| spend.tokens >= *max) {
+            return Err(ClientError::BudgetExhausted(format!(
+                "{} of {} tokens used",
+                spend.tokens, max_tokens
+            )));
+        }
+        if let Some(max_cost) = self.max_cost.filter(|max| spend.cost >= *max) {
+            return Err(ClientError::BudgetExhausted(format!(
+                "${:.2} of ${:.2} spent",
+                spend.cost, max_cost
+            )));
+        }
+        Ok(())
+    }
+
+    /// Add `usage` to the budget, invoking the callbacks of crossed thresholds.
+    pub fn record(&self, usage: &Usage) {
+        let tokens = u64::from(usage.prompt_tokens.unwrap_or(0))
+            + u64::from(usage.completion_tokens.unwrap_or(0));
+        let cost = self.pricing.map_or(0.0, |pricing| pricing.cost(usage));
+
+        let alerts: Vec<_> = {
+            let mut state = self.state();
+            state.spend.tokens += tokens;
+            state.spend.cost += cost;
+            let spend = state.spend;
+            let used = self.used_fraction(spend);
+            state.alerted.resize(self.thresholds.len(), false);
+
+            self.thresholds
+                .iter()
+                .zip(state.alerted.iter_mut())
+                .filter(|((threshold, _), alerted)| !**alerted && used >= *threshold)
+                .map(|((threshold, callback), alerted)| {
+                    *alerted = true;
+                    let alert = BudgetAlert {
+                        threshold: *threshold,
+                        spend,
+                    };
+                    (alert, callback.clone())
+                })
+                .collect()
+        };
+
+        // Callbacks run without the lock, so they may inspect the budget.
+        for (alert, callback) in alerts {
+            callback(&alert);
+        }
+    }
+
+    /// Start the current period over.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.spend = Spend::default();
+        state.alerted.clear();
+    }
+
+    /// Largest fraction of a limit used by `spend`.
+    fn used_fraction(&self, spend: Spend) -> f64 {
+        let tokens = self
+            .max_tokens
+            .map_or(0.0, |max| spend.tokens as f64 / max.max(1) as f64);
+        let cost = self
+            .max_cost
+            .map_or(0.0, |max| if max > 0.0 { spend.cost / max } else { 1.0 });
+        tokens.max(cost)
+    }
+
+    /// Locked state, reset first if a new period has started.
+    fn state(&self) -> std::sync::MutexGuard<'_, BudgetState> {
+        let mut state = self.state.lock().unwrap();
+        if self.period == BudgetPeriod::Daily {
+            let day = today();
+            if state.day != day {
+                state.day = day;
+                state.spend = Spend::default();
+                state.alerted.clear();
+            }
+        }
+        state
+    }
+}
+
+/// Days since the Unix epoch, in UTC.
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default()
+}
+
+/// Client wrapper enforcing a [`Budget`].
+///
+/// Streaming requests are charged with the usage of their final snapshot.
+pub struct Budgeted<C> {
+    client: C,
+    budget: Budget,
+}
+
+impl<C> Budgeted<C> {
+    pub fn new(client: C, budget: Budget) -> Self {
+        Self { client, budget }
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Budgeted<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        self.budget.check()?;
+        let response = self.client.request(messages, tools).await?;
+        self.budget.record(&response.usage);
+        Ok(response)
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<C: StreamingClient> StreamingClient for Budgeted<C> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        self.budget.check()?;
+        let mut stream = self.client.request_stream(messages, tools).await?;
+
+        let budget = self.budget.clone();
+        Ok(Box::pin(async_stream::stream! {
+            let mut charge = Charge::new(budget);
+            while let Some(item) = stream.next().await {
+                if let Ok(response) = &item {
+                    charge.update(&response.usage);
+                }
+                yield item;
+            }
+        }))
+    }
+}
+
+/// Records the last usage seen on a stream once it is dropped, so streams abandoned
+/// before the end are charged too.
+struct Charge {
+    budget: Budget,
+    usage: Usage,
+}
+
+impl Charge {
+    fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            usage: Usage::default(),
+        }
+    }
+
+    fn update(&mut self, usage: &Usage) {
+        self.usage = usage.clone();
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.budget.record(&self.usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FinishReason;
+    use crate::test_fixtures::snapshot;
+
+    fn usage(prompt: u32, completion: u32) -> Usage {
+        Usage {
+            prompt_tokens: Some(prompt),
+            completion_tokens: Some(completion),
+            cached_prompt_tokens: None,
+            accepted_prediction_tokens: None,
+            rejected_prediction_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_budget_exhausts_and_alerts_once() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let budget = Budget::new()
+            .with_max_tokens(1000)
+            .with_pricing(Pricing::new(3.0, 15.0))
+            .on_threshold(0.5, {
+                let alerts = alerts.clone();
+                move |alert| alerts.lock().unwrap().push(alert.spend.tokens)
+            });
+
+        budget.record(&usage(400, 200));
+        budget.record(&usage(100, 0));
+        assert!(budget.check().is_ok());
+        assert_eq!(*alerts.lock().unwrap(), vec![600]);
+
+        budget.record(&usage(300, 100));
+        assert!(matches!(
+            budget.check(),
+            Err(ClientError::BudgetExhausted(_))
+        ));
+
+        let spend = budget.spend();
+        assert_eq!(spend.tokens, 1100);
+        assert!((spend.cost - (800.0 * 3.0 + 300.0 * 15.0) / 1e6).abs() < 1e-12);
+
+        budget.reset();
+        assert!(budget.check().is_ok());
+    }
+
+    #[test]
+    fn test_cost_limit() {
+        let budget = Budget::new()
+            .with_period(BudgetPeriod::Lifetime)
+            .with_pricing(Pricing::new(1.0, 1.0))
+            .with_max_cost(0.001);
+
+        budget.record(&usage(500, 400));
+        assert!(budget.check().is_ok());
+        budget.record(&usage(100, 0));
+        assert!(budget.check().is_err());
+    }
+
+    /// Client streaming three snapshots with growing usage.
+    struct Counting {
+        options: ModelOptions<()>,
+        transport: TransportOptions,
+    }
+
+    #[async_trait]
+    impl Client for Counting {
+        type ModelProvider = ();
+
+        async fn request(&self, _: Vec<Message>, _: Vec<Tool>) -> Result<Response, ClientError> {
+            unimplemented!()
+        }
+
+        fn model_options(&self) -> &ModelOptions<()> {
+            &self.options
+        }
+
+        fn transport_options(&self) -> &TransportOptions {
+            &self.transport
+        }
+    }
+
+    #[async_trait]
+    impl StreamingClient for Counting {
+        async fn request_stream(
+            &self,
+            _: Vec<Message>,
+            _: Vec<Tool>,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+        {
+            Ok(Box::pin(futures::stream::iter((1..=3).map(|n| {
+                Ok(Response {
+                    usage: usage(10, n),
+                    ..snapshot("Hi", FinishReason::Unfinished)
+                })
+            }))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_stream_is_charged() {
+        let budget = Budget::new();
+        let client = Budgeted::new(
+            Counting {
+                options: ModelOptions::new("counting"),
+                transport: TransportOptions::default(),
+            },
+            budget.clone(),
+        );
+
+        let mut stream = client.request_stream(vec![], vec![]).await.unwrap();
+        stream.next().await.unwrap().unwrap();
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(budget.spend().tokens, 0);
+        drop(stream);
+        assert_eq!(budget.spend().tokens, 12);
+
+        let stream = client.request_stream(vec![], vec![]).await.unwrap();
+        stream.collect::<Vec<_>>().await;
+        assert_eq!(budget.spend().tokens, 25);
+    }
+
+    #[test]
+    fn test_cost_limit_requires_pricing() {
+        let budget = Budget::new().with_max_cost(0.001);
+        assert!(matches!(budget.check(), Err(ClientError::Config(_))));
+    }
+}
@@ -0,0 +1,382 @@
+//! Semantic response caching.
+//!
+//! FAQ-style workloads receive the same questions in many phrasings. A
+//! [`SemanticCache`] embeds the latest user message and answers from a cached
+//! response when a previous question was similar enough, without calling the model.
+//! Entries live in a [`VectorStore`]; [`InMemoryVectorStore`] is provided.
+
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use rmcp::model::Tool;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::embed::{cosine_similarity, Embedder};
+use crate::model::{FinishReason, Message, Part, Response, Usage};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Similarity above which a cached response is served, unless configured otherwise.
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.95;
+
+/// A cached response with its similarity to the query.
+#[derive(Debug, Clone)]
+pub struct CacheHit {
+    pub similarity: f32,
+    pub response: Response,
+}
+
+/// Storage for embedded queries and their responses.
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// The entry most similar to `embedding`, if any.
+    async fn nearest(&self, embedding: &[f32]) -> Result<Option<CacheHit>, ClientError>;
+
+    /// Store `response` as the answer to the query with the given embedding.
+    async fn insert(&self, embedding: Vec<f32>, response: Response) -> Result<(), ClientError>;
+}
+
+/// Vector store keeping entries in memory, searched linearly.
+#[derive(Debug, Default)]
+pub struct InMemoryVectorStore {
+    entries: Mutex<VecDeque<(Vec<f32>, Response)>>,
+    capacity: Option<usize>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `capacity` entries, evicting the oldest first.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn nearest(&self, embedding: &[f32]) -> Result<Option<CacheHit>, ClientError> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .iter()
+            .map(|(key, response)| (cosine_similarity(key, embedding), response))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(similarity, response)| CacheHit {
+                similarity,
+                response: response.clone(),
+            }))
+    }
+
+    async fn insert(&self, embedding: Vec<f32>, response: Response) -> Result<(), ClientError> {
+        let mut entries = self.entries.lock().unwrap();
+        if self.capacity == Some(0) {
+            return Ok(());
+        }
+        if self
+            .capacity
+            .is_some_and(|capacity| entries.len() >= capacity)
+        {
+            entries.pop_front();
+        }
+        entries.push_back((embedding, response));
+        Ok(())
+    }
+}
+
+/// Client wrapper serving cached responses to semantically similar questions.
+///
+/// Only the latest message is considered, and only if it is a user message with
+/// text: follow-ups whose meaning depends on earlier turns should not be cached this
+/// way. Responses are stored when they finish with [`FinishReason::Stop`]. Cached
+/// responses report zero usage. Failures of the embedder or the store are logged and
+/// the request is passed through uncached.
+pub struct SemanticCache<C, E, S = InMemoryVectorStore> {
+    client: C,
+    embedder: E,
+    store: Arc<S>,
+    threshold: f32,
+}
+
+impl<C, E> SemanticCache<C, E> {
+    /// Cache the responses of `client` in memory, embedding queries with `embedder`.
+    pub fn new(client: C, embedder: E) -> Self {
+        Self {
+            client,
+            embedder,
+            store: Arc::new(InMemoryVectorStore::new()),
+            threshold: DEFAULT_SIMILARITY_THRESHOLD,
+        }
+    }
+}
+
+impl<C, E, S> SemanticCache<C, E, S> {
+    /// Keep entries in `store` instead.
+    pub fn with_store<T>(self, store: T) -> SemanticCache<C, E, T> {
+        SemanticCache {
+            client: self.client,
+            embedder: self.embedder,
+            store: Arc::new(store),
+            threshold: self.threshold,
+        }
+    }
+
+    /// Minimum cosine similarity for a cached response to be served.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// Get a reference to the vector store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+impl<C, E: Embedder, S: VectorStore> SemanticCache<C, E, S> {
+    /// Embed the query of a request, or `None` if it should not be cached.
+    async fn embed_query(&self, messages: &[Message]) -> Option<Vec<f32>> {
+        let query = query(messages)?;
+        match self.embedder.embed(vec![query]).await {
+            Ok(mut embeddings) => embeddings.pop(),
+            Err(e) => {
+                warn!("Failed to embed query for the semantic cache: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The cached response for a query, if one is similar enough.
+    async fn lookup(&self, embedding: &[f32]) -> Option<Response> {
+        match self.store.nearest(embedding).await {
+            Ok(Some(hit)) if hit.similarity >= self.threshold => {
+                debug!("Semantic cache hit (similarity {})", hit.similarity);
+                Some(Response {
+                    usage: Usage::default(),
+                    ..hit.response
+                })
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Semantic cache lookup failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Store `response` if it is complete.
+async fn store<S: VectorStore + ?Sized>(store: &S, embedding: Vec<f32>, response: &Response) {
+    if response.finish != FinishReason::Stop {
+        return;
+    }
+    if let Err(e) = store.insert(embedding, response.clone()).await {
+        warn!("Failed to store response in the semantic cache: {}", e);
+    }
+}
+
+/// Text of the latest message, if it is a user message with text.
+fn query(messages: &[Message]) -> Option<String> {
+    let Some(Message::User(parts)) = messages.last() else {
+        return None;
+    };
+    let text: String = parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .collect();
+    (!text.trim().is_empty()).then_some(text)
+}
+
+#[async_trait]
+impl<C, E, S> Client for SemanticCache<C, E, S>
+where
+    C: Client,
+    E: Embedder,
+    S: VectorStore,
+{
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let Some(embedding) = self.embed_query(&messages).await else {
+            return self.client.request(messages, tools).await;
+        };
+        if let Some(response) = self.lookup(&embedding).await {
+            return Ok(response);
+        }
+
+        let response = self.client.request(messages, tools).await?;
+        store(self.store.as_ref(), embedding, &response).await;
+        Ok(response)
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<C, E, S> StreamingClient for SemanticCache<C, E, S>
+where
+    C: StreamingClient,
+    E: Embedder,
+    S: VectorStore + 'static,
+{
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let Some(embedding) = self.embed_query(&messages).await else {
+            return self.client.request_stream(messages, tools).await;
+        };
+        if let Some(response) = self.lookup(&embedding).await {
+            return Ok(Box::pin(stream::once(async move { Ok(response) })));
+        }
+
+        let mut stream = self.client.request_stream(messages, tools).await?;
+        let cache = self.store.clone();
+
+        // The final snapshot is stored once the stream is exhausted.
+        Ok(Box::pin(async_stream::stream! {
+            let mut last = None;
+            while let Some(item) = stream.next().await {
+                if let Ok(response) = &item {
+                    last = Some(response.clone());
+                }
+                yield item;
+            }
+            if let Some(response) = last {
+                store(cache.as_ref(), embedding, &response).await;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds texts by their first letter, so texts sharing it are identical.
+    struct FirstLetter;
+
+    #[async_trait]
+    impl Embedder for FirstLetter {
+        async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ClientError> {
+            Ok(inputs
+                .iter()
+                .map(|text| {
+                    let letter = text.to_lowercase().chars().next().unwrap_or('a');
+                    let mut embedding = vec![0.0; 26];
+                    embedding[(letter as usize).saturating_sub('a' as usize) % 26] = 1.0;
+                    embedding
+                })
+                .collect())
+        }
+    }
+
+    struct Counting {
+        calls: AtomicUsize,
+        options: ModelOptions<()>,
+        transport: TransportOptions,
+    }
+
+    #[async_trait]
+    impl Client for Counting {
+        type ModelProvider = ();
+
+        async fn request(&self, _: Vec<Message>, _: Vec<Tool>) -> Result<Response, ClientError> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Response {
+                data: vec![Message::assistant(format!("answer {}", calls))],
+                usage: Usage {
+                    prompt_tokens: Some(10),
+                    completion_tokens: Some(5),
+                    cached_prompt_tokens: None,
+                    accepted_prediction_tokens: None,
+                    rejected_prediction_tokens: None,
+                },
+                finish: FinishReason::Stop,
+                stop_sequence: None,
+                service_tier: None,
+            })
+        }
+
+        fn model_options(&self) -> &ModelOptions<()> {
+            &self.options
+        }
+
+        fn transport_options(&self) -> &TransportOptions {
+            &self.transport
+        }
+    }
+
+    #[tokio::test]
+    async fn test_similar_questions_hit_the_cache() {
+        let client = Counting {
+            calls: AtomicUsize::new(0),
+            options: ModelOptions::new("test"),
+            transport: TransportOptions::default(),
+        };
+        let cache = SemanticCache::new(client, FirstLetter);
+
+        let first = cache
+            .request(vec![Message::user("How do I reset my password?")], vec![])
+            .await
+            .unwrap();
+        let second = cache
+            .request(vec![Message::user("how can I reset the password")], vec![])
+            .await
+            .unwrap();
+        let other = cache
+            .request(vec![Message::user("What are your opening hours?")], vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(first.data, second.data);
+        assert_eq!(second.usage, Usage::default());
+        assert_eq!(other.data[0].content().as_deref(), Some("answer 2"));
+        assert_eq!(cache.inner().calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.store().len(), 2);
+    }
+}
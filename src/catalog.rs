@@ -0,0 +1,417 @@
+//! Well-known model identifiers.
+//!
+//! [`KnownModel`] names the major OpenAI, Anthropic and Gemini models, so that
+//! [`ModelOptions`](crate::options::ModelOptions) can be created without spelling out
+//! model ids by hand, and describes what each of them supports. Any other id is
+//! carried verbatim in [`KnownModel::Other`].
+//!
+//! The catalog also tracks aliases, which [`resolve_alias`] maps to the snapshot they
+//! currently point to, and deprecated models, see [`deprecation`]. Clients resolve
+//! aliases when [`ModelOptions::resolve_aliases`](crate::options::ModelOptions::resolve_aliases)
+//! is set, and log a warning the first time a deprecated model is requested.
+//!
+//! ```
+//! use unia::catalog::KnownModel;
+//! use unia::options::ModelOptions;
+//!
+//! let options = ModelOptions::<()>::new(KnownModel::ClaudeSonnet45);
+//! assert_eq!(options.model, "claude-sonnet-4-5");
+//!
+//! let model: KnownModel = "gpt-5-mini".parse().unwrap();
+//! assert!(model.capabilities().is_some_and(|c| c.reasoning));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Company publishing a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModelVendor {
+    OpenAI,
+    Anthropic,
+    Google,
+}
+
+/// What a model supports, as documented by its vendor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    /// Maximum number of input and output tokens.
+    pub context_window: u32,
+    pub max_output_tokens: u32,
+    /// Whether images are accepted as input.
+    pub vision: bool,
+    pub tools: bool,
+    /// Whether the model can think before answering.
+    pub reasoning: bool,
+}
+
+impl ModelCapabilities {
+    const fn new(context_window: u32, max_output_tokens: u32, reasoning: bool) -> Self {
+        Self {
+            context_window,
+            max_output_tokens,
+            vision: true,
+            tools: true,
+            reasoning,
+        }
+    }
+}
+
+/// Identifier of a well-known model.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+pub enum KnownModel {
+    /// `gpt-5`
+    Gpt5,
+    /// `gpt-5-mini`
+    Gpt5Mini,
+    /// `gpt-5-nano`
+    Gpt5Nano,
+    /// `gpt-4.1`
+    Gpt41,
+    /// `gpt-4.1-mini`
+    Gpt41Mini,
+    /// `gpt-4o`
+    Gpt4o,
+    /// `gpt-4o-mini`
+    Gpt4oMini,
+    /// `o3`
+    O3,
+    /// `o4-mini`
+    O4Mini,
+    /// `claude-opus-4-5`
+    ClaudeOpus45,
+    /// `claude-sonnet-4-5`
+    ClaudeSonnet45,
+    /// `claude-haiku-4-5`
+    ClaudeHaiku45,
+    /// `claude-opus-4-1`
+    ClaudeOpus41,
+    /// `claude-sonnet-4-0`
+    ClaudeSonnet4,
+    /// `claude-3-7-sonnet-latest`
+    Claude37Sonnet,
+    /// `gemini-2.5-pro`
+    Gemini25Pro,
+    /// `gemini-2.5-flash`
+    Gemini25Flash,
+    /// `gemini-2.5-flash-lite`
+    Gemini25FlashLite,
+    /// `gemini-2.0-flash`
+    Gemini20Flash,
+    /// `gemma-3-27b-it`, served by the Gemini API without tool calling.
+    Gemma3,
+    /// Any other model id, sent verbatim.
+    Other(String),
+}
+
+impl KnownModel {
+    /// Every named model.
+    pub const ALL: &'static [KnownModel] = &[
+        KnownModel::Gpt5,
+        KnownModel::Gpt5Mini,
+        KnownModel::Gpt5Nano,
+        KnownModel::Gpt41,
+        KnownModel::Gpt41Mini,
+        KnownModel::Gpt4o,
+        KnownModel::Gpt4oMini,
+        KnownModel::O3,
+        KnownModel::O4Mini,
+        KnownModel::ClaudeOpus45,
+        KnownModel::ClaudeSonnet45,
+        KnownModel::ClaudeHaiku45,
+        KnownModel::ClaudeOpus41,
+        KnownModel::ClaudeSonnet4,
+        KnownModel::Claude37Sonnet,
+        KnownModel::Gemini25Pro,
+        KnownModel::Gemini25Flash,
+        KnownModel::Gemini25FlashLite,
+        KnownModel::Gemini20Flash,
+        KnownModel::Gemma3,
+    ];
+
+    /// Get the model id sent to the provider.
+    pub fn as_str(&self) -> &str {
+        match self {
+            KnownModel::Gpt5 => "gpt-5",
+            KnownModel::Gpt5Mini => "gpt-5-mini",
+            KnownModel::Gpt5Nano => "gpt-5-nano",
+            KnownModel::Gpt41 => "gpt-4.1",
+            KnownModel::Gpt41Mini => "gpt-4.1-mini",
+            KnownModel::Gpt4o => "gpt-4o",
+            KnownModel::Gpt4oMini => "gpt-4o-mini",
+            KnownModel::O3 => "o3",
+            KnownModel::O4Mini => "o4-mini",
+            KnownModel::ClaudeOpus45 => "claude-opus-4-5",
+            KnownModel::ClaudeSonnet45 => "claude-sonnet-4-5",
+            KnownModel::ClaudeHaiku45 => "claude-haiku-4-5",
+            KnownModel::ClaudeOpus41 => "claude-opus-4-1",
+            KnownModel::ClaudeSonnet4 => "claude-sonnet-4-0",
+            KnownModel::Claude37Sonnet => "claude-3-7-sonnet-latest",
+            KnownModel::Gemini25Pro => "gemini-2.5-pro",
+            KnownModel::Gemini25Flash => "gemini-2.5-flash",
+            KnownModel::Gemini25FlashLite => "gemini-2.5-flash-lite",
+            KnownModel::Gemini20Flash => "gemini-2.0-flash",
+            KnownModel::Gemma3 => "gemma-3-27b-it",
+            KnownModel::Other(model) => model,
+        }
+    }
+
+    /// Vendor of the model, or `None` for other models.
+    pub fn vendor(&self) -> Option<ModelVendor> {
+        match self {
+            KnownModel::Gpt5
+            | KnownModel::Gpt5Mini
+            | KnownModel::Gpt5Nano
+            | KnownModel::Gpt41
+            | KnownModel::Gpt41Mini
+            | KnownModel::Gpt4o
+            | KnownModel::Gpt4oMini
+            | KnownModel::O3
+            | KnownModel::O4Mini => Some(ModelVendor::OpenAI),
+            KnownModel::ClaudeOpus45
+            | KnownModel::ClaudeSonnet45
+            | KnownModel::ClaudeHaiku45
+            | KnownModel::ClaudeOpus41
+            | KnownModel::ClaudeSonnet4
+            | KnownModel::Claude37Sonnet => Some(ModelVendor::Anthropic),
+            KnownModel::Gemini25Pro
+            | KnownModel::Gemini25Flash
+            | KnownModel::Gemini25FlashLite
+            | KnownModel::Gemini20Flash
+            | KnownModel::Gemma3 => Some(ModelVendor::Google),
+            KnownModel::Other(_) => None,
+        }
+    }
+
+    /// Capabilities of the model, or `None` for other models.
+    pub fn capabilities(&self) -> Option<ModelCapabilities> {
+        let capabilities = match self {
+            KnownModel::Gpt5 | KnownModel::Gpt5Mini | KnownModel::Gpt5Nano => {
+                ModelCapabilities::new(400_000, 128_000, true)
+            }
+            KnownModel::Gpt41 | KnownModel::Gpt41Mini => {
+                ModelCapabilities::new(1_047_576, 32_768, false)
+            }
+            KnownModel::Gpt4o | KnownModel::Gpt4oMini => {
+                ModelCapabilities::new(128_000, 16_384, false)
+            }
+            KnownModel::O3 | KnownModel::O4Mini => ModelCapabilities::new(200_000, 100_000, true),
+            KnownModel::ClaudeOpus45
+            | KnownModel::ClaudeSonnet45
+            | KnownModel::ClaudeHaiku45
+            | KnownModel::ClaudeSonnet4
+            | KnownModel::Claude37Sonnet => ModelCapabilities::new(200_000, 64_000, true),
+            KnownModel::ClaudeOpus41 => ModelCapabilities::new(200_000, 32_000, true),
+            KnownModel::Gemini25Pro | KnownModel::Gemini25Flash | KnownModel::Gemini25FlashLite => {
+                ModelCapabilities::new(1_048_576, 65_536, true)
+            }
+            KnownModel::Gemini20Flash => ModelCapabilities::new(1_048_576, 8_192, false),
+            KnownModel::Gemma3 => ModelCapabilities {
+                tools: false,
+                ..ModelCapabilities::new(131_072, 8_192, false)
+            },
+            KnownModel::Other(_) => return None,
+        };
+        Some(capabilities)
+    }
+}
+
+impl fmt::Display for KnownModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KnownModel {
+    type Err = Infallible;
+
+    fn from_str(model: &str) -> Result<Self, Self::Err> {
+        Ok(model.to_string().into())
+    }
+}
+
+impl From<String> for KnownModel {
+    fn from(model: String) -> Self {
+        KnownModel::ALL
+            .iter()
+            .find(|known| known.as_str() == model)
+            .cloned()
+            .unwrap_or(KnownModel::Other(model))
+    }
+}
+
+impl From<KnownModel> for String {
+    fn from(model: KnownModel) -> Self {
+        match model {
+            KnownModel::Other(model) => model,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+/// Aliases and the model they currently point to.
+const ALIASES: &[(&str, &str)] = &[
+    ("gpt-5", "gpt-5-2025-08-07"),
+    ("gpt-5-mini", "gpt-5-mini-2025-08-07"),
+    ("gpt-5-nano", "gpt-5-nano-2025-08-07"),
+    ("gpt-4.1", "gpt-4.1-2025-04-14"),
+    ("gpt-4.1-mini", "gpt-4.1-mini-2025-04-14"),
+    ("gpt-4o", "gpt-4o-2024-08-06"),
+    ("gpt-4o-mini", "gpt-4o-mini-2024-07-18"),
+    ("claude-opus-4-5", "claude-opus-4-5-20251101"),
+    ("claude-sonnet-4-5", "claude-sonnet-4-5-20250929"),
+    ("claude-haiku-4-5", "claude-haiku-4-5-20251001"),
+    ("claude-opus-4-1", "claude-opus-4-1-20250805"),
+    ("claude-opus-4-0", "claude-opus-4-20250514"),
+    ("claude-sonnet-4-0", "claude-sonnet-4-20250514"),
+    ("claude-3-7-sonnet-latest", "claude-3-7-sonnet-20250219"),
+    ("claude-3-5-sonnet", "claude-3-5-sonnet-20241022"),
+    ("claude-3-5-sonnet-latest", "claude-3-5-sonnet-20241022"),
+    ("claude-3-5-haiku-latest", "claude-3-5-haiku-20241022"),
+    ("gemini-pro", "gemini-2.5-pro"),
+    ("gemini-flash", "gemini-2.5-flash"),
+    ("gemini-flash-lite", "gemini-2.5-flash-lite"),
+];
+
+/// Notice that a model is deprecated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    pub model: &'static str,
+    /// Date (`YYYY-MM-DD`) on which the model stops or stopped serving requests.
+    pub retirement: &'static str,
+    /// Model recommended instead.
+    pub replacement: &'static str,
+}
+
+const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation {
+        model: "claude-3-5-sonnet-20240620",
+        retirement: "2025-10-22",
+        replacement: "claude-sonnet-4-5",
+    },
+    Deprecation {
+        model: "claude-3-5-sonnet-20241022",
+        retirement: "2025-10-22",
+        replacement: "claude-sonnet-4-5",
+    },
+    Deprecation {
+        model: "claude-3-opus-20240229",
+        retirement: "2026-01-05",
+        replacement: "claude-opus-4-5",
+    },
+    Deprecation {
+        model: "claude-3-sonnet-20240229",
+        retirement: "2025-07-21",
+        replacement: "claude-sonnet-4-5",
+    },
+    Deprecation {
+        model: "claude-2.1",
+        retirement: "2025-07-21",
+        replacement: "claude-sonnet-4-5",
+    },
+    Deprecation {
+        model: "gemini-1.5-pro",
+        retirement: "2025-09-24",
+        replacement: "gemini-2.5-pro",
+    },
+    Deprecation {
+        model: "gemini-1.5-flash",
+        retirement: "2025-09-24",
+        replacement: "gemini-2.5-flash",
+    },
+    Deprecation {
+        model: "gpt-4.5-preview",
+        retirement: "2025-07-14",
+        replacement: "gpt-4.1",
+    },
+    Deprecation {
+        model: "o1-preview",
+        retirement: "2025-07-28",
+        replacement: "o3",
+    },
+    Deprecation {
+        model: "o1-mini",
+        retirement: "2025-10-27",
+        replacement: "o4-mini",
+    },
+];
+
+/// Deprecated models already reported by [`warn_if_deprecated`].
+static WARNED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// The model an alias currently points to, or `None` if `model` is not an alias.
+pub fn resolve_alias(model: &str) -> Option<&'static str> {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == model)
+        .map(|(_, target)| *target)
+}
+
+/// The deprecation notice of a model, if it is deprecated.
+pub fn deprecation(model: &str) -> Option<&'static Deprecation> {
+    DEPRECATIONS
+        .iter()
+        .find(|deprecation| deprecation.model == model)
+}
+
+/// Log a warning if `model` is deprecated, once per model and process.
+pub fn warn_if_deprecated(model: &str) {
+    let Some(deprecation) = deprecation(model) else {
+        return;
+    };
+    let mut warned = WARNED.lock().unwrap();
+    if warned.contains(&deprecation.model) {
+        return;
+    }
+    warned.push(deprecation.model);
+    warn!(
+        "Model {} is deprecated and retires on {}; use {} instead",
+        deprecation.model, deprecation.retirement, deprecation.replacement
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_models_round_trip() {
+        for model in KnownModel::ALL {
+            assert_eq!(model.as_str().parse::<KnownModel>().unwrap(), *model);
+            assert!(model.vendor().is_some() && model.capabilities().is_some());
+        }
+
+        let custom: KnownModel = "ft:gpt-4o:acme".parse().unwrap();
+        assert_eq!(custom, KnownModel::Other("ft:gpt-4o:acme".to_string()));
+        assert_eq!(String::from(custom), "ft:gpt-4o:acme");
+        assert_eq!(
+            serde_json::to_value(KnownModel::Gemini25Flash).unwrap(),
+            "gemini-2.5-flash"
+        );
+    }
+
+    #[test]
+    fn test_aliases_and_deprecations() {
+        assert_eq!(resolve_alias("gemini-flash"), Some("gemini-2.5-flash"));
+        assert_eq!(resolve_alias("gemini-2.5-flash"), None);
+
+        let target = resolve_alias("claude-3-5-sonnet").unwrap();
+        assert_eq!(
+            deprecation(target).unwrap().replacement,
+            "claude-sonnet-4-5"
+        );
+        assert!(deprecation("claude-sonnet-4-5").is_none());
+
+        // Every alias of a named model points to a model that is not deprecated.
+        for model in KnownModel::ALL {
+            let target = resolve_alias(model.as_str()).unwrap_or(model.as_str());
+            assert!(deprecation(target).is_none(), "{}", target);
+        }
+    }
+}
@@ -1,15 +1,26 @@
 //! Core client trait and error types.
 
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-use crate::model::{Message, Response};
+use crate::model::{Message, PartIndex, Response, Usage};
 use crate::options::{ModelOptions, TransportOptions};
+use crate::structured::{parse_complete, repair_json, response_text, Partial};
 use rmcp::model::Tool;
 
 /// Errors that can occur during client operations.
+///
+/// New variants may be added in minor releases. Match on [`code`](Self::code) or the
+/// `is_*` predicates to handle errors without a catch-all arm for every variant.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
@@ -17,14 +28,218 @@ pub enum ClientError {
     #[error("JSON parse error: {0}")]
     Parse(#[from] serde_json::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("Provider error: {0}")]
     ProviderError(String),
 
     #[error("Stream cancelled")]
     StreamCancelled,
 
+    #[error("Batch request {outcome} before it was processed")]
+    BatchItem { outcome: BatchItemOutcome },
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
     #[error("Configuration error: {0}")]
     Config(String),
+
+    #[error("Budget exhausted: {0}")]
+    BudgetExhausted(String),
+
+    #[error("Response failed validation after {attempts} attempts: {message}")]
+    ValidationFailed { attempts: usize, message: String },
+
+    #[error("{provider} does not support {capability}")]
+    Unsupported {
+        provider: String,
+        capability: String,
+    },
+
+    #[error("{stage} blocked by the content filter: {}", categories.join(", "))]
+    ContentFiltered {
+        stage: FilterStage,
+        /// Categories reported by the provider, e.g. `HARM_CATEGORY_HARASSMENT`.
+        categories: Vec<String>,
+    },
+
+    #[error("Request is about {size} bytes, more than the limit of {limit}")]
+    RequestTooLarge {
+        size: usize,
+        limit: usize,
+        /// Largest parts, by position in the request messages, that must be dropped
+        /// or shrunk to fit the limit.
+        parts: Vec<PartIndex>,
+    },
+
+    #[error("Provider error: {message}{}", request_id.as_ref().map(|id| format!(" (request id: {})", id)).unwrap_or_default())]
+    Api {
+        /// HTTP status code, if the error was returned as an HTTP response.
+        status: Option<u16>,
+        /// Provider request id, useful when contacting provider support.
+        request_id: Option<String>,
+        message: String,
+    },
+}
+
+/// Why a request of a batch was not processed.
+///
+/// New outcomes may be added in minor releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BatchItemOutcome {
+    /// The batch was canceled.
+    Canceled,
+    /// The batch expired.
+    Expired,
+}
+
+impl fmt::Display for BatchItemOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchItemOutcome::Canceled => write!(f, "was canceled"),
+            BatchItemOutcome::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// Stage at which a provider's content filter blocked a request.
+///
+/// New stages may be added in minor releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterStage {
+    /// The prompt was blocked before anything was generated.
+    Prompt,
+    /// The generated completion was blocked.
+    Completion,
+}
+
+impl fmt::Display for FilterStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterStage::Prompt => write!(f, "Prompt"),
+            FilterStage::Completion => write!(f, "Completion"),
+        }
+    }
+}
+
+impl ClientError {
+    /// Stable, machine-readable code of the error kind.
+    ///
+    /// Codes are part of the public API: an existing code is never renamed or reused
+    /// for another kind of error, and new variants come with new codes.
+    ///
+    /// | Code | Variant |
+    /// |------|---------|
+    /// | `http` | [`Http`](Self::Http) |
+    /// | `parse` | [`Parse`](Self::Parse) |
+    /// | `io` | [`Io`](Self::Io) |
+    /// | `provider` | [`ProviderError`](Self::ProviderError) |
+    /// | `stream_cancelled` | [`StreamCancelled`](Self::StreamCancelled) |
+    /// | `batch_item` | [`BatchItem`](Self::BatchItem) |
+    /// | `timeout` | [`Timeout`](Self::Timeout) |
+    /// | `config` | [`Config`](Self::Config) |
+    /// | `budget_exhausted` | [`BudgetExhausted`](Self::BudgetExhausted) |
+    /// | `validation_failed` | [`ValidationFailed`](Self::ValidationFailed) |
+    /// | `unsupported` | [`Unsupported`](Self::Unsupported) |
+    /// | `content_filtered` | [`ContentFiltered`](Self::ContentFiltered) |
+    /// | `request_too_large` | [`RequestTooLarge`](Self::RequestTooLarge) |
+    /// | `api` | [`Api`](Self::Api) |
+    pub fn code(&self) -> &'static str {
+        match self {
+            ClientError::Http(_) => "http",
+            ClientError::Parse(_) => "parse",
+            ClientError::Io(_) => "io",
+            ClientError::ProviderError(_) => "provider",
+            ClientError::StreamCancelled => "stream_cancelled",
+            ClientError::BatchItem { .. } => "batch_item",
+            ClientError::Timeout(_) => "timeout",
+            ClientError::Config(_) => "config",
+            ClientError::BudgetExhausted(_) => "budget_exhausted",
+            ClientError::ValidationFailed { .. } => "validation_failed",
+            ClientError::Unsupported { .. } => "unsupported",
+            ClientError::ContentFiltered { .. } => "content_filtered",
+            ClientError::RequestTooLarge { .. } => "request_too_large",
+            ClientError::Api { .. } => "api",
+        }
+    }
+
+    /// Whether the request timed out, locally or at the connection level.
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            ClientError::Timeout(_) => true,
+            ClientError::Http(e) => e.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// Whether the provider rejected the request for exceeding a rate limit (429).
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(429)
+    }
+
+    /// Whether a content filter blocked the prompt or the completion.
+    pub fn is_content_filtered(&self) -> bool {
+        matches!(self, ClientError::ContentFiltered { .. })
+    }
+
+    /// Whether the provider or model does not support a requested capability.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, ClientError::Unsupported { .. })
+    }
+
+    /// Whether the client or its options are misconfigured.
+    pub fn is_config(&self) -> bool {
+        matches!(self, ClientError::Config(_))
+    }
+
+    /// Whether a token or cost budget ran out.
+    pub fn is_budget_exhausted(&self) -> bool {
+        matches!(self, ClientError::BudgetExhausted(_))
+    }
+
+    /// Whether the request was rejected locally for its size.
+    pub fn is_request_too_large(&self) -> bool {
+        matches!(self, ClientError::RequestTooLarge { .. })
+    }
+
+    /// Whether the stream was cancelled by the caller.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, ClientError::StreamCancelled)
+    }
+
+    /// HTTP status code associated with the error, if any.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Api { status, .. } => *status,
+            ClientError::Http(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Provider request id associated with the error, if any.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            ClientError::Api { request_id, .. } => request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the same request may succeed.
+    ///
+    /// True for connection failures, timeouts, rate limits (429) and server errors (5xx).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(e) if e.is_timeout() || e.is_connect() => true,
+            ClientError::Timeout(_) => true,
+            _ => self
+                .status()
+                .is_some_and(|status| matches!(status, 408 | 425 | 429 | 500..=599)),
+        }
+    }
 }
 
 /// Main client trait for LLM providers.
@@ -45,8 +260,89 @@ pub trait Client: Send + Sync {
 
     /// Get reference to the transport options.
     fn transport_options(&self) -> &TransportOptions;
+
+    /// Build the request [`request`](Self::request) would send, without sending it.
+    ///
+    /// Useful to debug serialization issues and audit prompts before spending tokens.
+    /// Credentials are redacted from the preview. Clients that do not talk to a
+    /// provider API return [`ClientError::Unsupported`].
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        let _ = (messages, tools);
+        Err(ClientError::Unsupported {
+            provider: std::any::type_name::<Self>().to_string(),
+            capability: "dry_run".to_string(),
+        })
+    }
+}
+
+/// Provider request built by [`Client::dry_run`], with credentials redacted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestPreview {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Serialized request body.
+    pub body: Value,
+    /// Estimated input tokens of the messages, system prompt and tools.
+    pub estimated_tokens: usize,
+}
+
+/// Results of [`ClientExt::chat_many`].
+#[derive(Debug)]
+pub struct ChatResults {
+    /// Result of every chat, in the order the chats were given.
+    pub results: Vec<Result<Response, ClientError>>,
+    /// Usage summed over the chats that succeeded.
+    pub usage: Usage,
+}
+
+impl ChatResults {
+    /// Number of chats that succeeded.
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|result| result.is_ok()).count()
+    }
+
+    /// Number of chats that failed.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+/// Convenience methods available on every [`Client`].
+#[async_trait]
+pub trait ClientExt: Client {
+    /// Send many independent chats, running at most `concurrency` at a time.
+    ///
+    /// A failed chat does not affect the others: its error is returned in its place
+    /// in [`ChatResults::results`]. A `concurrency` of zero is treated as one.
+    async fn chat_many(&self, chats: Vec<Vec<Message>>, concurrency: usize) -> ChatResults {
+        let mut indexed: Vec<_> = futures::stream::iter(chats.into_iter().enumerate())
+            .map(|(index, messages)| async move { (index, self.request(messages, vec![]).await) })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+        indexed.sort_by_key(|(index, _)| *index);
+
+        let mut usage = Usage::default();
+        let results = indexed
+            .into_iter()
+            .map(|(_, result)| {
+                if let Ok(response) = &result {
+                    usage += response.usage.clone();
+                }
+                result
+            })
+            .collect();
+        ChatResults { results, usage }
+    }
 }
 
+impl<C: Client + ?Sized> ClientExt for C {}
+
 /// Extension trait for streaming support.
 #[async_trait]
 pub trait StreamingClient: Client {
@@ -60,3 +356,111 @@ pub trait StreamingClient: Client {
         ClientError,
     >;
 }
+
+/// Liveness information reported while waiting for a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Time elapsed since the request was started.
+    pub elapsed: Duration,
+    /// Number of stream chunks received so far.
+    pub chunks: usize,
+}
+
+/// Convenience methods available on every [`StreamingClient`].
+#[async_trait]
+pub trait StreamingClientExt: StreamingClient {
+    /// Send a request and wait for the complete response, reporting liveness along the way.
+    ///
+    /// The request is streamed internally, but only the final [`Response`] is returned.
+    /// `on_progress` is invoked every `interval` until the response is complete, which
+    /// gives integrations a heartbeat for long requests (e.g. large reasoning budgets)
+    /// without having to accumulate the stream themselves.
+    ///
+    /// Fails with [`ClientError::Config`] before sending anything if `interval` is zero.
+    async fn request_with_progress<F>(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        interval: Duration,
+        on_progress: F,
+    ) -> Result<Response, ClientError>
+    where
+        F: Fn(Progress) + Send + Sync,
+    {
+        if interval.is_zero() {
+            return Err(ClientError::Config(
+                "Progress interval must be greater than zero".to_string(),
+            ));
+        }
+        let start = Instant::now();
+        let mut stream = self.request_stream(messages, tools).await?;
+
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        let mut last_response = None;
+        let mut chunks = 0;
+
+        loop {
+            tokio::select! {
+                item = stream.next() => match item {
+                    Some(response) => {
+                        last_response = Some(response?);
+                        chunks += 1;
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => on_progress(Progress {
+                    elapsed: start.elapsed(),
+                    chunks,
+                }),
+            }
+        }
+
+        last_response.ok_or_else(|| {
+            ClientError::ProviderError("Stream ended without a response".to_string())
+        })
+    }
+
+    /// Stream a JSON response as progressively more complete values of `T`.
+    ///
+    /// The client must be configured to answer in JSON (e.g. through
+    /// [`ModelOptions::output`](crate::options::ModelOptions::output) or the system prompt). Every time the streamed text repairs into
+    /// a new document that deserializes into `T`, a [`Partial`] is yielded. The last
+    /// item is parsed from the full output and has `complete` set; if the full output
+    /// is not valid for `T`, the stream ends with [`ClientError::Parse`].
+    fn chat_structured_stream<'a, T>(
+        &'a self,
+        messages: Vec<Message>,
+    ) -> Pin<Box<dyn Stream<Item = Result<Partial<T>, ClientError>> + Send + 'a>>
+    where
+        T: DeserializeOwned + Send + 'a,
+        Self: Sync,
+    {
+        Box::pin(async_stream::try_stream! {
+            let mut stream = self.request_stream(messages, vec![]).await?;
+            let mut text = String::new();
+            let mut last_document = None;
+
+            while let Some(response) = stream.next().await {
+                text = response_text(&response?);
+
+                let Some(document) = repair_json(&text) else {
+                    continue;
+                };
+                if last_document.as_ref() == Some(&document) {
+                    continue;
+                }
+                if let Ok(value) = serde_json::from_str::<T>(&document) {
+                    yield Partial { value, complete: false };
+                }
+                last_document = Some(document);
+            }
+
+            yield Partial {
+                value: parse_complete(&text)?,
+                complete: true,
+            };
+        })
+    }
+}
+
+impl<C: StreamingClient + ?Sized> StreamingClientExt for C {}
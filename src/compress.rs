@@ -0,0 +1,417 @@
+//! Prompt compression.
+//!
+//! Long conversations are expensive to resend on every turn. A [`Compressor`] rewrites
+//! the history into a shorter one before it is sent, trading some fidelity for cost:
+//! [`SentenceDropper`] removes repeated and trailing sentences from older turns, and
+//! [`SummaryCompressor`] replaces them with a summary written by a (cheap) model.
+//! [`Compactor`] summarizes only once a history grows past a threshold, and can
+//! archive what it replaces in a [`Conversation`](crate::conversation::Conversation).
+//! Token counts are estimated, see [`estimate_tokens`].
+
+use async_trait::async_trait;
+use std::collections::HashSet;
+
+use crate::client::{Client, ClientError};
+use crate::history::{normalize_history, step_boundary};
+use crate::model::{Message, Part};
+
+/// Tokens assumed for a media part, whose real cost depends on the provider.
+pub const MEDIA_TOKEN_ESTIMATE: usize = 256;
+
+/// Estimate the number of tokens of a history, at roughly four characters per token.
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .flat_map(|message| message.parts())
+        .map(|part| match part {
+            Part::Text { content, .. } | Part::Reasoning { content, .. } => estimate_text(content),
+            Part::FunctionCall {
+                name, arguments, ..
+            } => estimate_text(name) + estimate_text(&arguments.to_string()),
+            Part::FunctionResponse {
+                name,
+                response,
+                parts,
+                ..
+            } => {
+                estimate_text(name)
+                    + estimate_text(&response.to_string())
+                    + estimate_tokens(&[Message::User(parts.clone())])
+            }
+            Part::Media { .. } => MEDIA_TOKEN_ESTIMATE,
+        })
+        .sum()
+}
+
+pub(crate) fn estimate_text(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Estimated size of a history before and after compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+}
+
+impl CompressionReport {
+    /// Estimated number of tokens saved.
+    pub fn saved(&self) -> usize {
+        self.tokens_before.saturating_sub(self.tokens_after)
+    }
+}
+
+/// A stage that shortens a history before it is sent.
+#[async_trait]
+pub trait Compressor: Send + Sync {
+    /// Return a shorter history conveying the same conversation.
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>, ClientError>;
+}
+
+/// Compress `messages` and report the estimated token counts.
+pub async fn compress(
+    compressor: &dyn Compressor,
+    messages: Vec<Message>,
+) -> Result<(Vec<Message>, CompressionReport), ClientError> {
+    let tokens_before = estimate_tokens(&messages);
+    let messages = compressor.compress(messages).await?;
+    let report = CompressionReport {
+        tokens_before,
+        tokens_after: estimate_tokens(&messages),
+    };
+    Ok((messages, report))
+}
+
+/// Heuristic compressor dropping sentences from older turns.
+///
+/// While the history is over the token target, sentences repeating an earlier one are
+/// removed first, then the sentences after the first of each text part, starting
+/// with the oldest message. The most recent messages are never modified, and neither
+/// are tool calls, tool results or media.
+#[derive(Debug, Clone)]
+pub struct SentenceDropper {
+    target_tokens: usize,
+    keep_recent: usize,
+}
+
+impl SentenceDropper {
+    /// Compress histories estimated above `target_tokens`.
+    pub fn new(target_tokens: usize) -> Self {
+        Self {
+            target_tokens,
+            keep_recent: 2,
+        }
+    }
+
+    /// Number of trailing messages left untouched. Defaults to 2.
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Remove sentences matching `drop` from the older text parts while over target.
+    fn drop_sentences<F>(&self, messages: &mut [Message], mut drop: F)
+    where
+        F: FnMut(usize, &str) -> bool,
+    {
+        let mut tokens = estimate_tokens(messages);
+        let boundary = messages.len().saturating_sub(self.keep_recent);
+
+        for message in &mut messages[..boundary] {
+            for part in message.parts_mut() {
+                if tokens <= self.target_tokens {
+                    return;
+                }
+                let Part::Text { content, .. } = part else {
+                    continue;
+                };
+
+                let before = estimate_text(content);
+                let kept: String = sentences(content)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, sentence)| !drop(*index, sentence))
+                    .map(|(_, sentence)| sentence)
+                    .collect();
+                if kept.trim().is_empty() {
+                    continue;
+                }
+                tokens = tokens - before + estimate_text(&kept);
+                *content = kept.into();
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Compressor for SentenceDropper {
+    async fn compress(&self, mut messages: Vec<Message>) -> Result<Vec<Message>, ClientError> {
+        let mut seen = HashSet::new();
+        self.drop_sentences(&mut messages, |_, sentence| {
+            !seen.insert(sentence.trim().to_lowercase())
+        });
+        self.drop_sentences(&mut messages, |index, _| index > 0);
+        Ok(messages)
+    }
+}
+
+/// Split text into sentences, keeping the whitespace that follows each one.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        if chars.peek().is_some_and(|(_, next)| !next.is_whitespace()) {
+            continue;
+        }
+        while let Some((_, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+        let end = chars.peek().map_or(text.len(), |(index, _)| *index);
+        sentences.push(&text[start..end]);
+        start = end;
+    }
+
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
+/// Default instructions given to the summarization model.
+pub const SUMMARY_INSTRUCTIONS: &str = "Summarize the following conversation between a user \
+and an assistant. Keep every fact, decision and open question needed to continue it; \
+leave out pleasantries.";
+
+/// Compressor replacing older messages with a summary written by a model.
+pub struct SummaryCompressor<C: Client> {
+    client: C,
+    keep_recent: usize,
+    instructions: String,
+}
+
+impl<C: Client> SummaryCompressor<C> {
+    /// Summarize with `client`, typically configured with a small, cheap model.
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            keep_recent: 4,
+            instructions: SUMMARY_INSTRUCTIONS.to_string(),
+        }
+    }
+
+    /// Number of trailing messages kept verbatim. Defaults to 4.
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Replace the instructions given to the summarization model.
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+
+    /// Split off the messages to summarize, leaving the recent ones in `messages`.
+    ///
+    /// The split moves back to the start of a step, so that tool results stay with
+    /// their calls.
+    fn split_older(&self, messages: &mut Vec<Message>) -> Vec<Message> {
+        let at = step_boundary(messages, messages.len().saturating_sub(self.keep_recent));
+        let recent = messages.split_off(at);
+        std::mem::replace(messages, recent)
+    }
+
+    /// Summarize `older` and put the summary ahead of `recent`.
+    async fn summarize(
+        &self,
+        older: &[Message],
+        recent: Vec<Message>,
+    ) -> Result<Vec<Message>, ClientError> {
+        let prompt = format!("{}\n\n{}", self.instructions, transcript(older));
+        let response = self
+            .client
+            .request(vec![Message::user(prompt)], vec![])
+            .await?;
+        let summary = response
+            .data
+            .iter()
+            .filter_map(Message::content)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut compressed = vec![Message::user(format!(
+            "Summary of the earlier conversation:\n{}",
+            summary
+        ))];
+        compressed.extend(recent);
+        Ok(normalize_history(compressed))
+    }
+}
+
+#[async_trait]
+impl<C: Client> Compressor for SummaryCompressor<C> {
+    async fn compress(&self, mut messages: Vec<Message>) -> Result<Vec<Message>, ClientError> {
+        if messages.len() <= self.keep_recent {
+            return Ok(messages);
+        }
+
+        let older = self.split_older(&mut messages);
+        if older.is_empty() {
+            return Ok(messages);
+        }
+        self.summarize(&older, messages).await
+    }
+}
+
+/// Summarization applied once a history grows past a token threshold.
+///
+/// Used with [`Conversation::compact`](crate::conversation::Conversation::compact), the
+/// summarized messages are archived in the conversation rather than dropped. As a
+/// [`Compressor`], histories under the threshold are sent unchanged.
+pub struct Compactor<C: Client> {
+    summarizer: SummaryCompressor<C>,
+    threshold_tokens: usize,
+}
+
+impl<C: Client> Compactor<C> {
+    /// Summarize with `client` once a history is estimated above `threshold_tokens`.
+    pub fn new(client: C, threshold_tokens: usize) -> Self {
+        Self {
+            summarizer: SummaryCompressor::new(client),
+            threshold_tokens,
+        }
+    }
+
+    /// Number of trailing messages kept verbatim. Defaults to 4.
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.summarizer = self.summarizer.with_keep_recent(keep_recent);
+        self
+    }
+
+    /// Replace the instructions given to the summarization model.
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.summarizer = self.summarizer.with_instructions(instructions);
+        self
+    }
+
+    /// Whether `messages` are long enough to be compacted.
+    pub fn exceeds_threshold(&self, messages: &[Message]) -> bool {
+        estimate_tokens(messages) > self.threshold_tokens
+            && messages.len() > self.summarizer.keep_recent
+    }
+
+    /// Compact `messages` if they exceed the threshold, returning the compacted
+    /// history and the summarized messages, or `None` if nothing was done.
+    pub async fn compact(
+        &self,
+        messages: &[Message],
+    ) -> Result<Option<(Vec<Message>, Vec<Message>)>, ClientError> {
+        if !self.exceeds_threshold(messages) {
+            return Ok(None);
+        }
+        let mut recent = messages.to_vec();
+        let older = self.summarizer.split_older(&mut recent);
+        if older.is_empty() {
+            return Ok(None);
+        }
+        let compacted = self.summarizer.summarize(&older, recent).await?;
+        Ok(Some((compacted, older)))
+    }
+}
+
+#[async_trait]
+impl<C: Client> Compressor for Compactor<C> {
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>, ClientError> {
+        match self.compact(&messages).await? {
+            Some((compacted, _)) => Ok(compacted),
+            None => Ok(messages),
+        }
+    }
+}
+
+/// Render a history as plain text for summarization.
+fn transcript(messages: &[Message]) -> String {
+    let mut lines = Vec::new();
+    for message in messages {
+        let speaker = match message {
+            Message::User(_) => "User",
+            Message::Assistant(_) => "Assistant",
+        };
+        for part in message.parts() {
+            let line = match part {
+                Part::Text { content, .. } => format!("{}: {}", speaker, content),
+                Part::FunctionCall {
+                    name, arguments, ..
+                } => format!("{} called {} with {}", speaker, name, arguments),
+                Part::FunctionResponse { name, response, .. } => {
+                    format!("{} returned {}", name, response)
+                }
+                Part::Media { mime_type, .. } => format!("{} sent a {} file", speaker, mime_type),
+                Part::Reasoning { .. } => continue,
+            };
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentences_keep_text_intact() {
+        let text = "First one. Second one!  Third? v1.2 stays whole";
+        let split = sentences(text);
+
+        assert_eq!(
+            split,
+            vec![
+                "First one. ",
+                "Second one!  ",
+                "Third? ",
+                "v1.2 stays whole"
+            ]
+        );
+        assert_eq!(split.concat(), text);
+    }
+
+    #[tokio::test]
+    async fn test_sentence_dropper_spares_recent_messages() {
+        let long = "Intro. Detail one. Detail two. Detail three.";
+        let messages = vec![
+            Message::user(long),
+            Message::assistant("Intro. Answer."),
+            Message::user(long),
+        ];
+
+        let dropper = SentenceDropper::new(0).with_keep_recent(1);
+        let (compressed, report) = compress(&dropper, messages).await.unwrap();
+
+        assert_eq!(compressed[0].content().as_deref(), Some("Intro. "));
+        assert_eq!(compressed[1].content().as_deref(), Some("Answer."));
+        assert_eq!(compressed[2].content().as_deref(), Some(long));
+        assert!(report.tokens_after < report.tokens_before);
+    }
+
+    #[tokio::test]
+    async fn test_sentence_dropper_stops_at_target() {
+        let messages = vec![
+            Message::user("Keep. Me. Please."),
+            Message::assistant("Ok."),
+        ];
+
+        let dropper = SentenceDropper::new(100).with_keep_recent(0);
+        let compressed = dropper.compress(messages.clone()).await.unwrap();
+
+        assert_eq!(compressed, messages);
+    }
+}
@@ -0,0 +1,207 @@
+//! Language and formatting constraints on answers.
+//!
+//! [`Constraints`] describe what an answer must look like: its language, a word
+//! limit, phrases it must avoid and text it must start or end with. Set on
+//! [`ModelOptions::constraints`](crate::options::ModelOptions::constraints), they are
+//! appended to the system prompt in the wording each provider follows best. Models
+//! do not always comply, so `Constraints` also implement [`Validator`]: wrap a client
+//! in [`Validated`](crate::validate::Validated) with them to re-ask until the answer
+//! complies.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::Response;
+use crate::structured::response_text;
+use crate::validate::Validator;
+
+/// How constraints are worded in a system prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptStyle {
+    /// A bulleted list.
+    Plain,
+    /// A bulleted list inside `<output_constraints>` tags, as Anthropic recommends
+    /// for structuring prompts.
+    Xml,
+}
+
+/// Constraints on the language and formatting of answers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constraints {
+    /// Language to answer in, e.g. `French`. Not checked by the validator.
+    pub language: Option<String>,
+    /// Maximum number of whitespace-separated words.
+    pub max_words: Option<usize>,
+    /// Phrases the answer must not contain, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_phrases: Vec<String>,
+    /// Text the answer must start with.
+    pub prefix: Option<String>,
+    /// Text the answer must end with.
+    pub suffix: Option<String>,
+}
+
+impl Constraints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require answers in `language`.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Limit answers to `max_words` words.
+    pub fn with_max_words(mut self, max_words: usize) -> Self {
+        self.max_words = Some(max_words);
+        self
+    }
+
+    /// Forbid `phrase` in answers.
+    pub fn with_forbidden_phrase(mut self, phrase: impl Into<String>) -> Self {
+        self.forbidden_phrases.push(phrase.into());
+        self
+    }
+
+    /// Require answers to start with `prefix`.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Require answers to end with `suffix`.
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// Whether no constraint is set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// System prompt snippet stating the constraints, or `None` if there are none.
+    pub fn render(&self, style: PromptStyle) -> Option<String> {
+        let mut rules = Vec::new();
+        if let Some(language) = &self.language {
+            rules.push(format!("Answer in {}.", language));
+        }
+        if let Some(max_words) = self.max_words {
+            rules.push(format!("Use at most {} words.", max_words));
+        }
+        if !self.forbidden_phrases.is_empty() {
+            let phrases = self
+                .forbidden_phrases
+                .iter()
+                .map(|phrase| format!("\"{}\"", phrase))
+                .collect::<Vec<_>>()
+                .join(", ");
+            rules.push(format!("Never use these phrases: {}.", phrases));
+        }
+        if let Some(prefix) = &self.prefix {
+            rules.push(format!("Start the answer with exactly \"{}\".", prefix));
+        }
+        if let Some(suffix) = &self.suffix {
+            rules.push(format!("End the answer with exactly \"{}\".", suffix));
+        }
+        if rules.is_empty() {
+            return None;
+        }
+
+        let list = rules
+            .iter()
+            .map(|rule| format!("- {}", rule))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(match style {
+            PromptStyle::Plain => format!("Your answer must follow these rules:\n{}", list),
+            PromptStyle::Xml => format!("<output_constraints>\n{}\n</output_constraints>", list),
+        })
+    }
+
+    /// Check `text` against the constraints, describing every violation.
+    pub fn check(&self, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let mut problems = Vec::new();
+        if let Some(max_words) = self.max_words {
+            let words = text.split_whitespace().count();
+            if words > max_words {
+                problems.push(format!(
+                    "The answer has {} words but must have at most {}.",
+                    words, max_words
+                ));
+            }
+        }
+        let lowercase = text.to_lowercase();
+        for phrase in &self.forbidden_phrases {
+            if lowercase.contains(&phrase.to_lowercase()) {
+                problems.push(format!("The answer must not contain \"{}\".", phrase));
+            }
+        }
+        if let Some(prefix) = &self.prefix {
+            if !text.starts_with(prefix.as_str()) {
+                problems.push(format!("The answer must start with \"{}\".", prefix));
+            }
+        }
+        if let Some(suffix) = &self.suffix {
+            if !text.ends_with(suffix.as_str()) {
+                problems.push(format!("The answer must end with \"{}\".", suffix));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join(" "))
+        }
+    }
+}
+
+impl Validator for Constraints {
+    fn validate(&self, response: &Response) -> Result<(), String> {
+        self.check(&response_text(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraints() -> Constraints {
+        Constraints::new()
+            .with_language("French")
+            .with_max_words(5)
+            .with_forbidden_phrase("As an AI")
+            .with_prefix("Réponse :")
+    }
+
+    #[test]
+    fn test_render_styles() {
+        assert_eq!(Constraints::new().render(PromptStyle::Plain), None);
+
+        let plain = constraints().render(PromptStyle::Plain).unwrap();
+        assert_eq!(
+            plain,
+            "Your answer must follow these rules:\n\
+             - Answer in French.\n\
+             - Use at most 5 words.\n\
+             - Never use these phrases: \"As an AI\".\n\
+             - Start the answer with exactly \"Réponse :\"."
+        );
+        let xml = constraints().render(PromptStyle::Xml).unwrap();
+        assert!(xml.starts_with("<output_constraints>\n- Answer in French."));
+        assert!(xml.ends_with("\n</output_constraints>"));
+    }
+
+    #[test]
+    fn test_check_reports_every_violation() {
+        assert!(constraints().check("Réponse : Paris. ").is_ok());
+
+        let error = constraints()
+            .check("as an ai, I think the answer is Paris")
+            .unwrap_err();
+        assert!(error.contains("at most 5"));
+        assert!(error.contains("\"As an AI\""));
+        assert!(error.contains("start with"));
+    }
+}
@@ -0,0 +1,472 @@
+//! Conversation history with named branches.
+//!
+//! Chat applications that let users edit a message or regenerate an answer end up
+//! with several alternate continuations of the same history. A [`Conversation`]
+//! keeps them as named branches sharing a common prefix, so switching between
+//! alternatives does not require manual `Vec` surgery.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::client::{Client, ClientError};
+use crate::compress::{self, Compactor, CompressionReport, Compressor};
+use crate::instructions::Instructions;
+use crate::model::{Message, Response};
+
+/// Name of the branch a new conversation starts on.
+pub const MAIN_BRANCH: &str = "main";
+
+/// Errors returned by branch operations.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConversationError {
+    #[error("Unknown branch: {0}")]
+    UnknownBranch(String),
+
+    #[error("Branch already exists: {0}")]
+    BranchExists(String),
+
+    #[error("Cannot remove the current branch: {0}")]
+    CurrentBranch(String),
+
+    #[error("Index {index} is out of range for a history of {len} messages")]
+    OutOfRange { index: usize, len: usize },
+
+    #[error("Branches diverge in a user turn at message {0}")]
+    Conflict(usize),
+}
+
+/// A conversation history with named alternate continuations.
+///
+/// Messages are always appended to the current branch. New branches start as a
+/// copy of a prefix of the current branch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    branches: BTreeMap<String, Vec<Message>>,
+    current: String,
+    /// Messages replaced by summaries in each branch, oldest first.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    archives: BTreeMap<String, Vec<Message>>,
+    /// Instructions shared by all branches.
+    #[serde(default, skip_serializing_if = "Instructions::is_empty")]
+    instructions: Instructions,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Conversation {
+    /// Start a conversation on the [`MAIN_BRANCH`].
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            branches: BTreeMap::from([(MAIN_BRANCH.to_string(), messages)]),
+            current: MAIN_BRANCH.to_string(),
+            archives: BTreeMap::new(),
+            instructions: Instructions::new(),
+        }
+    }
+
+    /// Set the instructions sent with every request of the conversation, typically
+    /// on the [`InstructionLayer::Conversation`](crate::instructions::InstructionLayer::Conversation)
+    /// layer.
+    pub fn with_instructions(mut self, instructions: Instructions) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// Instructions of the conversation.
+    pub fn instructions(&self) -> &Instructions {
+        &self.instructions
+    }
+
+    /// Mutable instructions of the conversation.
+    pub fn instructions_mut(&mut self) -> &mut Instructions {
+        &mut self.instructions
+    }
+
+    /// Messages of the current branch.
+    pub fn messages(&self) -> &[Message] {
+        &self.branches[&self.current]
+    }
+
+    /// Append a message to the current branch.
+    pub fn push(&mut self, message: Message) {
+        self.current_mut().push(message);
+    }
+
+    /// Append the messages generated for a response to the current branch.
+    pub fn push_response(&mut self, response: &Response) {
+        self.current_mut().extend(response.data.iter().cloned());
+    }
+
+    /// Replace the current branch with its compressed form.
+    ///
+    /// Other branches are left untouched, so the full history stays available if the
+    /// branch was created before compressing.
+    pub async fn compress(
+        &mut self,
+        compressor: &dyn Compressor,
+    ) -> Result<CompressionReport, ClientError> {
+        let messages = std::mem::take(self.current_mut());
+        let (messages, report) = compress::compress(compressor, messages).await?;
+        *self.current_mut() = messages;
+        Ok(report)
+    }
+
+    /// Summarize the older messages of the current branch once it exceeds the
+    /// threshold of `compactor`, returning `None` if it does not.
+    ///
+    /// The summarized messages are moved to the [archive](Self::archived) of the branch.
+    pub async fn compact<C: Client>(
+        &mut self,
+        compactor: &Compactor<C>,
+    ) -> Result<Option<CompressionReport>, ClientError> {
+        let Some((compacted, older)) = compactor.compact(self.messages()).await? else {
+            return Ok(None);
+        };
+        let report = CompressionReport {
+            tokens_before: compress::estimate_tokens(self.messages()),
+            tokens_after: compress::estimate_tokens(&compacted),
+        };
+        *self.current_mut() = compacted;
+        self.archives
+            .entry(self.current.clone())
+            .or_default()
+            .extend(older);
+        Ok(Some(report))
+    }
+
+    /// Messages of the current branch replaced by summaries through
+    /// [`compact`](Self::compact), oldest first.
+    pub fn archived(&self) -> &[Message] {
+        self.archives.get(&self.current).map_or(&[], Vec::as_slice)
+    }
+
+    /// Create an independent copy of the current branch as a new conversation.
+    pub fn fork(&self) -> Conversation {
+        let mut conversation = Conversation::new(self.messages().to_vec())
+            .with_instructions(self.instructions.clone());
+        if !self.archived().is_empty() {
+            conversation
+                .archives
+                .insert(MAIN_BRANCH.to_string(), self.archived().to_vec());
+        }
+        conversation
+    }
+
+    /// Name of the current branch.
+    pub fn current_branch(&self) -> &str {
+        &self.current
+    }
+
+    /// Names of all branches, in lexicographic order.
+    pub fn branches(&self) -> impl Iterator<Item = &str> {
+        self.branches.keys().map(String::as_str)
+    }
+
+    /// Messages of a branch.
+    pub fn branch(&self, name: &str) -> Option<&[Message]> {
+        self.branches.get(name).map(Vec::as_slice)
+    }
+
+    /// Create a branch holding the first `at` messages of the current branch and switch to it.
+    ///
+    /// To regenerate the answer at index `i`, branch at `i` and send the request again.
+    /// To edit the user message at index `i`, branch at `i` and push the edited message.
+    pub fn branch_at(
+        &mut self,
+        name: impl Into<String>,
+        at: usize,
+    ) -> Result<(), ConversationError> {
+        let name = name.into();
+        if self.branches.contains_key(&name) {
+            return Err(ConversationError::BranchExists(name));
+        }
+        let messages = self.messages();
+        if at > messages.len() {
+            return Err(ConversationError::OutOfRange {
+                index: at,
+                len: messages.len(),
+            });
+        }
+        let prefix = messages[..at].to_vec();
+        // The prefix starts with the summary of the archived messages.
+        if at > 0 && !self.archived().is_empty() {
+            self.archives.insert(name.clone(), self.archived().to_vec());
+        }
+        self.branches.insert(name.clone(), prefix);
+        self.current = name;
+        Ok(())
+    }
+
+    /// Switch to an existing branch.
+    pub fn checkout(&mut self, name: &str) -> Result<(), ConversationError> {
+        if !self.branches.contains_key(name) {
+            return Err(ConversationError::UnknownBranch(name.to_string()));
+        }
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    /// Remove a branch other than the current one, returning its messages.
+    pub fn remove_branch(&mut self, name: &str) -> Result<Vec<Message>, ConversationError> {
+        if name == self.current {
+            return Err(ConversationError::CurrentBranch(name.to_string()));
+        }
+        self.archives.remove(name);
+        self.branches
+            .remove(name)
+            .ok_or_else(|| ConversationError::UnknownBranch(name.to_string()))
+    }
+
+    /// Number of leading messages the current branch shares with another branch.
+    pub fn common_prefix(&self, name: &str) -> Result<usize, ConversationError> {
+        let other = self
+            .branch(name)
+            .ok_or_else(|| ConversationError::UnknownBranch(name.to_string()))?;
+        Ok(self
+            .messages()
+            .iter()
+            .zip(other)
+            .take_while(|(a, b)| a == b)
+            .count())
+    }
+
+    /// Alternate messages at `index` across all branches, with the branch they come from.
+    ///
+    /// Branches holding the same message are reported once, under the first branch name.
+    pub fn alternatives(&self, index: usize) -> Vec<(&str, &Message)> {
+        let mut alternatives: Vec<(&str, &Message)> = Vec::new();
+        for (name, messages) in &self.branches {
+            if let Some(message) = messages.get(index) {
+                if !alternatives.iter().any(|(_, m)| *m == message) {
+                    alternatives.push((name, message));
+                }
+            }
+        }
+        alternatives
+    }
+
+    /// Adopt the continuation of another branch into the current one.
+    ///
+    /// Both branches must only diverge in assistant turns (e.g. regenerated answers):
+    /// the divergent assistant turns of the current branch are replaced by those of
+    /// `name`, followed by the rest of its history. If the current branch diverges in a
+    /// user turn, [`ConversationError::Conflict`] is returned and nothing changes.
+    pub fn merge(&mut self, name: &str) -> Result<(), ConversationError> {
+        let shared = self.common_prefix(name)?;
+        let divergent = &self.messages()[shared..];
+        if let Some(offset) = divergent
+            .iter()
+            .position(|message| matches!(message, Message::User(_)))
+        {
+            return Err(ConversationError::Conflict(shared + offset));
+        }
+
+        let tail = self.branches[name][shared..].to_vec();
+        let current = self.current_mut();
+        current.truncate(shared);
+        current.extend(tail);
+        Ok(())
+    }
+
+    fn current_mut(&mut self) -> &mut Vec<Message> {
+        self.branches
+            .get_mut(&self.current)
+            .expect("current branch always exists")
+    }
+}
+
+impl From<Vec<Message>> for Conversation {
+    fn from(messages: Vec<Message>) -> Self {
+        Self::new(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Extensions, Part, TextAnnotations};
+
+    fn user(text: &str) -> Message {
+        Message::User(vec![Part::Text {
+            content: text.into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }])
+    }
+
+    fn assistant(text: &str) -> Message {
+        Message::Assistant(vec![Part::Text {
+            content: text.into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }])
+    }
+
+    #[test]
+    fn test_regenerate_on_branch() {
+        let mut conversation = Conversation::new(vec![user("Hi"), assistant("Hello")]);
+
+        conversation.branch_at("retry", 1).unwrap();
+        conversation.push(assistant("Hey"));
+
+        assert_eq!(conversation.current_branch(), "retry");
+        assert_eq!(conversation.messages(), &[user("Hi"), assistant("Hey")]);
+        assert_eq!(conversation.common_prefix(MAIN_BRANCH).unwrap(), 1);
+        assert_eq!(
+            conversation.alternatives(1),
+            vec![("main", &assistant("Hello")), ("retry", &assistant("Hey"))]
+        );
+
+        conversation.checkout(MAIN_BRANCH).unwrap();
+        assert_eq!(conversation.messages(), &[user("Hi"), assistant("Hello")]);
+    }
+
+    #[test]
+    fn test_merge_divergent_assistant_turns() {
+        let mut conversation = Conversation::new(vec![user("Hi"), assistant("Hello")]);
+        conversation.branch_at("retry", 1).unwrap();
+        conversation.push(assistant("Hey"));
+        conversation.push(user("How are you?"));
+
+        conversation.checkout(MAIN_BRANCH).unwrap();
+        conversation.merge("retry").unwrap();
+
+        assert_eq!(
+            conversation.messages(),
+            &[user("Hi"), assistant("Hey"), user("How are you?")]
+        );
+    }
+
+    #[test]
+    fn test_merge_rejects_divergent_user_turns() {
+        let mut conversation = Conversation::new(vec![user("Hi")]);
+        conversation.branch_at("edit", 0).unwrap();
+        conversation.push(user("Hello"));
+
+        conversation.checkout(MAIN_BRANCH).unwrap();
+
+        assert_eq!(
+            conversation.merge("edit"),
+            Err(ConversationError::Conflict(0))
+        );
+        assert_eq!(conversation.messages(), &[user("Hi")]);
+    }
+
+    #[test]
+    fn test_branch_errors() {
+        let mut conversation = Conversation::default();
+
+        assert_eq!(
+            conversation.branch_at(MAIN_BRANCH, 0),
+            Err(ConversationError::BranchExists(MAIN_BRANCH.to_string()))
+        );
+        assert_eq!(
+            conversation.branch_at("other", 1),
+            Err(ConversationError::OutOfRange { index: 1, len: 0 })
+        );
+        assert_eq!(
+            conversation.remove_branch(MAIN_BRANCH),
+            Err(ConversationError::CurrentBranch(MAIN_BRANCH.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compress_rewrites_current_branch_only() {
+        let mut conversation = Conversation::new(vec![
+            user("Hi. I need help. It is urgent."),
+            assistant("Sure."),
+        ]);
+        conversation.branch_at("full", 2).unwrap();
+        conversation.checkout(MAIN_BRANCH).unwrap();
+
+        let dropper = crate::compress::SentenceDropper::new(0).with_keep_recent(1);
+        let report = conversation.compress(&dropper).await.unwrap();
+
+        assert!(report.saved() > 0);
+        assert_eq!(conversation.messages()[0], user("Hi. "));
+        assert_eq!(
+            conversation.branch("full").unwrap()[0],
+            user("Hi. I need help. It is urgent.")
+        );
+    }
+
+    struct Summarizer {
+        options: crate::options::ModelOptions<()>,
+        transport: crate::options::TransportOptions,
+    }
+
+    #[async_trait::async_trait]
+    impl Client for Summarizer {
+        type ModelProvider = ();
+
+        async fn request(
+            &self,
+            _: Vec<Message>,
+            _: Vec<rmcp::model::Tool>,
+        ) -> Result<Response, ClientError> {
+            Ok(Response {
+                data: vec![Message::assistant("The user said hi twice.")],
+                usage: Default::default(),
+                finish: crate::model::FinishReason::Stop,
+                stop_sequence: None,
+                service_tier: None,
+            })
+        }
+
+        fn model_options(&self) -> &crate::options::ModelOptions<()> {
+            &self.options
+        }
+
+        fn transport_options(&self) -> &crate::options::TransportOptions {
+            &self.transport
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_archives_summarized_messages() {
+        let history = vec![
+            user("Hi there, how are you doing today?"),
+            assistant("Hello! I am fine."),
+            user("Hi again."),
+            assistant("Hello again."),
+        ];
+        let mut conversation = Conversation::new(history.clone());
+        let compactor = Compactor::new(
+            Summarizer {
+                options: crate::options::ModelOptions::new("small"),
+                transport: Default::default(),
+            },
+            10,
+        )
+        .with_keep_recent(2);
+
+        let report = conversation.compact(&compactor).await.unwrap().unwrap();
+
+        // The summary is merged into the following user turn.
+        assert_eq!(
+            conversation.messages()[0].content().as_deref(),
+            Some("Summary of the earlier conversation:\nThe user said hi twice.\nHi again.")
+        );
+        assert_eq!(conversation.messages()[1], history[3]);
+        assert_eq!(conversation.archived(), &history[..2]);
+        assert_eq!(report.tokens_before, compress::estimate_tokens(&history));
+
+        let compactor = Compactor::new(
+            Summarizer {
+                options: crate::options::ModelOptions::new("small"),
+                transport: Default::default(),
+            },
+            1000,
+        );
+        assert_eq!(conversation.compact(&compactor).await.unwrap(), None);
+    }
+}
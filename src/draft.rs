@@ -0,0 +1,183 @@
+//! Crash-safe persistence of partially streamed responses.
+//!
+//! [`ResponseStreamExt::drafted`](crate::stream::ResponseStreamExt::drafted) saves
+//! every snapshot of a stream as the draft of a response in a [`DraftStore`]. Once the
+//! application has stored the final response, it calls [`DraftStore::commit`] to drop
+//! the draft. Drafts still [`pending`](DraftStore::pending) after a restart are
+//! answers that were interrupted, and can be restored with [`DraftStore::load`].
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::client::ClientError;
+use crate::model::Response;
+
+/// Storage for the drafts of responses being streamed, by id.
+#[async_trait]
+pub trait DraftStore: Send + Sync {
+    /// Save `draft` as the latest snapshot of the response `id`.
+    async fn save(&self, id: &str, draft: &Response) -> Result<(), ClientError>;
+
+    /// Mark the response `id` committed, discarding its draft.
+    async fn commit(&self, id: &str) -> Result<(), ClientError>;
+
+    /// The draft of the response `id`, unless it was committed.
+    async fn load(&self, id: &str) -> Result<Option<Response>, ClientError>;
+
+    /// Ids of the drafts that were never committed, in order.
+    async fn pending(&self) -> Result<Vec<String>, ClientError>;
+}
+
+/// Draft store keeping drafts in memory, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryDraftStore {
+    drafts: Mutex<BTreeMap<String, Response>>,
+}
+
+impl InMemoryDraftStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DraftStore for InMemoryDraftStore {
+    async fn save(&self, id: &str, draft: &Response) -> Result<(), ClientError> {
+        let mut drafts = self.drafts.lock().unwrap();
+        drafts.insert(id.to_string(), draft.clone());
+        Ok(())
+    }
+
+    async fn commit(&self, id: &str) -> Result<(), ClientError> {
+        self.drafts.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Response>, ClientError> {
+        Ok(self.drafts.lock().unwrap().get(id).cloned())
+    }
+
+    async fn pending(&self) -> Result<Vec<String>, ClientError> {
+        Ok(self.drafts.lock().unwrap().keys().cloned().collect())
+    }
+}
+
+/// Draft store writing each draft as a JSON file in a directory.
+///
+/// Drafts are written to a temporary file first and renamed into place, so a crash
+/// while saving leaves the previous snapshot intact. Ids are used as file names and
+/// may only contain ASCII letters, digits, `-` and `_`.
+#[derive(Debug, Clone)]
+pub struct FileDraftStore {
+    dir: PathBuf,
+}
+
+impl FileDraftStore {
+    /// Store drafts in `dir`, which is created if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, ClientError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, ClientError> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ClientError::Config(format!("Invalid draft id {:?}", id)));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+#[async_trait]
+impl DraftStore for FileDraftStore {
+    async fn save(&self, id: &str, draft: &Response) -> Result<(), ClientError> {
+        let path = self.path(id)?;
+        let temp = path.with_extension("json.tmp");
+        tokio::fs::write(&temp, serde_json::to_vec(draft)?).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        Ok(())
+    }
+
+    async fn commit(&self, id: &str) -> Result<(), ClientError> {
+        match tokio::fs::remove_file(self.path(id)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Response>, ClientError> {
+        match tokio::fs::read(self.path(id)?).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn pending(&self) -> Result<Vec<String>, ClientError> {
+        let mut ids = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FinishReason;
+    use crate::stream::ResponseStreamExt;
+    use futures::StreamExt;
+    use std::sync::Arc;
+
+    fn snapshot(text: &str, finish: FinishReason) -> Result<Response, ClientError> {
+        Ok(crate::test_fixtures::snapshot(text, finish))
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_stream_leaves_a_draft() {
+        let dir = std::env::temp_dir().join(format!("unia-drafts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = Arc::new(FileDraftStore::new(&dir).unwrap());
+
+        let stream = futures::stream::iter(vec![
+            snapshot("Hel", FinishReason::Unfinished),
+            snapshot("Hello", FinishReason::Unfinished),
+            snapshot("Hello there", FinishReason::Stop),
+        ]);
+        let mut stream = stream.drafted(store.clone(), "reply-1");
+        stream.next().await.unwrap().unwrap();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+
+        assert_eq!(store.pending().await.unwrap(), vec!["reply-1"]);
+        let draft = store.load("reply-1").await.unwrap().unwrap();
+        assert_eq!(draft.data[0].content().as_deref(), Some("Hello"));
+
+        store.commit("reply-1").await.unwrap();
+        assert!(store.pending().await.unwrap().is_empty());
+        assert!(store.load("reply-1").await.unwrap().is_none());
+        assert!(store.save("../escape", &draft).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+//! Text embeddings.
+//!
+//! An [`Embedder`] maps texts to vectors whose cosine similarity reflects how close
+//! their meanings are. It backs the [`SemanticCache`](crate::cache::SemanticCache).
+//! An implementation for OpenAI-compatible `/embeddings` endpoints lives in
+//! [`api::embed`](crate::api::embed).
+
+use async_trait::async_trait;
+
+use crate::client::ClientError;
+
+/// Trait for services that embed texts into vectors.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Embed `inputs`, returning one vector per input in the same order.
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ClientError>;
+}
+
+/// Cosine similarity of two vectors, in `[-1, 1]`.
+///
+/// Returns 0 for vectors of different lengths or with a zero norm.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
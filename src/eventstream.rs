@@ -0,0 +1,290 @@
+//! AWS event stream decoding.
+//!
+//! Amazon Bedrock streams responses in the binary `application/vnd.amazon.eventstream`
+//! format instead of Server-Sent Events. Each message is framed as:
+//!
+//! ```text
+//! total length (u32) | headers length (u32) | prelude crc (u32)
+//! headers | payload | message crc (u32)
+//! ```
+//!
+//! Model output arrives in `chunk` events whose JSON payload carries the provider's
+//! own event, base64-encoded, in a `bytes` field. [`EventStreamResponseExt::chunks`]
+//! yields those inner events, so they can be fed to the same parsers as SSE data.
+
+use base64::prelude::*;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use crate::client::ClientError;
+
+/// Size of the prelude and the trailing message checksum.
+const FRAME_OVERHEAD: usize = 16;
+
+/// A decoded event stream message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventMessage {
+    /// String-valued headers, such as `:message-type` and `:event-type`.
+    pub headers: HashMap<String, String>,
+    pub payload: Bytes,
+}
+
+impl EventMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Extension trait for `reqwest::Response` to decode AWS event streams.
+pub trait EventStreamResponseExt {
+    /// Convert the response into a stream of the decoded payloads of `chunk` events.
+    ///
+    /// Exception messages end the stream with a [`ClientError::ProviderError`].
+    fn chunks(self) -> impl Stream<Item = Result<String, ClientError>> + Send;
+}
+
+impl EventStreamResponseExt for reqwest::Response {
+    fn chunks(self) -> impl Stream<Item = Result<String, ClientError>> + Send {
+        let state = Decoder {
+            bytes: Box::pin(self.bytes_stream()),
+            buffer: BytesMut::new(),
+            done: false,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+            let chunk = state.next_chunk().await;
+            if !matches!(chunk, Some(Ok(_))) {
+                state.done = true;
+            }
+            Some((chunk?, state))
+        })
+    }
+}
+
+type ByteStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+struct Decoder {
+    bytes: ByteStream,
+    buffer: BytesMut,
+    done: bool,
+}
+
+impl Decoder {
+    /// Next chunk payload, or `None` once the stream ends.
+    async fn next_chunk(&mut self) -> Option<Result<String, ClientError>> {
+        loop {
+            match decode_message(&mut self.buffer) {
+                Ok(Some(message)) => {
+                    if let Some(chunk) = chunk_payload(message) {
+                        return Some(chunk);
+                    }
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+
+            match self.bytes.next().await {
+                Some(Ok(bytes)) => self.buffer.extend_from_slice(&bytes),
+                Some(Err(e)) => return Some(Err(e.into())),
+                None if self.buffer.is_empty() => return None,
+                None => {
+                    return Some(Err(ClientError::ProviderError(
+                        "Event stream ended in the middle of a message".to_string(),
+                    )))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChunkPayload {
+    bytes: String,
+}
+
+#[derive(Deserialize)]
+struct ExceptionPayload {
+    #[serde(alias = "Message")]
+    message: Option<String>,
+}
+
+/// Inner event of a `chunk` message, an error for exceptions, and `None` for any
+/// other event.
+fn chunk_payload(message: EventMessage) -> Option<Result<String, ClientError>> {
+    match message.header(":message-type") {
+        Some("exception") | Some("error") => {
+            let kind = message
+                .header(":exception-type")
+                .or_else(|| message.header(":error-code"))
+                .unwrap_or("unknown");
+            let detail = serde_json::from_slice::<ExceptionPayload>(&message.payload)
+                .ok()
+                .and_then(|payload| payload.message)
+                .unwrap_or_else(|| String::from_utf8_lossy(&message.payload).into_owned());
+            Some(Err(ClientError::ProviderError(format!(
+                "Bedrock {}: {}",
+                kind, detail
+            ))))
+        }
+        _ if message.header(":event-type") == Some("chunk") => Some(
+            serde_json::from_slice::<ChunkPayload>(&message.payload)
+                .map_err(ClientError::from)
+                .and_then(|chunk| {
+                    BASE64_STANDARD.decode(chunk.bytes).map_err(|e| {
+                        ClientError::ProviderError(format!("Invalid event stream chunk: {}", e))
+                    })
+                })
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        ),
+        _ => None,
+    }
+}
+
+/// Decode the message at the start of `buffer`, or return `None` if it is incomplete.
+///
+/// Checksums are not verified; the transport already guarantees integrity.
+pub fn decode_message(buffer: &mut BytesMut) -> Result<Option<EventMessage>, ClientError> {
+    if buffer.len() < 12 {
+        return Ok(None);
+    }
+    let total_len = u32::from_be_bytes(buffer[0..4].try_into().unwrap()) as usize;
+    let headers_len = u32::from_be_bytes(buffer[4..8].try_into().unwrap()) as usize;
+    if total_len < FRAME_OVERHEAD + headers_len {
+        return Err(ClientError::ProviderError(format!(
+            "Invalid event stream message length {}",
+            total_len
+        )));
+    }
+    if buffer.len() < total_len {
+        return Ok(None);
+    }
+
+    let mut message = buffer.split_to(total_len).freeze();
+    message.advance(12);
+    let mut headers_bytes = message.split_to(headers_len);
+    let payload = message.split_to(total_len - FRAME_OVERHEAD - headers_len);
+
+    let mut headers = HashMap::new();
+    while headers_bytes.has_remaining() {
+        let (name, value) = decode_header(&mut headers_bytes)?;
+        if let Some(value) = value {
+            headers.insert(name, value);
+        }
+    }
+
+    Ok(Some(EventMessage { headers, payload }))
+}
+
+/// Decode one header, keeping the value only if it is a string.
+fn decode_header(bytes: &mut Bytes) -> Result<(String, Option<String>), ClientError> {
+    let truncated = || ClientError::ProviderError("Truncated event stream header".to_string());
+
+    let name_len = usize::from(*bytes.first().ok_or_else(truncated)?);
+    if bytes.len() < 2 + name_len {
+        return Err(truncated());
+    }
+    bytes.advance(1);
+    let name = String::from_utf8_lossy(&bytes.split_to(name_len)).into_owned();
+    let value_type = bytes.get_u8();
+
+    let fixed_len = match value_type {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        4 => 4,
+        5 | 8 => 8,
+        9 => 16,
+        6 | 7 => {
+            if bytes.len() < 2 {
+                return Err(truncated());
+            }
+            let len = usize::from(bytes.get_u16());
+            if bytes.len() < len {
+                return Err(truncated());
+            }
+            let value = bytes.split_to(len);
+            let value = (value_type == 7).then(|| String::from_utf8_lossy(&value).into_owned());
+            return Ok((name, value));
+        }
+        other => {
+            return Err(ClientError::ProviderError(format!(
+                "Unknown event stream header type {}",
+                other
+            )))
+        }
+    };
+    if bytes.len() < fixed_len {
+        return Err(truncated());
+    }
+    bytes.advance(fixed_len);
+    Ok((name, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a message with string headers and zeroed checksums.
+    fn encode(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut encoded_headers = Vec::new();
+        for (name, value) in headers {
+            encoded_headers.push(name.len() as u8);
+            encoded_headers.extend_from_slice(name.as_bytes());
+            encoded_headers.push(7);
+            encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            encoded_headers.extend_from_slice(value.as_bytes());
+        }
+
+        let total_len = FRAME_OVERHEAD + encoded_headers.len() + payload.len();
+        let mut message = Vec::new();
+        message.extend_from_slice(&(total_len as u32).to_be_bytes());
+        message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&encoded_headers);
+        message.extend_from_slice(payload);
+        message.extend_from_slice(&[0; 4]);
+        message
+    }
+
+    #[test]
+    fn test_decode_chunk_messages() {
+        let inner = r#"{"type":"message_stop"}"#;
+        let payload = format!(r#"{{"bytes":"{}"}}"#, BASE64_STANDARD.encode(inner));
+        let headers = [(":message-type", "event"), (":event-type", "chunk")];
+        let encoded = encode(&headers, payload.as_bytes());
+
+        let mut buffer = BytesMut::from(&encoded[..10]);
+        assert!(decode_message(&mut buffer).unwrap().is_none());
+
+        buffer.extend_from_slice(&encoded[10..]);
+        buffer.extend_from_slice(&encoded[..3]);
+        let message = decode_message(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.header(":event-type"), Some("chunk"));
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(chunk_payload(message).unwrap().unwrap(), inner);
+    }
+
+    #[test]
+    fn test_exceptions_become_errors() {
+        let headers = [
+            (":message-type", "exception"),
+            (":exception-type", "throttlingException"),
+        ];
+        let mut buffer = BytesMut::from(&encode(&headers, br#"{"message":"Slow down"}"#)[..]);
+        let message = decode_message(&mut buffer).unwrap().unwrap();
+
+        match chunk_payload(message) {
+            Some(Err(ClientError::ProviderError(message))) => {
+                assert_eq!(message, "Bedrock throttlingException: Slow down")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Export conversations as fine-tuning datasets.
+//!
+//! Conversations collected through a [`Client`](crate::client::Client) or an
+//! [`Agent`](crate::agent::Agent), including tool calls and their results, can be
+//! written as JSONL training files for OpenAI fine-tuning or in the Anthropic
+//! Messages layout. Messages are rendered with the same request builders the
+//! clients use, so exported data matches what the models see at inference time.
+
+use rmcp::model::Tool;
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use serde_json::Value;
+
+#[cfg(feature = "anthropic")]
+use crate::api::anthropic;
+#[cfg(feature = "openai")]
+use crate::api::openai;
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use crate::client::ClientError;
+use crate::model::Message;
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use crate::options::ModelOptions;
+#[cfg(feature = "anthropic")]
+use crate::providers::AnthropicModel;
+#[cfg(feature = "openai")]
+use crate::providers::OpenAIModel;
+
+/// A single conversation to be exported as a training example.
+#[derive(Debug, Clone, Default)]
+pub struct TrainingExample {
+    /// The conversation, including function calls and function responses.
+    pub messages: Vec<Message>,
+    /// System prompt the conversation was held with.
+    pub system: Option<String>,
+    /// Tools that were available to the model.
+    pub tools: Vec<Tool>,
+}
+
+impl TrainingExample {
+    /// Create an example from a conversation.
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Set the system prompt.
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Set the available tools.
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Render the example in the OpenAI chat fine-tuning format.
+    #[cfg(feature = "openai")]
+    pub fn to_openai(&self) -> Result<Value, ClientError> {
+        openai::training_example(
+            self.messages.clone(),
+            &self.model_options::<OpenAIModel>(),
+            self.tools.clone(),
+        )
+    }
+
+    /// Render the example in the Anthropic Messages layout.
+    #[cfg(feature = "anthropic")]
+    pub fn to_anthropic(&self) -> Result<Value, ClientError> {
+        anthropic::training_example(
+            self.messages.clone(),
+            &self.model_options::<AnthropicModel>(),
+            self.tools.clone(),
+        )
+    }
+
+    #[cfg(any(feature = "openai", feature = "anthropic"))]
+    fn model_options<T: Default>(&self) -> ModelOptions<T> {
+        let mut options = ModelOptions::new(String::new());
+        options.system = self.system.clone();
+        options.anchor_media = Some(false);
+        options
+    }
+}
+
+/// Write examples as OpenAI fine-tuning JSONL, one example per line.
+#[cfg(feature = "openai")]
+pub fn to_openai_jsonl(examples: &[TrainingExample]) -> Result<String, ClientError> {
+    to_jsonl(examples, TrainingExample::to_openai)
+}
+
+/// Write examples as Anthropic Messages JSONL, one example per line.
+#[cfg(feature = "anthropic")]
+pub fn to_anthropic_jsonl(examples: &[TrainingExample]) -> Result<String, ClientError> {
+    to_jsonl(examples, TrainingExample::to_anthropic)
+}
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+fn to_jsonl(
+    examples: &[TrainingExample],
+    render: impl Fn(&TrainingExample) -> Result<Value, ClientError>,
+) -> Result<String, ClientError> {
+    let mut output = String::new();
+    for example in examples {
+        output.push_str(&serde_json::to_string(&render(example)?)?);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(all(test, any(feature = "openai", feature = "anthropic")))]
+mod tests {
+    use super::*;
+    use crate::model::{Extensions, Part, TextAnnotations};
+    use serde_json::json;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::User(vec![Part::Text {
+                content: "Weather in Paris?".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }]),
+            Message::Assistant(vec![Part::FunctionCall {
+                id: Some("call_1".to_string()),
+                name: "get_weather".to_string(),
+                arguments: json!({ "city": "Paris" }),
+                signature: None,
+                repaired: false,
+                extensions: Extensions::new(),
+                finished: true,
+            }]),
+            Message::User(vec![Part::FunctionResponse {
+                id: Some("call_1".to_string()),
+                name: "get_weather".to_string(),
+                response: json!({ "temp": 21 }),
+                parts: vec![],
+                error: None,
+                finished: true,
+            }]),
+            Message::Assistant(vec![Part::Text {
+                content: "It is 21°C.".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }]),
+        ]
+    }
+
+    #[test]
+    #[cfg(feature = "openai")]
+    fn test_openai_export() {
+        let example = TrainingExample::new(conversation()).with_system("Be brief.");
+        let value = example.to_openai().unwrap();
+
+        let messages = value["messages"].as_array().unwrap();
+        let roles: Vec<&str> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            roles,
+            vec!["system", "user", "assistant", "tool", "assistant"]
+        );
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"],
+            json!(r#"{"city":"Paris"}"#)
+        );
+        assert_eq!(messages[3]["tool_call_id"], json!("call_1"));
+        assert!(value.get("model").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "anthropic")]
+    fn test_anthropic_export() {
+        let example = TrainingExample::new(conversation()).with_system("Be brief.");
+        let value = example.to_anthropic().unwrap();
+
+        assert_eq!(value["system"][0]["text"], json!("Be brief."));
+        assert_eq!(
+            value["messages"][1]["content"][0]["type"],
+            json!("tool_use")
+        );
+        assert_eq!(
+            value["messages"][2]["content"][0]["tool_use_id"],
+            json!("call_1")
+        );
+        assert!(value.get("max_tokens").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "openai")]
+    fn test_jsonl_has_one_line_per_example() {
+        let examples = vec![
+            TrainingExample::new(conversation()),
+            TrainingExample::new(conversation()),
+        ];
+
+        let jsonl = to_openai_jsonl(&examples).unwrap();
+
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(jsonl.ends_with('\n'));
+    }
+}
@@ -0,0 +1,534 @@
+//! Tools running selected operations of a GraphQL API.
+//!
+//! Each named query or mutation added to [`GraphQlTools`] becomes a [`Tool`] whose
+//! arguments are the variables of the operation. Argument schemas are derived from the
+//! variable types; with an introspection result, input objects and enums are expanded
+//! too. Calls post the operation with the arguments as variables, so the model can
+//! only run the operations it was given.
+
+use async_trait::async_trait;
+use regex::Regex;
+use rmcp::model::{GetPromptResult, Prompt, ReadResourceResult, Resource, Tool};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
+
+use crate::http::build_http_client;
+use crate::mcp::{MCPError, MCPServer, Servable, Served};
+use crate::model::Part;
+use crate::openapi::{send, tool_name, OpenApiAuth};
+use crate::options::TransportOptions;
+use crate::tools::{ToolError, ToolErrorKind, ToolService};
+
+/// Maximum nesting of input objects expanded into argument schemas, which bounds
+/// recursive input types.
+const MAX_TYPE_DEPTH: usize = 8;
+
+/// Errors returned while configuring GraphQL tools.
+#[derive(Error, Debug)]
+pub enum GraphQlError {
+    #[error("Invalid GraphQL operation: {0}")]
+    Operation(String),
+
+    #[error("Invalid introspection result: {0}")]
+    Schema(String),
+
+    #[error("HTTP client error: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// Type of a variable, e.g. `[ID!]!`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>),
+}
+
+impl TypeRef {
+    /// Parse a type written in an operation.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(inner) = text.strip_suffix('!') {
+            return Some(Self::NonNull(Box::new(Self::parse(inner)?)));
+        }
+        if let Some(inner) = text.strip_prefix('[') {
+            return Some(Self::List(Box::new(Self::parse(inner.strip_suffix(']')?)?)));
+        }
+        let valid = !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        valid.then(|| Self::Named(text.to_string()))
+    }
+
+    /// Read a type reference of an introspection result.
+    fn from_introspection(value: &Value) -> Option<Self> {
+        let of_type = || Self::from_introspection(value.get("ofType")?);
+        match value.get("kind")?.as_str()? {
+            "NON_NULL" => Some(Self::NonNull(Box::new(of_type()?))),
+            "LIST" => Some(Self::List(Box::new(of_type()?))),
+            _ => Some(Self::Named(value.get("name")?.as_str()?.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    name: String,
+    type_ref: TypeRef,
+    has_default: bool,
+}
+
+/// A named operation exposed as a tool.
+#[derive(Debug, Clone)]
+struct Operation {
+    name: String,
+    tool_name: String,
+    description: String,
+    document: String,
+    variables: Vec<Variable>,
+}
+
+impl Operation {
+    fn parse(document: &str, description: &str) -> Result<Self, GraphQlError> {
+        static HEADER: OnceLock<Regex> = OnceLock::new();
+        let header = HEADER.get_or_init(|| {
+            Regex::new(r"^\s*(query|mutation)\s+([_A-Za-z][_0-9A-Za-z]*)\s*(\()?").unwrap()
+        });
+        let captures = header.captures(document).ok_or_else(|| {
+            GraphQlError::Operation("expected a named query or mutation".to_string())
+        })?;
+        let name = captures[2].to_string();
+
+        let variables = match captures.get(3) {
+            Some(open) => {
+                let definitions = balanced(&document[open.end()..]).ok_or_else(|| {
+                    GraphQlError::Operation(format!("unclosed variables of {}", name))
+                })?;
+                parse_variables(definitions).ok_or_else(|| {
+                    GraphQlError::Operation(format!("invalid variables of {}", name))
+                })?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            tool_name: tool_name(&name),
+            name,
+            description: description.to_string(),
+            document: document.to_string(),
+            variables,
+        })
+    }
+}
+
+/// Text up to the parenthesis closing the one just before `text`.
+fn balanced(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => return Some(&text[..i]),
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse variable definitions such as `$id: ID!, $first: Int = 10`.
+fn parse_variables(definitions: &str) -> Option<Vec<Variable>> {
+    definitions
+        .split('$')
+        .skip(1)
+        .map(|definition| {
+            let (name, rest) = definition.split_once(':')?;
+            let rest = rest.trim_start();
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_[]!".contains(c)))
+                .unwrap_or(rest.len());
+            Some(Variable {
+                name: name.trim().to_string(),
+                type_ref: TypeRef::parse(&rest[..end])?,
+                has_default: rest[end..].trim_start().starts_with('='),
+            })
+        })
+        .collect()
+}
+
+/// Tools running named GraphQL operations against an endpoint.
+#[derive(Debug, Clone)]
+pub struct GraphQlTools {
+    endpoint: String,
+    operations: Vec<Operation>,
+    types: HashMap<String, Value>,
+    auth: Option<OpenApiAuth>,
+    http_client: reqwest::Client,
+}
+
+impl GraphQlTools {
+    pub fn new(endpoint: impl Into<String>) -> Result<Self, GraphQlError> {
+        Ok(Self {
+            endpoint: endpoint.into(),
+            operations: Vec::new(),
+            types: HashMap::new(),
+            auth: None,
+            http_client: build_http_client(&TransportOptions::default())?,
+        })
+    }
+
+    /// Expose the named query or mutation in `document` as a tool.
+    pub fn with_operation(
+        mut self,
+        document: impl AsRef<str>,
+        description: impl AsRef<str>,
+    ) -> Result<Self, GraphQlError> {
+        let operation = Operation::parse(document.as_ref(), description.as_ref())?;
+        self.operations.push(operation);
+        Ok(self)
+    }
+
+    /// Expand input objects and enums using the result of an introspection query,
+    /// with or without its `data` envelope.
+    pub fn with_schema(mut self, introspection: &Value) -> Result<Self, GraphQlError> {
+        let schema = introspection
+            .pointer("/data/__schema")
+            .or_else(|| introspection.get("__schema"))
+            .ok_or_else(|| GraphQlError::Schema("missing __schema".to_string()))?;
+        let types = schema
+            .get("types")
+            .and_then(Value::as_array)
+            .ok_or_else(|| GraphQlError::Schema("missing types".to_string()))?;
+        self.types = types
+            .iter()
+            .filter_map(|t| Some((t.get("name")?.as_str()?.to_string(), t.clone())))
+            .collect();
+        Ok(self)
+    }
+
+    /// Send credentials with every call. Query credentials are added to the endpoint.
+    pub fn with_auth(mut self, auth: OpenApiAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Send calls with a client configured by `transport_options`.
+    pub fn with_transport_options(
+        mut self,
+        transport_options: &TransportOptions,
+    ) -> Result<Self, GraphQlError> {
+        self.http_client = build_http_client(transport_options)?;
+        Ok(self)
+    }
+
+    /// Tool definitions of the operations.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.operations
+            .iter()
+            .map(|operation| {
+                let mut properties = Map::new();
+                let mut required = Vec::new();
+                for variable in &operation.variables {
+                    properties.insert(variable.name.clone(), self.schema(&variable.type_ref, 0));
+                    if matches!(variable.type_ref, TypeRef::NonNull(_)) && !variable.has_default {
+                        required.push(Value::String(variable.name.clone()));
+                    }
+                }
+                let mut schema = Map::new();
+                schema.insert("type".to_string(), json!("object"));
+                schema.insert("properties".to_string(), Value::Object(properties));
+                if !required.is_empty() {
+                    schema.insert("required".to_string(), Value::Array(required));
+                }
+                Tool::new(
+                    operation.tool_name.clone(),
+                    operation.description.clone(),
+                    Arc::new(schema),
+                )
+            })
+            .collect()
+    }
+
+    /// JSON schema of values of a GraphQL type.
+    fn schema(&self, type_ref: &TypeRef, depth: usize) -> Value {
+        let name = match type_ref {
+            TypeRef::NonNull(inner) => return self.schema(inner, depth),
+            TypeRef::List(inner) => {
+                return json!({ "type": "array", "items": self.schema(inner, depth) })
+            }
+            TypeRef::Named(name) => name.as_str(),
+        };
+        match name {
+            "ID" | "String" => return json!({ "type": "string" }),
+            "Int" => return json!({ "type": "integer" }),
+            "Float" => return json!({ "type": "number" }),
+            "Boolean" => return json!({ "type": "boolean" }),
+            _ => {}
+        }
+
+        let Some(definition) = self.types.get(name).filter(|_| depth < MAX_TYPE_DEPTH) else {
+            return json!({});
+        };
+        let mut schema = match definition.get("kind").and_then(Value::as_str) {
+            Some("ENUM") => {
+                let values: Vec<Value> = definition
+                    .get("enumValues")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|value| value.get("name").cloned())
+                    .collect();
+                json!({ "type": "string", "enum": values })
+            }
+            Some("INPUT_OBJECT") => {
+                let mut properties = Map::new();
+                let mut required = Vec::new();
+                let fields = definition
+                    .get("inputFields")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten();
+                for field in fields {
+                    let (Some(field_name), Some(field_type)) = (
+                        field.get("name").and_then(Value::as_str),
+                        field.get("type").and_then(TypeRef::from_introspection),
+                    ) else {
+                        continue;
+                    };
+                    let mut field_schema = self.schema(&field_type, depth + 1);
+                    if let (Some(description), Value::Object(fields)) = (
+                        field.get("description").filter(|d| d.is_string()),
+                        &mut field_schema,
+                    ) {
+                        fields.insert("description".to_string(), description.clone());
+                    }
+                    properties.insert(field_name.to_string(), field_schema);
+                    let has_default = field.get("defaultValue").is_some_and(|d| !d.is_null());
+                    if matches!(field_type, TypeRef::NonNull(_)) && !has_default {
+                        required.push(Value::String(field_name.to_string()));
+                    }
+                }
+                let mut schema = json!({ "type": "object", "properties": properties });
+                if !required.is_empty() {
+                    schema["required"] = Value::Array(required);
+                }
+                schema
+            }
+            _ => json!({}),
+        };
+        if let Some(description) = definition.get("description").filter(|d| d.is_string()) {
+            schema["description"] = description.clone();
+        }
+        schema
+    }
+
+    /// HTTP request for a call of the tool `name`.
+    fn request(&self, name: &str, args: &Value) -> Result<reqwest::Request, ToolError> {
+        let operation = self
+            .operations
+            .iter()
+            .find(|operation| operation.tool_name == name)
+            .ok_or_else(|| {
+                ToolError::new(ToolErrorKind::NotFound, format!("Unknown tool {}", name))
+            })?;
+        let variables = match args {
+            Value::Object(_) => args.clone(),
+            Value::Null => json!({}),
+            _ => return Err(ToolError::invalid_arguments("Arguments must be an object")),
+        };
+
+        let body = json!({
+            "query": operation.document,
+            "operationName": operation.name,
+            "variables": variables,
+        });
+        let mut request = self.http_client.post(&self.endpoint).json(&body);
+        match &self.auth {
+            Some(OpenApiAuth::Bearer(token)) => request = request.bearer_auth(token),
+            Some(OpenApiAuth::Header { name, value }) => request = request.header(name, value),
+            Some(OpenApiAuth::Query { name, value }) => request = request.query(&[(name, value)]),
+            None => {}
+        }
+        request
+            .build()
+            .map_err(|e| ToolError::invalid_arguments(e.to_string()))
+    }
+
+    /// Execute a call, returning the `data` of the result.
+    ///
+    /// Results with both data and errors are returned whole, so that the model sees
+    /// the partial data along with the errors.
+    async fn execute(&self, name: &str, args: &Value) -> Result<Value, ToolError> {
+        let request = self.request(name, args)?;
+        let text = send(&self.http_client, request).await?;
+        let mut result: Value = serde_json::from_str(&text)
+            .map_err(|e| ToolError::execution(format!("Invalid GraphQL response: {}", e)))?;
+
+        let errors = result
+            .get("errors")
+            .and_then(Value::as_array)
+            .filter(|errors| !errors.is_empty());
+        let data = result.get("data").filter(|data| !data.is_null());
+        match (data, errors) {
+            (_, None) => Ok(result.get_mut("data").map(Value::take).unwrap_or_default()),
+            (Some(_), Some(_)) => Ok(result),
+            (None, Some(errors)) => {
+                let messages: Vec<&str> = errors
+                    .iter()
+                    .filter_map(|error| error.get("message")?.as_str())
+                    .collect();
+                Err(ToolError::execution(messages.join("; ")))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ToolService for GraphQlTools {
+    async fn list_tools(&self) -> Result<Vec<Tool>, ToolError> {
+        Ok(self.tools())
+    }
+
+    async fn call_tool(&self, name: String, args: Value) -> Result<Value, ToolError> {
+        self.execute(&name, &args).await
+    }
+}
+
+#[async_trait]
+impl MCPServer for GraphQlTools {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        Ok(self.tools().into_iter().map(|t| t.served(None)).collect())
+    }
+
+    async fn call_tool(
+        &self,
+        name: String,
+        args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        Ok(match self.execute(&name, &args).await {
+            Ok(response) => Part::FunctionResponse {
+                id: None,
+                name,
+                response,
+                parts: vec![],
+                error: None,
+                finished: true,
+            },
+            Err(error) => Part::function_error(None, name, error),
+        })
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn get_prompt(
+        &self,
+        prompt: &Served<Prompt>,
+        _args: Option<Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        Err(MCPError::PromptNotFound(prompt.value.name.clone()))
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn read_resource(
+        &self,
+        resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        Err(MCPError::ResourceNotFound(resource.value.uri.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH: &str = "query SearchUsers($filter: UserFilter!, $first: Int = 10, $ids: [ID!]) {
+        users(filter: $filter, first: $first, ids: $ids) { id name }
+    }";
+
+    fn introspection() -> Value {
+        json!({ "data": { "__schema": { "types": [
+            {
+                "kind": "INPUT_OBJECT",
+                "name": "UserFilter",
+                "inputFields": [
+                    { "name": "name", "type": { "kind": "SCALAR", "name": "String" } },
+                    { "name": "role", "description": "Role to match", "type": {
+                        "kind": "NON_NULL", "ofType": { "kind": "ENUM", "name": "Role" }
+                    } }
+                ]
+            },
+            {
+                "kind": "ENUM",
+                "name": "Role",
+                "enumValues": [{ "name": "ADMIN" }, { "name": "MEMBER" }]
+            }
+        ] } } })
+    }
+
+    #[test]
+    fn test_variables_become_arguments() {
+        let tools = GraphQlTools::new("https://api.example.com/graphql")
+            .unwrap()
+            .with_operation(SEARCH, "Search users")
+            .unwrap()
+            .with_schema(&introspection())
+            .unwrap()
+            .tools();
+
+        assert_eq!(tools[0].name, "SearchUsers");
+        let schema = Value::Object(tools[0].input_schema.as_ref().clone());
+        assert_eq!(schema["required"], json!(["filter"]));
+        assert_eq!(schema["properties"]["first"], json!({ "type": "integer" }));
+        assert_eq!(
+            schema["properties"]["ids"],
+            json!({ "type": "array", "items": { "type": "string" } })
+        );
+        assert_eq!(
+            schema["properties"]["filter"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "role": {
+                        "type": "string",
+                        "enum": ["ADMIN", "MEMBER"],
+                        "description": "Role to match"
+                    }
+                },
+                "required": ["role"]
+            })
+        );
+
+        assert!(GraphQlTools::new("https://api.example.com/graphql")
+            .unwrap()
+            .with_operation("{ users { id } }", "Anonymous")
+            .is_err());
+    }
+
+    #[test]
+    fn test_calls_post_the_operation() {
+        let tools = GraphQlTools::new("https://api.example.com/graphql")
+            .unwrap()
+            .with_operation(SEARCH, "Search users")
+            .unwrap()
+            .with_auth(OpenApiAuth::Header {
+                name: "X-Api-Key".to_string(),
+                value: "secret".to_string(),
+            });
+
+        let request = tools
+            .request("SearchUsers", &json!({ "filter": { "role": "ADMIN" } }))
+            .unwrap();
+        assert_eq!(request.headers()["x-api-key"], "secret");
+        let body: Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["operationName"], "SearchUsers");
+        assert_eq!(body["variables"], json!({ "filter": { "role": "ADMIN" } }));
+        assert_eq!(body["query"], SEARCH);
+    }
+}
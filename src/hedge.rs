@@ -0,0 +1,297 @@
+//! Hedged requests for the latency tail.
+//!
+//! A [`Hedged`] client sends each request to its primary client. If no token has
+//! arrived after a delay, the same request is also sent to a secondary client (another
+//! model, region or provider) and whichever answers first is used. The other request
+//! is dropped, which cancels it. Interactive apps trade the occasional duplicate
+//! request for a bounded wait on slow responses.
+
+use async_trait::async_trait;
+use futures::{stream, Future, Stream, StreamExt};
+use rmcp::model::Tool;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{FinishReason, Message, Response};
+use crate::options::{ModelOptions, TransportOptions};
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>;
+
+/// Client wrapper hedging slow requests with a secondary client.
+///
+/// Model and transport options are those of the primary client. A request that fails
+/// before the delay is not hedged; once both requests are in flight, a failure of one
+/// waits for the other.
+pub struct Hedged<P, S> {
+    primary: P,
+    secondary: S,
+    delay: Duration,
+}
+
+impl<P, S> Hedged<P, S> {
+    pub fn new(primary: P, secondary: S, delay: Duration) -> Self {
+        Self {
+            primary,
+            secondary,
+            delay,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Get a reference to the primary client.
+    pub fn inner(&self) -> &P {
+        &self.primary
+    }
+
+    /// Get a reference to the secondary client.
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+}
+
+/// Result of whichever of `primary` and `secondary` succeeds first, polling
+/// `secondary` only once `primary` has taken longer than `delay`.
+async fn race<T>(
+    primary: impl Future<Output = Result<T, ClientError>>,
+    secondary: impl Future<Output = Result<T, ClientError>>,
+    delay: Duration,
+) -> Result<T, ClientError> {
+    tokio::pin!(primary);
+    tokio::pin!(secondary);
+    if let Ok(result) = tokio::time::timeout(delay, &mut primary).await {
+        return result;
+    }
+
+    tracing::debug!("No response after {:?}, hedging the request", delay);
+    tokio::select! {
+        result = &mut primary => match result {
+            Ok(value) => Ok(value),
+            Err(_) => secondary.await,
+        },
+        result = &mut secondary => match result {
+            Ok(value) => Ok(value),
+            Err(_) => primary.await,
+        },
+    }
+}
+
+/// A stream whose first token has arrived, and the responses received until then.
+struct Started {
+    received: Vec<Result<Response, ClientError>>,
+    rest: ResponseStream,
+    ended: bool,
+}
+
+impl Started {
+    /// Wait for the first response with content, a finish reason or an error.
+    async fn wait(
+        request: impl Future<Output = Result<ResponseStream, ClientError>>,
+    ) -> Result<Self, ClientError> {
+        let mut rest = request.await?;
+        let mut received = Vec::new();
+        while let Some(item) = rest.next().await {
+            let started = item.as_ref().map_or(true, |response| {
+                response.finish != FinishReason::Unfinished
+                    || response.data.iter().any(|m| !m.parts().is_empty())
+            });
+            received.push(item);
+            if started {
+                return Ok(Self {
+                    received,
+                    rest,
+                    ended: false,
+                });
+            }
+        }
+        Ok(Self {
+            received,
+            rest,
+            ended: true,
+        })
+    }
+
+    fn into_stream(self) -> ResponseStream {
+        let received = stream::iter(self.received);
+        if self.ended {
+            Box::pin(received)
+        } else {
+            Box::pin(received.chain(self.rest))
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Client, S: Client> Client for Hedged<P, S> {
+    type ModelProvider = P::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let primary = self.primary.request(messages.clone(), tools.clone());
+        let secondary = self.secondary.request(messages, tools);
+        race(primary, secondary, self.delay).await
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.primary.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.primary.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.primary.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<P: StreamingClient, S: StreamingClient> StreamingClient for Hedged<P, S> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<ResponseStream, ClientError> {
+        let primary = Started::wait(self.primary.request_stream(messages.clone(), tools.clone()));
+        let secondary = Started::wait(self.secondary.request_stream(messages, tools));
+        let started = race(primary, secondary, self.delay).await?;
+        Ok(started.into_stream())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Delayed {
+        name: &'static str,
+        delay: Duration,
+        calls: AtomicUsize,
+        options: ModelOptions<()>,
+        transport: TransportOptions,
+    }
+
+    impl Delayed {
+        fn new(name: &'static str, delay_ms: u64) -> Self {
+            Self {
+                name,
+                delay: Duration::from_millis(delay_ms),
+                calls: AtomicUsize::new(0),
+                options: ModelOptions::new(name),
+                transport: TransportOptions::default(),
+            }
+        }
+
+        fn response(&self, finish: FinishReason) -> Response {
+            Response {
+                data: vec![Message::assistant(self.name)],
+                usage: Default::default(),
+                finish,
+                stop_sequence: None,
+                service_tier: None,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Client for Delayed {
+        type ModelProvider = ();
+
+        async fn request(&self, _: Vec<Message>, _: Vec<Tool>) -> Result<Response, ClientError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(self.response(FinishReason::Stop))
+        }
+
+        fn model_options(&self) -> &ModelOptions<()> {
+            &self.options
+        }
+
+        fn transport_options(&self) -> &TransportOptions {
+            &self.transport
+        }
+    }
+
+    #[async_trait]
+    impl StreamingClient for Delayed {
+        async fn request_stream(
+            &self,
+            _: Vec<Message>,
+            _: Vec<Tool>,
+        ) -> Result<ResponseStream, ClientError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let empty = Response {
+                data: vec![],
+                ..self.response(FinishReason::Unfinished)
+            };
+            let delay = self.delay;
+            let partial = self.response(FinishReason::Unfinished);
+            let last = self.response(FinishReason::Stop);
+            Ok(Box::pin(async_stream::stream! {
+                yield Ok(empty);
+                tokio::time::sleep(delay).await;
+                yield Ok(partial);
+                yield Ok(last);
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_primary_is_hedged() {
+        let client = Hedged::new(
+            Delayed::new("primary", 2000),
+            Delayed::new("secondary", 10),
+            Duration::from_millis(50),
+        );
+
+        let response = client
+            .request(vec![Message::user("Hi")], vec![])
+            .await
+            .unwrap();
+        assert_eq!(response.data[0].content().as_deref(), Some("secondary"));
+
+        let responses: Vec<_> = client
+            .request_stream(vec![Message::user("Hi")], vec![])
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(responses.len(), 3);
+        assert!(responses[0].as_ref().unwrap().data.is_empty());
+        let last = responses[2].as_ref().unwrap();
+        assert_eq!(last.data[0].content().as_deref(), Some("secondary"));
+    }
+
+    #[tokio::test]
+    async fn test_fast_primary_is_not_hedged() {
+        let client = Hedged::new(
+            Delayed::new("primary", 10),
+            Delayed::new("secondary", 10),
+            Duration::from_millis(500),
+        );
+
+        let response = client
+            .request(vec![Message::user("Hi")], vec![])
+            .await
+            .unwrap();
+        assert_eq!(response.data[0].content().as_deref(), Some("primary"));
+        let stream = client
+            .request_stream(vec![Message::user("Hi")], vec![])
+            .await
+            .unwrap();
+        assert_eq!(stream.count().await, 3);
+        assert_eq!(client.secondary().calls.load(Ordering::SeqCst), 0);
+    }
+}
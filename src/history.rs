@@ -0,0 +1,636 @@
+//! Conversation history utilities.
+//!
+//! Providers are strict about the shape of the conversation they accept: Anthropic
+//! requires alternating user/assistant turns, every provider rejects tool calls
+//! without a matching result, and empty assistant turns are refused outright.
+//! [`normalize_history`] rewrites a history so that it satisfies all of these
+//! constraints instead of surfacing them as opaque HTTP 400 errors.
+//!
+//! [`turns`] groups a flat history into [`Turn`]s, each holding a user prompt and the
+//! model's work on it: assistant messages together with the results of their tool
+//! calls. This is the shape UIs render and compaction cuts along;
+//! [`flatten_turns`] restores the original messages.
+//!
+//! [`rehydrate_for`] prepares a history for another provider, e.g. when switching
+//! models in the middle of a conversation.
+
+use std::collections::HashSet;
+use tracing::debug;
+
+use crate::catalog::ModelVendor;
+use crate::model::{Extensions, Message, Part, TextAnnotations};
+use crate::tools::ToolError;
+
+/// Placeholder text inserted into assistant turns that have no content.
+pub const EMPTY_ASSISTANT_PLACEHOLDER: &str = "(no content)";
+
+/// Normalize a conversation history so that it satisfies common provider constraints.
+///
+/// The following rules are applied, in order:
+/// 1. Empty user messages are dropped and empty assistant messages receive a
+///    placeholder text part.
+/// 2. Every function call is paired with a function response in the following user
+///    turn; missing responses are filled in with an error result.
+/// 3. Function responses that do not answer a preceding function call are converted
+///    into plain text so the information is kept without confusing the provider.
+/// 4. Consecutive messages with the same role are merged, producing strictly
+///    alternating turns.
+///
+/// Part order within each message is preserved.
+pub fn normalize_history(messages: Vec<Message>) -> Vec<Message> {
+    let mut output: Vec<Message> = Vec::with_capacity(messages.len());
+    let mut pending_calls: Vec<PendingCall> = Vec::new();
+
+    for message in messages {
+        match message {
+            Message::Assistant(mut parts) => {
+                flush_pending_calls(&mut output, &mut pending_calls);
+
+                if parts.is_empty() {
+                    debug!("Inserting placeholder into empty assistant message");
+                    parts.push(Part::Text {
+                        content: EMPTY_ASSISTANT_PLACEHOLDER.into(),
+                        signature: None,
+                        extensions: Extensions::new(),
+                        annotations: TextAnnotations::default(),
+                        finished: true,
+                    });
+                }
+
+                pending_calls.extend(parts.iter().filter_map(PendingCall::from_part));
+                push_merged(&mut output, Message::Assistant(parts));
+            }
+            Message::User(parts) => {
+                if parts.is_empty() {
+                    debug!("Dropping empty user message");
+                    continue;
+                }
+
+                let parts = parts
+                    .into_iter()
+                    .map(|part| match part {
+                        Part::FunctionResponse { .. } => {
+                            match pending_calls
+                                .iter()
+                                .position(|call| call.answered_by(&part))
+                            {
+                                Some(index) => {
+                                    pending_calls.remove(index);
+                                    part
+                                }
+                                None => orphaned_response_to_text(part),
+                            }
+                        }
+                        other => other,
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut message = Message::User(parts);
+                if !pending_calls.is_empty() {
+                    let missing = pending_calls.drain(..).map(PendingCall::into_response);
+                    message.parts_mut().splice(0..0, missing);
+                }
+                push_merged(&mut output, message);
+            }
+        }
+    }
+
+    flush_pending_calls(&mut output, &mut pending_calls);
+    output
+}
+
+/// Index of the first message of `original` that `normalized` does not carry over
+/// unchanged, or `None` if normalization kept the history as is.
+///
+/// Providers with prefix caching only reuse a cached prefix if it is sent byte for
+/// byte again, so earlier turns rewritten by [`normalize_history`] (such as function
+/// responses moved ahead of a user's text) can cost cache hits on later requests.
+pub fn first_rewritten(original: &[Message], normalized: &[Message]) -> Option<usize> {
+    original
+        .iter()
+        .zip(normalized)
+        .position(|(original, normalized)| original != normalized)
+        .or_else(|| {
+            (original.len() != normalized.len()).then(|| original.len().min(normalized.len()))
+        })
+}
+
+/// Rewrite a history produced by one provider so that another provider accepts it.
+///
+/// Switching models mid-conversation otherwise fails on provider-specific leftovers:
+/// 1. Reasoning parts are dropped and signatures are cleared from text and function
+///    calls, since they are opaque to any provider but the one that issued them.
+/// 2. Function call ids that `target` would reject (missing, too long or containing
+///    unsupported characters) are replaced with fresh ones, and the function
+///    responses answering them are updated to match.
+/// 3. The result is passed through [`normalize_history`].
+pub fn rehydrate_for(messages: Vec<Message>, target: ModelVendor) -> Vec<Message> {
+    let mut taken: HashSet<String> = messages
+        .iter()
+        .flat_map(|message| message.parts())
+        .filter_map(|part| match part {
+            Part::FunctionCall { id: Some(id), .. } => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut next_id = 0;
+    let mut remapped: Vec<(PendingCall, String)> = Vec::new();
+
+    let messages = messages
+        .into_iter()
+        .map(|message| match message {
+            Message::Assistant(parts) => {
+                remapped.clear();
+                let parts = parts
+                    .into_iter()
+                    .filter(|part| !matches!(part, Part::Reasoning { .. }))
+                    .map(|mut part| {
+                        clear_signature(&mut part);
+                        if let Part::FunctionCall { id, name, .. } = &mut part {
+                            if !id.as_deref().is_some_and(|id| valid_call_id(id, target)) {
+                                let fresh = loop {
+                                    next_id += 1;
+                                    let candidate = format!("call_{}", next_id);
+                                    if taken.insert(candidate.clone()) {
+                                        break candidate;
+                                    }
+                                };
+                                debug!("Re-mapping id of function call {} to {}", name, fresh);
+                                let original = PendingCall {
+                                    id: id.replace(fresh.clone()),
+                                    name: name.clone(),
+                                };
+                                remapped.push((original, fresh));
+                            }
+                        }
+                        part
+                    })
+                    .collect();
+                Message::Assistant(parts)
+            }
+            Message::User(mut parts) => {
+                for part in &mut parts {
+                    clear_signature(part);
+                    if let Some(index) =
+                        remapped.iter().position(|(call, _)| call.answered_by(part))
+                    {
+                        let (_, fresh) = remapped.remove(index);
+                        if let Part::FunctionResponse { id, .. } = part {
+                            *id = Some(fresh);
+                        }
+                    }
+                }
+                Message::User(parts)
+            }
+        })
+        .collect();
+
+    normalize_history(messages)
+}
+
+fn clear_signature(part: &mut Part) {
+    if let Part::Text { signature, .. } | Part::FunctionCall { signature, .. } = part {
+        *signature = None;
+    }
+}
+
+/// Whether `target` accepts `id` as a function call id.
+fn valid_call_id(id: &str, target: ModelVendor) -> bool {
+    match target {
+        ModelVendor::Anthropic => {
+            (1..=64).contains(&id.len())
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }
+        ModelVendor::OpenAI => (1..=40).contains(&id.len()),
+        ModelVendor::Google => true,
+    }
+}
+
+/// A user prompt followed by the model's work on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    /// Message starting the turn. `None` if the history starts with the assistant.
+    pub prompt: Option<Message>,
+    /// Assistant messages, each with the results of its tool calls, in order.
+    pub steps: Vec<Step>,
+}
+
+/// An assistant message and the tool results answering it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub assistant: Message,
+    /// User messages made only of function responses that follow the assistant message.
+    pub results: Vec<Message>,
+}
+
+impl Turn {
+    /// The last assistant message of the turn, usually its final answer.
+    pub fn answer(&self) -> Option<&Message> {
+        self.steps.last().map(|step| &step.assistant)
+    }
+
+    /// Number of messages of the turn.
+    pub fn len(&self) -> usize {
+        self.prompt.iter().count()
+            + self
+                .steps
+                .iter()
+                .map(|step| 1 + step.results.len())
+                .sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages of the turn, in their original order.
+    pub fn into_messages(self) -> Vec<Message> {
+        let mut messages: Vec<Message> = self.prompt.into_iter().collect();
+        for step in self.steps {
+            messages.push(step.assistant);
+            messages.extend(step.results);
+        }
+        messages
+    }
+}
+
+impl Step {
+    /// Function calls of the assistant message, each with its result if there is one.
+    ///
+    /// Results are matched by id, or by name for calls without an id.
+    pub fn calls(&self) -> Vec<(&Part, Option<&Part>)> {
+        let mut results: Vec<&Part> = self.results.iter().flat_map(|m| m.parts()).collect();
+        self.assistant
+            .parts()
+            .iter()
+            .filter_map(|part| {
+                let pending = PendingCall::from_part(part)?;
+                let result = results
+                    .iter()
+                    .position(|result| pending.answered_by(result))
+                    .map(|index| results.remove(index));
+                Some((part, result))
+            })
+            .collect()
+    }
+
+    /// Whether every function call of the assistant message has a result.
+    pub fn is_complete(&self) -> bool {
+        self.calls().iter().all(|(_, result)| result.is_some())
+    }
+}
+
+/// Whether `message` only carries tool results.
+fn is_results(message: &Message) -> bool {
+    matches!(message, Message::User(parts) if !parts.is_empty()
+        && parts.iter().all(|part| matches!(part, Part::FunctionResponse { .. })))
+}
+
+/// Group a history into turns.
+///
+/// A user message starts a new turn unless it only carries function responses and
+/// follows an assistant message, in which case it belongs to that step. No message is
+/// dropped or reordered: [`flatten_turns`] returns the original history.
+pub fn turns(messages: &[Message]) -> Vec<Turn> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for message in messages {
+        let current = turns.last_mut();
+        match (message, current) {
+            (Message::Assistant(_), Some(turn)) => turn.steps.push(Step {
+                assistant: message.clone(),
+                results: Vec::new(),
+            }),
+            (Message::Assistant(_), None) => turns.push(Turn {
+                prompt: None,
+                steps: vec![Step {
+                    assistant: message.clone(),
+                    results: Vec::new(),
+                }],
+            }),
+            (Message::User(_), Some(turn)) if is_results(message) && !turn.steps.is_empty() => {
+                let step = turn.steps.last_mut().expect("checked above");
+                step.results.push(message.clone());
+            }
+            (Message::User(_), _) => turns.push(Turn {
+                prompt: Some(message.clone()),
+                steps: Vec::new(),
+            }),
+        }
+    }
+    turns
+}
+
+/// Messages of `turns`, in order.
+pub fn flatten_turns(turns: impl IntoIterator<Item = Turn>) -> Vec<Message> {
+    turns.into_iter().flat_map(Turn::into_messages).collect()
+}
+
+/// Largest index at most `index` at which a prompt or an assistant message starts, so
+/// that splitting `messages` there keeps tool results with their calls.
+pub(crate) fn step_boundary(messages: &[Message], index: usize) -> usize {
+    if index >= messages.len() {
+        return messages.len();
+    }
+    let mut boundary = 0;
+    let mut offset = 0;
+    for turn in turns(messages) {
+        let starts = turn
+            .prompt
+            .iter()
+            .map(|_| 1)
+            .chain(turn.steps.iter().map(|step| 1 + step.results.len()));
+        for len in starts {
+            if offset > index {
+                return boundary;
+            }
+            boundary = offset;
+            offset += len;
+        }
+    }
+    boundary
+}
+
+/// A function call that has not been answered yet.
+struct PendingCall {
+    id: Option<String>,
+    name: String,
+}
+
+impl PendingCall {
+    fn from_part(part: &Part) -> Option<Self> {
+        match part {
+            Part::FunctionCall { id, name, .. } => Some(Self {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn answered_by(&self, part: &Part) -> bool {
+        match part {
+            Part::FunctionResponse { id, name, .. } => match (&self.id, id) {
+                (Some(call_id), Some(response_id)) => call_id == response_id,
+                _ => &self.name == name,
+            },
+            _ => false,
+        }
+    }
+
+    fn into_response(self) -> Part {
+        debug!("Inserting missing function response for {}", self.name);
+        Part::function_error(
+            self.id,
+            self.name,
+            ToolError::execution("Tool call was not executed"),
+        )
+    }
+}
+
+/// Answer every pending call in a dedicated user turn.
+fn flush_pending_calls(output: &mut Vec<Message>, pending_calls: &mut Vec<PendingCall>) {
+    if pending_calls.is_empty() {
+        return;
+    }
+    let responses = pending_calls
+        .drain(..)
+        .map(PendingCall::into_response)
+        .collect();
+    push_merged(output, Message::User(responses));
+}
+
+fn orphaned_response_to_text(part: Part) -> Part {
+    match part {
+        Part::FunctionResponse { name, response, .. } => {
+            debug!("Converting orphaned function response for {} to text", name);
+            Part::Text {
+                content: format!("Result of {}: {}", name, response).into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }
+        }
+        other => other,
+    }
+}
+
+/// Push a message, merging it into the previous one if both share the same role.
+pub(crate) fn push_merged(output: &mut Vec<Message>, message: Message) {
+    if let Some(last) = output.last_mut() {
+        if last.role() == message.role() {
+            let parts = match message {
+                Message::User(parts) | Message::Assistant(parts) => parts,
+            };
+            last.parts_mut().extend(parts);
+            return;
+        }
+    }
+    output.push(message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Role;
+    use serde_json::json;
+
+    fn call(id: &str, name: &str) -> Part {
+        Part::FunctionCall {
+            id: Some(id.to_string()),
+            name: name.to_string(),
+            arguments: json!({}),
+            signature: None,
+            repaired: false,
+            extensions: Extensions::new(),
+            finished: true,
+        }
+    }
+
+    fn response(id: &str, name: &str) -> Part {
+        Part::FunctionResponse {
+            id: Some(id.to_string()),
+            name: name.to_string(),
+            response: json!({ "ok": true }),
+            parts: vec![],
+            error: None,
+            finished: true,
+        }
+    }
+
+    #[test]
+    fn test_merges_consecutive_roles() {
+        let history = normalize_history(vec![
+            Message::User(vec![Part::text("a")]),
+            Message::User(vec![Part::text("b")]),
+            Message::Assistant(vec![Part::text("c")]),
+        ]);
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].parts().len(), 2);
+        assert_eq!(history[1].role(), Role::Assistant);
+    }
+
+    #[test]
+    fn test_empty_assistant_gets_placeholder() {
+        let history = normalize_history(vec![
+            Message::User(vec![Part::text("a")]),
+            Message::Assistant(vec![]),
+            Message::User(vec![]),
+        ]);
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[1].content().as_deref(),
+            Some(EMPTY_ASSISTANT_PLACEHOLDER)
+        );
+    }
+
+    #[test]
+    fn test_missing_tool_result_is_inserted() {
+        let history = normalize_history(vec![
+            Message::User(vec![Part::text("a")]),
+            Message::Assistant(vec![call("1", "search"), call("2", "fetch")]),
+            Message::User(vec![response("2", "fetch"), Part::text("thanks")]),
+        ]);
+
+        assert_eq!(history.len(), 3);
+        let parts = history[2].parts();
+        assert_eq!(parts.len(), 3);
+        match &parts[0] {
+            Part::FunctionResponse { id, response, .. } => {
+                assert_eq!(id.as_deref(), Some("1"));
+                assert!(response.get("error").is_some());
+            }
+            other => panic!("Expected function response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_trailing_tool_call_is_answered() {
+        let history = normalize_history(vec![
+            Message::User(vec![Part::text("a")]),
+            Message::Assistant(vec![call("1", "search")]),
+        ]);
+
+        assert_eq!(history.len(), 3);
+        assert!(matches!(
+            history[2].parts()[0],
+            Part::FunctionResponse { .. }
+        ));
+    }
+
+    #[test]
+    fn test_orphaned_tool_result_becomes_text() {
+        let history = normalize_history(vec![Message::User(vec![response("9", "search")])]);
+
+        match &history[0].parts()[0] {
+            Part::Text { content, .. } => assert!(content.starts_with("Result of search")),
+            other => panic!("Expected text part, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_first_rewritten() {
+        let history = vec![
+            Message::User(vec![Part::text("Weather?")]),
+            Message::Assistant(vec![call("1", "weather")]),
+            Message::User(vec![Part::text("Paris, please"), response("1", "weather")]),
+        ];
+        assert_eq!(
+            first_rewritten(&history, &normalize_history(history.clone())),
+            None
+        );
+
+        let history = vec![
+            Message::User(vec![Part::text("Weather?")]),
+            Message::Assistant(vec![call("1", "weather")]),
+            Message::User(vec![Part::text("Paris, please")]),
+        ];
+        assert_eq!(
+            first_rewritten(&history, &normalize_history(history.clone())),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_turns_group_tool_results() {
+        let history = vec![
+            Message::User(vec![Part::text("Weather in Paris and Rome?")]),
+            Message::Assistant(vec![call("1", "weather"), call("2", "weather")]),
+            Message::User(vec![response("2", "weather")]),
+            Message::User(vec![response("1", "weather")]),
+            Message::Assistant(vec![Part::text("Sunny in both.")]),
+            Message::User(vec![Part::text("Thanks")]),
+        ];
+
+        let grouped = turns(&history);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].steps.len(), 2);
+        assert_eq!(grouped[0].len(), 5);
+        assert_eq!(
+            grouped[0].answer(),
+            Some(&Message::Assistant(vec![Part::text("Sunny in both.")]))
+        );
+        let calls = grouped[0].steps[0].calls();
+        assert_eq!(calls[0].1, Some(&response("1", "weather")));
+        assert_eq!(calls[1].1, Some(&response("2", "weather")));
+        assert!(grouped[1].steps.is_empty());
+
+        assert_eq!(flatten_turns(grouped), history);
+        assert_eq!(step_boundary(&history, 3), 1);
+        assert_eq!(step_boundary(&history, 5), 5);
+    }
+
+    #[test]
+    fn test_rehydrate_for_another_provider() {
+        let mut gemini_call = call("", "search");
+        if let Part::FunctionCall { id, signature, .. } = &mut gemini_call {
+            *id = None;
+            *signature = Some("gemini-thought".to_string());
+        }
+        let mut gemini_response = response("", "search");
+        if let Part::FunctionResponse { id, .. } = &mut gemini_response {
+            *id = None;
+        }
+        let reasoning = Part::Reasoning {
+            content: "Thinking".into(),
+            summary: None,
+            signature: Some("opaque".to_string()),
+            extensions: Extensions::new(),
+            finished: true,
+        };
+        let history = vec![
+            Message::User(vec![Part::text("Find it")]),
+            Message::Assistant(vec![reasoning, gemini_call, call("call.1", "fetch")]),
+            Message::User(vec![response("call.1", "fetch"), gemini_response]),
+            Message::User(vec![Part::text("Thanks")]),
+        ];
+
+        let rehydrated = rehydrate_for(history.clone(), ModelVendor::Anthropic);
+        assert_eq!(rehydrated.len(), 3);
+        let ids = |message: &Message| {
+            message
+                .parts()
+                .iter()
+                .filter_map(|part| match part {
+                    Part::FunctionCall { id, signature, .. } => {
+                        assert!(signature.is_none());
+                        id.clone()
+                    }
+                    Part::FunctionResponse { id, .. } => id.clone(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rehydrated[1].parts().len(), 2);
+        assert_eq!(ids(&rehydrated[1]), vec!["call_1", "call_2"]);
+        assert_eq!(ids(&rehydrated[2]), vec!["call_2", "call_1"]);
+        assert_eq!(rehydrated[2].parts().len(), 3);
+
+        let rehydrated = rehydrate_for(history, ModelVendor::OpenAI);
+        assert_eq!(ids(&rehydrated[1]), vec!["call_1", "call.1"]);
+    }
+}
@@ -1,19 +1,35 @@
 //! HTTP client utilities for making requests to LLM APIs.
 
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest::{Client, RequestBuilder};
 
-use crate::client::ClientError;
-use crate::options::TransportOptions;
+use crate::audit::{is_secret_key, redact, redact_str, REDACTED};
+use crate::client::{ClientError, RequestPreview};
+use crate::compress::{estimate_text, estimate_tokens};
+use crate::model::{Message, PartIndex};
+use crate::options::{AppInfo, TransportOptions};
+
+/// Name and version of this crate, as sent in the `User-Agent` header.
+pub const CRATE_PRODUCT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
 /// Build a configured HTTP client from transport options.
 pub fn build_http_client(transport_options: &TransportOptions) -> Result<Client, reqwest::Error> {
-    let mut builder = Client::builder();
+    let mut builder =
+        Client::builder().default_headers(identification_headers(transport_options.app().as_ref()));
 
     match transport_options {
-        TransportOptions::Http { timeout, proxy, .. } => {
+        TransportOptions::Http {
+            timeout,
+            connect_timeout,
+            proxy,
+            ..
+        } => {
             if let Some(t) = timeout {
                 builder = builder.timeout(*t);
             }
+            if let Some(t) = connect_timeout {
+                builder = builder.connect_timeout(*t);
+            }
             if let Some(proxy_url) = proxy {
                 if let Ok(p) = reqwest::Proxy::all(proxy_url) {
                     builder = builder.proxy(p);
@@ -25,6 +41,52 @@ pub fn build_http_client(transport_options: &TransportOptions) -> Result<Client,
     builder.build()
 }
 
+/// Headers identifying the crate and, if known, the application sending requests.
+///
+/// The `User-Agent` lists the application product first, followed by this crate.
+/// The `X-Unia-*` headers describe the client platform.
+pub fn identification_headers(app: Option<&AppInfo>) -> HeaderMap {
+    let user_agent = match app {
+        Some(app) => format!("{} {}", app.product(), CRATE_PRODUCT),
+        None => CRATE_PRODUCT.to_string(),
+    };
+
+    let mut headers = HeaderMap::new();
+    let values = [
+        (USER_AGENT, user_agent),
+        (
+            HeaderName::from_static("x-unia-package-version"),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        (HeaderName::from_static("x-unia-lang"), "rust".to_string()),
+        (
+            HeaderName::from_static("x-unia-os"),
+            std::env::consts::OS.to_string(),
+        ),
+        (
+            HeaderName::from_static("x-unia-arch"),
+            std::env::consts::ARCH.to_string(),
+        ),
+    ];
+    for (name, value) in values {
+        // Application names with non-visible characters are left out rather than failing requests.
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+    headers
+}
+
+/// Extract the provider request id from response headers, if present.
+///
+/// OpenAI-compatible APIs use `x-request-id`, Anthropic uses `request-id`.
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+    ["x-request-id", "request-id"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_string)
+}
+
 /// Add extra headers to a request if specified in transport options.
 pub fn add_extra_headers(
     mut request: RequestBuilder,
@@ -42,6 +104,163 @@ pub fn add_extra_headers(
     request
 }
 
+/// Fail with [`ClientError::RequestTooLarge`] if `messages` are estimated to exceed
+/// the `max_request_bytes` transport option.
+///
+/// The estimate sums [`Part::estimated_bytes`](crate::model::Part::estimated_bytes),
+/// so inline media counts at its base64 size. The error names the fewest largest
+/// parts whose removal would bring the request under the limit.
+pub fn check_request_size(
+    transport_options: &TransportOptions,
+    messages: &[Message],
+) -> Result<(), ClientError> {
+    let Some(limit) = transport_options.max_request_bytes() else {
+        return Ok(());
+    };
+    let mut sizes: Vec<(PartIndex, usize)> = messages
+        .iter()
+        .enumerate()
+        .flat_map(|(message, m)| {
+            m.parts()
+                .iter()
+                .enumerate()
+                .map(move |(part, p)| (PartIndex { message, part }, p.estimated_bytes()))
+        })
+        .collect();
+    let size: usize = sizes.iter().map(|(_, size)| size).sum();
+    if size <= limit {
+        return Ok(());
+    }
+
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    let mut remaining = size;
+    let parts = sizes
+        .into_iter()
+        .take_while(|(_, part_size)| {
+            let over = remaining > limit;
+            remaining -= part_size;
+            over
+        })
+        .map(|(index, _)| index)
+        .collect();
+    Err(ClientError::RequestTooLarge { size, limit, parts })
+}
+
+tokio::task_local! {
+    static CALL_IDEMPOTENCY_KEY: String;
+}
+
+/// Run `call` with `key` as the `Idempotency-Key` of the requests it sends, overriding
+/// [`TransportOptions::idempotency_key`].
+///
+/// Wrap a single request, e.g. each attempt of a retry loop, with the same key: every
+/// request sent by `call` gets it, so providers would deduplicate distinct requests.
+pub async fn with_idempotency_key<F: std::future::Future>(
+    key: impl Into<String>,
+    call: F,
+) -> F::Output {
+    CALL_IDEMPOTENCY_KEY.scope(key.into(), call).await
+}
+
+/// Key set with [`with_idempotency_key`] for the current call, if any.
+pub(crate) fn call_idempotency_key() -> Option<String> {
+    CALL_IDEMPOTENCY_KEY.try_with(Clone::clone).ok()
+}
+
+/// Add an `Idempotency-Key` header as configured in transport options.
+pub fn add_idempotency_key(
+    request: RequestBuilder,
+    transport_options: &TransportOptions,
+) -> RequestBuilder {
+    match transport_options.idempotency_key() {
+        Some(key) => {
+            tracing::debug!("Idempotency key: {}", key);
+            request.header("Idempotency-Key", key)
+        }
+        None => request,
+    }
+}
+
+/// 128-bit FNV-1a hash of raw bytes, stable across processes and crate versions.
+pub fn fingerprint_bytes(bytes: &[u8]) -> String {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    let hash = bytes.iter().fold(OFFSET, |hash, byte| {
+        (hash ^ u128::from(*byte)).wrapping_mul(PRIME)
+    });
+    format!("{:032x}", hash)
+}
+
+/// Query parameters holding credentials, in addition to the usual secret keys.
+const SECRET_QUERY_PARAMS: &[&str] = &["key"];
+
+/// Estimated input tokens of a request, see [`estimate_tokens`].
+pub fn estimate_request_tokens(
+    system: Option<&str>,
+    messages: &[Message],
+    tools: &[rmcp::model::Tool],
+) -> usize {
+    let tools = tools
+        .iter()
+        .map(|tool| estimate_text(&serde_json::to_string(tool).unwrap_or_default()))
+        .sum::<usize>();
+    system.map_or(0, estimate_text) + estimate_tokens(messages) + tools
+}
+
+/// Preview of a built request, with credentials redacted from the URL, headers and body.
+pub fn preview_request(
+    request: RequestBuilder,
+    estimated_tokens: usize,
+) -> Result<RequestPreview, ClientError> {
+    let request = request.build()?;
+
+    let mut url = request.url().clone();
+    if url.query().is_some() {
+        let pairs = url
+            .query_pairs()
+            .map(|(name, value)| {
+                let secret = is_secret_key(&name) || SECRET_QUERY_PARAMS.contains(&name.as_ref());
+                let value = if secret {
+                    REDACTED.to_string()
+                } else {
+                    value.into_owned()
+                };
+                (name.into_owned(), value)
+            })
+            .collect::<Vec<_>>();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    let headers = request
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            let value = if is_secret_key(name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                redact_str(&value)
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+
+    let mut body = match request.body().and_then(|body| body.as_bytes()) {
+        Some(bytes) => serde_json::from_slice(bytes)?,
+        None => serde_json::Value::Null,
+    };
+    redact(&mut body, &[]);
+
+    Ok(RequestPreview {
+        method: request.method().to_string(),
+        url: url.to_string(),
+        headers,
+        body,
+        estimated_tokens,
+    })
+}
+
 /// Extension trait for RequestBuilder that logs request body.
 pub trait RequestBuilderExt {
     /// Set JSON request body and log it. Returns the RequestBuilder for chaining.
@@ -0,0 +1,188 @@
+//! System instructions composed from layers.
+//!
+//! Instructions usually come from several places: defaults of a library built on
+//! unia, the application, the conversation (e.g. a persona picked by the user) and
+//! the current call. [`Instructions`] keeps them as blocks per [`InstructionLayer`]
+//! and renders them in a deterministic order, broadest layer first, dropping
+//! repeated blocks.
+//!
+//! The system prompt of a client ([`ModelOptions::system`](crate::options::ModelOptions::system))
+//! is fixed when the client is built, so it acts as the base of every request. The
+//! layered instructions are sent ahead of the conversation instead, as the first
+//! part of the first user message, without the blocks the base already contains.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::model::{Message, Part};
+
+/// Origin of instruction blocks, in rendering order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstructionLayer {
+    /// Defaults of a library or framework.
+    Library,
+    /// Instructions of the application, e.g. set on an [`Agent`](crate::agent::Agent).
+    App,
+    /// Instructions of a single [`Conversation`](crate::conversation::Conversation).
+    Conversation,
+    /// Instructions of a single call.
+    Call,
+}
+
+/// Instruction blocks by layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Instructions {
+    layers: BTreeMap<InstructionLayer, Vec<String>>,
+}
+
+impl Instructions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block to `layer`.
+    pub fn with(mut self, layer: InstructionLayer, text: impl Into<String>) -> Self {
+        self.push(layer, text);
+        self
+    }
+
+    /// Add a block to `layer`. Blank blocks are ignored.
+    pub fn push(&mut self, layer: InstructionLayer, text: impl Into<String>) {
+        let text = text.into();
+        if !text.trim().is_empty() {
+            self.layers.entry(layer).or_default().push(text);
+        }
+    }
+
+    /// Blocks of `layer`, in the order they were added.
+    pub fn layer(&self, layer: InstructionLayer) -> &[String] {
+        self.layers.get(&layer).map_or(&[], Vec::as_slice)
+    }
+
+    /// Remove the blocks of `layer`, returning them.
+    pub fn clear(&mut self, layer: InstructionLayer) -> Vec<String> {
+        self.layers.remove(&layer).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Combine with `other`, whose blocks follow those of `self` within each layer.
+    pub fn overlay(&self, other: &Instructions) -> Instructions {
+        let mut combined = self.clone();
+        for (layer, blocks) in &other.layers {
+            combined
+                .layers
+                .entry(*layer)
+                .or_default()
+                .extend(blocks.iter().cloned());
+        }
+        combined
+    }
+
+    /// Blocks to send, by layer then insertion order.
+    ///
+    /// A block repeating an earlier one (ignoring surrounding whitespace) is dropped,
+    /// so its first, broadest occurrence decides its position.
+    pub fn blocks(&self) -> Vec<&str> {
+        let mut blocks: Vec<&str> = Vec::new();
+        for block in self.layers.values().flatten() {
+            let block = block.trim();
+            if !blocks.contains(&block) {
+                blocks.push(block);
+            }
+        }
+        blocks
+    }
+
+    /// All blocks separated by blank lines, or `None` if there are none.
+    pub fn render(&self) -> Option<String> {
+        let blocks = self.blocks();
+        (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+    }
+
+    /// Insert the instructions ahead of `messages`, without the blocks already part of
+    /// the `base` system prompt.
+    ///
+    /// The rendered blocks become the first part of the first user message, or a new
+    /// user message if there is none.
+    pub fn apply(&self, base: Option<&str>, mut messages: Vec<Message>) -> Vec<Message> {
+        let base: Vec<&str> = base
+            .unwrap_or_default()
+            .split("\n\n")
+            .map(str::trim)
+            .collect();
+        let blocks: Vec<&str> = self
+            .blocks()
+            .into_iter()
+            .filter(|block| !base.contains(block))
+            .collect();
+        if blocks.is_empty() {
+            return messages;
+        }
+
+        let part = Part::text(blocks.join("\n\n"));
+        match messages.iter_mut().find(|m| matches!(m, Message::User(_))) {
+            Some(message) => message.parts_mut().insert(0, part),
+            None => messages.insert(0, Message::User(vec![part])),
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_render_in_order() {
+        let instructions = Instructions::new()
+            .with(InstructionLayer::Call, "Answer in French.")
+            .with(InstructionLayer::App, "You are a travel assistant.")
+            .with(InstructionLayer::Conversation, "Be brief.")
+            .with(InstructionLayer::Call, "  Be brief.\n")
+            .with(InstructionLayer::Library, " ");
+
+        assert_eq!(
+            instructions.render().as_deref(),
+            Some("You are a travel assistant.\n\nBe brief.\n\nAnswer in French.")
+        );
+        assert!(instructions.layer(InstructionLayer::Library).is_empty());
+
+        let overlaid = instructions
+            .overlay(&Instructions::new().with(InstructionLayer::App, "Never book flights."));
+        assert_eq!(
+            overlaid.blocks(),
+            vec![
+                "You are a travel assistant.",
+                "Never book flights.",
+                "Be brief.",
+                "Answer in French."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_skips_base_blocks() {
+        let instructions = Instructions::new()
+            .with(InstructionLayer::App, "Be brief.")
+            .with(InstructionLayer::Call, "Answer in French.");
+
+        let messages = instructions.apply(
+            Some("You are helpful.\n\nBe brief."),
+            vec![Message::user("Hi")],
+        );
+        assert_eq!(
+            messages[0].content().as_deref(),
+            Some("Answer in French.\nHi")
+        );
+
+        let messages = Instructions::new()
+            .with(InstructionLayer::App, "Be brief.")
+            .apply(Some("Be brief."), vec![Message::user("Hi")]);
+        assert_eq!(messages, vec![Message::user("Hi")]);
+    }
+}
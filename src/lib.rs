@@ -10,6 +10,20 @@
 //! - Streaming support via Server-Sent Events
 //! - Type-safe request/response models
 //!
+//! ## Cargo Features
+//!
+//! - `openai`: Chat Completions client and the OpenAI-compatible providers
+//! - `anthropic`: Anthropic Messages client, including Vertex AI and Bedrock
+//! - `gemini`: Gemini client
+//! - `mcp`: Using MCP servers through `rmcp` clients
+//! - `server`: HTTP gateway accepting the OpenAI, Anthropic and Gemini wire formats;
+//!   enables `openai`, `anthropic` and `gemini` (not enabled by default)
+//! - `testing`: Local emulation of provider APIs (`MockProviderServer`) for downstream
+//!   tests; enables `server` (not enabled by default)
+//!
+//! All features except `server` and `testing` are enabled by default. Commonly used
+//! items are re-exported from [`prelude`].
+//!
 //! ## Architecture
 //!
 //! The library uses a factory-based design:
@@ -29,10 +43,9 @@
 //!
 //! ## Example
 //! ```no_run
-//! use unia::client::Client;
-//! use unia::model::{Message, Part};
-//! use unia::providers::{OpenAI, Provider};
+//! use unia::prelude::*;
 //!
+//! # #[cfg(feature = "openai")]
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     // Create client using the factory
@@ -42,7 +55,10 @@
 //!     let messages = vec![
 //!         Message::User(vec![
 //!             Part::Text {
-//!                 content: "Hello!".to_string(),
+//!                 content: "Hello!".into(),
+//!                 signature: None,
+//!                 extensions: Extensions::new(),
+//!                 annotations: TextAnnotations::default(),
 //!                 finished: true,
 //!             }
 //!         ])
@@ -53,25 +69,63 @@
 //!     println!("{:?}", response);
 //!     Ok(())
 //! }
+//! # #[cfg(not(feature = "openai"))]
+//! # fn main() {}
 //! ```
 
 pub mod agent;
 pub mod api;
+pub mod attach;
+pub mod audit;
+pub mod budget;
+pub mod cache;
+pub mod catalog;
 pub mod client;
+pub mod compress;
+pub mod constraints;
+pub mod conversation;
+pub mod draft;
+pub mod embed;
+pub mod eventstream;
+pub mod export;
+pub mod graphql;
+pub mod hedge;
+pub mod history;
 pub mod http;
+pub mod instructions;
+pub mod limiter;
 pub mod mcp;
 pub mod model;
+pub mod normalize;
+pub mod openapi;
 pub mod options;
+pub mod prelude;
 pub mod providers;
+pub mod react;
+pub mod region;
+pub mod rerank;
+pub mod schema;
+pub mod scratchpad;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod sse;
 pub mod stream;
+pub mod structured;
+pub mod template;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tools;
+pub mod trace;
+pub mod transcript;
+pub mod validate;
 
 pub use agent::Agent;
-pub use client::{Client, ClientError, StreamingClient};
+pub use client::{Client, ClientError, StreamingClient, StreamingClientExt};
 pub use mcp::{AttachResources, MCPServer};
 pub use model::{GeneralRequest, Message, Response};
-pub use tools::{Tool, ToolError, ToolService};
+pub use tools::{Tool, ToolConfig, ToolError, ToolErrorKind, ToolRetryPolicy, ToolService};
 
 // Re-export rmcp for convenience
 pub use rmcp;
@@ -0,0 +1,357 @@
+//! Client-side rate limiting with prioritized dispatch.
+//!
+//! A [`RateLimiter`] bounds the number of requests in flight (and optionally the
+//! request rate) across every client sharing it. Waiting requests are dispatched by
+//! [`Priority`], so user-facing traffic is not stuck behind batch jobs.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{Message, Response};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Dispatch priority of a request waiting for the limiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// User-facing requests. Always dispatched before background requests.
+    Interactive,
+    /// Batch and other latency-insensitive traffic.
+    Background,
+}
+
+/// Shared limiter for concurrent requests and request rate.
+///
+/// Cloning a limiter yields a handle to the same limits.
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: Arc<Mutex<LimiterState>>,
+    min_interval: Option<Duration>,
+}
+
+struct LimiterState {
+    available: usize,
+    next_dispatch: Instant,
+    interactive: VecDeque<oneshot::Sender<()>>,
+    background: VecDeque<oneshot::Sender<()>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing at most `max_concurrent` requests in flight.
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(LimiterState {
+                available: max_concurrent,
+                next_dispatch: Instant::now(),
+                interactive: VecDeque::new(),
+                background: VecDeque::new(),
+            })),
+            min_interval: None,
+        }
+    }
+
+    /// Additionally limit the number of requests started per minute.
+    pub fn with_requests_per_minute(mut self, requests: u32) -> Self {
+        self.min_interval = Some(Duration::from_secs(60) / requests.max(1));
+        self
+    }
+
+    /// Wait for a dispatch slot. The slot is released when the returned permit is dropped.
+    pub async fn acquire(&self, priority: Priority) -> Permit {
+        let waiter = {
+            let mut state = self.state.lock().unwrap();
+            if state.available > 0 && state.interactive.is_empty() && state.background.is_empty() {
+                state.available -= 1;
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                match priority {
+                    Priority::Interactive => state.interactive.push_back(sender),
+                    Priority::Background => state.background.push_back(sender),
+                }
+                Some(receiver)
+            }
+        };
+
+        if let Some(receiver) = waiter {
+            let mut waiter = Waiter {
+                receiver,
+                state: self.state.clone(),
+                received: false,
+            };
+            // The sender is only dropped after handing over the slot.
+            let _ = (&mut waiter.receiver).await;
+            waiter.received = true;
+        }
+        // Created once the slot is ours, so that a cancelled waiter never releases
+        // a slot it did not get.
+        let permit = Permit {
+            state: self.state.clone(),
+        };
+
+        if let Some(interval) = self.min_interval {
+            let start = {
+                let mut state = self.state.lock().unwrap();
+                let start = state.next_dispatch.max(Instant::now());
+                state.next_dispatch = start + interval;
+                start
+            };
+            tokio::time::sleep_until(start).await;
+        }
+
+        permit
+    }
+}
+
+/// A dispatch slot held for the duration of a request.
+pub struct Permit {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        release(&mut self.state.lock().unwrap());
+    }
+}
+
+/// A queued request waiting for a slot.
+struct Waiter {
+    receiver: oneshot::Receiver<()>,
+    state: Arc<Mutex<LimiterState>>,
+    received: bool,
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if self.received {
+            return;
+        }
+        // Cancelled while queued: if the slot was already handed over, pass it on.
+        self.receiver.close();
+        if self.receiver.try_recv().is_ok() {
+            release(&mut self.state.lock().unwrap());
+        }
+    }
+}
+
+/// Hand a slot to the next live waiter, interactive ones first, or make it available.
+fn release(state: &mut LimiterState) {
+    loop {
+        let next = match state.interactive.pop_front() {
+            Some(waiter) => Some(waiter),
+            None => state.background.pop_front(),
+        };
+        match next {
+            Some(waiter) => {
+                if waiter.send(()).is_ok() {
+                    return;
+                }
+            }
+            None => {
+                state.available += 1;
+                return;
+            }
+        }
+    }
+}
+
+/// Client wrapper dispatching every request through a [`RateLimiter`].
+///
+/// Unless a priority is set explicitly, streaming requests are treated as
+/// [`Priority::Interactive`] and non-streaming requests as [`Priority::Background`].
+pub struct Limited<C> {
+    client: C,
+    limiter: RateLimiter,
+    priority: Option<Priority>,
+}
+
+impl<C> Limited<C> {
+    /// Wrap a client so that its requests go through `limiter`.
+    pub fn new(client: C, limiter: RateLimiter) -> Self {
+        Self {
+            client,
+            limiter,
+            priority: None,
+        }
+    }
+
+    /// Use the given priority for every request of this client.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Limited<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let _permit = self
+            .limiter
+            .acquire(self.priority.unwrap_or(Priority::Background))
+            .await;
+        self.client.request(messages, tools).await
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<C: StreamingClient> StreamingClient for Limited<C> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let permit = self
+            .limiter
+            .acquire(self.priority.unwrap_or(Priority::Interactive))
+            .await;
+        let mut stream = self.client.request_stream(messages, tools).await?;
+
+        // The slot stays taken until the stream is exhausted or dropped.
+        Ok(Box::pin(async_stream::stream! {
+            let _permit = permit;
+            while let Some(item) = stream.next().await {
+                yield item;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interactive_requests_preempt_background() {
+        let limiter = RateLimiter::new(1);
+        let held = limiter.acquire(Priority::Background).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (name, priority) in [
+            ("background", Priority::Background),
+            ("interactive", Priority::Interactive),
+        ] {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = limiter.acquire(priority).await;
+                order.lock().unwrap().push(name);
+            }));
+            // Make sure the background request is queued first.
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(held);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec!["interactive", "background"]);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_waiter_releases_slot() {
+        let limiter = RateLimiter::new(1);
+        let held = limiter.acquire(Priority::Interactive).await;
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(Priority::Interactive).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        waiting.abort();
+        let _ = waiting.await;
+
+        drop(held);
+        let permit = tokio::time::timeout(
+            Duration::from_millis(100),
+            limiter.acquire(Priority::Background),
+        )
+        .await;
+        assert!(permit.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_aborted_waiter_does_not_release_foreign_slot() {
+        let limiter = RateLimiter::new(1);
+        let held = limiter.acquire(Priority::Interactive).await;
+
+        let acquired = Arc::new(Mutex::new(false));
+        let first = tokio::spawn({
+            let limiter = limiter.clone();
+            let acquired = acquired.clone();
+            async move {
+                let permit = limiter.acquire(Priority::Interactive).await;
+                *acquired.lock().unwrap() = true;
+                permit
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let behind = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(Priority::Interactive).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        behind.abort();
+        let _ = behind.await;
+
+        // The slot is still held, so the first waiter must keep waiting.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!*acquired.lock().unwrap());
+
+        drop(held);
+        let permit = first.await.unwrap();
+        assert_eq!(limiter.state.lock().unwrap().available, 0);
+        drop(permit);
+        assert_eq!(limiter.state.lock().unwrap().available, 1);
+    }
+
+    #[tokio::test]
+    async fn test_waiter_cancelled_after_handover_passes_slot_on() {
+        let limiter = RateLimiter::new(1);
+        let held = limiter.acquire(Priority::Interactive).await;
+
+        let mut waiting = Box::pin(limiter.acquire(Priority::Interactive));
+        assert!(futures::poll!(&mut waiting).is_pending());
+        // The slot is handed to the waiter, which is dropped before receiving it.
+        drop(held);
+        drop(waiting);
+
+        assert_eq!(limiter.state.lock().unwrap().available, 1);
+    }
+}
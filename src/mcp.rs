@@ -1,15 +1,12 @@
-use crate::model::{MediaType, Message, Part};
+use crate::model::{base64_data, Extensions, MediaType, Message, Part, TextAnnotations};
 use async_trait::async_trait;
+use bytes::Bytes;
 use rmcp::model::{
-    AnnotateAble, Annotated, CallToolRequestParam, GetPromptRequestParam, GetPromptResult, Prompt,
-    PromptMessage, PromptMessageContent, PromptMessageRole, RawContent, ReadResourceRequestParam,
-    ReadResourceResult, Resource, ResourceContents, Tool,
+    AnnotateAble, Annotated, GetPromptResult, Prompt, PromptMessage, PromptMessageContent,
+    PromptMessageRole, ReadResourceResult, Resource, ResourceContents, Tool,
 };
-use rmcp::service::{RoleClient, RunningService};
-use rmcp::ClientHandler;
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::collections::HashMap;
-use std::ops::Deref;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -76,6 +73,27 @@ pub trait MCPServer: Send + Sync {
         server_id: Option<String>,
     ) -> Result<Part, MCPError>;
 
+    /// Whether a tool wants to receive its arguments while the model is still generating them.
+    ///
+    /// Tools that opt in get [`partial_arguments`](MCPServer::partial_arguments) calls during
+    /// streaming (e.g. for progressive rendering or early validation). Defaults to `false`.
+    fn streams_arguments(&self, _name: &str, _server_id: Option<&str>) -> bool {
+        false
+    }
+
+    /// Receive the arguments of a call that is still being generated.
+    ///
+    /// `args` is the best-effort parse of the incomplete arguments JSON and grows with
+    /// every call. The complete call is still delivered through [`call_tool`](MCPServer::call_tool).
+    async fn partial_arguments(
+        &self,
+        _name: String,
+        _call_id: Option<String>,
+        _args: Value,
+        _server_id: Option<String>,
+    ) {
+    }
+
     /// List available prompts.
     async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError>;
 
@@ -96,133 +114,8 @@ pub trait MCPServer: Send + Sync {
     ) -> Result<Served<ReadResourceResult>, MCPError>;
 }
 
-#[async_trait]
-impl<S: ClientHandler + Send + Sync> MCPServer for RunningService<RoleClient, S> {
-    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
-        let result = self
-            .deref()
-            .list_tools(None)
-            .await
-            .map_err(|e| MCPError::Mcp(e.to_string()))?;
-        Ok(result.tools.into_iter().map(|t| t.served(None)).collect())
-    }
-
-    async fn call_tool(
-        &self,
-        name: String,
-        args: Value,
-        _server_id: Option<String>,
-    ) -> Result<Part, MCPError> {
-        let params = CallToolRequestParam {
-            name: name.clone().into(),
-            arguments: args.as_object().cloned(),
-        };
-
-        let result = self
-            .deref()
-            .call_tool(params)
-            .await
-            .map_err(|e| MCPError::Mcp(e.to_string()))?;
-
-        let mut structured = json!({});
-        let mut parts = Vec::new();
-        let mut parsed_text_content: Option<Value> = None;
-        let mut raw_text_content: Vec<String> = Vec::new();
-
-        for content in result.content {
-            match content.raw {
-                RawContent::Text(text_content) => {
-                    if let Ok(parsed) = serde_json::from_str::<Value>(&text_content.text) {
-                        parsed_text_content = Some(parsed);
-                    } else {
-                        raw_text_content.push(text_content.text);
-                    }
-                }
-                RawContent::Image(image_content) => {
-                    parts.push(Part::Media {
-                        media_type: MediaType::Image,
-                        data: image_content.data,
-                        mime_type: image_content.mime_type,
-                        uri: None,
-                        finished: true,
-                    });
-                }
-                RawContent::Resource(resource) => {
-                    parts.push(Part::from(resource.resource));
-                }
-                _ => {}
-            }
-        }
-
-        if let Some(s) = result.structured_content {
-            structured = s;
-        } else if let Some(parsed) = parsed_text_content {
-            structured = parsed;
-        } else if !raw_text_content.is_empty() {
-            structured = json!({ "response": raw_text_content });
-        }
-
-        Ok(Part::FunctionResponse {
-            id: None,
-            name,
-            response: structured,
-            parts,
-            finished: true,
-        })
-    }
-
-    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
-        let result = self
-            .deref()
-            .list_prompts(None)
-            .await
-            .map_err(|e| MCPError::Mcp(e.to_string()))?;
-        Ok(result.prompts.into_iter().map(|p| p.served(None)).collect())
-    }
-
-    async fn get_prompt(
-        &self,
-        prompt: &Served<Prompt>,
-        args: Option<serde_json::Map<String, Value>>,
-    ) -> Result<Served<GetPromptResult>, MCPError> {
-        let params = GetPromptRequestParam {
-            name: prompt.value.name.clone(),
-            arguments: args,
-        };
-        self.deref()
-            .get_prompt(params)
-            .await
-            .map(|r| r.served(None))
-            .map_err(|e| MCPError::Mcp(e.to_string()))
-    }
-
-    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
-        let result = self
-            .deref()
-            .list_resources(None)
-            .await
-            .map_err(|e| MCPError::Mcp(e.to_string()))?;
-        Ok(result
-            .resources
-            .into_iter()
-            .map(|r| r.served(None))
-            .collect())
-    }
-
-    async fn read_resource(
-        &self,
-        resource: &Served<Resource>,
-    ) -> Result<Served<ReadResourceResult>, MCPError> {
-        let params = ReadResourceRequestParam {
-            uri: resource.value.uri.clone(),
-        };
-        self.deref()
-            .read_resource(params)
-            .await
-            .map(|r| r.served(None))
-            .map_err(|e| MCPError::Mcp(e.to_string()))
-    }
-}
+#[cfg(feature = "mcp")]
+mod client;
 
 /// A helper to combine multiple MCP servers into one.
 pub struct MultiMCPServer {
@@ -300,6 +193,24 @@ impl MCPServer for MultiMCPServer {
         Err(MCPError::ToolNotFound(name))
     }
 
+    fn streams_arguments(&self, name: &str, server_id: Option<&str>) -> bool {
+        server_id
+            .and_then(|id| self.servers.get(id))
+            .is_some_and(|server| server.streams_arguments(name, None))
+    }
+
+    async fn partial_arguments(
+        &self,
+        name: String,
+        call_id: Option<String>,
+        args: Value,
+        server_id: Option<String>,
+    ) {
+        if let Some(server) = server_id.and_then(|id| self.servers.get(&id)) {
+            server.partial_arguments(name, call_id, args, None).await;
+        }
+    }
+
     async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
         let mut all_prompts = Vec::new();
         for (id, server) in &self.servers {
@@ -405,7 +316,7 @@ impl From<ResourceContents> for Part {
                 ..
             } => Part::Media {
                 media_type: MediaType::Text,
-                data: text,
+                data: Bytes::from(text),
                 mime_type: mime_type.unwrap_or_else(|| "text/plain".to_string()),
                 uri: Some(uri),
                 finished: true,
@@ -417,17 +328,11 @@ impl From<ResourceContents> for Part {
                 ..
             } => {
                 let mime = mime_type.unwrap_or_else(|| "application/octet-stream".to_string());
-                let media_type = if mime.starts_with("image/") {
-                    MediaType::Image
-                } else if mime == "application/pdf" {
-                    MediaType::Document
-                } else {
-                    MediaType::Binary
-                };
+                let media_type = MediaType::from_mime_type(&mime);
 
                 Part::Media {
                     media_type,
-                    data: blob,
+                    data: base64_data::decode_lenient(blob),
                     mime_type: mime,
                     uri: Some(uri),
                     finished: true,
@@ -441,12 +346,15 @@ impl From<PromptMessage> for Message {
     fn from(pm: PromptMessage) -> Self {
         let part = match pm.content {
             PromptMessageContent::Text { text } => Part::Text {
-                content: text,
+                content: text.into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
                 finished: true,
             },
             PromptMessageContent::Image { image, .. } => Part::Media {
                 media_type: MediaType::Image,
-                data: image.data.clone(),
+                data: base64_data::decode_lenient(image.data.clone()),
                 mime_type: image.mime_type.clone(),
                 uri: None,
                 finished: true,
@@ -0,0 +1,149 @@
+//! [`MCPServer`] implementation for rmcp client sessions.
+
+use rmcp::model::{
+    CallToolRequestParam, GetPromptRequestParam, RawContent, ReadResourceRequestParam,
+};
+use rmcp::service::{RoleClient, RunningService};
+use rmcp::ClientHandler;
+use serde_json::json;
+use std::ops::Deref;
+
+use super::*;
+use crate::tools::ToolError;
+
+#[async_trait]
+impl<S: ClientHandler + Send + Sync> MCPServer for RunningService<RoleClient, S> {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        let result = self
+            .deref()
+            .list_tools(None)
+            .await
+            .map_err(|e| MCPError::Mcp(e.to_string()))?;
+        Ok(result.tools.into_iter().map(|t| t.served(None)).collect())
+    }
+
+    async fn call_tool(
+        &self,
+        name: String,
+        args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        let params = CallToolRequestParam {
+            name: name.clone().into(),
+            arguments: args.as_object().cloned(),
+        };
+
+        let result = self
+            .deref()
+            .call_tool(params)
+            .await
+            .map_err(|e| MCPError::Mcp(e.to_string()))?;
+
+        // Structured content goes to `response` and text blocks stay text parts, except
+        // the JSON serialization of the structured content tools send for older clients.
+        let mut structured = result.structured_content;
+        let mut parts = Vec::new();
+        let mut raw_text_content: Vec<String> = Vec::new();
+
+        for content in result.content {
+            match content.raw {
+                RawContent::Text(text_content) => {
+                    match serde_json::from_str::<Value>(&text_content.text) {
+                        Ok(parsed) if structured.is_none() => structured = Some(parsed),
+                        Ok(parsed) if structured.as_ref() == Some(&parsed) => {}
+                        _ => {
+                            parts.push(Part::text(text_content.text.clone()));
+                            raw_text_content.push(text_content.text);
+                        }
+                    }
+                }
+                RawContent::Image(image_content) => {
+                    parts.push(Part::Media {
+                        media_type: MediaType::Image,
+                        data: base64_data::decode_lenient(image_content.data),
+                        mime_type: image_content.mime_type,
+                        uri: None,
+                        finished: true,
+                    });
+                }
+                RawContent::Resource(resource) => {
+                    parts.push(Part::from(resource.resource));
+                }
+                _ => {}
+            }
+        }
+
+        let structured = structured.unwrap_or_else(|| json!({}));
+
+        // Tools report failures in-band, with the error description as content.
+        let error = result.is_error.unwrap_or(false).then(|| {
+            let message = match &structured {
+                _ if !raw_text_content.is_empty() => raw_text_content.join("\n"),
+                Value::Object(map) if map.is_empty() => "Tool execution failed".to_string(),
+                other => other.to_string(),
+            };
+            ToolError::execution(message)
+        });
+
+        Ok(Part::FunctionResponse {
+            id: None,
+            name,
+            response: structured,
+            parts,
+            error,
+            finished: true,
+        })
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        let result = self
+            .deref()
+            .list_prompts(None)
+            .await
+            .map_err(|e| MCPError::Mcp(e.to_string()))?;
+        Ok(result.prompts.into_iter().map(|p| p.served(None)).collect())
+    }
+
+    async fn get_prompt(
+        &self,
+        prompt: &Served<Prompt>,
+        args: Option<serde_json::Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        let params = GetPromptRequestParam {
+            name: prompt.value.name.clone(),
+            arguments: args,
+        };
+        self.deref()
+            .get_prompt(params)
+            .await
+            .map(|r| r.served(None))
+            .map_err(|e| MCPError::Mcp(e.to_string()))
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        let result = self
+            .deref()
+            .list_resources(None)
+            .await
+            .map_err(|e| MCPError::Mcp(e.to_string()))?;
+        Ok(result
+            .resources
+            .into_iter()
+            .map(|r| r.served(None))
+            .collect())
+    }
+
+    async fn read_resource(
+        &self,
+        resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        let params = ReadResourceRequestParam {
+            uri: resource.value.uri.clone(),
+        };
+        self.deref()
+            .read_resource(params)
+            .await
+            .map(|r| r.served(None))
+            .map_err(|e| MCPError::Mcp(e.to_string()))
+    }
+}
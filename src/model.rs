@@ -1,45 +1,414 @@
 //! Common data models for provider-agnostic LLM requests and responses.
 
+use base64::prelude::*;
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::skip_serializing_none;
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+#[cfg(feature = "anthropic")]
+use crate::api::anthropic;
+#[cfg(feature = "gemini")]
+use crate::api::gemini;
+#[cfg(feature = "openai")]
+use crate::api::openai;
+#[cfg(any(feature = "openai", feature = "anthropic", feature = "gemini"))]
+use crate::client::ClientError;
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use crate::export::TrainingExample;
+use crate::options::ServiceTier;
+use crate::tools::ToolError;
+
+/// Reference-counted string used for part contents.
+///
+/// Streams yield a full snapshot of the response for every chunk, so part contents
+/// are cloned often. Clones share the underlying buffer; mutation copies it only if
+/// it is shared (copy-on-write). It dereferences to `str` and converts from and into
+/// `String`, and serializes as a plain string.
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SharedString(Arc<String>);
+
+impl SharedString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Mutable access to the string, copying it first if it is shared.
+    pub fn make_mut(&mut self) -> &mut String {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Append to the string, copying it first if it is shared.
+    pub fn push_str(&mut self, string: &str) {
+        self.make_mut().push_str(string);
+    }
+
+    /// Convert into a `String`, copying only if the buffer is shared.
+    pub fn into_string(self) -> String {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl Deref for SharedString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for SharedString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SharedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SharedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl From<String> for SharedString {
+    fn from(string: String) -> Self {
+        Self(Arc::new(string))
+    }
+}
+
+impl From<&str> for SharedString {
+    fn from(string: &str) -> Self {
+        Self(Arc::new(string.to_string()))
+    }
+}
+
+impl From<SharedString> for String {
+    fn from(string: SharedString) -> Self {
+        string.into_string()
+    }
+}
+
+impl PartialEq<str> for SharedString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SharedString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for SharedString {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl Serialize for SharedString {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedString {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Serde representation of media data as a base64 string.
+pub(crate) mod base64_data {
+    use base64::prelude::*;
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(data))
+    }
+
+    /// Text content of media data, or its base64 encoding if it is not valid UTF-8.
+    #[cfg(feature = "anthropic")]
+    pub fn to_text(data: &Bytes) -> String {
+        match std::str::from_utf8(data) {
+            Ok(text) => text.to_string(),
+            Err(_) => BASE64_STANDARD.encode(data),
+        }
+    }
+
+    /// Decode base64 data received from a peer, keeping it verbatim if it is not valid base64.
+    pub fn decode_lenient(encoded: String) -> Bytes {
+        match BASE64_STANDARD.decode(&encoded) {
+            Ok(data) => Bytes::from(data),
+            Err(_) => Bytes::from(encoded),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom)
+    }
+}
 
 /// Role of the message sender.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Role {
     User,
     Assistant,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum MediaType {
     /// Image content (e.g., PNG, JPEG)
     Image,
     /// Document content (e.g., PDF, TXT)
     Document,
+    /// Video content (e.g., MP4, WebM)
+    Video,
     /// Plain text content
     Text,
     /// Binary or other content
     Binary,
 }
 
+impl MediaType {
+    /// Classify content by its MIME type.
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        if mime_type.starts_with("image/") {
+            MediaType::Image
+        } else if mime_type.starts_with("video/") {
+            MediaType::Video
+        } else if mime_type == "application/pdf" {
+            MediaType::Document
+        } else {
+            MediaType::Binary
+        }
+    }
+}
+
+/// Provider-specific data attached to a part, keyed by provider field name.
+///
+/// Providers stash data the unified model has no place for here (Anthropic
+/// `citations`, OpenAI `annotations`, Gemini `groundingMetadata`) instead of dropping
+/// it, and read it back when the part is replayed to them.
+pub type Extensions = serde_json::Map<String, Value>;
+
+/// Rendering hints for a text part, so renderers don't have to re-derive its
+/// structure from the raw text.
+///
+/// Filled in by [`Part::annotate`] once the part is finished: markdown and code blocks
+/// are detected from the content, citations are read from the provider extensions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TextAnnotations {
+    /// Whether the text uses markdown syntax.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub markdown: bool,
+    /// Fenced code blocks, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_blocks: Vec<CodeBlock>,
+    /// Sources cited by the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+}
+
+impl TextAnnotations {
+    pub fn is_empty(&self) -> bool {
+        !self.markdown && self.code_blocks.is_empty() && self.citations.is_empty()
+    }
+
+    /// Detect markdown and fenced code blocks in `text`.
+    pub fn detect(text: &str) -> Self {
+        let mut code_blocks = Vec::new();
+        let mut markdown = false;
+        // Marker, language and code offset of the open fence.
+        let mut open: Option<(&str, Option<String>, usize)> = None;
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let trimmed = line.trim();
+
+            match &open {
+                Some((fence, _, _))
+                    if trimmed.starts_with(fence)
+                        && trimmed.trim_start_matches(['`', '~']).is_empty() =>
+                {
+                    let (_, language, code_start) = open.take().unwrap();
+                    code_blocks.push(CodeBlock {
+                        language,
+                        range: code_start..start,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    let fence = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f));
+                    if let Some(fence) = fence {
+                        let language = trimmed
+                            .trim_start_matches(['`', '~'])
+                            .split_whitespace()
+                            .next();
+                        open = Some((fence, language.map(str::to_string), offset));
+                    } else if is_markdown_line(trimmed) {
+                        markdown = true;
+                    }
+                }
+            }
+        }
+        if let Some((_, language, code_start)) = open {
+            code_blocks.push(CodeBlock {
+                language,
+                range: code_start..text.len(),
+            });
+        }
+
+        Self {
+            markdown: markdown || !code_blocks.is_empty(),
+            code_blocks,
+            citations: Vec::new(),
+        }
+    }
+}
+
+/// Whether a line outside code blocks uses markdown syntax.
+fn is_markdown_line(line: &str) -> bool {
+    let block = ["# ", "## ", "### ", "- ", "* ", "> ", "| "]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+        || line.split_once(". ").is_some_and(|(number, _)| {
+            !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+        });
+    let inline = line.contains("**") || line.contains("](") || line.matches('`').count() >= 2;
+    block || inline
+}
+
+/// A fenced code block of a text part.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CodeBlock {
+    /// Language of the info string (e.g. `rust` for ` ```rust `).
+    pub language: Option<String>,
+    /// Byte range of the code in the text, without the fences.
+    pub range: std::ops::Range<usize>,
+}
+
+/// A source cited by a text part.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Citation {
+    /// Range of the text backed by the source, in the offsets reported by the provider.
+    pub range: Option<std::ops::Range<usize>>,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    /// Quoted text of the source.
+    pub cited_text: Option<String>,
+}
+
+impl Citation {
+    /// Citations from the provider data of a text part: OpenAI `annotations`,
+    /// Anthropic `citations` and Gemini `groundingMetadata`.
+    pub fn from_extensions(extensions: &Extensions) -> Vec<Self> {
+        let string = |value: &Value, key: &str| value[key].as_str().map(str::to_string);
+        let range = |value: &Value, start: &str, end: &str| {
+            let start = value[start].as_u64().unwrap_or(0) as usize;
+            value[end].as_u64().map(|end| start..end as usize)
+        };
+
+        let mut citations = Vec::new();
+        if let Some(Value::Array(annotations)) = extensions.get("annotations") {
+            citations.extend(annotations.iter().filter_map(|annotation| {
+                let citation = annotation.get("url_citation")?;
+                Some(Self {
+                    range: range(citation, "start_index", "end_index"),
+                    url: string(citation, "url"),
+                    title: string(citation, "title"),
+                    cited_text: None,
+                })
+            }));
+        }
+        if let Some(Value::Array(cited)) = extensions.get("citations") {
+            citations.extend(cited.iter().map(|citation| Self {
+                range: None,
+                url: string(citation, "url"),
+                title: string(citation, "title").or_else(|| string(citation, "document_title")),
+                cited_text: string(citation, "cited_text"),
+            }));
+        }
+        if let Some(metadata) = extensions.get("groundingMetadata") {
+            let chunks = metadata["groundingChunks"].as_array();
+            for support in metadata["groundingSupports"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                let range = range(&support["segment"], "startIndex", "endIndex");
+                let indices = support["groundingChunkIndices"]
+                    .as_array()
+                    .into_iter()
+                    .flatten();
+                for index in indices.filter_map(Value::as_u64) {
+                    let web = chunks
+                        .and_then(|chunks| chunks.get(index as usize))
+                        .map(|chunk| &chunk["web"]);
+                    citations.push(Self {
+                        range: range.clone(),
+                        url: web.and_then(|web| string(web, "uri")),
+                        title: web.and_then(|web| string(web, "title")),
+                        cited_text: None,
+                    });
+                }
+            }
+        }
+        citations
+    }
+}
+
 /// A part of a message content.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// New kinds of parts may be added in minor releases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "data")]
+#[non_exhaustive]
 pub enum Part {
     /// Text content
     Text {
-        content: String,
+        content: SharedString,
+        /// Opaque provider signature that must be replayed with the text (Gemini
+        /// thought signatures).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+        #[serde(default, skip_serializing_if = "Extensions::is_empty")]
+        extensions: Extensions,
+        /// Rendering hints, see [`Part::annotate`].
+        #[serde(default, skip_serializing_if = "TextAnnotations::is_empty")]
+        annotations: TextAnnotations,
         #[serde(default)]
         finished: bool,
     },
     /// Reasoning/Thought content (e.g. from reasoning models)
     Reasoning {
-        content: String,
+        content: SharedString,
         summary: Option<String>,
         signature: Option<String>,
+        #[serde(default, skip_serializing_if = "Extensions::is_empty")]
+        extensions: Extensions,
         #[serde(default)]
         finished: bool,
     },
@@ -49,6 +418,12 @@ pub enum Part {
         name: String,
         arguments: Value,
         signature: Option<String>,
+        /// Set when the arguments were truncated (e.g. by the output token limit) and
+        /// had to be repaired, so they may be incomplete.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        repaired: bool,
+        #[serde(default, skip_serializing_if = "Extensions::is_empty")]
+        extensions: Extensions,
         #[serde(default)]
         finished: bool,
     },
@@ -56,14 +431,27 @@ pub enum Part {
     FunctionResponse {
         id: Option<String>,
         name: String,
+        /// Structured result of the tool (MCP `structuredContent`), or `{}` if it only
+        /// returned content blocks.
         response: Value,
+        /// Content blocks returned by the tool, in order: text and media parts.
+        /// Providers render them next to `response` as their API allows.
         parts: Vec<Part>,
+        /// Set when the tool failed; `response` then describes the failure.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ToolError>,
         #[serde(default)]
         finished: bool,
     },
+    /// Media content, either inline as raw `data` or, when `data` is empty, a
+    /// remote reference to `uri` (see [`Part::remote_media`]).
+    ///
+    /// Data is base64 encoded only when a request is built for a provider that expects
+    /// it, and when the part is serialized.
     Media {
         media_type: MediaType,
-        data: String,
+        #[serde(default, with = "base64_data")]
+        data: Bytes,
         mime_type: String,
         #[serde(default)]
         uri: Option<String>,
@@ -73,6 +461,138 @@ pub enum Part {
 }
 
 impl Part {
+    /// Create a finished text part.
+    pub fn text(content: impl Into<SharedString>) -> Self {
+        Part::Text {
+            content: content.into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }
+    }
+
+    /// Fill in the annotations of a text part from its content and extensions.
+    ///
+    /// Providers call this when a text part is finished; other parts are unchanged.
+    pub fn annotate(&mut self) {
+        if let Part::Text {
+            content,
+            extensions,
+            annotations,
+            ..
+        } = self
+        {
+            *annotations = TextAnnotations {
+                citations: Citation::from_extensions(extensions),
+                ..TextAnnotations::detect(content)
+            };
+        }
+    }
+
+    /// Create a function response reporting a failed tool call.
+    pub fn function_error(id: Option<String>, name: impl Into<String>, error: ToolError) -> Self {
+        Part::FunctionResponse {
+            id,
+            name: name.into(),
+            response: error.to_response(),
+            parts: Vec::new(),
+            error: Some(error),
+            finished: true,
+        }
+    }
+
+    /// Create a media part carrying inline data.
+    pub fn media(
+        media_type: MediaType,
+        mime_type: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        Part::Media {
+            media_type,
+            data: data.into(),
+            mime_type: mime_type.into(),
+            uri: None,
+            finished: true,
+        }
+    }
+
+    /// Create a media part from base64 encoded data.
+    pub fn media_base64(
+        media_type: MediaType,
+        mime_type: impl Into<String>,
+        data: &str,
+    ) -> Result<Self, base64::DecodeError> {
+        let data = BASE64_STANDARD.decode(data)?;
+        Ok(Self::media(media_type, mime_type, data))
+    }
+
+    /// Provider extension data of the part, for the variants that carry it.
+    pub fn extensions(&self) -> Option<&Extensions> {
+        match self {
+            Part::Text { extensions, .. }
+            | Part::Reasoning { extensions, .. }
+            | Part::FunctionCall { extensions, .. } => Some(extensions),
+            _ => None,
+        }
+    }
+
+    /// Base64 encoding of the inline data of a media part.
+    pub fn base64_data(&self) -> Option<String> {
+        match self {
+            Part::Media { data, .. } => Some(BASE64_STANDARD.encode(data)),
+            _ => None,
+        }
+    }
+
+    /// Create a media part referencing a remote URI instead of carrying inline data.
+    ///
+    /// Providers that can fetch media themselves (Gemini `fileData`, Anthropic and
+    /// OpenAI URL sources) receive the URI directly.
+    pub fn remote_media(
+        media_type: MediaType,
+        mime_type: impl Into<String>,
+        uri: impl Into<String>,
+    ) -> Self {
+        Part::Media {
+            media_type,
+            data: Bytes::new(),
+            mime_type: mime_type.into(),
+            uri: Some(uri.into()),
+            finished: true,
+        }
+    }
+
+    /// Create a video part referencing a YouTube URL. Only supported by Gemini.
+    pub fn youtube(url: impl Into<String>) -> Self {
+        Self::remote_media(MediaType::Video, "video/*", url)
+    }
+
+    /// Estimated size of the part in a request body, in bytes: its text, its inline
+    /// media encoded as base64, or its JSON arguments or result. Framing added by the
+    /// provider's wire format is not counted.
+    pub fn estimated_bytes(&self) -> usize {
+        match self {
+            Part::Text { content, .. } => content.len(),
+            Part::Reasoning {
+                content, signature, ..
+            } => content.len() + signature.as_ref().map_or(0, String::len),
+            Part::FunctionCall { arguments, .. } => arguments.to_string().len(),
+            Part::FunctionResponse {
+                response, parts, ..
+            } => {
+                response.to_string().len() + parts.iter().map(Part::estimated_bytes).sum::<usize>()
+            }
+            Part::Media { data, uri, .. } if data.is_empty() => uri.as_ref().map_or(0, String::len),
+            Part::Media { data, .. } => data.len().div_ceil(3) * 4,
+        }
+    }
+
+    /// Whether this is a media part that references a remote URI without inline data.
+    pub fn is_remote_media(&self) -> bool {
+        matches!(self, Part::Media { data, uri: Some(_), .. } if data.is_empty())
+    }
+
     pub fn anchor_media(&self) -> String {
         match self {
             Part::Media { mime_type, uri, .. } => {
@@ -85,7 +605,18 @@ impl Part {
 }
 
 /// A single message in a conversation.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Only user and assistant turns exist. The system prompt is set with
+/// [`ModelOptions::with_system`](crate::options::ModelOptions::with_system), and tool
+/// results are [`Part::FunctionResponse`] parts of a user message; each provider maps
+/// them to its own system and tool roles.
+///
+/// Parts are sent to providers in the order they appear in the message, so text and
+/// media can be freely interleaved (e.g. text, image, text, image). Provider request
+/// builders never reorder parts; the only additions are the optional media anchors
+/// (see [`ModelOptions::anchor_media`](crate::options::ModelOptions::anchor_media)),
+/// which are placed directly before the media part they describe.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "role", content = "content")]
 pub enum Message {
     #[serde(rename = "user")]
@@ -95,6 +626,16 @@ pub enum Message {
 }
 
 impl Message {
+    /// Create a user message with a single text part.
+    pub fn user(text: impl Into<SharedString>) -> Self {
+        Message::User(vec![Part::text(text)])
+    }
+
+    /// Create an assistant message with a single text part.
+    pub fn assistant(text: impl Into<SharedString>) -> Self {
+        Message::Assistant(vec![Part::text(text)])
+    }
+
     /// Get the role of the message.
     pub fn role(&self) -> Role {
         match self {
@@ -137,12 +678,48 @@ impl Message {
             Some(text_parts.join("\n"))
         }
     }
+
+    /// Parse an OpenAI Chat Completions `messages` array.
+    ///
+    /// `tool` messages become function responses in the following user turn. System
+    /// and developer messages are skipped; set
+    /// [`ModelOptions::system`](crate::options::ModelOptions::system) instead.
+    #[cfg(feature = "openai")]
+    pub fn from_openai_json(value: Value) -> Result<Vec<Message>, ClientError> {
+        openai::messages_from_json(value)
+    }
+
+    /// Render messages as an OpenAI Chat Completions `messages` array.
+    #[cfg(feature = "openai")]
+    pub fn to_openai_json(messages: &[Message]) -> Result<Value, ClientError> {
+        let mut example = TrainingExample::new(messages.to_vec()).to_openai()?;
+        Ok(example["messages"].take())
+    }
+
+    /// Parse an Anthropic Messages API `messages` array.
+    #[cfg(feature = "anthropic")]
+    pub fn from_anthropic_json(value: Value) -> Result<Vec<Message>, ClientError> {
+        anthropic::messages_from_json(value)
+    }
+
+    /// Render messages as an Anthropic Messages API `messages` array.
+    #[cfg(feature = "anthropic")]
+    pub fn to_anthropic_json(messages: &[Message]) -> Result<Value, ClientError> {
+        let mut example = TrainingExample::new(messages.to_vec()).to_anthropic()?;
+        Ok(example["messages"].take())
+    }
+
+    /// Parse a Gemini `contents` array.
+    #[cfg(feature = "gemini")]
+    pub fn from_gemini_json(value: Value) -> Result<Vec<Message>, ClientError> {
+        gemini::messages_from_json(value)
+    }
 }
 
 /// Provider-agnostic request structure.
 /// Contains only model behavior parameters, not API configuration.
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GeneralRequest {
     /// Model identifier (e.g., "gpt-5", "claude-4.5-opus")
     pub model: String,
@@ -167,7 +744,10 @@ pub struct GeneralRequest {
 }
 
 /// Reason for finishing the response generation.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+///
+/// New reasons may be added in minor releases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum FinishReason {
     Stop,
     PromptTokens,
@@ -182,13 +762,23 @@ pub enum FinishReason {
 
 /// Token usage information.
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
 pub struct Usage {
     /// Total prompt tokens used
     pub prompt_tokens: Option<u32>,
 
     /// Total completion tokens used
     pub completion_tokens: Option<u32>,
+
+    /// Prompt tokens served from the provider's cache, included in `prompt_tokens`
+    pub cached_prompt_tokens: Option<u32>,
+
+    /// Tokens of a predicted output that appeared in the completion
+    pub accepted_prediction_tokens: Option<u32>,
+
+    /// Tokens of a predicted output that did not appear in the completion. They are
+    /// still billed as completion tokens
+    pub rejected_prediction_tokens: Option<u32>,
 }
 
 impl std::ops::Add for Usage {
@@ -204,6 +794,18 @@ impl std::ops::Add for Usage {
                 .completion_tokens
                 .map(|v| v + other.completion_tokens.unwrap_or(0))
                 .or(other.completion_tokens),
+            cached_prompt_tokens: self
+                .cached_prompt_tokens
+                .map(|v| v + other.cached_prompt_tokens.unwrap_or(0))
+                .or(other.cached_prompt_tokens),
+            accepted_prediction_tokens: self
+                .accepted_prediction_tokens
+                .map(|v| v + other.accepted_prediction_tokens.unwrap_or(0))
+                .or(other.accepted_prediction_tokens),
+            rejected_prediction_tokens: self
+                .rejected_prediction_tokens
+                .map(|v| v + other.rejected_prediction_tokens.unwrap_or(0))
+                .or(other.rejected_prediction_tokens),
         }
     }
 }
@@ -215,8 +817,13 @@ impl std::ops::AddAssign for Usage {
 }
 
 /// Provider-agnostic response structure.
+///
+/// Parts are in the order the provider generated them (its content block order), so
+/// reasoning, text and tool calls interleave exactly as they were produced. While
+/// streaming, parts are only ever appended or updated in place: a [`PartIndex`] taken
+/// from one snapshot designates the same part in every later snapshot.
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Response {
     /// Generated messages (typically one assistant message, but can be multiple)
     pub data: Vec<Message>,
@@ -226,17 +833,96 @@ pub struct Response {
 
     /// Finish reason for the response generation
     pub finish: FinishReason,
+
+    /// Stop sequence that ended generation, if the provider reports it
+    pub stop_sequence: Option<String>,
+
+    /// Tier that served the request, if the provider reports it
+    pub service_tier: Option<ServiceTier>,
+}
+
+impl Response {
+    /// Parts of every message with their position, in order.
+    pub fn indexed_parts(&self) -> impl Iterator<Item = (PartIndex, &Part)> {
+        self.data.iter().enumerate().flat_map(|(message, m)| {
+            m.parts()
+                .iter()
+                .enumerate()
+                .map(move |(part, p)| (PartIndex { message, part }, p))
+        })
+    }
+
+    /// Part at `index`, if the response has it.
+    pub fn part(&self, index: PartIndex) -> Option<&Part> {
+        self.data.get(index.message)?.parts().get(index.part)
+    }
+}
+
+/// Position of a part in a [`Response`]: the message in `data`, and the part in
+/// that message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PartIndex {
+    pub message: usize,
+    pub part: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_text_annotations() {
+        let text = "Use `map`:\n\n```rust\nlet x = 1;\n```\nDone.";
+        let mut part = Part::text(text);
+        if let Part::Text { extensions, .. } = &mut part {
+            extensions.insert(
+                "annotations".to_string(),
+                serde_json::json!([{
+                    "type": "url_citation",
+                    "url_citation": { "start_index": 0, "end_index": 10, "url": "https://docs.rs", "title": "Docs" }
+                }]),
+            );
+        }
+        part.annotate();
+
+        let Part::Text { annotations, .. } = &part else {
+            unreachable!()
+        };
+        assert!(annotations.markdown);
+        assert_eq!(annotations.code_blocks.len(), 1);
+        let block = &annotations.code_blocks[0];
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(&text[block.range.clone()], "let x = 1;\n");
+        assert_eq!(
+            annotations.citations,
+            vec![Citation {
+                range: Some(0..10),
+                url: Some("https://docs.rs".to_string()),
+                title: Some("Docs".to_string()),
+                cited_text: None,
+            }]
+        );
+
+        assert!(TextAnnotations::detect("Just a plain sentence. Nothing else.").is_empty());
+    }
+
+    #[test]
+    fn test_shared_string_copy_on_write() {
+        let mut text = SharedString::from("Hello");
+        let snapshot = text.clone();
+        text.push_str(", world");
+
+        assert_eq!(snapshot, "Hello");
+        assert_eq!(text, "Hello, world");
+        assert_eq!(serde_json::to_value(&text).unwrap(), "Hello, world");
+        assert_eq!(String::from(snapshot), "Hello");
+    }
+
     #[test]
     fn test_anchor_media() {
         let part = Part::Media {
             media_type: MediaType::Document,
-            data: "base64data".to_string(),
+            data: Bytes::from_static(b"%PDF"),
             mime_type: "application/pdf".to_string(),
             uri: Some("file:///path/to/doc.pdf".to_string()),
             finished: true,
@@ -248,11 +934,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_media_data_serializes_as_base64() {
+        let part = Part::media(
+            MediaType::Image,
+            "image/png",
+            Bytes::from_static(b"\x89PNG"),
+        );
+
+        let value = serde_json::to_value(&part).unwrap();
+        assert_eq!(value["data"]["data"], "iVBORw==");
+        assert_eq!(part.base64_data().as_deref(), Some("iVBORw=="));
+        assert_eq!(serde_json::from_value::<Part>(value).unwrap(), part);
+        assert_eq!(
+            Part::media_base64(MediaType::Image, "image/png", "iVBORw==").unwrap(),
+            part
+        );
+    }
+
     #[test]
     fn test_anchor_media_no_uri() {
         let part = Part::Media {
             media_type: MediaType::Image,
-            data: "base64data".to_string(),
+            data: Bytes::from_static(b"\x89PNG"),
             mime_type: "image/png".to_string(),
             uri: None,
             finished: true,
@@ -260,4 +964,117 @@ mod tests {
 
         assert_eq!(part.anchor_media(), "File (image/png) at unknown:");
     }
+
+    #[test]
+    #[cfg(feature = "openai")]
+    fn test_openai_json_round_trip() {
+        let json = serde_json::json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Weather in Paris and Rome?" },
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [
+                    { "id": "a", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Paris\"}" } },
+                    { "id": "b", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Rome\"}" } }
+                ]
+            },
+            { "role": "tool", "tool_call_id": "a", "content": "{\"temp\":21}" },
+            { "role": "tool", "tool_call_id": "b", "content": "sunny" },
+            { "role": "assistant", "content": "21°C in Paris, sunny in Rome." }
+        ]);
+
+        let messages = Message::from_openai_json(json.clone()).unwrap();
+
+        assert_eq!(messages.len(), 4);
+        match &messages[2].parts()[1] {
+            Part::FunctionResponse { name, response, .. } => {
+                assert_eq!(name, "weather");
+                assert_eq!(response, &serde_json::json!({ "response": "sunny" }));
+            }
+            other => panic!("Expected function response, got {:?}", other),
+        }
+
+        let exported = Message::to_openai_json(&messages).unwrap();
+        let roles: Vec<&str> = exported
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            roles,
+            vec!["user", "assistant", "tool", "tool", "assistant"]
+        );
+        assert_eq!(exported[1]["tool_calls"], json[2]["tool_calls"]);
+        assert_eq!(exported[2]["tool_call_id"], "a");
+    }
+
+    #[test]
+    #[cfg(feature = "anthropic")]
+    fn test_anthropic_json_round_trip() {
+        let json = serde_json::json!([
+            { "role": "user", "content": "Weather in Paris?" },
+            {
+                "role": "assistant",
+                "content": [
+                    { "type": "text", "text": "Checking." },
+                    { "type": "tool_use", "id": "a", "name": "weather", "input": { "city": "Paris" } }
+                ]
+            },
+            {
+                "role": "user",
+                "content": [
+                    { "type": "tool_result", "tool_use_id": "a", "content": "{\"temp\":21}" }
+                ]
+            }
+        ]);
+
+        let messages = Message::from_anthropic_json(json).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].content().as_deref(), Some("Weather in Paris?"));
+        match &messages[2].parts()[0] {
+            Part::FunctionResponse { name, response, .. } => {
+                assert_eq!(name, "weather");
+                assert_eq!(response, &serde_json::json!({ "temp": 21 }));
+            }
+            other => panic!("Expected function response, got {:?}", other),
+        }
+
+        let exported = Message::to_anthropic_json(&messages).unwrap();
+        assert_eq!(exported[1]["content"][1]["type"], "tool_use");
+        assert_eq!(exported[2]["content"][0]["tool_use_id"], "a");
+    }
+
+    #[test]
+    #[cfg(feature = "gemini")]
+    fn test_gemini_json_import() {
+        let json = serde_json::json!([
+            { "role": "user", "parts": [{ "text": "Weather in Paris?" }] },
+            {
+                "role": "model",
+                "parts": [{ "functionCall": { "name": "weather", "args": { "city": "Paris" } } }]
+            },
+            {
+                "role": "user",
+                "parts": [
+                    { "functionResponse": { "name": "weather", "response": { "temp": 21 } } },
+                    { "inlineData": { "mimeType": "image/png", "data": "iVBORw0KGgo=" } }
+                ]
+            }
+        ]);
+
+        let messages = Message::from_gemini_json(json).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[1], Message::Assistant(_)));
+        assert!(matches!(
+            &messages[2].parts()[1],
+            Part::Media {
+                media_type: MediaType::Image,
+                ..
+            }
+        ));
+    }
 }
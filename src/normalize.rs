@@ -0,0 +1,214 @@
+//! Post-processing of provider responses.
+//!
+//! Some models leave artifacts in their output that every caller has to clean up:
+//! XML tags such as `<thinking>`, stray whitespace, characters mangled by a wrong
+//! encoding. A [`ResponseNormalizer`] fixes such quirks once, and wrapping a client in
+//! [`Normalized`] applies it to every response and streamed snapshot of that client.
+//! Closures taking `&mut Response` are normalizers.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{Message, Part, Response};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Rewrites responses in place.
+///
+/// Streamed responses are normalized snapshot by snapshot, so normalizers must
+/// accept partial content, e.g. a tag that is opened but not closed yet.
+pub trait ResponseNormalizer: Send + Sync {
+    fn normalize(&self, response: &mut Response);
+}
+
+impl<F> ResponseNormalizer for F
+where
+    F: Fn(&mut Response) + Send + Sync,
+{
+    fn normalize(&self, response: &mut Response) {
+        self(response)
+    }
+}
+
+/// Normalizer removing XML-style tags and their content from text parts, e.g.
+/// `<thinking>…</thinking>` emitted as plain text by some models.
+///
+/// A tag still open at the end of the text is removed up to the end, so that
+/// streamed snapshots never show its content. Text parts left empty are dropped.
+#[derive(Debug, Clone)]
+pub struct StripTags {
+    tags: Vec<String>,
+}
+
+impl StripTags {
+    pub fn new<I, S>(tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// `text` without the configured tags.
+    pub fn strip(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for tag in &self.tags {
+            let open = format!("<{}>", tag);
+            let close = format!("</{}>", tag);
+            while let Some(start) = text.find(&open) {
+                let end = text[start..]
+                    .find(&close)
+                    .map_or(text.len(), |end| start + end + close.len());
+                text.replace_range(start..end, "");
+            }
+        }
+        text.trim().to_string()
+    }
+}
+
+impl ResponseNormalizer for StripTags {
+    fn normalize(&self, response: &mut Response) {
+        for message in &mut response.data {
+            if !matches!(message, Message::Assistant(_)) {
+                continue;
+            }
+            message.parts_mut().retain_mut(|part| match part {
+                Part::Text { content, .. } => {
+                    if self
+                        .tags
+                        .iter()
+                        .any(|tag| content.contains(&format!("<{}>", tag)))
+                    {
+                        *content = self.strip(content).into();
+                    }
+                    !content.is_empty()
+                }
+                _ => true,
+            });
+        }
+    }
+}
+
+/// Client wrapper applying [`ResponseNormalizer`]s to every response, in the order
+/// they were added.
+pub struct Normalized<C> {
+    client: C,
+    normalizers: Vec<Arc<dyn ResponseNormalizer>>,
+}
+
+impl<C> Normalized<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            normalizers: Vec::new(),
+        }
+    }
+
+    /// Add a normalizer, run after those added before.
+    pub fn with_normalizer<N: ResponseNormalizer + 'static>(mut self, normalizer: N) -> Self {
+        self.normalizers.push(Arc::new(normalizer));
+        self
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+fn normalize(normalizers: &[Arc<dyn ResponseNormalizer>], mut response: Response) -> Response {
+    for normalizer in normalizers {
+        normalizer.normalize(&mut response);
+    }
+    response
+}
+
+#[async_trait]
+impl<C: Client> Client for Normalized<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let response = self.client.request(messages, tools).await?;
+        Ok(normalize(&self.normalizers, response))
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[async_trait]
+impl<C: StreamingClient> StreamingClient for Normalized<C> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let stream = self.client.request_stream(messages, tools).await?;
+        let normalizers = self.normalizers.clone();
+        Ok(Box::pin(stream.map(move |item| {
+            item.map(|response| normalize(&normalizers, response))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FinishReason;
+
+    #[test]
+    fn test_strip_tags() {
+        let strip = StripTags::new(["thinking"]);
+        assert_eq!(
+            strip.strip("<thinking>Plan it.</thinking>\nThe answer is 4."),
+            "The answer is 4."
+        );
+        assert_eq!(strip.strip("Sure. <thinking>Still plann"), "Sure.");
+
+        let mut response = Response {
+            data: vec![
+                Message::assistant("<thinking>Hmm</thinking>"),
+                Message::assistant("Hello <thinking>x</thinking>there"),
+            ],
+            usage: Default::default(),
+            finish: FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        };
+        let upper = |response: &mut Response| {
+            for message in &mut response.data {
+                for part in message.parts_mut() {
+                    if let Part::Text { content, .. } = part {
+                        *content = content.to_uppercase().into();
+                    }
+                }
+            }
+        };
+        response = normalize(&[Arc::new(strip), Arc::new(upper)], response);
+        assert!(response.data[0].parts().is_empty());
+        assert_eq!(response.data[1].content().as_deref(), Some("HELLO THERE"));
+    }
+}
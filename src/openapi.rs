@@ -0,0 +1,607 @@
+//! Tools calling a REST API described by an OpenAPI 3 spec.
+//!
+//! [`OpenApiTools`] turns every operation of a spec into a [`Tool`]: path, query and
+//! header parameters become properties of the input schema, and a JSON request body
+//! becomes its `body` property. Calls are executed as HTTP requests against the API,
+//! so an agent can use the API through [`Agent::with_server`](crate::agent::Agent::with_server)
+//! without an MCP server in between. Only JSON specs are read; convert YAML specs
+//! first.
+
+use async_trait::async_trait;
+use reqwest::Method;
+use rmcp::model::{GetPromptResult, Prompt, ReadResourceResult, Resource, Tool};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::http::build_http_client;
+use crate::mcp::{MCPError, MCPServer, Servable, Served};
+use crate::model::Part;
+use crate::options::TransportOptions;
+use crate::tools::{ToolError, ToolErrorKind, ToolService};
+
+/// Maximum depth of `$ref`s followed when inlining schemas, which bounds recursive
+/// schemas.
+const MAX_REF_DEPTH: usize = 8;
+
+/// Errors returned while reading an OpenAPI spec.
+#[derive(Error, Debug)]
+pub enum OpenApiError {
+    #[error("Invalid OpenAPI spec: {0}")]
+    Invalid(String),
+
+    #[error("HTTP client error: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// Credentials sent with every call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenApiAuth {
+    /// `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// An API key in a header, e.g. `X-Api-Key`.
+    Header { name: String, value: String },
+    /// An API key in the query string.
+    Query { name: String, value: String },
+}
+
+/// Where an operation parameter is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Path,
+    Query,
+    Header,
+}
+
+#[derive(Debug, Clone)]
+struct Parameter {
+    name: String,
+    location: Location,
+}
+
+/// An operation of the spec, exposed as a tool.
+#[derive(Debug, Clone)]
+struct Operation {
+    tool: Tool,
+    method: Method,
+    path: String,
+    parameters: Vec<Parameter>,
+    body: bool,
+}
+
+/// Tools executing the operations of an OpenAPI 3 spec over HTTP.
+#[derive(Debug, Clone)]
+pub struct OpenApiTools {
+    operations: Vec<Operation>,
+    base_url: Option<String>,
+    auth: Option<OpenApiAuth>,
+    allowed: Option<HashSet<String>>,
+    http_client: reqwest::Client,
+}
+
+impl OpenApiTools {
+    /// Read the operations of `spec`. The base URL is the first absolute URL in
+    /// `servers`, if any.
+    pub fn from_spec(spec: &Value) -> Result<Self, OpenApiError> {
+        let paths = spec
+            .get("paths")
+            .and_then(Value::as_object)
+            .ok_or_else(|| OpenApiError::Invalid("missing paths".to_string()))?;
+
+        let mut operations = Vec::new();
+        for (path, item) in paths {
+            let item = resolve(item, spec, 0);
+            let shared = item.get("parameters").cloned().unwrap_or(json!([]));
+            for method in ["get", "put", "post", "delete", "patch", "head", "options"] {
+                if let Some(operation) = item.get(method) {
+                    operations.push(Operation::read(path, method, operation, &shared, spec)?);
+                }
+            }
+        }
+
+        let base_url = spec
+            .get("servers")
+            .and_then(Value::as_array)
+            .and_then(|servers| {
+                servers
+                    .iter()
+                    .filter_map(|server| server.get("url")?.as_str())
+                    .find(|url| url.starts_with("http://") || url.starts_with("https://"))
+            })
+            .map(|url| url.trim_end_matches('/').to_string());
+
+        Ok(Self {
+            operations,
+            base_url,
+            auth: None,
+            allowed: None,
+            http_client: build_http_client(&TransportOptions::default())?,
+        })
+    }
+
+    /// Send calls to `base_url` instead of the server of the spec.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn with_auth(mut self, auth: OpenApiAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Only expose the operations with the given tool names (their `operationId`).
+    pub fn with_allowed_operations(
+        mut self,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.allowed = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Send calls with a client configured by `transport_options`.
+    pub fn with_transport_options(
+        mut self,
+        transport_options: &TransportOptions,
+    ) -> Result<Self, OpenApiError> {
+        self.http_client = build_http_client(transport_options)?;
+        Ok(self)
+    }
+
+    /// Tool definitions of the allowed operations.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.allowed_operations()
+            .map(|operation| operation.tool.clone())
+            .collect()
+    }
+
+    fn allowed_operations(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().filter(|operation| {
+            self.allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(operation.tool.name.as_ref()))
+        })
+    }
+
+    /// HTTP request for a call of the tool `name`.
+    fn request(&self, name: &str, args: &Value) -> Result<reqwest::Request, ToolError> {
+        let operation = self
+            .allowed_operations()
+            .find(|operation| operation.tool.name == name)
+            .ok_or_else(|| {
+                ToolError::new(ToolErrorKind::NotFound, format!("Unknown tool {}", name))
+            })?;
+        let base_url = self.base_url.as_deref().ok_or_else(|| {
+            ToolError::new(
+                ToolErrorKind::Unavailable,
+                "The spec has no absolute server URL, set one with with_base_url",
+            )
+        })?;
+        let empty = Map::new();
+        let args = match args {
+            Value::Object(args) => args,
+            Value::Null => &empty,
+            _ => return Err(ToolError::invalid_arguments("Arguments must be an object")),
+        };
+
+        let mut path = operation.path.clone();
+        let mut query = Vec::new();
+        let mut headers = Vec::new();
+        for parameter in &operation.parameters {
+            let Some(value) = args.get(&parameter.name).filter(|value| !value.is_null()) else {
+                if parameter.location == Location::Path {
+                    return Err(ToolError::invalid_arguments(format!(
+                        "Missing path parameter {}",
+                        parameter.name
+                    )));
+                }
+                continue;
+            };
+            match parameter.location {
+                Location::Path => {
+                    let placeholder = format!("{{{}}}", parameter.name);
+                    path = path.replace(&placeholder, &encode_segment(&to_param(value)));
+                }
+                Location::Query => match value {
+                    Value::Array(items) => query.extend(
+                        items
+                            .iter()
+                            .map(|item| (parameter.name.clone(), to_param(item))),
+                    ),
+                    value => query.push((parameter.name.clone(), to_param(value))),
+                },
+                Location::Header => headers.push((parameter.name.clone(), to_param(value))),
+            }
+        }
+
+        let mut request = self
+            .http_client
+            .request(operation.method.clone(), format!("{}{}", base_url, path));
+        match &self.auth {
+            Some(OpenApiAuth::Bearer(token)) => request = request.bearer_auth(token),
+            Some(OpenApiAuth::Header { name, value }) => request = request.header(name, value),
+            Some(OpenApiAuth::Query { name, value }) => query.push((name.clone(), value.clone())),
+            None => {}
+        }
+        if !query.is_empty() {
+            request = request.query(&query);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = args.get("body").filter(|_| operation.body) {
+            request = request.json(body);
+        }
+        request
+            .build()
+            .map_err(|e| ToolError::invalid_arguments(e.to_string()))
+    }
+
+    /// Execute a call, returning the JSON (or text) body of a successful response.
+    async fn execute(&self, name: &str, args: &Value) -> Result<Value, ToolError> {
+        let request = self.request(name, args)?;
+        let text = send(&self.http_client, request).await?;
+        Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+    }
+}
+
+/// Send `request`, returning the body of a successful response. Failed requests and
+/// error statuses are tool errors, retryable for timeouts, 429 and 5xx.
+pub(crate) async fn send(
+    http_client: &reqwest::Client,
+    request: reqwest::Request,
+) -> Result<String, ToolError> {
+    let response = http_client.execute(request).await.map_err(|e| {
+        let kind = if e.is_timeout() {
+            ToolErrorKind::Timeout
+        } else {
+            ToolErrorKind::Unavailable
+        };
+        ToolError::new(kind, e.to_string())
+    })?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ToolError::new(ToolErrorKind::Unavailable, e.to_string()))?;
+    if !status.is_success() {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        return Err(
+            ToolError::execution(format!("HTTP {}: {}", status, text)).with_retryable(retryable)
+        );
+    }
+    Ok(text)
+}
+
+impl Operation {
+    fn read(
+        path: &str,
+        method: &str,
+        operation: &Value,
+        shared: &Value,
+        spec: &Value,
+    ) -> Result<Self, OpenApiError> {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        let mut parameters: Vec<Parameter> = Vec::new();
+
+        let declared = shared
+            .as_array()
+            .into_iter()
+            .chain(operation.get("parameters").and_then(Value::as_array))
+            .flatten();
+        for parameter in declared {
+            let parameter = resolve(parameter, spec, 0);
+            let name = parameter
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    OpenApiError::Invalid(format!("parameter without name in {}", path))
+                })?;
+            let location = match parameter.get("in").and_then(Value::as_str) {
+                Some("path") => Location::Path,
+                Some("query") => Location::Query,
+                Some("header") => Location::Header,
+                _ => continue,
+            };
+
+            let mut schema = parameter
+                .get("schema")
+                .map(|schema| resolve(schema, spec, 0))
+                .unwrap_or_else(|| json!({ "type": "string" }));
+            if let (Some(description), Value::Object(fields)) =
+                (parameter.get("description"), &mut schema)
+            {
+                fields
+                    .entry("description")
+                    .or_insert_with(|| description.clone());
+            }
+            // Operation parameters override path-level ones of the same name.
+            parameters.retain(|p: &Parameter| p.name != name);
+            parameters.push(Parameter {
+                name: name.to_string(),
+                location,
+            });
+            properties.insert(name.to_string(), schema);
+            if location == Location::Path || parameter.get("required") == Some(&json!(true)) {
+                required.push(Value::String(name.to_string()));
+            }
+        }
+
+        let request_body = operation
+            .get("requestBody")
+            .map(|body| resolve(body, spec, 0));
+        let body_schema = request_body.as_ref().and_then(|body| {
+            let content = body.get("content")?.as_object()?;
+            let (_, media) = content
+                .iter()
+                .find(|(mime_type, _)| mime_type.contains("json"))?;
+            Some(resolve(media.get("schema")?, spec, 0))
+        });
+        if let Some(schema) = &body_schema {
+            properties.insert("body".to_string(), schema.clone());
+            if request_body.as_ref().and_then(|body| body.get("required")) == Some(&json!(true)) {
+                required.push(Value::String("body".to_string()));
+            }
+        }
+        required.dedup();
+
+        let name = match operation.get("operationId").and_then(Value::as_str) {
+            Some(id) => tool_name(id),
+            None => tool_name(&format!("{}_{}", method, path)),
+        };
+        let description = ["summary", "description"]
+            .iter()
+            .filter_map(|key| operation.get(*key)?.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let mut schema = Map::new();
+        schema.insert("type".to_string(), json!("object"));
+        schema.insert("properties".to_string(), Value::Object(properties));
+        if !required.is_empty() {
+            schema.insert("required".to_string(), Value::Array(required));
+        }
+
+        let mut tool = Tool::new(name, description.clone(), Arc::new(schema));
+        if description.is_empty() {
+            tool.description = None;
+        }
+        Ok(Self {
+            tool,
+            method: Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|e| OpenApiError::Invalid(e.to_string()))?,
+            path: path.to_string(),
+            parameters,
+            body: body_schema.is_some(),
+        })
+    }
+}
+
+/// `value` with local `$ref`s (`#/components/...`) inlined, up to [`MAX_REF_DEPTH`].
+fn resolve(value: &Value, spec: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(fields) => {
+            if let Some(reference) = fields.get("$ref").and_then(Value::as_str) {
+                let target = reference
+                    .strip_prefix('#')
+                    .and_then(|pointer| spec.pointer(pointer));
+                return match target {
+                    Some(target) if depth < MAX_REF_DEPTH => resolve(target, spec, depth + 1),
+                    _ => json!({}),
+                };
+            }
+            Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), resolve(value, spec, depth)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| resolve(item, spec, depth))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Tool name accepted by every provider: letters, digits, `_` and `-`, at most 64
+/// characters.
+pub(crate) fn tool_name(name: &str) -> String {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            sanitized.push(c);
+        } else if !sanitized.is_empty() && !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    let sanitized = sanitized.trim_end_matches('_');
+    sanitized.chars().take(64).collect()
+}
+
+fn to_param(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Percent-encode a path segment.
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[async_trait]
+impl ToolService for OpenApiTools {
+    async fn list_tools(&self) -> Result<Vec<Tool>, ToolError> {
+        Ok(self.tools())
+    }
+
+    async fn call_tool(&self, name: String, args: Value) -> Result<Value, ToolError> {
+        self.execute(&name, &args).await
+    }
+}
+
+#[async_trait]
+impl MCPServer for OpenApiTools {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        Ok(self.tools().into_iter().map(|t| t.served(None)).collect())
+    }
+
+    async fn call_tool(
+        &self,
+        name: String,
+        args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        Ok(match self.execute(&name, &args).await {
+            Ok(response) => Part::FunctionResponse {
+                id: None,
+                name,
+                response,
+                parts: vec![],
+                error: None,
+                finished: true,
+            },
+            Err(error) => Part::function_error(None, name, error),
+        })
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn get_prompt(
+        &self,
+        prompt: &Served<Prompt>,
+        _args: Option<Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        Err(MCPError::PromptNotFound(prompt.value.name.clone()))
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn read_resource(
+        &self,
+        resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        Err(MCPError::ResourceNotFound(resource.value.uri.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "servers": [{ "url": "https://petstore.example.com/v1/" }],
+            "paths": {
+                "/pets/{petId}": {
+                    "parameters": [
+                        { "name": "petId", "in": "path", "required": true, "schema": { "type": "string" } }
+                    ],
+                    "get": {
+                        "operationId": "getPet",
+                        "summary": "Get a pet",
+                        "parameters": [
+                            { "name": "fields", "in": "query", "schema": { "type": "array", "items": { "type": "string" } } },
+                            { "name": "X-Trace", "in": "header", "description": "Trace id" }
+                        ]
+                    },
+                    "put": {
+                        "operationId": "updatePet",
+                        "requestBody": {
+                            "required": true,
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Pet" } } }
+                        }
+                    }
+                },
+                "/pets": { "get": { "summary": "List pets" } }
+            },
+            "components": {
+                "schemas": {
+                    "Pet": { "type": "object", "properties": { "name": { "type": "string" } } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_operations_become_tools() {
+        let tools = OpenApiTools::from_spec(&spec()).unwrap().tools();
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_ref()).collect();
+        assert_eq!(names, vec!["get_pets", "getPet", "updatePet"]);
+
+        let get_pet = &tools[1];
+        assert_eq!(get_pet.description.as_deref(), Some("Get a pet"));
+        let schema = Value::Object(get_pet.input_schema.as_ref().clone());
+        assert_eq!(schema["required"], json!(["petId"]));
+        assert_eq!(
+            schema["properties"]["X-Trace"],
+            json!({ "type": "string", "description": "Trace id" })
+        );
+
+        let update_pet = Value::Object(tools[2].input_schema.as_ref().clone());
+        assert_eq!(update_pet["required"], json!(["petId", "body"]));
+        assert_eq!(
+            update_pet["properties"]["body"]["properties"]["name"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn test_calls_become_requests() {
+        let tools = OpenApiTools::from_spec(&spec())
+            .unwrap()
+            .with_auth(OpenApiAuth::Bearer("secret".to_string()))
+            .with_allowed_operations(["getPet", "updatePet"]);
+
+        let request = tools
+            .request(
+                "getPet",
+                &json!({ "petId": "a b", "fields": ["name", "age"], "X-Trace": "t1" }),
+            )
+            .unwrap();
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.url().as_str(),
+            "https://petstore.example.com/v1/pets/a%20b?fields=name&fields=age"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer secret");
+        assert_eq!(request.headers()["x-trace"], "t1");
+
+        let request = tools
+            .request(
+                "updatePet",
+                &json!({ "petId": 1, "body": { "name": "Rex" } }),
+            )
+            .unwrap();
+        assert_eq!(request.method(), Method::PUT);
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some(br#"{"name":"Rex"}"#.as_slice())
+        );
+
+        let error = tools.request("get_pets", &json!({})).unwrap_err();
+        assert_eq!(error.kind, ToolErrorKind::NotFound);
+        let error = tools.request("getPet", &json!({})).unwrap_err();
+        assert_eq!(error.kind, ToolErrorKind::InvalidArguments);
+    }
+}
@@ -3,7 +3,13 @@
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
 use std::time::Duration;
+use thiserror::Error;
+
+use crate::catalog::{resolve_alias, warn_if_deprecated};
+use crate::constraints::{Constraints, PromptStyle};
 
 /// Generic model options containing common model behavior parameters
 /// and provider-specific model configuration.
@@ -35,6 +41,50 @@ pub struct ModelOptions<T> {
     /// Limits the length of the response.
     pub max_tokens: Option<u32>,
 
+    /// Prefix every media part with a textual anchor (see [`Part::anchor_media`](crate::model::Part::anchor_media)).
+    /// The anchor lets the model refer to files by URI; disable it to send media parts verbatim.
+    /// Defaults to `true`.
+    pub anchor_media: Option<bool>,
+
+    /// Run [`normalize_history`](crate::history::normalize_history) on the conversation
+    /// before every request. Defaults to `false`.
+    pub normalize_history: Option<bool>,
+
+    /// Ask the provider to enforce tool input schemas exactly (OpenAI `strict` mode).
+    /// Schemas are sanitized for the strict dialect (see [`crate::schema`]). Defaults to `false`.
+    pub strict_tools: Option<bool>,
+
+    /// Processing tier to request, trading latency against price. Providers without
+    /// the tier ignore it; [`validate`](Self::validate) reports them.
+    pub service_tier: Option<ServiceTier>,
+
+    /// Strictness of the provider's content filters. Providers map it to their own
+    /// settings (Gemini `safetySettings`), and a raw per-provider setting takes
+    /// precedence. Providers without configurable filters report it in
+    /// [`validate`](Self::validate) unless it is [`SafetyLevel::Default`].
+    pub safety: Option<SafetyLevel>,
+
+    /// Shape the answer must take: any JSON, JSON valid against a schema, or one of a
+    /// fixed set of strings. Providers map it to their own settings (Gemini
+    /// `responseMimeType` and response schemas), and raw per-provider settings take
+    /// precedence. Providers without constrained output report it in
+    /// [`validate`](Self::validate).
+    pub output: Option<OutputFormat>,
+
+    /// Language and formatting rules appended to the system prompt, see
+    /// [`system_prompt`](Self::system_prompt).
+    pub constraints: Option<Constraints>,
+
+    /// Forward images returned by tools in a user message following the tool results,
+    /// for APIs whose tool results can only hold text (OpenAI Chat Completions).
+    /// Defaults to `true`; when disabled, the images are replaced by a placeholder.
+    pub forward_tool_media: Option<bool>,
+
+    /// Send the snapshot an alias currently points to (see
+    /// [`resolve_alias`](crate::catalog::resolve_alias)) instead of the alias itself,
+    /// so that requests keep using the same model when the alias moves. Defaults to `false`.
+    pub resolve_aliases: Option<bool>,
+
     /// Provider-specific model options.
     /// Contains fields unique to the specific provider (e.g., `top_k` for Anthropic/Gemini).
     pub provider: T,
@@ -49,11 +99,314 @@ impl<T: Default> ModelOptions<T> {
             temperature: None,
             top_p: None,
             max_tokens: None,
+            anchor_media: None,
+            normalize_history: None,
+            strict_tools: None,
+            service_tier: None,
+            safety: None,
+            output: None,
+            constraints: None,
+            forward_tool_media: None,
+            resolve_aliases: None,
             provider: T::default(),
         }
     }
 }
 
+impl<T> ModelOptions<T> {
+    /// Whether media parts should be preceded by their text anchor.
+    pub fn anchors_media(&self) -> bool {
+        self.anchor_media.unwrap_or(true)
+    }
+
+    /// Model id to send, with its alias resolved if enabled.
+    ///
+    /// Logs a warning the first time a deprecated model is requested.
+    pub fn request_model(&self) -> String {
+        let model = match self.resolve_aliases {
+            Some(true) => resolve_alias(&self.model).unwrap_or(&self.model),
+            _ => &self.model,
+        };
+        warn_if_deprecated(model);
+        model.to_string()
+    }
+
+    /// Set the system instructions.
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Enable or disable reasoning.
+    pub fn with_reasoning(mut self, reasoning: bool) -> Self {
+        self.reasoning = Some(reasoning);
+        self
+    }
+
+    /// Set the sampling temperature.
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the top-p sampling parameter.
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set the maximum number of tokens to generate.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the processing tier.
+    pub fn with_service_tier(mut self, service_tier: ServiceTier) -> Self {
+        self.service_tier = Some(service_tier);
+        self
+    }
+
+    /// Set the strictness of content filters.
+    pub fn with_safety(mut self, safety: SafetyLevel) -> Self {
+        self.safety = Some(safety);
+        self
+    }
+
+    /// Set the shape of the answer.
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Set the language and formatting constraints of answers.
+    pub fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.constraints = Some(constraints);
+        self
+    }
+
+    /// System prompt to send: [`system`](Self::system) followed by the
+    /// [`constraints`](Self::constraints) worded in `style`.
+    pub fn system_prompt(&self, style: PromptStyle) -> Option<String> {
+        let constraints = self
+            .constraints
+            .as_ref()
+            .and_then(|constraints| constraints.render(style));
+        match (&self.system, constraints) {
+            (Some(system), Some(constraints)) => Some(format!("{}\n\n{}", system, constraints)),
+            (system, constraints) => system.clone().or(constraints),
+        }
+    }
+
+    /// Set the provider-specific options.
+    pub fn with_provider(mut self, provider: T) -> Self {
+        self.provider = provider;
+        self
+    }
+}
+
+impl<T: ProviderOptions> ModelOptions<T> {
+    /// Check the options against the ranges and constraints of the provider.
+    ///
+    /// Every violation is reported at once, so callers can fix their configuration
+    /// without going through one provider `400` at a time.
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        let mut violations = Vec::new();
+
+        if self.model.is_empty() {
+            violations.push(OptionViolation::new("model", "must not be empty"));
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                violations.push(OptionViolation::new(
+                    "temperature",
+                    format!("must be between 0.0 and 2.0, got {}", temperature),
+                ));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                violations.push(OptionViolation::new(
+                    "top_p",
+                    format!("must be between 0.0 and 1.0, got {}", top_p),
+                ));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            violations.push(OptionViolation::new("max_tokens", "must be positive"));
+        }
+        if matches!(&self.output, Some(OutputFormat::Enum(values)) if values.is_empty()) {
+            violations.push(OptionViolation::new(
+                "output",
+                "must allow at least one value",
+            ));
+        }
+
+        T::validate(self, &mut violations);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(OptionsError { violations })
+        }
+    }
+
+    /// Set temperature, top-p and top-k from a named preset, replacing earlier values.
+    pub fn with_preset(mut self, preset: SamplingPreset) -> Self {
+        T::apply_preset(&mut self, preset);
+        self
+    }
+
+    /// Finish building the options, rejecting them if [`validate`](Self::validate) fails.
+    pub fn build(self) -> Result<Self, OptionsError> {
+        self.validate().map(|()| self)
+    }
+}
+
+/// Processing tier of a request, trading latency against price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+    /// Let the provider decide, using priority capacity where the account has it.
+    Auto,
+    /// Regular on-demand capacity.
+    Standard,
+    /// Cheaper processing that may be slower or rejected under load (OpenAI, Groq).
+    Flex,
+    /// Faster processing at a higher price (OpenAI, Anthropic).
+    Priority,
+    /// Asynchronous batch processing. Only reported by providers, never requested.
+    Batch,
+}
+
+impl ServiceTier {
+    /// Parse a tier as reported in a provider response.
+    pub fn from_reported(tier: &str) -> Option<Self> {
+        match tier {
+            "auto" => Some(Self::Auto),
+            "default" | "standard" | "on_demand" => Some(Self::Standard),
+            "flex" => Some(Self::Flex),
+            "priority" | "scale" => Some(Self::Priority),
+            "batch" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// Provider-agnostic strictness of content safety filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyLevel {
+    /// Block content with even a low probability of being harmful.
+    Strict,
+    /// The provider's default filtering.
+    Default,
+    /// Block as little as the provider allows.
+    Permissive,
+}
+
+/// Provider-agnostic constraint on the shape of the answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Any JSON document.
+    Json,
+    /// A JSON document valid against the schema.
+    JsonSchema(serde_json::Value),
+    /// Exactly one of the values, as plain text.
+    Enum(Vec<String>),
+}
+
+impl OutputFormat {
+    /// JSON valid against the schema of `T`.
+    pub fn json_schema_for<T: schemars::JsonSchema>() -> Self {
+        let schema = schemars::schema_for!(T);
+        Self::JsonSchema(serde_json::to_value(schema).unwrap_or_default())
+    }
+
+    /// One of `values`.
+    pub fn one_of<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::Enum(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Named sampling settings, applied with [`ModelOptions::with_preset`].
+///
+/// Each provider picks values following its vendor's guidance, so the same preset
+/// may set different parameters on different providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingPreset {
+    /// Focused, repeatable answers for extraction, classification and code.
+    Precise,
+    /// General-purpose chat.
+    Balanced,
+    /// Varied answers for brainstorming and creative writing.
+    Creative,
+}
+
+impl SamplingPreset {
+    /// Temperature of the preset on providers without specific guidance.
+    pub fn temperature(self) -> f32 {
+        match self {
+            Self::Precise => 0.2,
+            Self::Balanced => 0.7,
+            Self::Creative => 1.1,
+        }
+    }
+}
+
+/// Provider-specific constraints checked by [`ModelOptions::validate`].
+pub trait ProviderOptions: Sized {
+    /// Push a violation for every constraint of the provider the options break.
+    fn validate(_options: &ModelOptions<Self>, _violations: &mut Vec<OptionViolation>) {}
+
+    /// Set the sampling parameters of `preset`.
+    ///
+    /// Only the temperature is set unless the provider overrides this, since most
+    /// vendors advise against tuning both temperature and top-p.
+    fn apply_preset(options: &mut ModelOptions<Self>, preset: SamplingPreset) {
+        options.temperature = Some(preset.temperature());
+        options.top_p = None;
+    }
+}
+
+impl ProviderOptions for () {}
+
+/// A single constraint broken by a [`ModelOptions`] field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionViolation {
+    /// Name of the offending field, e.g. `temperature` or `provider.thinking_budget`.
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl OptionViolation {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for OptionViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Model options rejected by [`ModelOptions::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid model options: {}", .violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+pub struct OptionsError {
+    pub violations: Vec<OptionViolation>,
+}
+
 /// Transport configuration options.
 ///
 /// Controls how requests are sent over the network.
@@ -61,12 +414,45 @@ impl<T: Default> ModelOptions<T> {
 pub enum TransportOptions {
     /// HTTP transport configuration
     Http {
-        /// Request timeout. If None, default client timeout is used.
+        /// Total request timeout, including reading the whole (streamed) body.
+        /// If None, default client timeout is used.
         timeout: Option<Duration>,
+        /// Timeout for establishing the connection.
+        connect_timeout: Option<Duration>,
+        /// Maximum time between sending a streaming request and receiving the first
+        /// content. Streams can run far longer than this bound once they have started.
+        first_token_timeout: Option<Duration>,
+        /// Maximum time without receiving anything on an event stream, keep-alive
+        /// comments included. An idle stream is resumed if a reconnection policy allows
+        /// it and fails otherwise.
+        idle_timeout: Option<Duration>,
         /// HTTP proxy URL.
         proxy: Option<String>,
         /// Additional HTTP headers to send with every request.
         headers: Option<HashMap<String, String>>,
+        /// Application identified in the `User-Agent` header. Falls back to
+        /// [`AppInfo::global`] if None.
+        app: Option<AppInfo>,
+        /// Reconnection of interrupted event streams. If None, interrupted streams fail.
+        reconnect: Option<ReconnectPolicy>,
+        /// Idempotency key sent with requests to providers that support one.
+        idempotency_key: IdempotencyKey,
+        /// Largest request to send, in bytes, as estimated from the messages before
+        /// sending (see [`check_request_size`](crate::http::check_request_size)). If None,
+        /// any request is sent.
+        max_request_bytes: Option<usize>,
+        /// Largest number of completion tokens to stream before closing the stream
+        /// (see [`ResponseStreamExt::max_tokens`](crate::stream::ResponseStreamExt::max_tokens)),
+        /// for providers without a hard cap. If None, streams run until the provider ends them.
+        max_stream_tokens: Option<u32>,
+        /// End a stream interrupted by a transport error with the content received so
+        /// far (see [`ResponseStreamExt::recover_partial`](crate::stream::ResponseStreamExt::recover_partial))
+        /// instead of the error. Defaults to `false`.
+        recover_partial: Option<bool>,
+        /// OpenAI organization sent in the `OpenAI-Organization` header.
+        organization: Option<String>,
+        /// OpenAI project sent in the `OpenAI-Project` header.
+        project: Option<String>,
     },
 }
 
@@ -74,8 +460,19 @@ impl Default for TransportOptions {
     fn default() -> Self {
         TransportOptions::Http {
             timeout: None,
+            connect_timeout: None,
+            first_token_timeout: None,
+            idle_timeout: None,
             proxy: None,
             headers: None,
+            app: None,
+            reconnect: None,
+            idempotency_key: IdempotencyKey::default(),
+            max_request_bytes: None,
+            max_stream_tokens: None,
+            recover_partial: None,
+            organization: None,
+            project: None,
         }
     }
 }
@@ -94,6 +491,37 @@ impl TransportOptions {
         self
     }
 
+    /// Set the connect timeout.
+    pub fn with_connect_timeout(mut self, duration: Duration) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                connect_timeout, ..
+            } => *connect_timeout = Some(duration),
+        }
+        self
+    }
+
+    /// Set the time-to-first-token timeout for streaming requests.
+    pub fn with_first_token_timeout(mut self, duration: Duration) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                first_token_timeout,
+                ..
+            } => *first_token_timeout = Some(duration),
+        }
+        self
+    }
+
+    /// Time-to-first-token timeout for streaming requests, if configured.
+    pub fn first_token_timeout(&self) -> Option<Duration> {
+        match self {
+            TransportOptions::Http {
+                first_token_timeout,
+                ..
+            } => *first_token_timeout,
+        }
+    }
+
     /// Set the proxy.
     pub fn with_proxy(mut self, proxy_url: String) -> Self {
         match &mut self {
@@ -111,4 +539,261 @@ impl TransportOptions {
         }
         self
     }
+
+    /// Identify the application sending requests through this client.
+    pub fn with_app(mut self, info: AppInfo) -> Self {
+        match &mut self {
+            TransportOptions::Http { app, .. } => *app = Some(info),
+        }
+        self
+    }
+
+    /// Application identified by requests, either set on these options or globally.
+    pub fn app(&self) -> Option<AppInfo> {
+        match self {
+            TransportOptions::Http { app, .. } => app.clone().or_else(AppInfo::global),
+        }
+    }
+
+    /// Set the maximum time without receiving anything on an event stream.
+    pub fn with_idle_timeout(mut self, duration: Duration) -> Self {
+        match &mut self {
+            TransportOptions::Http { idle_timeout, .. } => *idle_timeout = Some(duration),
+        }
+        self
+    }
+
+    /// Idle timeout of event streams, if configured.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self {
+            TransportOptions::Http { idle_timeout, .. } => *idle_timeout,
+        }
+    }
+
+    /// Reconnect interrupted event streams according to `policy`.
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        match &mut self {
+            TransportOptions::Http { reconnect, .. } => *reconnect = Some(policy),
+        }
+        self
+    }
+
+    /// Reconnection policy for event streams, if configured.
+    pub fn reconnect(&self) -> Option<&ReconnectPolicy> {
+        match self {
+            TransportOptions::Http { reconnect, .. } => reconnect.as_ref(),
+        }
+    }
+
+    /// Set how idempotency keys are chosen.
+    pub fn with_idempotency_key(mut self, key: IdempotencyKey) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                idempotency_key, ..
+            } => *idempotency_key = key,
+        }
+        self
+    }
+
+    /// Set the largest request to send, in bytes.
+    pub fn with_max_request_bytes(mut self, limit: usize) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                max_request_bytes, ..
+            } => *max_request_bytes = Some(limit),
+        }
+        self
+    }
+
+    /// Largest request to send, in bytes, if limited.
+    pub fn max_request_bytes(&self) -> Option<usize> {
+        match self {
+            TransportOptions::Http {
+                max_request_bytes, ..
+            } => *max_request_bytes,
+        }
+    }
+
+    /// Set the largest number of completion tokens to stream.
+    pub fn with_max_stream_tokens(mut self, limit: u32) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                max_stream_tokens, ..
+            } => *max_stream_tokens = Some(limit),
+        }
+        self
+    }
+
+    /// Largest number of completion tokens to stream, if limited.
+    pub fn max_stream_tokens(&self) -> Option<u32> {
+        match self {
+            TransportOptions::Http {
+                max_stream_tokens, ..
+            } => *max_stream_tokens,
+        }
+    }
+
+    /// Enable or disable recovering the partial content of interrupted streams.
+    pub fn with_recover_partial(mut self, enabled: bool) -> Self {
+        match &mut self {
+            TransportOptions::Http {
+                recover_partial, ..
+            } => *recover_partial = Some(enabled),
+        }
+        self
+    }
+
+    /// Whether interrupted streams end with their partial content.
+    pub fn recover_partial(&self) -> bool {
+        match self {
+            TransportOptions::Http {
+                recover_partial, ..
+            } => recover_partial.unwrap_or(false),
+        }
+    }
+
+    /// Set the OpenAI organization requests are attributed to.
+    pub fn with_organization(mut self, id: impl Into<String>) -> Self {
+        match &mut self {
+            TransportOptions::Http { organization, .. } => *organization = Some(id.into()),
+        }
+        self
+    }
+
+    /// OpenAI organization requests are attributed to, if set.
+    pub fn organization(&self) -> Option<&str> {
+        match self {
+            TransportOptions::Http { organization, .. } => organization.as_deref(),
+        }
+    }
+
+    /// Set the OpenAI project requests are attributed to.
+    pub fn with_project(mut self, id: impl Into<String>) -> Self {
+        match &mut self {
+            TransportOptions::Http { project, .. } => *project = Some(id.into()),
+        }
+        self
+    }
+
+    /// OpenAI project requests are attributed to, if set.
+    pub fn project(&self) -> Option<&str> {
+        match self {
+            TransportOptions::Http { project, .. } => project.as_deref(),
+        }
+    }
+
+    /// Idempotency key for a new request, if one should be sent.
+    ///
+    /// A key set for the current call with
+    /// [`with_idempotency_key`](crate::http::with_idempotency_key) takes precedence.
+    /// Otherwise a fresh random key is generated, so every call gets its own key, which
+    /// is reused when its stream is resumed.
+    pub fn idempotency_key(&self) -> Option<String> {
+        if let Some(key) = crate::http::call_idempotency_key() {
+            return Some(key);
+        }
+        match self {
+            TransportOptions::Http {
+                idempotency_key, ..
+            } => match idempotency_key {
+                IdempotencyKey::Random => Some(uuid::Uuid::new_v4().to_string()),
+                IdempotencyKey::Disabled => None,
+            },
+        }
+    }
+}
+
+/// How the `Idempotency-Key` of a request is chosen.
+///
+/// Providers that honour the header process a key at most once, so a request that is
+/// sent again after a dropped connection is not billed or executed twice. To reuse a
+/// key across calls, e.g. when retrying a call yourself, set it for each attempt with
+/// [`with_idempotency_key`](crate::http::with_idempotency_key).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum IdempotencyKey {
+    /// Generate a random key for every call.
+    #[default]
+    Random,
+    /// Do not send an idempotency key unless one is set for the call.
+    Disabled,
+}
+
+/// How interrupted Server-Sent Events streams are resumed.
+///
+/// When a stream breaks after the server has sent event `id:`s, the request is sent
+/// again with a `Last-Event-ID` header so that the server can continue where it left
+/// off. Streams without event ids are never reconnected, since replaying the request
+/// would start a new generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Maximum number of reconnection attempts per stream.
+    pub max_attempts: u32,
+    /// Delay before reconnecting, unless the server sent a `retry:` hint.
+    pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn new(max_attempts: u32, delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            delay,
+        }
+    }
+}
+
+static GLOBAL_APP: RwLock<Option<AppInfo>> = RwLock::new(None);
+
+/// Identification of the application built on top of this crate.
+///
+/// Providers use the `User-Agent` to attribute traffic, so applications should identify
+/// themselves either per client via [`TransportOptions::with_app`] or once for the whole
+/// process via [`AppInfo::set_global`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppInfo {
+    pub name: String,
+    pub version: String,
+    pub url: Option<String>,
+}
+
+impl AppInfo {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            url: None,
+        }
+    }
+
+    /// Set the URL where the application can be found.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Use this application info for clients that do not set their own.
+    pub fn set_global(self) {
+        *GLOBAL_APP.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+
+    /// Application info set with [`AppInfo::set_global`], if any.
+    pub fn global() -> Option<AppInfo> {
+        GLOBAL_APP.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// `User-Agent` product token, e.g. `my-app/1.2.0 (+https://example.com)`.
+    pub fn product(&self) -> String {
+        match &self.url {
+            Some(url) => format!("{}/{} (+{})", self.name, self.version, url),
+            None => format!("{}/{}", self.name, self.version),
+        }
+    }
 }
@@ -0,0 +1,23 @@
+//! Commonly used types and traits.
+//!
+//! ```no_run
+//! use unia::prelude::*;
+//! ```
+//!
+//! Provider factories are only included for the enabled provider features.
+
+pub use crate::agent::Agent;
+pub use crate::client::{Client, ClientError, ClientExt, StreamingClient, StreamingClientExt};
+pub use crate::mcp::MCPServer;
+pub use crate::model::{Extensions, FinishReason, Message, Part, Response, TextAnnotations, Usage};
+pub use crate::options::{ModelOptions, TransportOptions};
+pub use crate::providers::Provider;
+pub use crate::stream::ResponseStreamExt;
+pub use crate::tools::{Tool, ToolError, ToolExt};
+
+#[cfg(feature = "anthropic")]
+pub use crate::providers::Anthropic;
+#[cfg(feature = "gemini")]
+pub use crate::providers::Gemini;
+#[cfg(feature = "openai")]
+pub use crate::providers::OpenAI;
@@ -32,33 +32,61 @@ pub trait Provider {
     ) -> Self::Client;
 }
 
+#[cfg(feature = "anthropic")]
 pub mod anthropic;
+#[cfg(feature = "openai")]
 pub mod deepseek;
+#[cfg(feature = "openai")]
 pub mod fireworks;
+#[cfg(feature = "gemini")]
 pub mod gemini;
+#[cfg(feature = "openai")]
 pub mod groq;
+#[cfg(feature = "openai")]
 pub mod hyperbolic;
+#[cfg(feature = "openai")]
 pub mod mistral;
+#[cfg(feature = "openai")]
 pub mod moonshot;
+#[cfg(feature = "openai")]
 pub mod ollama;
+#[cfg(feature = "openai")]
 pub mod openai;
+#[cfg(feature = "openai")]
 pub mod openrouter;
+#[cfg(feature = "openai")]
 pub mod perplexity;
+#[cfg(feature = "openai")]
 pub mod together;
+#[cfg(feature = "openai")]
 pub mod xai;
 
 // Re-export for convenience
+#[cfg(feature = "anthropic")]
 pub use anthropic::{Anthropic, AnthropicClient, AnthropicModel};
+#[cfg(feature = "openai")]
 pub use deepseek::{DeepSeek, DeepSeekClient, DeepSeekModel};
+#[cfg(feature = "openai")]
 pub use fireworks::{Fireworks, FireworksClient, FireworksModel};
+#[cfg(feature = "gemini")]
 pub use gemini::{Gemini, GeminiClient, GeminiModel};
+#[cfg(feature = "openai")]
 pub use groq::{Groq, GroqClient, GroqModel};
+#[cfg(feature = "openai")]
 pub use hyperbolic::{Hyperbolic, HyperbolicClient, HyperbolicModel};
+#[cfg(feature = "openai")]
 pub use mistral::{Mistral, MistralClient, MistralModel};
+#[cfg(feature = "openai")]
 pub use moonshot::{Moonshot, MoonshotClient, MoonshotModel};
+#[cfg(feature = "openai")]
 pub use ollama::{Ollama, OllamaClient, OllamaModel};
-pub use openai::{OpenAI, OpenAIClient, OpenAIModel};
+#[cfg(feature = "openai")]
+pub use openai::{OpenAI, OpenAIClient, OpenAIModel, OpenAIPrediction};
+#[cfg(feature = "openai")]
 pub use openrouter::{OpenRouter, OpenRouterClient, OpenRouterModel};
+#[cfg(feature = "openai")]
 pub use perplexity::{Perplexity, PerplexityClient, PerplexityModel};
+#[cfg(feature = "openai")]
 pub use together::{Together, TogetherClient, TogetherModel};
+#[cfg(feature = "openai")]
 pub use xai::{XAIClient, XAIModel, XAI};
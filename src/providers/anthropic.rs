@@ -1,6 +1,8 @@
 //! Anthropic API client implementation.
 
-pub use crate::api::anthropic::{AnthropicClient, AnthropicModel};
+pub use crate::api::anthropic::{
+    AnthropicBackend, AnthropicCacheTtl, AnthropicClient, AnthropicModel,
+};
 use crate::options::{ModelOptions, TransportOptions};
 use crate::providers::Provider;
 
@@ -1,14 +1,23 @@
 //! Groq API client implementation.
 
 use crate::api::openai::{OpenAIClient, OpenAICompatibleModel};
-use crate::options::{ModelOptions, TransportOptions};
+use crate::options::{ModelOptions, ServiceTier, TransportOptions};
 use crate::providers::Provider;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GroqModel;
 
-impl OpenAICompatibleModel for GroqModel {}
+impl OpenAICompatibleModel for GroqModel {
+    fn service_tier(tier: ServiceTier) -> Option<&'static str> {
+        match tier {
+            ServiceTier::Auto => Some("auto"),
+            ServiceTier::Standard => Some("on_demand"),
+            ServiceTier::Flex => Some("flex"),
+            ServiceTier::Priority | ServiceTier::Batch => None,
+        }
+    }
+}
 
 pub type GroqClient = OpenAIClient<GroqModel>;
 
@@ -1,17 +1,59 @@
 //! OpenAI API client implementation.
 
 use crate::api::openai::{OpenAIClient as GenericOpenAIClient, OpenAICompatibleModel};
-use crate::options::{ModelOptions, TransportOptions};
+use crate::options::{ModelOptions, ServiceTier, TransportOptions};
 use crate::providers::Provider;
 use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
 
+/// OpenAI model options.
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct OpenAIModel;
+pub struct OpenAIModel {
+    /// Expected output, e.g. the current version of a file being edited. Matching
+    /// parts of it are accepted instead of generated, which speeds up responses that
+    /// mostly repeat it. Accepted and rejected tokens are reported in
+    /// [`Usage`](crate::model::Usage).
+    pub prediction: Option<OpenAIPrediction>,
+}
+
+/// Predicted output of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAIPrediction {
+    /// Static text the completion is expected to largely match.
+    Content { content: String },
+}
 
-impl OpenAICompatibleModel for OpenAIModel {}
+impl OpenAIPrediction {
+    pub fn content(content: impl Into<String>) -> Self {
+        Self::Content {
+            content: content.into(),
+        }
+    }
+}
+
+impl OpenAICompatibleModel for OpenAIModel {
+    fn service_tier(tier: ServiceTier) -> Option<&'static str> {
+        match tier {
+            ServiceTier::Auto => Some("auto"),
+            ServiceTier::Standard => Some("default"),
+            ServiceTier::Flex => Some("flex"),
+            ServiceTier::Priority => Some("priority"),
+            ServiceTier::Batch => None,
+        }
+    }
+}
 
 pub type OpenAIClient = GenericOpenAIClient<OpenAIModel>;
 
+impl OpenAIClient {
+    /// Send requests to a regional endpoint for data residency, e.g. `eu` or `us`.
+    pub fn with_region(self, region: &str) -> Self {
+        self.with_base_url(format!("https://{}.api.openai.com", region))
+    }
+}
+
 pub struct OpenAI;
 
 impl Provider for OpenAI {
@@ -0,0 +1,370 @@
+//! Text-based tool calling for models without native tool support.
+//!
+//! Small local models and some hosted ones cannot be sent tools. [`TextTools`]
+//! describes the tools in the prompt instead and asks for ReAct-style replies:
+//!
+//! ```text
+//! Thought: I need the weather first.
+//! Action: get_weather
+//! Action Input: {"city": "Paris"}
+//! ```
+//!
+//! Such replies are turned into regular function calls, and earlier calls and their
+//! results are rendered back as text, so an [`Agent`](crate::agent::Agent) runs its
+//! tool loop unchanged on top of these models.
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use serde_json::Value;
+use std::pin::Pin;
+
+use crate::catalog::KnownModel;
+use crate::client::{Client, ClientError, RequestPreview, StreamingClient};
+use crate::model::{Extensions, FinishReason, Message, Part, Response, TextAnnotations};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// When [`TextTools`] describes tools in the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToolFallback {
+    /// Only for models that the [catalog](crate::catalog) lists without tool support.
+    #[default]
+    Auto,
+    /// For every request with tools, e.g. for local models unknown to the catalog.
+    Always,
+}
+
+/// A tool invocation written as text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextAction {
+    /// Text preceding the action, without its `Thought:` label.
+    pub thought: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Instructions describing `tools` and the format of tool invocations.
+pub fn tool_prompt(tools: &[Tool]) -> String {
+    let mut prompt = String::from("You can use the following tools:\n");
+    for tool in tools {
+        prompt.push_str(&format!(
+            "\n- {}: {}\n  Input schema: {}",
+            tool.name,
+            tool.description.as_deref().unwrap_or("No description"),
+            Value::Object(tool.input_schema.as_ref().clone())
+        ));
+    }
+    prompt.push_str(
+        "\n\nTo use a tool, reply with exactly the following and stop:\n\
+         Thought: <your reasoning>\n\
+         Action: <tool name>\n\
+         Action Input: <arguments as a JSON object>\n\n\
+         The result will be sent back as an Observation. \
+         Once you can answer without a tool, reply with:\n\
+         Final Answer: <your answer>",
+    );
+    prompt
+}
+
+/// Parse the first tool invocation in `text`, if it is complete.
+///
+/// Anything after an `Observation:` line is ignored, since the model may have made up
+/// the result of its own call.
+pub fn parse_action(text: &str) -> Option<TextAction> {
+    let text = text.split("\nObservation:").next().unwrap_or(text);
+    let start = text.find("Action:")?;
+    let (thought, rest) = text.split_at(start);
+    let rest = &rest["Action:".len()..];
+
+    let (name, rest) = rest.split_once('\n')?;
+    let name = name.trim().trim_matches('`').trim();
+    let input = rest.trim_start().strip_prefix("Action Input:")?;
+
+    // Only a complete JSON object counts, so partial streams stay text.
+    let object = input.find('{').map(|i| &input[i..])?;
+    let arguments = serde_json::Deserializer::from_str(object)
+        .into_iter::<Value>()
+        .next()?
+        .ok()?;
+
+    let thought = thought.trim();
+    let thought = thought.strip_prefix("Thought:").unwrap_or(thought).trim();
+    (!name.is_empty()).then(|| TextAction {
+        thought: thought.to_string(),
+        name: name.to_string(),
+        arguments,
+    })
+}
+
+/// Client wrapper emulating tool calls through the prompt.
+///
+/// The wrapped client owns its system prompt, so the tool instructions are sent ahead
+/// of the conversation instead, as the first part of the first user message. Calls
+/// parsed from the reply have no id.
+pub struct TextTools<C> {
+    client: C,
+    mode: ToolFallback,
+}
+
+impl<C> TextTools<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            mode: ToolFallback::default(),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: ToolFallback) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+impl<C: Client> TextTools<C> {
+    /// Whether the tools of a request must be described in the prompt.
+    fn emulates(&self, tools: &[Tool]) -> bool {
+        if tools.is_empty() {
+            return false;
+        }
+        match self.mode {
+            ToolFallback::Always => true,
+            ToolFallback::Auto => {
+                let model = KnownModel::from(self.client.model_options().model.clone());
+                model.capabilities().is_some_and(|c| !c.tools)
+            }
+        }
+    }
+}
+
+/// History with tool calls and results rendered as text, preceded by the tool prompt.
+fn text_history(messages: Vec<Message>, tools: &[Tool]) -> Vec<Message> {
+    let mut messages: Vec<Message> = messages
+        .into_iter()
+        .map(|mut message| {
+            for part in message.parts_mut() {
+                match part {
+                    Part::FunctionCall {
+                        name, arguments, ..
+                    } => {
+                        *part =
+                            Part::text(format!("Action: {}\nAction Input: {}", name, arguments));
+                    }
+                    Part::FunctionResponse { response, .. } => {
+                        *part = Part::text(format!("Observation: {}", response));
+                    }
+                    _ => {}
+                }
+            }
+            message
+        })
+        .collect();
+
+    let prompt = Part::text(tool_prompt(tools));
+    match messages.iter_mut().find(|m| matches!(m, Message::User(_))) {
+        Some(message) => message.parts_mut().insert(0, prompt),
+        None => messages.insert(0, Message::User(vec![prompt])),
+    }
+    messages
+}
+
+/// Response with textual tool invocations turned into function calls.
+fn parse_response(mut response: Response) -> Response {
+    let mut called = false;
+    for message in &mut response.data {
+        let Message::Assistant(parts) = message else {
+            continue;
+        };
+        *parts = std::mem::take(parts)
+            .into_iter()
+            .flat_map(|part| {
+                let Part::Text {
+                    content, finished, ..
+                } = &part
+                else {
+                    return vec![part];
+                };
+                match parse_action(content) {
+                    Some(action) => {
+                        called = true;
+                        let mut parts = Vec::new();
+                        if !action.thought.is_empty() {
+                            parts.push(Part::text(action.thought));
+                        }
+                        parts.push(Part::FunctionCall {
+                            id: None,
+                            name: action.name,
+                            arguments: action.arguments,
+                            signature: None,
+                            repaired: false,
+                            extensions: Extensions::new(),
+                            finished: *finished,
+                        });
+                        parts
+                    }
+                    None => match content.split_once("Final Answer:") {
+                        Some((_, answer)) => {
+                            let mut answer = Part::Text {
+                                content: answer.trim_start().to_string().into(),
+                                signature: None,
+                                extensions: Extensions::new(),
+                                annotations: TextAnnotations::default(),
+                                finished: *finished,
+                            };
+                            if *finished {
+                                answer.annotate();
+                            }
+                            vec![answer]
+                        }
+                        None => vec![part],
+                    },
+                }
+            })
+            .collect();
+    }
+    if called && response.finish == FinishReason::Stop {
+        response.finish = FinishReason::ToolCalls;
+    }
+    response
+}
+
+#[async_trait]
+impl<C: Client> Client for TextTools<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        if !self.emulates(&tools) {
+            return self.client.request(messages, tools).await;
+        }
+        let response = self
+            .client
+            .request(text_history(messages, &tools), vec![])
+            .await?;
+        Ok(parse_response(response))
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        if !self.emulates(&tools) {
+            return self.client.dry_run(messages, tools);
+        }
+        self.client.dry_run(text_history(messages, &tools), vec![])
+    }
+}
+
+#[async_trait]
+impl<C: StreamingClient> StreamingClient for TextTools<C> {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        if !self.emulates(&tools) {
+            return self.client.request_stream(messages, tools).await;
+        }
+        let stream = self
+            .client
+            .request_stream(text_history(messages, &tools), vec![])
+            .await?;
+        Ok(Box::pin(stream.map(|item| item.map(parse_response))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_action() {
+        let text = "Thought: I need the weather.\nAction: `get_weather`\n\
+                    Action Input: {\"city\": \"Paris\"}\nObservation: sunny";
+        assert_eq!(
+            parse_action(text),
+            Some(TextAction {
+                thought: "I need the weather.".to_string(),
+                name: "get_weather".to_string(),
+                arguments: json!({ "city": "Paris" }),
+            })
+        );
+
+        assert_eq!(
+            parse_action("Action: get_weather\nAction Input: {\"city\": \"Par"),
+            None
+        );
+        assert_eq!(parse_action("Final Answer: It is sunny."), None);
+    }
+
+    #[test]
+    fn test_history_is_rendered_as_text() {
+        let tool = Tool::new(
+            "get_weather",
+            "Current weather",
+            Arc::new(Default::default()),
+        );
+        let history = vec![
+            Message::user("Weather in Paris?"),
+            Message::Assistant(vec![Part::FunctionCall {
+                id: None,
+                name: "get_weather".to_string(),
+                arguments: json!({ "city": "Paris" }),
+                signature: None,
+                repaired: false,
+                extensions: Extensions::new(),
+                finished: true,
+            }]),
+            Message::User(vec![Part::FunctionResponse {
+                id: None,
+                name: "get_weather".to_string(),
+                response: json!("sunny"),
+                parts: vec![],
+                error: None,
+                finished: true,
+            }]),
+        ];
+
+        let history = text_history(history, &[tool]);
+        let first = history[0].parts();
+        assert!(
+            matches!(&first[0], Part::Text { content, .. } if content.contains("- get_weather: Current weather"))
+        );
+        assert_eq!(
+            history[1].content().as_deref(),
+            Some("Action: get_weather\nAction Input: {\"city\":\"Paris\"}")
+        );
+        assert_eq!(
+            history[2].content().as_deref(),
+            Some("Observation: \"sunny\"")
+        );
+
+        let response = parse_response(Response {
+            data: vec![Message::assistant("Final Answer: Sunny.")],
+            usage: Default::default(),
+            finish: FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        });
+        assert_eq!(response.data[0].content().as_deref(), Some("Sunny."));
+    }
+}
@@ -0,0 +1,59 @@
+//! Regional endpoints.
+//!
+//! Data residency requirements often mean sending requests to a specific region.
+//! The clients accept a region through their `with_region` builders and format the
+//! matching base URL, so it does not have to be built by hand.
+
+/// Google Cloud project and region through which Vertex AI serves models.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLocation {
+    pub project: String,
+    /// Region such as `europe-west4`, or `global`.
+    pub region: String,
+}
+
+impl VertexLocation {
+    pub fn new(project: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            project: project.into(),
+            region: region.into(),
+        }
+    }
+
+    /// Host serving the region. The `global` region has no regional host.
+    pub fn host(&self) -> String {
+        if self.region == "global" {
+            "aiplatform.googleapis.com".to_string()
+        } else {
+            format!("{}-aiplatform.googleapis.com", self.region)
+        }
+    }
+
+    /// Base URL of the models of a publisher, such as `google` or `anthropic`.
+    pub fn publisher_url(&self, publisher: &str) -> String {
+        format!(
+            "https://{}/v1/projects/{}/locations/{}/publishers/{}",
+            self.host(),
+            self.project,
+            self.region,
+            publisher
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publisher_urls() {
+        assert_eq!(
+            VertexLocation::new("acme", "europe-west4").publisher_url("google"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/acme/locations/europe-west4/publishers/google"
+        );
+        assert_eq!(
+            VertexLocation::new("acme", "global").publisher_url("anthropic"),
+            "https://aiplatform.googleapis.com/v1/projects/acme/locations/global/publishers/anthropic"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Document reranking for retrieval pipelines.
+//!
+//! A [`Reranker`] scores documents by their relevance to a query, so the best
+//! candidates of a first-stage search can be kept before they are sent to a model.
+//! Implementations for the Cohere and Voyage rerank endpoints live in
+//! [`api::rerank`](crate::api::rerank).
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::client::ClientError;
+
+/// A document with its relevance to the query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredDocument {
+    /// Position of the document in the input.
+    pub index: usize,
+    /// Relevance score; higher is more relevant.
+    pub score: f64,
+    pub document: String,
+}
+
+/// Trait for services that rank documents by relevance to a query.
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// Score `documents` against `query`.
+    ///
+    /// Results are ordered from most to least relevant and may hold fewer documents
+    /// than the input when the reranker is configured to keep only the best ones.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+    ) -> Result<Vec<ScoredDocument>, ClientError>;
+}
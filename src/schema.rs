@@ -0,0 +1,423 @@
+//! JSON Schema sanitization for provider-specific schema dialects.
+//!
+//! Tool input schemas usually come from `schemars` or MCP servers and use the full
+//! JSON Schema vocabulary. Providers accept only subsets of it: the legacy Gemini
+//! schema fields take an OpenAPI subset without references, while OpenAI strict mode
+//! requires closed objects with every property listed as required. [`sanitize_schema`]
+//! rewrites a schema for a given dialect and logs a warning whenever a transform
+//! loses information.
+
+use serde_json::{json, Map, Value};
+use tracing::{debug, warn};
+
+/// Maximum depth to which `$ref`s are inlined before giving up on recursive schemas.
+const MAX_REF_DEPTH: usize = 8;
+
+/// Schema dialect accepted by a provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaDialect {
+    /// The OpenAPI subset of Gemini's legacy `parameters` and `responseSchema` fields.
+    Gemini,
+    /// JSON Schema as taken by Gemini's `parametersJsonSchema` and `responseJsonSchema`
+    /// fields. Only annotations are removed.
+    GeminiJsonSchema,
+    /// OpenAI function calling and structured outputs with `strict: true`.
+    OpenAIStrict,
+}
+
+/// Keywords dropped for every dialect, as they carry no meaning for generation.
+const ANNOTATION_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "examples"];
+
+/// Keywords the Gemini dialect does not understand.
+const GEMINI_UNSUPPORTED: &[&str] = &[
+    "additionalProperties",
+    "patternProperties",
+    "unevaluatedProperties",
+    "dependentRequired",
+    "dependentSchemas",
+    "allOf",
+    "not",
+    "if",
+    "then",
+    "else",
+    "default",
+];
+
+/// String formats supported by Gemini.
+const GEMINI_FORMATS: &[&str] = &["enum", "date-time"];
+
+/// Keywords OpenAI strict mode rejects.
+const OPENAI_STRICT_UNSUPPORTED: &[&str] = &[
+    "patternProperties",
+    "unevaluatedProperties",
+    "dependentRequired",
+    "dependentSchemas",
+    "minProperties",
+    "maxProperties",
+    "allOf",
+    "not",
+    "if",
+    "then",
+    "else",
+    "default",
+];
+
+/// Rewrite a JSON Schema so that it is accepted by the given dialect.
+///
+/// Lossy transforms (dropped keywords, inlined recursive references, widened
+/// optional properties) are reported with `tracing::warn!` including the path of
+/// the affected subschema.
+pub fn sanitize_schema(schema: Value, dialect: SchemaDialect) -> Value {
+    let defs = match &schema {
+        Value::Object(map) => map
+            .get("$defs")
+            .or_else(|| map.get("definitions"))
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default(),
+        _ => Map::new(),
+    };
+
+    let sanitizer = Sanitizer { dialect, defs };
+    let mut schema = sanitizer.sanitize(schema, "#", 0);
+
+    if dialect == SchemaDialect::Gemini {
+        if let Value::Object(map) = &mut schema {
+            map.remove("$defs");
+            map.remove("definitions");
+        }
+    }
+
+    schema
+}
+
+struct Sanitizer {
+    dialect: SchemaDialect,
+    defs: Map<String, Value>,
+}
+
+impl Sanitizer {
+    fn sanitize(&self, schema: Value, path: &str, depth: usize) -> Value {
+        let Value::Object(mut map) = schema else {
+            return schema;
+        };
+
+        if self.dialect == SchemaDialect::Gemini {
+            if let Some(reference) = map.remove("$ref") {
+                return self.inline_ref(reference, map, path, depth);
+            }
+        }
+
+        for keyword in ANNOTATION_KEYWORDS {
+            // References may point at an `$id`.
+            if self.dialect != SchemaDialect::GeminiJsonSchema || *keyword != "$id" {
+                map.remove(*keyword);
+            }
+        }
+
+        let unsupported = match self.dialect {
+            SchemaDialect::Gemini => GEMINI_UNSUPPORTED,
+            SchemaDialect::GeminiJsonSchema => &[],
+            SchemaDialect::OpenAIStrict => OPENAI_STRICT_UNSUPPORTED,
+        };
+        for keyword in unsupported {
+            if let Some(value) = map.remove(*keyword) {
+                // `additionalProperties: true` is the default and loses nothing.
+                if *keyword != "additionalProperties" || value != Value::Bool(true) {
+                    warn!(
+                        "Dropping unsupported schema keyword `{}` at {}",
+                        keyword, path
+                    );
+                }
+            }
+        }
+
+        match self.dialect {
+            SchemaDialect::Gemini => self.apply_gemini(&mut map, path),
+            SchemaDialect::GeminiJsonSchema => {}
+            SchemaDialect::OpenAIStrict => self.apply_openai_strict(&mut map, path),
+        }
+
+        self.sanitize_children(&mut map, path, depth);
+        Value::Object(map)
+    }
+
+    fn sanitize_children(&self, map: &mut Map<String, Value>, path: &str, depth: usize) {
+        for keyword in ["properties", "$defs", "definitions"] {
+            if let Some(Value::Object(children)) = map.get_mut(keyword) {
+                for (name, child) in children.iter_mut() {
+                    let child_path = format!("{}/{}/{}", path, keyword, name);
+                    *child = self.sanitize(child.take(), &child_path, depth);
+                }
+            }
+        }
+
+        for keyword in ["items", "additionalProperties"] {
+            if let Some(child) = map.get_mut(keyword) {
+                if child.is_object() {
+                    let child_path = format!("{}/{}", path, keyword);
+                    *child = self.sanitize(child.take(), &child_path, depth);
+                }
+            }
+        }
+
+        for keyword in ["anyOf", "oneOf", "prefixItems"] {
+            if let Some(Value::Array(children)) = map.get_mut(keyword) {
+                for (index, child) in children.iter_mut().enumerate() {
+                    let child_path = format!("{}/{}/{}", path, keyword, index);
+                    *child = self.sanitize(child.take(), &child_path, depth);
+                }
+            }
+        }
+    }
+
+    /// Replace a `$ref` with the definition it points to, merged with sibling keywords.
+    fn inline_ref(
+        &self,
+        reference: Value,
+        siblings: Map<String, Value>,
+        path: &str,
+        depth: usize,
+    ) -> Value {
+        let name = reference.as_str().and_then(|r| {
+            r.strip_prefix("#/$defs/")
+                .or_else(|| r.strip_prefix("#/definitions/"))
+        });
+
+        let target = match name.and_then(|name| self.defs.get(name)) {
+            Some(_) if depth >= MAX_REF_DEPTH => {
+                warn!(
+                    "Replacing recursive reference {} at {} with an unconstrained object",
+                    reference, path
+                );
+                json!({ "type": "object" })
+            }
+            Some(target) => target.clone(),
+            None => {
+                warn!("Dropping unresolvable reference {} at {}", reference, path);
+                json!({})
+            }
+        };
+
+        let mut merged = match target {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        merged.extend(siblings);
+        self.sanitize(Value::Object(merged), path, depth + 1)
+    }
+
+    fn apply_gemini(&self, map: &mut Map<String, Value>, path: &str) {
+        if let Some(value) = map.remove("const") {
+            debug!("Converting `const` to single-value `enum` at {}", path);
+            map.insert("enum".to_string(), json!([value]));
+        }
+
+        if let Some(format) = map.get("format").and_then(Value::as_str) {
+            if !GEMINI_FORMATS.contains(&format) {
+                warn!(
+                    "Dropping unsupported string format `{}` at {}",
+                    format, path
+                );
+                map.remove("format");
+            }
+        }
+
+        // `type: [T, "null"]` is expressed as `type: T` plus `nullable`.
+        if let Some(Value::Array(types)) = map.get("type") {
+            let non_null: Vec<Value> = types
+                .iter()
+                .filter(|t| t.as_str() != Some("null"))
+                .cloned()
+                .collect();
+            let nullable = non_null.len() < types.len();
+
+            match non_null.as_slice() {
+                [single] => {
+                    map.insert("type".to_string(), single.clone());
+                }
+                _ => {
+                    warn!(
+                        "Collapsing union type {:?} at {} to the first member",
+                        types, path
+                    );
+                    map.insert(
+                        "type".to_string(),
+                        non_null.first().cloned().unwrap_or(json!("string")),
+                    );
+                }
+            }
+            if nullable {
+                map.insert("nullable".to_string(), Value::Bool(true));
+            }
+        }
+    }
+
+    fn apply_openai_strict(&self, map: &mut Map<String, Value>, path: &str) {
+        if !map.contains_key("properties") {
+            if map.get("type").and_then(Value::as_str) == Some("object") {
+                map.insert("additionalProperties".to_string(), Value::Bool(false));
+            }
+            return;
+        }
+
+        let required: Vec<String> = map
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| {
+                r.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if let Some(Value::Object(properties)) = map.get_mut("properties") {
+            for (name, property) in properties.iter_mut() {
+                if !required.contains(name) {
+                    debug!("Making optional property {}/{} nullable", path, name);
+                    make_nullable(property);
+                }
+            }
+            let all: Vec<Value> = properties.keys().cloned().map(Value::String).collect();
+            map.insert("required".to_string(), Value::Array(all));
+        }
+
+        if let Some(previous) = map.insert("additionalProperties".to_string(), Value::Bool(false)) {
+            if previous != Value::Bool(false) {
+                warn!("Closing object at {} (additionalProperties: false)", path);
+            }
+        }
+    }
+}
+
+/// Allow `null` in addition to the values accepted by `schema`.
+fn make_nullable(schema: &mut Value) {
+    let Value::Object(map) = schema else {
+        return;
+    };
+
+    match map.get_mut("type") {
+        Some(Value::String(t)) => {
+            let t = t.clone();
+            map.insert("type".to_string(), json!([t, "null"]));
+        }
+        Some(Value::Array(types)) => {
+            if !types.iter().any(|t| t.as_str() == Some("null")) {
+                types.push(json!("null"));
+            }
+        }
+        _ => {
+            let inner = Value::Object(std::mem::take(map));
+            map.insert("anyOf".to_string(), json!([inner, { "type": "null" }]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gemini_inlines_refs_and_drops_keywords() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "location": { "$ref": "#/$defs/Location" },
+                "email": { "type": "string", "format": "email" },
+                "note": { "type": ["string", "null"] }
+            },
+            "$defs": {
+                "Location": {
+                    "type": "object",
+                    "properties": { "kind": { "const": "city" } }
+                }
+            }
+        });
+
+        let sanitized = sanitize_schema(schema, SchemaDialect::Gemini);
+
+        assert_eq!(
+            sanitized,
+            json!({
+                "type": "object",
+                "properties": {
+                    "location": {
+                        "type": "object",
+                        "properties": { "kind": { "enum": ["city"] } }
+                    },
+                    "email": { "type": "string" },
+                    "note": { "type": "string", "nullable": true }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_gemini_json_schema_keeps_json_schema() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "location": { "$ref": "#/$defs/Location" },
+                "note": { "type": ["string", "null"] }
+            },
+            "$defs": {
+                "Location": { "type": "object", "properties": { "kind": { "const": "city" } } }
+            }
+        });
+
+        let sanitized = sanitize_schema(schema.clone(), SchemaDialect::GeminiJsonSchema);
+
+        let mut expected = schema;
+        expected.as_object_mut().unwrap().remove("$schema");
+        assert_eq!(sanitized, expected);
+    }
+
+    #[test]
+    fn test_gemini_recursive_ref_terminates() {
+        let schema = json!({
+            "$ref": "#/$defs/Node",
+            "$defs": {
+                "Node": {
+                    "type": "object",
+                    "properties": { "child": { "$ref": "#/$defs/Node" } }
+                }
+            }
+        });
+
+        let sanitized = sanitize_schema(schema, SchemaDialect::Gemini);
+
+        assert_eq!(sanitized["type"], "object");
+        assert!(sanitized.get("$defs").is_none());
+    }
+
+    #[test]
+    fn test_openai_strict_closes_objects() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "days": { "type": "integer", "default": 1 }
+            },
+            "required": ["city"]
+        });
+
+        let sanitized = sanitize_schema(schema, SchemaDialect::OpenAIStrict);
+
+        assert_eq!(
+            sanitized,
+            json!({
+                "type": "object",
+                "properties": {
+                    "city": { "type": "string" },
+                    "days": { "type": ["integer", "null"] }
+                },
+                "required": ["city", "days"],
+                "additionalProperties": false
+            })
+        );
+    }
+}
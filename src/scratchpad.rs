@@ -0,0 +1,165 @@
+//! Shared state for the tools of an agent.
+//!
+//! Tools that need to hand data to each other (an id found by a search, a plan being
+//! worked through) would otherwise have to round-trip it through the model. A
+//! [`Scratchpad`] is a typed key-value store shared by cloning: give one clone to the
+//! tools and another to [`Agent::with_scratchpad`](crate::agent::Agent::with_scratchpad).
+//! While it holds entries, the agent appends a summary of them to every request, so
+//! the model sees the current state without it being stored in the conversation.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Characters of each value shown in the summary before it is truncated.
+pub const SUMMARY_VALUE_CHARS: usize = 200;
+
+/// Typed key-value store shared between the tools of an agent.
+///
+/// Values are stored as JSON. Clones share the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct Scratchpad {
+    entries: Arc<Mutex<BTreeMap<String, Value>>>,
+}
+
+impl Scratchpad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Value stored under `key`, or `None` if there is none.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        self.get_value(key).map(serde_json::from_value).transpose()
+    }
+
+    /// JSON value stored under `key`.
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    /// Store `value` under `key`, replacing the previous value.
+    pub fn set<T: Serialize>(
+        &self,
+        key: impl Into<String>,
+        value: &T,
+    ) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        self.entries.lock().unwrap().insert(key.into(), value);
+        Ok(())
+    }
+
+    /// Modify the value stored under `key` in place, starting from the default if
+    /// there is none. No other clone can change the value in between.
+    pub fn update<T, F>(&self, key: impl Into<String>, f: F) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        F: FnOnce(&mut T),
+    {
+        let mut entries = self.entries.lock().unwrap();
+        let key = key.into();
+        let mut value = match entries.get(&key) {
+            Some(value) => T::deserialize(value)?,
+            None => T::default(),
+        };
+        f(&mut value);
+        entries.insert(key, serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Remove and return the value stored under `key`.
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        self.entries.lock().unwrap().remove(key)
+    }
+
+    /// Keys of all entries, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.entries.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// All entries as a JSON object.
+    pub fn snapshot(&self) -> Value {
+        let entries = self.entries.lock().unwrap();
+        Value::Object(entries.clone().into_iter().collect())
+    }
+
+    /// Text describing the entries for the model, or `None` if there are none.
+    ///
+    /// Values longer than [`SUMMARY_VALUE_CHARS`] are truncated.
+    pub fn summary(&self) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        if entries.is_empty() {
+            return None;
+        }
+        let lines = entries
+            .iter()
+            .map(|(key, value)| {
+                let value = value.to_string();
+                match value.char_indices().nth(SUMMARY_VALUE_CHARS) {
+                    Some((end, _)) => format!("- {}: {}…", key, &value[..end]),
+                    None => format!("- {}: {}", key, value),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(format!("Current scratchpad of the tools:\n{}", lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_typed_entries_are_shared() {
+        let scratchpad = Scratchpad::new();
+        let tool = scratchpad.clone();
+        tool.set("order_id", &42u32).unwrap();
+        tool.update("visited", |visited: &mut Vec<String>| {
+            visited.push("inbox".to_string())
+        })
+        .unwrap();
+
+        assert_eq!(scratchpad.get::<u32>("order_id").unwrap(), Some(42));
+        assert_eq!(scratchpad.get::<u32>("missing").unwrap(), None);
+        assert!(scratchpad.get::<String>("order_id").is_err());
+        assert_eq!(
+            scratchpad.snapshot(),
+            json!({ "order_id": 42, "visited": ["inbox"] })
+        );
+        assert_eq!(
+            scratchpad.summary().unwrap(),
+            "Current scratchpad of the tools:\n- order_id: 42\n- visited: [\"inbox\"]"
+        );
+
+        scratchpad.clear();
+        assert_eq!(tool.summary(), None);
+    }
+
+    #[test]
+    fn test_summary_truncates_long_values() {
+        let scratchpad = Scratchpad::new();
+        scratchpad.set("notes", &"é".repeat(300)).unwrap();
+        let summary = scratchpad.summary().unwrap();
+        assert!(summary.ends_with('…'));
+        assert_eq!(
+            summary.lines().nth(1).unwrap().chars().count(),
+            "- notes: ".len() + 201
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Anthropic Messages endpoint.
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::Json;
+use futures::StreamExt;
+use rmcp::model::Tool;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use super::{error_status, final_turn, inbound_tool, updates, Backend, Update};
+use crate::api::anthropic::messages_from_json;
+use crate::model::{FinishReason, Part, Response};
+
+#[derive(Debug, Deserialize)]
+pub(super) struct MessagesRequest {
+    model: Option<String>,
+    system: Option<InboundSystem>,
+    messages: Value,
+    #[serde(default)]
+    tools: Vec<InboundTool>,
+    #[serde(default)]
+    stream: bool,
+}
+
+/// A system prompt, given as a string or as text blocks.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum InboundSystem {
+    Text(String),
+    Blocks(Vec<InboundSystemBlock>),
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundSystemBlock {
+    text: String,
+}
+
+impl InboundSystem {
+    fn into_text(self) -> Option<String> {
+        let text = match self {
+            InboundSystem::Text(text) => text,
+            InboundSystem::Blocks(blocks) => blocks
+                .into_iter()
+                .map(|block| block.text)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundTool {
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    input_schema: Map<String, Value>,
+}
+
+impl From<InboundTool> for Tool {
+    fn from(tool: InboundTool) -> Self {
+        inbound_tool(tool.name, tool.description, tool.input_schema)
+    }
+}
+
+/// `POST /v1/messages`
+pub(super) async fn messages(
+    State(backend): State<Arc<dyn Backend>>,
+    request: Result<Json<MessagesRequest>, JsonRejection>,
+) -> HttpResponse {
+    let request = match request {
+        Ok(Json(request)) => request,
+        Err(rejection) => return error_response(rejection.status(), &rejection.body_text()),
+    };
+    let messages = match messages_from_json(request.messages) {
+        Ok(messages) => messages,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let system = request.system.and_then(InboundSystem::into_text);
+    let tools: Vec<Tool> = request.tools.into_iter().map(Tool::from).collect();
+    let model = request.model.unwrap_or_else(|| backend.model());
+
+    if !request.stream {
+        return match backend.complete(system, messages, tools).await {
+            Ok(response) => Json(message(&response, &model)).into_response(),
+            Err(e) => {
+                let status = error_status(&e);
+                error_response(status, &e.to_string())
+            }
+        };
+    }
+
+    let stream = async_stream::stream! {
+        let start = json!({
+            "id": message_id(),
+            "type": "message",
+            "role": "assistant",
+            "model": model,
+            "content": [],
+            "stop_reason": null,
+            "stop_sequence": null,
+            "usage": { "input_tokens": 0, "output_tokens": 0 },
+        });
+        yield event("message_start", json!({ "type": "message_start", "message": start }));
+
+        // Text is streamed into block 0, opened on the first delta.
+        let mut text_open = false;
+        let updates = updates(backend, system, messages, tools);
+        futures::pin_mut!(updates);
+        while let Some(update) = updates.next().await {
+            match update {
+                Update::Text(delta) => {
+                    if !text_open {
+                        text_open = true;
+                        yield event("content_block_start", json!({
+                            "type": "content_block_start",
+                            "index": 0,
+                            "content_block": { "type": "text", "text": "" },
+                        }));
+                    }
+                    yield event("content_block_delta", json!({
+                        "type": "content_block_delta",
+                        "index": 0,
+                        "delta": { "type": "text_delta", "text": delta },
+                    }));
+                }
+                Update::Done(response) => {
+                    if text_open {
+                        yield event("content_block_stop", json!({ "type": "content_block_stop", "index": 0 }));
+                    }
+
+                    let (_, calls) = final_turn(&response);
+                    let first = usize::from(text_open);
+                    for (offset, call) in calls.into_iter().enumerate() {
+                        let Part::FunctionCall { arguments, .. } = call else {
+                            continue;
+                        };
+                        let mut block = tool_use(call);
+                        block["input"] = json!({});
+                        let index = first + offset;
+                        yield event("content_block_start", json!({
+                            "type": "content_block_start",
+                            "index": index,
+                            "content_block": block,
+                        }));
+                        yield event("content_block_delta", json!({
+                            "type": "content_block_delta",
+                            "index": index,
+                            "delta": { "type": "input_json_delta", "partial_json": arguments.to_string() },
+                        }));
+                        yield event("content_block_stop", json!({ "type": "content_block_stop", "index": index }));
+                    }
+
+                    yield event("message_delta", json!({
+                        "type": "message_delta",
+                        "delta": { "stop_reason": stop_reason(&response), "stop_sequence": response.stop_sequence },
+                        "usage": { "output_tokens": response.usage.completion_tokens.unwrap_or(0) },
+                    }));
+                    yield event("message_stop", json!({ "type": "message_stop" }));
+                }
+                Update::Failed(e) => {
+                    yield event("error", error_body(&e.to_string(), "api_error"));
+                    return;
+                }
+            }
+        }
+    };
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn event(name: &str, data: Value) -> Result<Event, Infallible> {
+    Ok(Event::default().event(name).data(data.to_string()))
+}
+
+fn message(response: &Response, model: &str) -> Value {
+    let (text, calls) = final_turn(response);
+    let mut content = Vec::new();
+    if !text.is_empty() {
+        content.push(json!({ "type": "text", "text": text }));
+    }
+    content.extend(calls.into_iter().map(tool_use));
+
+    json!({
+        "id": message_id(),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": stop_reason(response),
+        "stop_sequence": response.stop_sequence,
+        "usage": {
+            "input_tokens": response.usage.prompt_tokens.unwrap_or(0),
+            "output_tokens": response.usage.completion_tokens.unwrap_or(0),
+        },
+    })
+}
+
+fn message_id() -> String {
+    format!("msg_{}", uuid::Uuid::new_v4().simple())
+}
+
+fn tool_use(part: &Part) -> Value {
+    let Part::FunctionCall {
+        id,
+        name,
+        arguments,
+        ..
+    } = part
+    else {
+        return Value::Null;
+    };
+    json!({
+        "type": "tool_use",
+        "id": id.clone().unwrap_or_else(|| format!("toolu_{}", uuid::Uuid::new_v4().simple())),
+        "name": name,
+        "input": arguments,
+    })
+}
+
+fn stop_reason(response: &Response) -> &'static str {
+    if response.stop_sequence.is_some() {
+        return "stop_sequence";
+    }
+    match response.finish {
+        FinishReason::OutputTokens => "max_tokens",
+        FinishReason::ToolCalls => "tool_use",
+        _ => "end_turn",
+    }
+}
+
+fn error_body(message: &str, error_type: &str) -> Value {
+    json!({ "type": "error", "error": { "type": error_type, "message": message } })
+}
+
+fn error_response(status: StatusCode, message: &str) -> HttpResponse {
+    let error_type = match status.as_u16() {
+        400 | 422 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    };
+    (status, Json(error_body(message, error_type))).into_response()
+}
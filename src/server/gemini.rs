@@ -0,0 +1,242 @@
+//! Gemini `generateContent` endpoints.
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::Json;
+use futures::StreamExt;
+use rmcp::model::Tool;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use super::{error_status, final_turn, inbound_tool, updates, Backend, Update};
+use crate::api::gemini::messages_from_json;
+use crate::model::{FinishReason, Part, Response};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GenerateContentRequest {
+    contents: Value,
+    #[serde(alias = "system_instruction")]
+    system_instruction: Option<InboundContent>,
+    #[serde(default)]
+    tools: Vec<InboundTool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundContent {
+    #[serde(default)]
+    parts: Vec<InboundTextPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundTextPart {
+    text: Option<String>,
+}
+
+impl InboundContent {
+    fn into_text(self) -> Option<String> {
+        let text = self
+            .parts
+            .into_iter()
+            .filter_map(|part| part.text)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InboundTool {
+    #[serde(default)]
+    function_declarations: Vec<InboundFunctionDeclaration>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InboundFunctionDeclaration {
+    name: String,
+    description: Option<String>,
+    parameters: Option<Map<String, Value>>,
+    parameters_json_schema: Option<Map<String, Value>>,
+}
+
+impl From<InboundFunctionDeclaration> for Tool {
+    fn from(declaration: InboundFunctionDeclaration) -> Self {
+        let schema = declaration
+            .parameters_json_schema
+            .or(declaration.parameters)
+            .unwrap_or_default();
+        inbound_tool(declaration.name, declaration.description, schema)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct StreamQuery {
+    alt: Option<String>,
+}
+
+/// `POST /v1beta/models/{model}:generateContent` and `:streamGenerateContent`
+pub(super) async fn generate_content(
+    State(backend): State<Arc<dyn Backend>>,
+    Path(model_action): Path<String>,
+    Query(query): Query<StreamQuery>,
+    request: Result<Json<GenerateContentRequest>, JsonRejection>,
+) -> HttpResponse {
+    let Some((model, action)) = model_action.rsplit_once(':') else {
+        return error_response(StatusCode::NOT_FOUND, "Missing method in model path");
+    };
+    let stream = match action {
+        "generateContent" => false,
+        "streamGenerateContent" => true,
+        _ => {
+            return error_response(
+                StatusCode::NOT_FOUND,
+                &format!("Unknown method: {}", action),
+            )
+        }
+    };
+    let model = model.to_string();
+
+    let request = match request {
+        Ok(Json(request)) => request,
+        Err(rejection) => return error_response(rejection.status(), &rejection.body_text()),
+    };
+    let messages = match messages_from_json(request.contents) {
+        Ok(messages) => messages,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let system = request
+        .system_instruction
+        .and_then(InboundContent::into_text);
+    let tools: Vec<Tool> = request
+        .tools
+        .into_iter()
+        .flat_map(|tool| tool.function_declarations)
+        .map(Tool::from)
+        .collect();
+
+    // Without `alt=sse`, streamed responses are returned as a JSON array of chunks.
+    if !stream || query.alt.as_deref() != Some("sse") {
+        return match backend.complete(system, messages, tools).await {
+            Ok(response) => {
+                let body = candidate_response(&response, &model);
+                Json(if stream { json!([body]) } else { body }).into_response()
+            }
+            Err(e) => error_response(error_status(&e), &e.to_string()),
+        };
+    }
+
+    let stream = async_stream::stream! {
+        let updates = updates(backend, system, messages, tools);
+        futures::pin_mut!(updates);
+        while let Some(update) = updates.next().await {
+            match update {
+                Update::Text(delta) => {
+                    yield event(json!({
+                        "candidates": [{
+                            "content": { "role": "model", "parts": [{ "text": delta }] },
+                            "index": 0,
+                        }],
+                        "modelVersion": model,
+                    }));
+                }
+                Update::Done(response) => {
+                    // Text was already streamed, only function calls remain.
+                    let mut last = candidate_response(&response, &model);
+                    let parts = &mut last["candidates"][0]["content"]["parts"];
+                    if let Value::Array(parts) = parts {
+                        parts.retain(|part| part.get("text").is_none());
+                    }
+                    yield event(last);
+                }
+                Update::Failed(e) => {
+                    yield event(error_body(error_status(&e), &e.to_string()));
+                    return;
+                }
+            }
+        }
+    };
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn event(data: Value) -> Result<Event, Infallible> {
+    Ok(Event::default().data(data.to_string()))
+}
+
+fn candidate_response(response: &Response, model: &str) -> Value {
+    let (text, calls) = final_turn(response);
+    let mut parts = Vec::new();
+    if !text.is_empty() {
+        parts.push(json!({ "text": text }));
+    }
+    parts.extend(calls.into_iter().map(function_call));
+
+    let prompt_tokens = response.usage.prompt_tokens.unwrap_or(0);
+    let candidates_tokens = response.usage.completion_tokens.unwrap_or(0);
+    json!({
+        "candidates": [{
+            "content": { "role": "model", "parts": parts },
+            "finishReason": finish_reason(&response.finish),
+            "index": 0,
+        }],
+        "usageMetadata": {
+            "promptTokenCount": prompt_tokens,
+            "candidatesTokenCount": candidates_tokens,
+            "totalTokenCount": prompt_tokens + candidates_tokens,
+        },
+        "modelVersion": model,
+    })
+}
+
+fn function_call(part: &Part) -> Value {
+    let Part::FunctionCall {
+        name,
+        arguments,
+        signature,
+        ..
+    } = part
+    else {
+        return Value::Null;
+    };
+    let mut call = json!({ "functionCall": { "name": name, "args": arguments } });
+    if let Some(signature) = signature {
+        call["thoughtSignature"] = json!(signature);
+    }
+    call
+}
+
+/// Gemini reports `STOP` for function calls as well.
+fn finish_reason(finish: &FinishReason) -> &'static str {
+    match finish {
+        FinishReason::OutputTokens => "MAX_TOKENS",
+        FinishReason::ContentFilter => "SAFETY",
+        _ => "STOP",
+    }
+}
+
+fn error_body(status: StatusCode, message: &str) -> Value {
+    let code = match status.as_u16() {
+        400 | 422 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        429 => "RESOURCE_EXHAUSTED",
+        503 => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    };
+    json!({ "error": { "code": status.as_u16(), "message": message, "status": code } })
+}
+
+fn error_response(status: StatusCode, message: &str) -> HttpResponse {
+    (status, Json(error_body(status, message))).into_response()
+}
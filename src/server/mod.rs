@@ -0,0 +1,315 @@
+//! HTTP server adapter exposing a client or agent through provider wire formats.
+//!
+//! Enabled with the `server` feature. [`router`] builds an [`axum::Router`] that
+//! accepts requests in the wire format of each supported provider, translates them into
+//! the unified model and answers them with any [`Backend`], which turns unia into a
+//! drop-in gateway for existing SDKs:
+//!
+//! - OpenAI Chat Completions on `POST /v1/chat/completions`
+//! - Anthropic Messages on `POST /v1/messages`
+//! - Gemini on `POST /v1beta/models/{model}:generateContent` and `:streamGenerateContent`
+//!
+//! The backend's own [`ModelOptions`](crate::options::ModelOptions) apply to every
+//! request, and sampling parameters sent by callers are ignored. System prompts sent
+//! by callers are passed on as call-level [`Instructions`], following the backend's
+//! own system prompt.
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::agent::Agent;
+use crate::client::{ClientError, StreamingClient};
+use crate::instructions::{InstructionLayer, Instructions};
+use crate::model::{FinishReason, Message, Part, Response};
+
+mod anthropic;
+mod gemini;
+mod openai;
+
+/// Stream of cumulative response snapshots produced by a [`Backend`].
+pub type ResponseStream<'a> =
+    Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>;
+
+/// Anything that can answer requests received by the server.
+///
+/// Implemented for every [`StreamingClient`] and for [`Agent`]s wrapping one.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// Model name reported when the caller did not specify one.
+    fn model(&self) -> String;
+
+    /// Answer a request with a complete response.
+    ///
+    /// `system` is the system prompt sent by the caller, if any.
+    async fn complete(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError>;
+
+    /// Answer a request with a stream of cumulative snapshots.
+    async fn complete_stream(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<ResponseStream<'_>, ClientError>;
+}
+
+/// Instructions carrying the system prompt of a caller.
+fn call_instructions(system: Option<String>) -> Instructions {
+    let mut instructions = Instructions::new();
+    if let Some(system) = system {
+        instructions.push(InstructionLayer::Call, system);
+    }
+    instructions
+}
+
+#[async_trait]
+impl<C: StreamingClient> Backend for C {
+    fn model(&self) -> String {
+        self.model_options().model.clone()
+    }
+
+    async fn complete(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let base = self.model_options().system.as_deref();
+        let messages = call_instructions(system).apply(base, messages);
+        self.request(messages, tools).await
+    }
+
+    async fn complete_stream(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<ResponseStream<'_>, ClientError> {
+        let base = self.model_options().system.as_deref();
+        let messages = call_instructions(system).apply(base, messages);
+        self.request_stream(messages, tools).await
+    }
+}
+
+/// Agents execute their own tools; tools sent by the caller are ignored.
+#[async_trait]
+impl<C: StreamingClient> Backend for Agent<C> {
+    fn model(&self) -> String {
+        self.client().model_options().model.clone()
+    }
+
+    async fn complete(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        self.chat_with_instructions(messages, &call_instructions(system))
+            .await
+    }
+
+    async fn complete_stream(
+        &self,
+        system: Option<String>,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<ResponseStream<'_>, ClientError> {
+        // Sent ahead of the conversation like in `complete`; the agent's own
+        // instructions are inserted before them on every iteration.
+        let base = self.client().model_options().system.as_deref();
+        let messages = call_instructions(system).apply(base, messages);
+        Ok(self.chat_stream(messages))
+    }
+}
+
+/// Build a router serving the supported endpoints with the given backend.
+pub fn router<B: Backend + 'static>(backend: B) -> Router {
+    let backend: Arc<dyn Backend> = Arc::new(backend);
+    Router::new()
+        .route("/v1/chat/completions", post(openai::chat_completions))
+        .route("/v1/messages", post(anthropic::messages))
+        .route(
+            "/v1beta/models/{model_action}",
+            post(gemini::generate_content),
+        )
+        .with_state(backend)
+}
+
+/// Build a tool definition received from a caller.
+fn inbound_tool(name: String, description: Option<String>, mut schema: Map<String, Value>) -> Tool {
+    if schema.is_empty() {
+        schema.insert("type".to_string(), json!("object"));
+    }
+    let mut tool = Tool::new(name, String::new(), schema);
+    tool.description = description.map(Into::into);
+    tool
+}
+
+/// HTTP status to answer with when the backend fails.
+fn error_status(error: &ClientError) -> StatusCode {
+    match error {
+        ClientError::Parse(_) => StatusCode::BAD_REQUEST,
+        ClientError::Unsupported { .. } => StatusCode::BAD_REQUEST,
+        ClientError::ContentFiltered { .. } => StatusCode::BAD_REQUEST,
+        ClientError::RequestTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        ClientError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        ClientError::BudgetExhausted(_) => StatusCode::TOO_MANY_REQUESTS,
+        _ => error
+            .status()
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::BAD_GATEWAY),
+    }
+}
+
+/// Text and function calls of the final assistant turn of a response.
+///
+/// Function calls are only reported when the response stopped to have them
+/// executed by the caller.
+fn final_turn(response: &Response) -> (String, Vec<&Part>) {
+    let Some(parts) = response
+        .data
+        .iter()
+        .rev()
+        .find_map(|message| match message {
+            Message::Assistant(parts) => Some(parts),
+            Message::User(_) => None,
+        })
+    else {
+        return (String::new(), Vec::new());
+    };
+
+    let text = parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .collect();
+    let calls = if response.finish == FinishReason::ToolCalls {
+        parts
+            .iter()
+            .filter(|part| matches!(part, Part::FunctionCall { .. }))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    (text, calls)
+}
+
+/// Progress of a streamed request, as reported to the wire format adapters.
+enum Update {
+    /// Assistant text generated since the previous update.
+    Text(String),
+    /// The final response.
+    Done(Response),
+    /// The backend failed; no further updates follow.
+    Failed(ClientError),
+}
+
+/// Run a streaming request and report it as text deltas followed by the final response.
+fn updates(
+    backend: Arc<dyn Backend>,
+    system: Option<String>,
+    messages: Vec<Message>,
+    tools: Vec<Tool>,
+) -> impl Stream<Item = Update> + Send {
+    async_stream::stream! {
+        let mut snapshots = match backend.complete_stream(system, messages, tools).await {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                yield Update::Failed(e);
+                return;
+            }
+        };
+
+        let mut deltas = TextDeltas::default();
+        let mut last = None;
+        while let Some(snapshot) = snapshots.next().await {
+            match snapshot {
+                Ok(snapshot) => {
+                    let delta = deltas.next(&snapshot);
+                    if !delta.is_empty() {
+                        yield Update::Text(delta);
+                    }
+                    last = Some(snapshot);
+                }
+                Err(e) => {
+                    yield Update::Failed(e);
+                    return;
+                }
+            }
+        }
+
+        if let Some(response) = last {
+            yield Update::Done(response);
+        }
+    }
+}
+
+/// Turns cumulative snapshots into text deltas.
+#[derive(Default)]
+struct TextDeltas {
+    /// Bytes already sent per (message, part) index.
+    sent: HashMap<(usize, usize), usize>,
+}
+
+impl TextDeltas {
+    /// Assistant text added since the previous snapshot.
+    fn next(&mut self, response: &Response) -> String {
+        let mut delta = String::new();
+        for (message_index, message) in response.data.iter().enumerate() {
+            let Message::Assistant(parts) = message else {
+                continue;
+            };
+            for (part_index, part) in parts.iter().enumerate() {
+                let Part::Text { content, .. } = part else {
+                    continue;
+                };
+                let sent = self.sent.entry((message_index, part_index)).or_default();
+                if let Some(new) = content.get(*sent..) {
+                    delta.push_str(new);
+                    *sent = content.len();
+                }
+            }
+        }
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures as fixtures;
+
+    /// Unfinished snapshot with one assistant message per text.
+    fn snapshot(texts: &[&str]) -> Response {
+        let mut response = fixtures::snapshot("", FinishReason::Unfinished);
+        response.data = texts
+            .iter()
+            .flat_map(|text| fixtures::snapshot(text, FinishReason::Unfinished).data)
+            .collect();
+        response
+    }
+
+    #[test]
+    fn test_text_deltas() {
+        let mut deltas = TextDeltas::default();
+
+        assert_eq!(deltas.next(&snapshot(&["Hel"])), "Hel");
+        assert_eq!(deltas.next(&snapshot(&["Hello"])), "lo");
+        assert_eq!(deltas.next(&snapshot(&["Hello", "Bye"])), "Bye");
+        assert_eq!(deltas.next(&snapshot(&["Hello", "Bye"])), "");
+    }
+}
@@ -0,0 +1,243 @@
+//! OpenAI Chat Completions endpoint.
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::Json;
+use futures::StreamExt;
+use rmcp::model::Tool;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{error_status, final_turn, inbound_tool, updates, Backend, Update};
+use crate::api::openai::{messages_from_json, system_from_json};
+use crate::client::ClientError;
+use crate::model::{FinishReason, Part, Response};
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ChatCompletionRequest {
+    model: Option<String>,
+    messages: Value,
+    #[serde(default)]
+    tools: Vec<InboundTool>,
+    #[serde(default)]
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundTool {
+    function: InboundFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundFunction {
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    parameters: Map<String, Value>,
+}
+
+impl From<InboundTool> for Tool {
+    fn from(tool: InboundTool) -> Self {
+        inbound_tool(
+            tool.function.name,
+            tool.function.description,
+            tool.function.parameters,
+        )
+    }
+}
+
+/// `POST /v1/chat/completions`
+pub(super) async fn chat_completions(
+    State(backend): State<Arc<dyn Backend>>,
+    request: Result<Json<ChatCompletionRequest>, JsonRejection>,
+) -> HttpResponse {
+    let request = match request {
+        Ok(Json(request)) => request,
+        Err(rejection) => return error_response(rejection.status(), &rejection.body_text()),
+    };
+    let system = match system_from_json(&request.messages) {
+        Ok(system) => system,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let messages = match messages_from_json(request.messages) {
+        Ok(messages) => messages,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let tools: Vec<Tool> = request.tools.into_iter().map(Tool::from).collect();
+    let model = request.model.unwrap_or_else(|| backend.model());
+
+    if !request.stream {
+        return match backend.complete(system, messages, tools).await {
+            Ok(response) => Json(completion(&response, &model)).into_response(),
+            Err(e) => client_error_response(&e),
+        };
+    }
+
+    let stream = async_stream::stream! {
+        let chunk = Chunk::new(model);
+        yield chunk.event(json!({ "role": "assistant" }), None);
+
+        let updates = updates(backend, system, messages, tools);
+        futures::pin_mut!(updates);
+        while let Some(update) = updates.next().await {
+            match update {
+                Update::Text(delta) => yield chunk.event(json!({ "content": delta }), None),
+                Update::Done(response) => {
+                    let (_, calls) = final_turn(&response);
+                    if !calls.is_empty() {
+                        let calls: Vec<Value> = calls
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, call)| {
+                                let mut call = tool_call(call);
+                                call["index"] = json!(index);
+                                call
+                            })
+                            .collect();
+                        yield chunk.event(json!({ "tool_calls": calls }), None);
+                    }
+                    yield chunk.event(json!({}), Some(finish_reason(&response.finish)));
+                }
+                Update::Failed(e) => {
+                    yield error_event(&e);
+                    return;
+                }
+            }
+        }
+        yield Ok(Event::default().data("[DONE]"));
+    };
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Identity shared by all chunks of a streamed completion.
+struct Chunk {
+    id: String,
+    created: u64,
+    model: String,
+}
+
+impl Chunk {
+    fn new(model: String) -> Self {
+        Self {
+            id: completion_id(),
+            created: now(),
+            model,
+        }
+    }
+
+    fn event(&self, delta: Value, finish_reason: Option<&str>) -> Result<Event, Infallible> {
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason,
+            }],
+        });
+        Ok(Event::default().data(chunk.to_string()))
+    }
+}
+
+fn completion(response: &Response, model: &str) -> Value {
+    let (text, calls) = final_turn(response);
+    let mut message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !calls.is_empty() { Value::Null } else { json!(text) },
+    });
+    if !calls.is_empty() {
+        message["tool_calls"] = calls.into_iter().map(tool_call).collect();
+    }
+
+    let prompt_tokens = response.usage.prompt_tokens.unwrap_or(0);
+    let completion_tokens = response.usage.completion_tokens.unwrap_or(0);
+    json!({
+        "id": completion_id(),
+        "object": "chat.completion",
+        "created": now(),
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": finish_reason(&response.finish),
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    })
+}
+
+fn tool_call(part: &Part) -> Value {
+    let Part::FunctionCall {
+        id,
+        name,
+        arguments,
+        ..
+    } = part
+    else {
+        return Value::Null;
+    };
+    json!({
+        "id": id.clone().unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple())),
+        "type": "function",
+        "function": {
+            "name": name,
+            "arguments": arguments.to_string(),
+        },
+    })
+}
+
+fn finish_reason(finish: &FinishReason) -> &'static str {
+    match finish {
+        FinishReason::OutputTokens => "length",
+        FinishReason::ToolCalls => "tool_calls",
+        FinishReason::ContentFilter => "content_filter",
+        _ => "stop",
+    }
+}
+
+fn completion_id() -> String {
+    format!("chatcmpl-{}", uuid::Uuid::new_v4().simple())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn error_body(message: &str, error_type: &str) -> Value {
+    json!({ "error": { "message": message, "type": error_type, "code": null } })
+}
+
+fn error_response(status: StatusCode, message: &str) -> HttpResponse {
+    (status, Json(error_body(message, "invalid_request_error"))).into_response()
+}
+
+fn client_error_response(error: &ClientError) -> HttpResponse {
+    let status = error_status(error);
+    let error_type = if status.is_client_error() {
+        "invalid_request_error"
+    } else {
+        "api_error"
+    };
+    (status, Json(error_body(&error.to_string(), error_type))).into_response()
+}
+
+fn error_event(error: &ClientError) -> Result<Event, Infallible> {
+    Ok(Event::default().data(error_body(&error.to_string(), "api_error").to_string()))
+}
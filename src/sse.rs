@@ -11,10 +11,21 @@
 //!
 //! data: [DONE]
 //! ```
+//!
+//! Lines starting with `:` are comments, which servers send as keep-alives during
+//! long pauses. They carry no data but count as activity for the idle timeout
+//! ([`TransportOptions::idle_timeout`]).
 
+use bytes::Bytes;
 use futures::stream::{self, Stream, StreamExt};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::RequestBuilder;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::time::Duration;
 
 use crate::client::ClientError;
+use crate::options::{ReconnectPolicy, TransportOptions};
 
 /// Extension trait for `reqwest::Response` to enable SSE streaming.
 ///
@@ -39,80 +50,285 @@ pub trait SSEResponseExt {
     ///
     /// Returns the content after `data: ` prefix for each SSE event.
     /// Stops when `[DONE]` marker is encountered or stream ends.
+    ///
+    /// Responses declaring a `Content-Type` other than `text/event-stream` (e.g. JSON
+    /// error bodies returned with a `200` by some proxies) yield a single
+    /// [`ClientError::ProviderError`] carrying the body.
     fn sse(self) -> impl Stream<Item = Result<String, ClientError>> + Send;
+
+    /// Like [`sse`](SSEResponseExt::sse), but resume the stream if the connection
+    /// breaks.
+    ///
+    /// `id:` and `retry:` fields are tracked while reading. On a transport error after
+    /// an event id was received, `request` is sent again with a `Last-Event-ID` header,
+    /// as allowed by `policy`. Without a request or policy, or for streams without ids,
+    /// errors are returned as with [`sse`](SSEResponseExt::sse).
+    fn sse_with_reconnect(
+        self,
+        request: Option<RequestBuilder>,
+        policy: Option<ReconnectPolicy>,
+    ) -> impl Stream<Item = Result<String, ClientError>> + Send;
+
+    /// Like [`sse_with_reconnect`](SSEResponseExt::sse_with_reconnect), with the
+    /// reconnection policy and idle timeout of `transport_options`.
+    ///
+    /// A stream receiving nothing, not even a keep-alive comment, for the idle timeout
+    /// is resumed as if its connection broke, or fails with [`ClientError::Timeout`].
+    fn sse_with_transport(
+        self,
+        request: Option<RequestBuilder>,
+        transport_options: &TransportOptions,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, ClientError>> + Send>>;
 }
 
 impl SSEResponseExt for reqwest::Response {
     fn sse(self) -> impl Stream<Item = Result<String, ClientError>> + Send {
-        let byte_stream = self.bytes_stream();
-
-        stream::unfold(
-            (Box::pin(byte_stream), String::new(), false),
-            |(mut byte_stream, mut buffer, mut stream_ended)| async move {
-                loop {
-                    if !stream_ended {
-                        match byte_stream.next().await {
-                            Some(Ok(chunk)) => {
-                                if let Ok(s) = std::str::from_utf8(&chunk) {
-                                    buffer.push_str(s);
-                                }
-                            }
-                            Some(Err(e)) => {
-                                return Some((
-                                    Err(ClientError::from(e)),
-                                    (byte_stream, buffer, stream_ended),
-                                ));
-                            }
-                            None => {
-                                stream_ended = true;
-                            }
-                        }
-                    }
+        self.sse_with_reconnect(None, None)
+    }
 
-                    while let Some(pos) = buffer.find('\n') {
-                        let line = buffer[..pos].trim().to_string();
-                        buffer.drain(..=pos);
+    fn sse_with_reconnect(
+        self,
+        request: Option<RequestBuilder>,
+        policy: Option<ReconnectPolicy>,
+    ) -> impl Stream<Item = Result<String, ClientError>> + Send {
+        event_stream(self, request, policy, None)
+    }
 
-                        if line.is_empty() {
-                            continue;
-                        }
+    fn sse_with_transport(
+        self,
+        request: Option<RequestBuilder>,
+        transport_options: &TransportOptions,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, ClientError>> + Send>> {
+        Box::pin(event_stream(
+            self,
+            request,
+            transport_options.reconnect().cloned(),
+            transport_options.idle_timeout(),
+        ))
+    }
+}
 
-                        if let Some(data) = parse_sse_line(&line) {
-                            if is_done_marker(data) {
-                                return None;
-                            }
+/// Event stream of `response`, resumable with `request` according to `policy`.
+fn event_stream(
+    response: reqwest::Response,
+    request: Option<RequestBuilder>,
+    policy: Option<ReconnectPolicy>,
+    idle_timeout: Option<Duration>,
+) -> impl Stream<Item = Result<String, ClientError>> + Send {
+    if let Some(content_type) = unexpected_content_type(&response) {
+        return stream::once(async move {
+            let body = response.text().await.map_err(ClientError::from)?;
+            Err(ClientError::ProviderError(format!(
+                "Expected an event stream but received {}: {}",
+                content_type, body
+            )))
+        })
+        .left_stream();
+    }
 
-                            return Some((
-                                Ok(data.to_string()),
-                                (byte_stream, buffer, stream_ended),
-                            ));
-                        }
-                    }
+    let state = EventStream {
+        bytes: Box::pin(
+            response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(Interruption::Http)),
+        ),
+        buffer: String::new(),
+        ended: false,
+        event_id: None,
+        pending: Vec::new(),
+        ready: VecDeque::new(),
+        last_event_id: None,
+        retry: None,
+        reconnect: request.zip(policy),
+        attempts: 0,
+        idle_timeout,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        let data = state.next_data().await?;
+        Some((data, state))
+    })
+    .right_stream()
+}
+
+type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, Interruption>> + Send>>;
+
+/// Parser state of an event stream, with what is needed to resume it.
+struct EventStream {
+    bytes: ByteStream,
+    buffer: String,
+    ended: bool,
+    /// Id of the event being received, committed once the event is complete.
+    event_id: Option<String>,
+    /// Data lines of the event being received, held back until the event is complete
+    /// so that an event replayed after reconnecting is not yielded twice. `None` is the
+    /// done marker.
+    pending: Vec<Option<String>>,
+    /// Data lines of complete events, not yielded yet.
+    ready: VecDeque<Option<String>>,
+    last_event_id: Option<String>,
+    /// Reconnection delay requested by the server.
+    retry: Option<Duration>,
+    reconnect: Option<(RequestBuilder, ReconnectPolicy)>,
+    attempts: u32,
+    /// Longest wait for the next chunk.
+    idle_timeout: Option<Duration>,
+}
+
+impl EventStream {
+    /// Next data line, or `None` once the stream is done.
+    async fn next_data(&mut self) -> Option<Result<String, ClientError>> {
+        loop {
+            if let Some(data) = self.ready.pop_front() {
+                return data.map(Ok);
+            }
+            if let Some(pos) = self.buffer.find('\n') {
+                let line = self.buffer[..pos].trim().to_string();
+                self.buffer.drain(..=pos);
+                self.process_line(&line);
+                continue;
+            }
 
-                    if stream_ended {
-                        if !buffer.is_empty() {
-                            let line = buffer.trim().to_string();
-                            buffer.clear();
-                            if !line.is_empty() {
-                                if let Some(data) = parse_sse_line(&line) {
-                                    if !is_done_marker(data) {
-                                        return Some((
-                                            Ok(data.to_string()),
-                                            (byte_stream, buffer, stream_ended),
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-
-                        return None;
+            if self.ended {
+                // The last event may not be followed by a blank line.
+                let line = std::mem::take(&mut self.buffer);
+                self.process_line(line.trim());
+                self.process_line("");
+                return self.ready.pop_front().flatten().map(Ok);
+            }
+
+            let next = match self.idle_timeout {
+                Some(idle_timeout) => tokio::time::timeout(idle_timeout, self.bytes.next())
+                    .await
+                    .unwrap_or_else(|_| Some(Err(Interruption::Idle(idle_timeout)))),
+                None => self.bytes.next().await,
+            };
+            match next {
+                Some(Ok(chunk)) => {
+                    if let Ok(s) = std::str::from_utf8(&chunk) {
+                        self.buffer.push_str(s);
                     }
                 }
-            },
-        )
+                Some(Err(e)) => {
+                    if let Err(e) = self.resume(e.into()).await {
+                        // The stream cannot be continued after a failure.
+                        self.ended = true;
+                        self.buffer.clear();
+                        self.pending.clear();
+                        return Some(Err(e));
+                    }
+                }
+                None => self.ended = true,
+            }
+        }
+    }
+
+    /// Handle a complete line. A blank line completes the event, making its data lines
+    /// ready.
+    fn process_line(&mut self, line: &str) {
+        if line.is_empty() {
+            if let Some(id) = self.event_id.take() {
+                self.last_event_id = Some(id);
+            }
+            self.ready.extend(self.pending.drain(..));
+        } else if let Some(comment) = line.strip_prefix(':') {
+            tracing::trace!("Event stream comment: {}", comment.trim());
+        } else if let Some(data) = parse_sse_line(line) {
+            self.pending
+                .push((!is_done_marker(data)).then(|| data.to_string()));
+        } else if let Some(id) = line.strip_prefix("id:") {
+            self.event_id = Some(id.trim().to_string());
+        } else if let Some(retry) = line.strip_prefix("retry:") {
+            if let Ok(millis) = retry.trim().parse() {
+                self.retry = Some(Duration::from_millis(millis));
+            }
+        }
+    }
+
+    /// Reconnect after `error` from the last received event id, or return `error` if
+    /// the stream cannot be resumed.
+    async fn resume(&mut self, error: ClientError) -> Result<(), ClientError> {
+        let (Some((request, policy)), Some(last_event_id)) = (&self.reconnect, &self.last_event_id)
+        else {
+            return Err(error);
+        };
+
+        while self.attempts < policy.max_attempts {
+            self.attempts += 1;
+            let Some(request) = request.try_clone() else {
+                break;
+            };
+            tokio::time::sleep(self.retry.unwrap_or(policy.delay)).await;
+            tracing::warn!(
+                "Event stream interrupted ({}), reconnecting from event {} (attempt {}/{})",
+                error,
+                last_event_id,
+                self.attempts,
+                policy.max_attempts
+            );
+
+            match request.header("Last-Event-ID", last_event_id).send().await {
+                Ok(response)
+                    if response.status().is_success()
+                        && unexpected_content_type(&response).is_none() =>
+                {
+                    self.bytes = Box::pin(
+                        response
+                            .bytes_stream()
+                            .map(|chunk| chunk.map_err(Interruption::Http)),
+                    );
+                    self.buffer.clear();
+                    self.event_id = None;
+                    self.pending.clear();
+                    self.ended = false;
+                    return Ok(());
+                }
+                Ok(response) => {
+                    tracing::warn!("Reconnection rejected with HTTP {}", response.status());
+                }
+                Err(e) => tracing::warn!("Reconnection failed: {}", e),
+            }
+        }
+
+        Err(error)
+    }
+}
+
+/// Why no chunk was received.
+enum Interruption {
+    Http(reqwest::Error),
+    Idle(Duration),
+}
+
+impl From<Interruption> for ClientError {
+    fn from(interruption: Interruption) -> Self {
+        match interruption {
+            Interruption::Http(e) => e.into(),
+            Interruption::Idle(timeout) => {
+                ClientError::Timeout(format!("Event stream received nothing for {:?}", timeout))
+            }
+        }
     }
 }
 
+/// `Content-Type` of a response declaring something other than an event stream.
+fn unexpected_content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|ct| !is_event_stream(ct))
+        .map(str::to_string)
+}
+
+/// Whether a `Content-Type` header value denotes a Server-Sent Events stream.
+fn is_event_stream(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
 /// Parse an SSE line to extract the data portion.
 ///
 /// SSE lines are in the format: `data: <content>`
@@ -170,4 +386,185 @@ mod tests {
         assert!(!is_done_marker("data"));
         assert!(!is_done_marker("{\"key\": \"value\"}"));
     }
+
+    /// Serve a single HTTP response with the given content type and body.
+    async fn serve_once(content_type: &'static str, body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                content_type,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_sse_rejects_non_event_stream_bodies() {
+        let url = serve_once("application/json", r#"{"error":"quota exceeded"}"#).await;
+        let events: Vec<_> = reqwest::get(url).await.unwrap().sse().collect().await;
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            Err(ClientError::ProviderError(message)) => {
+                assert!(message.contains("application/json"));
+                assert!(message.contains("quota exceeded"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let url = serve_once(
+            "text/event-stream; charset=utf-8",
+            "data: a\n\ndata: [DONE]\n\n",
+        )
+        .await;
+        let events: Vec<_> = reqwest::get(url).await.unwrap().sse().collect().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].as_ref().unwrap(), "a");
+    }
+
+    /// Serve `first` on a connection that breaks before its declared length is sent,
+    /// then `second` to the reconnection, whose request head is returned.
+    async fn serve_interrupted(
+        first: &'static str,
+        second: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\r\n{}",
+                first.len() + 100,
+                first
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                second.len(),
+                second
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+        (format!("http://{}", addr), server)
+    }
+
+    async fn collect_with_reconnect(url: &str) -> Vec<String> {
+        let request = reqwest::Client::new().get(url);
+        let response = request.try_clone().unwrap().send().await.unwrap();
+        let events: Vec<_> = response
+            .sse_with_reconnect(
+                Some(request),
+                Some(ReconnectPolicy::new(1, Duration::from_secs(5))),
+            )
+            .collect()
+            .await;
+        events.into_iter().map(Result::unwrap).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sse_reconnects_with_last_event_id() {
+        let (url, server) = serve_interrupted(
+            "retry: 10\nid: 1\ndata: a\n\n",
+            "id: 2\ndata: b\n\ndata: [DONE]\n\n",
+        )
+        .await;
+
+        assert_eq!(collect_with_reconnect(&url).await, vec!["a", "b"]);
+        assert!(server.await.unwrap().contains("last-event-id: 1"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_event_broken_mid_way_is_not_duplicated() {
+        let (url, server) = serve_interrupted(
+            "retry: 10\nid: 1\ndata: a\n\nid: 2\ndata: b\n",
+            "id: 2\ndata: b\ndata: c\n\ndata: [DONE]\n\n",
+        )
+        .await;
+
+        assert_eq!(collect_with_reconnect(&url).await, vec!["a", "b", "c"]);
+        assert!(server.await.unwrap().contains("last-event-id: 1"));
+    }
+
+    /// Serve a single event stream, writing each chunk after its delay.
+    async fn serve_slowly(chunks: Vec<(u64, &'static str)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await;
+            let length: usize = chunks.iter().map(|(_, chunk)| chunk.len()).sum();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                length
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for (delay, chunk) in chunks {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                if socket.write_all(chunk.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_comments_reset_idle_timeout() {
+        let url = serve_slowly(vec![
+            (0, "data: a\n\n"),
+            (100, ": keep-alive\n\n"),
+            (100, ": data: not an event\n\n"),
+            (100, "data: b\n\ndata: [DONE]\n\n"),
+        ])
+        .await;
+        let transport = TransportOptions::new().with_idle_timeout(Duration::from_millis(250));
+        let events: Vec<_> = reqwest::get(url)
+            .await
+            .unwrap()
+            .sse_with_transport(None, &transport)
+            .collect()
+            .await;
+
+        let events: Vec<_> = events.into_iter().map(Result::unwrap).collect();
+        assert_eq!(events, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_idle_stream_times_out() {
+        let url = serve_slowly(vec![(0, "data: a\n\n"), (1000, "data: b\n\n")]).await;
+        let transport = TransportOptions::new().with_idle_timeout(Duration::from_millis(100));
+        let events: Vec<_> = reqwest::get(url)
+            .await
+            .unwrap()
+            .sse_with_transport(None, &transport)
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].as_ref().unwrap(), "a");
+        assert!(matches!(events[1], Err(ClientError::Timeout(_))));
+    }
 }
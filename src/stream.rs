@@ -1,3 +1,1156 @@
 //! Streaming support types and utilities.
 
+use futures::{Future, Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time::{sleep_until, timeout_at, Instant};
+use tracing::warn;
+
+use crate::client::ClientError;
+use crate::compress;
+use crate::draft::DraftStore;
+use crate::model::{FinishReason, Part, Response, Usage};
+use crate::options::TransportOptions;
+
 pub use crate::sse::{is_done_marker, parse_sse_line};
+
+/// Typed event derived from a stream of cumulative [`Response`] snapshots.
+///
+/// Provider streams yield the entire response generated so far on every item. The
+/// event layer keeps those snapshots but additionally reports usage and finish
+/// changes explicitly, so consumers can react to them without diffing snapshots.
+/// New events may be added in minor releases.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ResponseEvent {
+    /// The cumulative response generated so far.
+    Snapshot(Response),
+    /// Token usage changed. Carries the cumulative usage, not the increment.
+    Usage(Usage),
+    /// Generation finished with the given reason. Emitted at most once.
+    Finish(FinishReason),
+}
+
+/// Extension trait turning a snapshot stream into a stream of [`ResponseEvent`]s.
+pub trait ResponseStreamExt: Stream<Item = Result<Response, ClientError>> + Send {
+    /// Convert the snapshot stream into a typed event stream.
+    ///
+    /// For every snapshot a [`ResponseEvent::Snapshot`] is emitted, followed by a
+    /// [`ResponseEvent::Usage`] if usage changed since the previous snapshot and a
+    /// [`ResponseEvent::Finish`] the first time a finish reason is reported.
+    fn events<'a>(
+        self,
+    ) -> Pin<Box<dyn Stream<Item = Result<ResponseEvent, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+    {
+        Box::pin(async_stream::try_stream! {
+            let mut stream = Box::pin(self);
+            let mut last_usage = Usage::default();
+            let mut finished = false;
+
+            while let Some(response) = stream.next().await {
+                let response = response?;
+                let usage = response.usage.clone();
+                let finish = response.finish.clone();
+
+                yield ResponseEvent::Snapshot(response);
+
+                if usage != last_usage {
+                    last_usage = usage.clone();
+                    yield ResponseEvent::Usage(usage);
+                }
+
+                if !finished && finish != FinishReason::Unfinished {
+                    finished = true;
+                    yield ResponseEvent::Finish(finish);
+                }
+            }
+        })
+    }
+
+    /// Measure time to first token, throughput and duration of the stream.
+    ///
+    /// `started` is when the request was sent, i.e. taken before calling
+    /// [`request_stream`](crate::client::StreamingClient::request_stream), so that
+    /// connection and queueing time count towards the first token.
+    fn measured<'a>(self, started: std::time::Instant) -> MeasuredStream<'a>
+    where
+        Self: Sized + 'a,
+    {
+        MeasuredStream {
+            stream: Box::pin(self),
+            started,
+            stats: StreamStats::default(),
+        }
+    }
+
+    /// Save every snapshot as the draft of the response `id` in `store`.
+    ///
+    /// The stream is passed through unchanged. Call [`DraftStore::commit`] once the
+    /// final response has been stored, so that only interrupted responses keep a
+    /// draft. Failures of the store are logged and do not interrupt the stream.
+    fn drafted<'a>(
+        self,
+        store: Arc<dyn DraftStore>,
+        id: impl Into<String>,
+    ) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+    {
+        let id = id.into();
+        Box::pin(self.then(move |item| {
+            let store = store.clone();
+            let id = id.clone();
+            async move {
+                if let Ok(response) = &item {
+                    if let Err(e) = store.save(&id, response).await {
+                        warn!("Failed to save draft {}: {}", id, e);
+                    }
+                }
+                item
+            }
+        }))
+    }
+
+    /// Write the streamed text to `writer` as it arrives, returning the last snapshot.
+    ///
+    /// Only text is written, not reasoning or tool calls. The writer is flushed after
+    /// every snapshot, so each delta reaches the terminal or socket right away. Text
+    /// parts following earlier text start on a new line.
+    fn write_to<'a, W>(
+        self,
+        writer: &'a mut W,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+        W: AsyncWrite + Unpin + Send + ?Sized,
+    {
+        Box::pin(async move {
+            let mut stream = Box::pin(self);
+            let mut written = false;
+            let mut prev = Response {
+                data: Vec::new(),
+                usage: Usage::default(),
+                finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
+            };
+
+            while let Some(response) = stream.next().await {
+                let response = response?;
+                let mut text = String::new();
+                for delta in diff(&prev, &response) {
+                    match delta {
+                        PartDelta::Added {
+                            part: Part::Text { content, .. },
+                            ..
+                        } => {
+                            if written || !text.is_empty() {
+                                text.push('\n');
+                            }
+                            text.push_str(&content);
+                        }
+                        PartDelta::Appended {
+                            message,
+                            index,
+                            content,
+                        } if is_text(&response, message, index) => text.push_str(&content),
+                        _ => {}
+                    }
+                }
+                if !text.is_empty() {
+                    writer.write_all(text.as_bytes()).await?;
+                    writer.flush().await?;
+                    written = true;
+                }
+                prev = response;
+            }
+            Ok(prev)
+        })
+    }
+
+    /// Pace the stream for display, revealing at most `chars` characters of text and
+    /// reasoning per `interval`.
+    ///
+    /// Snapshots arriving in a burst are split into intermediate snapshots with
+    /// truncated text, so that rendering progresses smoothly regardless of how the
+    /// network delivers content. Up to `chars` characters are revealed immediately
+    /// after a pause. Intermediate snapshots are unfinished; the last snapshot of each
+    /// burst is emitted unchanged. An `interval` of zero disables pacing.
+    fn paced<'a>(
+        self,
+        chars: usize,
+        interval: Duration,
+    ) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+    {
+        if chars == 0 || interval.is_zero() {
+            return Box::pin(self);
+        }
+        Box::pin(async_stream::try_stream! {
+            let mut stream = Box::pin(self);
+            let mut shown = 0;
+            let mut bucket = chars;
+            let mut refilled = Instant::now();
+
+            while let Some(response) = stream.next().await {
+                let response = response?;
+                let total = text_len(&response);
+
+                while shown < total {
+                    let ticks = u32::try_from(refilled.elapsed().as_nanos() / interval.as_nanos())
+                        .unwrap_or(u32::MAX);
+                    if ticks > 0 {
+                        let refill = chars.saturating_mul(ticks as usize);
+                        if refill >= chars - bucket {
+                            // A full bucket does not carry the rest of the pause over.
+                            bucket = chars;
+                            refilled = Instant::now();
+                        } else {
+                            bucket += refill;
+                            refilled += interval * ticks;
+                        }
+                    }
+                    if bucket == 0 {
+                        sleep_until(refilled + interval).await;
+                        continue;
+                    }
+                    let step = bucket.min(total - shown);
+                    bucket -= step;
+                    shown += step;
+                    if shown < total {
+                        yield truncate(&response, shown);
+                    }
+                }
+                shown = total;
+                yield response;
+            }
+        })
+    }
+
+    /// End the stream once the completion exceeds `limit` tokens.
+    ///
+    /// Tokens are counted from the usage of each snapshot, or estimated from its
+    /// content when the provider reports usage only at the end. The snapshot crossing
+    /// the limit is emitted last, with [`FinishReason::OutputTokens`]: its text and
+    /// reasoning parts are finished and its incomplete tool calls are dropped. The
+    /// underlying stream is dropped right after, which closes the connection.
+    fn max_tokens<'a>(
+        self,
+        limit: u32,
+    ) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+    {
+        Box::pin(async_stream::try_stream! {
+            let mut stream = Box::pin(self);
+            while let Some(response) = stream.next().await {
+                let response = response?;
+                let estimate = compress::estimate_tokens(&response.data);
+                let tokens = response.usage.completion_tokens.unwrap_or(0) as usize;
+                if response.finish != FinishReason::Unfinished
+                    || tokens.max(estimate) <= limit as usize
+                {
+                    yield response;
+                    continue;
+                }
+                warn!("Stream exceeded {} completion tokens, stopping", limit);
+                yield cut_off(response, FinishReason::OutputTokens);
+                break;
+            }
+        })
+    }
+
+    /// End the stream with the content received so far when the transport fails.
+    ///
+    /// If the connection drops, times out or fails to read after at least one
+    /// unfinished snapshot, the last snapshot is emitted again with
+    /// [`FinishReason::Error`] instead of the error: its text and reasoning parts are
+    /// finished and its incomplete tool calls are dropped. The error is logged. Errors
+    /// before the first snapshot, and provider errors, are passed through.
+    fn recover_partial<'a>(
+        self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>
+    where
+        Self: Sized + 'a,
+    {
+        Box::pin(async_stream::stream! {
+            let mut stream = Box::pin(self);
+            let mut last: Option<Response> = None;
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(response) => {
+                        last = Some(response.clone());
+                        yield Ok(response);
+                    }
+                    Err(e) => {
+                        let transport = matches!(
+                            e,
+                            ClientError::Http(_) | ClientError::Io(_) | ClientError::Timeout(_)
+                        );
+                        match last.take() {
+                            Some(partial) if transport && partial.finish == FinishReason::Unfinished => {
+                                warn!("Stream interrupted, keeping the partial response: {}", e);
+                                yield Ok(cut_off(partial, FinishReason::Error));
+                            }
+                            _ => yield Err(e),
+                        }
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Group the streamed text into complete sentences, e.g. to feed a text-to-speech
+    /// engine as soon as each sentence is available.
+    ///
+    /// Only the text of the response is considered, not reasoning or tool calls. Each
+    /// text part is split separately, and the remainder of a part is emitted once the
+    /// part or the stream finishes.
+    fn sentences<'a>(self, splitter: SentenceSplitter) -> SentenceStream<'a>
+    where
+        Self: Sized + 'a,
+    {
+        Box::pin(async_stream::try_stream! {
+            let mut stream = Box::pin(self);
+            let mut splitter = splitter;
+            let mut prev = Response {
+                data: Vec::new(),
+                usage: Usage::default(),
+                finish: FinishReason::Unfinished,
+                stop_sequence: None,
+                service_tier: None,
+            };
+
+            while let Some(response) = stream.next().await {
+                let response = response?;
+                for delta in diff(&prev, &response) {
+                    let sentences = match delta {
+                        PartDelta::Added {
+                            part: Part::Text { content, .. },
+                            ..
+                        } => splitter.push(&content),
+                        PartDelta::Appended { message, index, content }
+                            if is_text(&response, message, index) =>
+                        {
+                            splitter.push(&content)
+                        }
+                        PartDelta::Finished { message, index } if is_text(&response, message, index) => {
+                            splitter.finish().into_iter().collect()
+                        }
+                        _ => Vec::new(),
+                    };
+                    for sentence in sentences {
+                        yield sentence;
+                    }
+                }
+                prev = response;
+            }
+
+            if let Some(sentence) = splitter.finish() {
+                yield sentence;
+            }
+        })
+    }
+}
+
+fn is_text(response: &Response, message: usize, index: usize) -> bool {
+    matches!(
+        response
+            .data
+            .get(message)
+            .and_then(|m| m.parts().get(index)),
+        Some(Part::Text { .. })
+    )
+}
+
+/// Number of text and reasoning characters in a response.
+fn text_len(response: &Response) -> usize {
+    response
+        .data
+        .iter()
+        .flat_map(|m| m.parts())
+        .map(|part| match part {
+            Part::Text { content, .. } | Part::Reasoning { content, .. } => content.chars().count(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Copy of `response` showing only its first `limit` text and reasoning characters.
+///
+/// Parts following the cut are dropped.
+fn truncate(response: &Response, mut limit: usize) -> Response {
+    let mut truncated = Response {
+        data: Vec::new(),
+        usage: response.usage.clone(),
+        finish: FinishReason::Unfinished,
+        stop_sequence: None,
+        service_tier: response.service_tier,
+    };
+    'messages: for message in &response.data {
+        let mut message = message.clone();
+        let parts = message.parts_mut();
+        for index in 0..parts.len() {
+            if let Part::Text {
+                content, finished, ..
+            }
+            | Part::Reasoning {
+                content, finished, ..
+            } = &mut parts[index]
+            {
+                let len = content.chars().count();
+                if len > limit {
+                    *content = content.chars().take(limit).collect::<String>().into();
+                    *finished = false;
+                    parts.truncate(index + 1);
+                    truncated.data.push(message);
+                    break 'messages;
+                }
+                limit -= len;
+            }
+        }
+        truncated.data.push(message);
+    }
+    truncated
+}
+
+/// Final form of a snapshot cut off by [`ResponseStreamExt::max_tokens`] or an
+/// interrupted transport, ending with `finish`.
+fn cut_off(mut response: Response, finish: FinishReason) -> Response {
+    for message in &mut response.data {
+        message.parts_mut().retain(|part| {
+            !matches!(
+                part,
+                Part::FunctionCall {
+                    finished: false,
+                    ..
+                }
+            )
+        });
+        for part in message.parts_mut() {
+            if let Part::Text { finished, .. } | Part::Reasoning { finished, .. } = part {
+                if !*finished {
+                    *finished = true;
+                    part.annotate();
+                }
+            }
+        }
+    }
+    response.finish = finish;
+    response
+}
+
+/// Apply the [`recover_partial`](TransportOptions::recover_partial) setting and the
+/// [`max_stream_tokens`](TransportOptions::max_stream_tokens) limit of a client to its
+/// stream.
+pub(crate) fn shape_stream(
+    transport_options: &TransportOptions,
+    stream: Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>,
+) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>> {
+    let stream = if transport_options.recover_partial() {
+        stream.recover_partial()
+    } else {
+        stream
+    };
+    match transport_options.max_stream_tokens() {
+        Some(limit) => stream.max_tokens(limit),
+        None => stream,
+    }
+}
+
+/// Stream of sentences produced by [`ResponseStreamExt::sentences`].
+pub type SentenceStream<'a> = Pin<Box<dyn Stream<Item = Result<String, ClientError>> + Send + 'a>>;
+
+/// Sentence delimiters used unless configured otherwise.
+pub const DEFAULT_SENTENCE_DELIMITERS: &[char] = &['.', '!', '?', '\n', '。', '！', '？'];
+
+/// Splits text arriving in pieces into sentences.
+///
+/// A sentence ends at a delimiter followed by whitespace, so that numbers such as
+/// `3.14` are not split. Delimiters that are whitespace themselves or not ASCII, like
+/// the CJK full stop `。`, end a sentence immediately. Sentences are trimmed and
+/// those shorter than the minimum length are merged with the following one.
+#[derive(Debug, Clone)]
+pub struct SentenceSplitter {
+    delimiters: Vec<char>,
+    min_chars: usize,
+    buffer: String,
+}
+
+impl Default for SentenceSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SentenceSplitter {
+    pub fn new() -> Self {
+        Self {
+            delimiters: DEFAULT_SENTENCE_DELIMITERS.to_vec(),
+            min_chars: 0,
+            buffer: String::new(),
+        }
+    }
+
+    /// Characters ending a sentence. Adding `,` and `;` splits at clauses for even
+    /// lower latency.
+    pub fn with_delimiters(mut self, delimiters: impl IntoIterator<Item = char>) -> Self {
+        self.delimiters = delimiters.into_iter().collect();
+        self
+    }
+
+    /// Minimum number of characters of a sentence, to avoid choppy speech from very
+    /// short fragments. Defaults to 0.
+    pub fn with_min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    /// Add text and return the sentences it completes.
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.buffer.push_str(text);
+
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            if !self.delimiters.contains(&c) {
+                continue;
+            }
+            let end = index + c.len_utf8();
+            let boundary = if c.is_whitespace() || !c.is_ascii() {
+                true
+            } else {
+                // The next character decides; wait for it if it has not arrived.
+                match chars.peek() {
+                    Some((_, next)) => next.is_whitespace(),
+                    None => false,
+                }
+            };
+            if !boundary {
+                continue;
+            }
+            let sentence = self.buffer[start..end].trim();
+            if sentence.chars().count() >= self.min_chars.max(1) {
+                sentences.push(sentence.to_string());
+                start = end;
+            }
+        }
+
+        self.buffer.drain(..start);
+        sentences
+    }
+
+    /// Return the remaining text, if any, and start over.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = rest.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+impl<S> ResponseStreamExt for S where S: Stream<Item = Result<Response, ClientError>> + Send {}
+
+/// Latency and throughput of a response stream, see [`ResponseStreamExt::measured`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamStats {
+    /// Time until the first snapshot with any content.
+    pub time_to_first_token: Option<Duration>,
+    /// Time until the latest snapshot, or the end of the stream.
+    pub duration: Duration,
+    /// Number of snapshots received.
+    pub chunks: usize,
+    /// Completion tokens reported by the provider so far.
+    pub completion_tokens: Option<u32>,
+}
+
+impl StreamStats {
+    /// Completion tokens per second of generation, from the first token on.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let generation = self.duration.checked_sub(self.time_to_first_token?)?;
+        let tokens = self.completion_tokens?;
+        (!generation.is_zero()).then(|| f64::from(tokens) / generation.as_secs_f64())
+    }
+}
+
+/// Response stream recording [`StreamStats`] as it is consumed.
+pub struct MeasuredStream<'a> {
+    stream: Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send + 'a>>,
+    started: std::time::Instant,
+    stats: StreamStats,
+}
+
+impl MeasuredStream<'_> {
+    /// Statistics of the snapshots received so far; final once the stream ended.
+    pub fn stats(&self) -> &StreamStats {
+        &self.stats
+    }
+}
+
+impl Stream for MeasuredStream<'_> {
+    type Item = Result<Response, ClientError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let item = futures::ready!(self.stream.as_mut().poll_next(cx));
+        let elapsed = self.started.elapsed();
+        let stats = &mut self.stats;
+        stats.duration = elapsed;
+        if let Some(Ok(response)) = &item {
+            stats.chunks += 1;
+            if stats.time_to_first_token.is_none() && has_output(response) {
+                stats.time_to_first_token = Some(elapsed);
+            }
+            if response.usage.completion_tokens.is_some() {
+                stats.completion_tokens = response.usage.completion_tokens;
+            }
+        }
+        std::task::Poll::Ready(item)
+    }
+}
+
+/// Whether a snapshot has any generated content.
+fn has_output(response: &Response) -> bool {
+    response
+        .data
+        .iter()
+        .flat_map(|message| message.parts())
+        .any(|part| match part {
+            Part::Text { content, .. } | Part::Reasoning { content, .. } => !content.is_empty(),
+            _ => true,
+        })
+}
+
+/// Change to a single part between two cumulative [`Response`] snapshots.
+///
+/// Parts are addressed by the index of their message in [`Response::data`] and their
+/// index within that message. New kinds of changes may be added in minor releases.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PartDelta {
+    /// A part that was not present in the previous snapshot.
+    Added {
+        message: usize,
+        index: usize,
+        part: Part,
+    },
+    /// Content appended to an existing text or reasoning part.
+    Appended {
+        message: usize,
+        index: usize,
+        content: String,
+    },
+    /// Any other change to an existing part, e.g. function call arguments.
+    /// Carries the new state of the part.
+    Updated {
+        message: usize,
+        index: usize,
+        part: Part,
+    },
+    /// The part is finished. Emitted for new parts that arrive finished as well.
+    Finished { message: usize, index: usize },
+}
+
+/// Compute the changes from `prev` to `next`, in part order.
+///
+/// Both responses are expected to be snapshots of the same stream, so `next` extends
+/// `prev`. Parts missing from `next` are not reported.
+pub fn diff(prev: &Response, next: &Response) -> Vec<PartDelta> {
+    let mut deltas = Vec::new();
+    for (message, next_message) in next.data.iter().enumerate() {
+        let prev_parts = prev.data.get(message).map(|m| m.parts().as_slice());
+        for (index, part) in next_message.parts().iter().enumerate() {
+            let old = prev_parts.and_then(|parts| parts.get(index));
+            match old {
+                None => deltas.push(PartDelta::Added {
+                    message,
+                    index,
+                    part: part.clone(),
+                }),
+                Some(old) if !same_content(old, part) => deltas.push(match appended(old, part) {
+                    Some(content) => PartDelta::Appended {
+                        message,
+                        index,
+                        content: content.to_string(),
+                    },
+                    None => PartDelta::Updated {
+                        message,
+                        index,
+                        part: part.clone(),
+                    },
+                }),
+                Some(_) => {}
+            }
+            if is_finished(part) && !old.is_some_and(is_finished) {
+                deltas.push(PartDelta::Finished { message, index });
+            }
+        }
+    }
+    deltas
+}
+
+fn is_finished(part: &Part) -> bool {
+    match part {
+        Part::Text { finished, .. }
+        | Part::Reasoning { finished, .. }
+        | Part::FunctionCall { finished, .. }
+        | Part::FunctionResponse { finished, .. }
+        | Part::Media { finished, .. } => *finished,
+    }
+}
+
+/// Whether two parts are equal apart from their finished flag.
+fn same_content(a: &Part, b: &Part) -> bool {
+    let mut a = a.clone();
+    match &mut a {
+        Part::Text { finished, .. }
+        | Part::Reasoning { finished, .. }
+        | Part::FunctionCall { finished, .. }
+        | Part::FunctionResponse { finished, .. }
+        | Part::Media { finished, .. } => *finished = is_finished(b),
+    }
+    a == *b
+}
+
+/// Content added to a text or reasoning part, if that is its only change.
+fn appended<'a>(old: &Part, new: &'a Part) -> Option<&'a str> {
+    match (old, new) {
+        (Part::Text { content: old, .. }, Part::Text { content: new, .. }) => {
+            new.strip_prefix(old.as_str())
+        }
+        (
+            Part::Reasoning {
+                content: old,
+                summary: old_summary,
+                signature: old_signature,
+                ..
+            },
+            Part::Reasoning {
+                content: new,
+                summary,
+                signature,
+                ..
+            },
+        ) if old_summary == summary && old_signature == signature => new.strip_prefix(old.as_str()),
+        _ => None,
+    }
+}
+
+/// Time-to-first-token bound of a streaming request.
+///
+/// The deadline starts when the request is sent and covers waiting for the response
+/// headers as well as for the first snapshot that carries content.
+pub(crate) struct FirstTokenDeadline(Option<Instant>);
+
+impl FirstTokenDeadline {
+    pub(crate) fn start(transport_options: &TransportOptions) -> Self {
+        Self(
+            transport_options
+                .first_token_timeout()
+                .map(|timeout| Instant::now() + timeout),
+        )
+    }
+
+    /// Send the request, failing if the response headers miss the deadline.
+    pub(crate) async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ClientError> {
+        match self.0 {
+            Some(deadline) => Ok(timeout_at(deadline, request.send())
+                .await
+                .map_err(|_| Self::elapsed())??),
+            None => Ok(request.send().await?),
+        }
+    }
+
+    /// Fail the stream if no content arrives before the deadline.
+    pub(crate) fn guard<S>(
+        self,
+        stream: S,
+    ) -> Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>
+    where
+        S: Stream<Item = Result<Response, ClientError>> + Send + 'static,
+    {
+        let Some(deadline) = self.0 else {
+            return Box::pin(stream);
+        };
+
+        Box::pin(async_stream::try_stream! {
+            let mut stream = Box::pin(stream);
+            let mut waiting = true;
+
+            loop {
+                let item = if waiting {
+                    timeout_at(deadline, stream.next())
+                        .await
+                        .map_err(|_| Self::elapsed())?
+                } else {
+                    stream.next().await
+                };
+                let Some(response) = item else {
+                    break;
+                };
+
+                let response = response?;
+                if response.data.iter().any(|m| !m.parts().is_empty()) {
+                    waiting = false;
+                }
+                yield response;
+            }
+        })
+    }
+
+    fn elapsed() -> ClientError {
+        ClientError::Timeout("No response content before the first token timeout".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Extensions, Message, Part};
+    use futures::stream;
+    use std::time::Duration;
+
+    fn snapshot(text: &str, completion_tokens: Option<u32>, finish: FinishReason) -> Response {
+        Response {
+            usage: Usage {
+                prompt_tokens: Some(3),
+                completion_tokens,
+                cached_prompt_tokens: None,
+                accepted_prediction_tokens: None,
+                rejected_prediction_tokens: None,
+            },
+            ..crate::test_fixtures::snapshot(text, finish)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_report_usage_and_finish() {
+        let snapshots = vec![
+            Ok(snapshot("He", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hello", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hello", Some(2), FinishReason::Stop)),
+            Ok(snapshot("Hello", Some(2), FinishReason::Stop)),
+        ];
+
+        let events: Vec<ResponseEvent> = stream::iter(snapshots)
+            .events()
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|e| match e {
+                ResponseEvent::Snapshot(_) => "snapshot",
+                ResponseEvent::Usage(_) => "usage",
+                ResponseEvent::Finish(_) => "finish",
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec!["snapshot", "usage", "snapshot", "snapshot", "usage", "finish", "snapshot"]
+        );
+        assert!(matches!(
+            events[5],
+            ResponseEvent::Finish(FinishReason::Stop)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_measured_stream_stats() {
+        let started = std::time::Instant::now();
+        let snapshots = stream::iter(vec![
+            Ok(snapshot("", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hi", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hi there", Some(20), FinishReason::Stop)),
+        ])
+        .then(|snapshot| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            snapshot
+        });
+
+        let mut measured = snapshots.measured(started);
+        while measured.next().await.is_some() {}
+        let stats = measured.stats();
+
+        assert_eq!(stats.chunks, 3);
+        assert_eq!(stats.completion_tokens, Some(20));
+        let first_token = stats.time_to_first_token.unwrap();
+        assert!(first_token >= Duration::from_millis(40));
+        assert!(stats.duration >= first_token + Duration::from_millis(20));
+        assert!(stats.tokens_per_second().unwrap() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_first_token_deadline() {
+        let options = TransportOptions::new().with_first_token_timeout(Duration::from_millis(20));
+
+        let slow = stream::once(async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(snapshot("Hi", None, FinishReason::Stop))
+        });
+        let events: Vec<_> = FirstTokenDeadline::start(&options)
+            .guard(slow)
+            .collect()
+            .await;
+        assert!(matches!(events[..], [Err(ClientError::Timeout(_))]));
+
+        let fast = stream::iter(vec![
+            Ok(snapshot("Hi", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hi!", None, FinishReason::Stop)),
+        ]);
+        let events: Vec<_> = FirstTokenDeadline::start(&options)
+            .guard(fast)
+            .collect()
+            .await;
+        assert!(events.iter().all(|e| e.is_ok()));
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let empty = Response {
+            data: Vec::new(),
+            usage: Usage::default(),
+            finish: FinishReason::Unfinished,
+            stop_sequence: None,
+            service_tier: None,
+        };
+        let first = snapshot("He", None, FinishReason::Unfinished);
+        let second = snapshot("Hello", None, FinishReason::Unfinished);
+        let mut last = snapshot("Hello", Some(2), FinishReason::Stop);
+        let call = Part::FunctionCall {
+            id: None,
+            name: "search".to_string(),
+            arguments: serde_json::json!({}),
+            signature: None,
+            repaired: false,
+            extensions: Extensions::new(),
+            finished: true,
+        };
+        last.data[0].parts_mut().push(call.clone());
+
+        assert_eq!(
+            diff(&empty, &first),
+            vec![PartDelta::Added {
+                message: 0,
+                index: 0,
+                part: first.data[0].parts()[0].clone(),
+            }]
+        );
+        assert_eq!(
+            diff(&first, &second),
+            vec![PartDelta::Appended {
+                message: 0,
+                index: 0,
+                content: "llo".to_string(),
+            }]
+        );
+        assert_eq!(
+            diff(&second, &last),
+            vec![
+                PartDelta::Finished {
+                    message: 0,
+                    index: 0
+                },
+                PartDelta::Added {
+                    message: 0,
+                    index: 1,
+                    part: call,
+                },
+                PartDelta::Finished {
+                    message: 0,
+                    index: 1
+                },
+            ]
+        );
+        assert!(diff(&last, &last).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_paced_splits_bursts() {
+        let snapshots = vec![
+            Ok(snapshot("Hello world!", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hello world!", Some(3), FinishReason::Stop)),
+        ];
+
+        let texts: Vec<(String, FinishReason)> = stream::iter(snapshots)
+            .paced(5, Duration::from_millis(5))
+            .map(|r| {
+                let r = r.unwrap();
+                (r.data[0].content().unwrap(), r.finish)
+            })
+            .collect()
+            .await;
+
+        assert_eq!(
+            texts,
+            vec![
+                ("Hello".to_string(), FinishReason::Unfinished),
+                ("Hello worl".to_string(), FinishReason::Unfinished),
+                ("Hello world!".to_string(), FinishReason::Unfinished),
+                ("Hello world!".to_string(), FinishReason::Stop),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paced_after_long_idle() {
+        let snapshots = async_stream::stream! {
+            yield Ok(snapshot("Hi", None, FinishReason::Unfinished));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            yield Ok(snapshot("Hi there", Some(2), FinishReason::Stop));
+        };
+
+        let texts: Vec<String> = snapshots
+            .paced(usize::MAX / 2, Duration::from_nanos(1))
+            .map(|r| r.unwrap().data[0].content().unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(texts, vec!["Hi", "Hi there"]);
+    }
+
+    #[tokio::test]
+    async fn test_events_propagate_errors() {
+        let snapshots = vec![
+            Ok(snapshot("He", None, FinishReason::Unfinished)),
+            Err(ClientError::StreamCancelled),
+        ];
+
+        let events: Vec<_> = stream::iter(snapshots).events().collect().await;
+
+        assert!(events[0].is_ok());
+        assert!(matches!(
+            events.last(),
+            Some(Err(ClientError::StreamCancelled))
+        ));
+    }
+
+    #[test]
+    fn test_sentence_splitter() {
+        let mut splitter = SentenceSplitter::new();
+        assert!(splitter.push("Pi is 3.").is_empty());
+        assert_eq!(splitter.push("14. Really"), vec!["Pi is 3.14."]);
+        assert_eq!(splitter.push("? Yes.\n"), vec!["Really?", "Yes."]);
+        assert_eq!(splitter.push("好的。"), vec!["好的。"]);
+        assert_eq!(splitter.finish(), None);
+
+        let mut splitter = SentenceSplitter::new()
+            .with_delimiters(['.', ','])
+            .with_min_chars(8);
+        assert_eq!(
+            splitter.push("Hi. Well, let me check, ok"),
+            vec!["Hi. Well,", "let me check,"]
+        );
+        assert_eq!(splitter.finish().as_deref(), Some("ok"));
+    }
+
+    #[tokio::test]
+    async fn test_sentences_from_snapshots() {
+        let snapshots = vec![
+            Ok(snapshot("Hello", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hello there. How", None, FinishReason::Unfinished)),
+            Ok(snapshot(
+                "Hello there. How are you",
+                Some(6),
+                FinishReason::Stop,
+            )),
+        ];
+
+        let sentences: Vec<String> = stream::iter(snapshots)
+            .sentences(SentenceSplitter::new())
+            .map(|s| s.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(sentences, vec!["Hello there.", "How are you"]);
+    }
+
+    #[tokio::test]
+    async fn test_write_to_sink() {
+        let mut second = snapshot("Hello there", Some(4), FinishReason::Stop);
+        second.data.push(Message::assistant("Bye"));
+        let snapshots = vec![
+            Ok(snapshot("Hel", None, FinishReason::Unfinished)),
+            Ok(snapshot("Hello there", None, FinishReason::Unfinished)),
+            Ok(second),
+        ];
+
+        let mut sink = Vec::new();
+        let response = stream::iter(snapshots).write_to(&mut sink).await.unwrap();
+
+        assert_eq!(String::from_utf8(sink).unwrap(), "Hello there\nBye");
+        assert_eq!(response.finish, FinishReason::Stop);
+        assert_eq!(response.data.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_cuts_off_stream() {
+        let polled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = polled.clone();
+        let snapshots = stream::iter(vec![
+            snapshot("Once upon", None, FinishReason::Unfinished),
+            snapshot("Once upon a time there", None, FinishReason::Unfinished),
+            snapshot(
+                "Once upon a time there was",
+                Some(2),
+                FinishReason::Unfinished,
+            ),
+            snapshot("Once upon a time there was a", None, FinishReason::Stop),
+        ])
+        .inspect(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        })
+        .map(Ok);
+
+        let responses: Vec<Response> = snapshots.max_tokens(4).map(Result::unwrap).collect().await;
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].finish, FinishReason::Unfinished);
+        let last = &responses[1];
+        assert_eq!(last.finish, FinishReason::OutputTokens);
+        assert!(matches!(
+            &last.data[0].parts()[0],
+            Part::Text { finished: true, .. }
+        ));
+        assert_eq!(polled.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recover_partial_keeps_interrupted_content() {
+        let snapshots = stream::iter(vec![
+            Ok(snapshot("Once upon", None, FinishReason::Unfinished)),
+            Ok(snapshot("Once upon a time", None, FinishReason::Unfinished)),
+            Err(ClientError::Timeout("stream idle".to_string())),
+        ]);
+        let responses: Vec<_> = snapshots.recover_partial().collect().await;
+
+        assert_eq!(responses.len(), 3);
+        let last = responses[2].as_ref().unwrap();
+        assert_eq!(last.finish, FinishReason::Error);
+        assert_eq!(last.data[0].content().as_deref(), Some("Once upon a time"));
+        assert!(matches!(
+            &last.data[0].parts()[0],
+            Part::Text { finished: true, .. }
+        ));
+
+        let failed = stream::iter(vec![
+            Ok(snapshot("Once", None, FinishReason::Unfinished)),
+            Err(ClientError::ProviderError("overloaded".to_string())),
+        ]);
+        let responses: Vec<_> = failed.recover_partial().collect().await;
+        assert!(matches!(responses[1], Err(ClientError::ProviderError(_))));
+    }
+}
@@ -0,0 +1,204 @@
+//! Structured output helpers.
+//!
+//! Models asked for JSON output emit it token by token. The helpers in this module
+//! turn incomplete JSON into the most complete valid document it describes, so
+//! typed values can be deserialized while the response is still streaming.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::client::ClientError;
+use crate::model::{Part, Response};
+
+/// A value deserialized from a possibly incomplete JSON response.
+///
+/// While streaming, `value` is built from a repaired prefix of the output: strings
+/// may be cut short and trailing fields may be missing. Types used with partial
+/// parsing should therefore mark fields with `#[serde(default)]` or wrap them in
+/// `Option`. `complete` is set only on the final value, parsed from the full output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partial<T> {
+    pub value: T,
+    pub complete: bool,
+}
+
+/// Repair an incomplete JSON document so that it can be parsed.
+///
+/// Open strings, arrays and objects are closed. Trailing tokens that cannot be
+/// completed (a dangling key, a half-written literal, a trailing comma) are dropped.
+/// Returns `None` if no valid prefix exists yet.
+pub fn repair_json(input: &str) -> Option<String> {
+    let start = input.find(['{', '['])?;
+    let input = &input[start..];
+
+    // Positions at which the document can be cut and closed, with the open
+    // containers at that point.
+    let mut cuts: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut stack: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut end = input.len();
+
+    for (i, c) in input.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => {
+                stack.push(if c == '{' { '}' } else { ']' });
+                cuts.push((i + 1, stack.clone()));
+            }
+            '}' | ']' => {
+                stack.pop();
+                if stack.is_empty() {
+                    end = i + 1;
+                    break;
+                }
+                cuts.push((i + 1, stack.clone()));
+            }
+            ',' => cuts.push((i, stack.clone())),
+            _ => {}
+        }
+    }
+
+    let input = &input[..end];
+    if stack.is_empty() {
+        return Some(input.to_string());
+    }
+
+    let mut full = input.to_string();
+    if in_string {
+        if escaped {
+            full.pop();
+        }
+        full.push('"');
+    }
+
+    std::iter::once((full, stack))
+        .chain(
+            cuts.into_iter()
+                .rev()
+                .map(|(at, stack)| (input[..at].to_string(), stack)),
+        )
+        .map(|(mut candidate, stack)| {
+            candidate.extend(stack.iter().rev());
+            candidate
+        })
+        .find(|candidate| serde_json::from_str::<serde_json::Value>(candidate).is_ok())
+}
+
+/// Parse a possibly incomplete JSON document into `T`.
+///
+/// Returns `None` if the repaired document does not deserialize into `T` yet.
+pub fn parse_partial<T: DeserializeOwned>(input: &str) -> Option<T> {
+    serde_json::from_str(&repair_json(input)?).ok()
+}
+
+/// Parse the raw JSON arguments of a finished function call.
+///
+/// Empty arguments, sent for calls without parameters, are an empty object. Arguments
+/// cut short (e.g. by the output token limit) are repaired with [`repair_json`],
+/// falling back to an empty object. The flag reports whether the arguments had to be
+/// repaired.
+pub fn parse_arguments(raw: &str) -> (Value, bool) {
+    if raw.trim().is_empty() {
+        return (json!({}), false);
+    }
+    if let Ok(value) = serde_json::from_str(raw) {
+        return (value, false);
+    }
+    let repaired = repair_json(raw)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| json!({}));
+    tracing::warn!("Repaired truncated function call arguments: {}", raw);
+    (repaired, true)
+}
+
+/// Concatenated text output of a response, excluding reasoning.
+pub(crate) fn response_text(response: &Response) -> String {
+    response
+        .data
+        .iter()
+        .flat_map(|message| message.parts())
+        .filter_map(|part| match part {
+            Part::Text { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse the complete JSON output of a response, ignoring surrounding prose or code fences.
+pub(crate) fn parse_complete<T: DeserializeOwned>(text: &str) -> Result<T, ClientError> {
+    let start = text.find(['{', '[']).unwrap_or(0);
+    let end = text.rfind(['}', ']']).map_or(text.len(), |i| i + 1);
+    Ok(serde_json::from_str(&text[start..end.max(start)])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn repaired(input: &str) -> Option<Value> {
+        repair_json(input).map(|s| serde_json::from_str(&s).unwrap())
+    }
+
+    #[test]
+    fn test_repair_closes_containers_and_strings() {
+        assert_eq!(repaired(r#"{"name": "Ad"#), Some(json!({ "name": "Ad" })));
+        assert_eq!(
+            repaired(r#"{"tags": ["a", "b"#),
+            Some(json!({ "tags": ["a", "b"] }))
+        );
+        assert_eq!(
+            repaired(r#"```json\n{"a": {"b": 1"#),
+            Some(json!({ "a": { "b": 1 } }))
+        );
+    }
+
+    #[test]
+    fn test_repair_drops_incomplete_tokens() {
+        assert_eq!(repaired(r#"{"a": 1, "b"#), Some(json!({ "a": 1 })));
+        assert_eq!(repaired(r#"{"a": 1, "b": tr"#), Some(json!({ "a": 1 })));
+        assert_eq!(repaired(r#"{"a": 1,"#), Some(json!({ "a": 1 })));
+        assert_eq!(repaired(r#"{"a": "x\"#), Some(json!({ "a": "x" })));
+        assert_eq!(repaired("Sure, here"), None);
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert_eq!(parse_arguments(r#"{"a": 1}"#), (json!({ "a": 1 }), false));
+        assert_eq!(
+            parse_arguments(r#"{"a": 1, "b": "te"#),
+            (json!({ "a": 1, "b": "te" }), true)
+        );
+        assert_eq!(parse_arguments(""), (json!({}), false));
+        assert_eq!(parse_arguments(" "), (json!({}), false));
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Person {
+            #[serde(default)]
+            name: String,
+            age: Option<u32>,
+        }
+
+        assert_eq!(
+            parse_partial::<Person>(r#"{"name": "Ada", "ag"#),
+            Some(Person {
+                name: "Ada".to_string(),
+                age: None
+            })
+        );
+    }
+}
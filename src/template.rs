@@ -0,0 +1,287 @@
+//! Chat templates for completion-only models.
+//!
+//! Some local backends only expose a raw text completion endpoint. A [`ChatTemplate`]
+//! renders a conversation into the single prompt string such models were trained on,
+//! and turns the generated completion back into assistant text. Presets are provided
+//! for the ChatML, Llama 3 and Mistral formats; other formats can be described with
+//! their own [`RoleFormat`]s.
+//!
+//! [`FimFormat`] describes how code models expect fill-in-the-middle prompts.
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::ClientError;
+use crate::model::{Message, Part};
+
+/// Text wrapped around the content of a single turn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct RoleFormat {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl RoleFormat {
+    pub fn new(prefix: impl Into<String>, suffix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    fn render(&self, content: &str) -> String {
+        format!("{}{}{}", self.prefix, content, self.suffix)
+    }
+}
+
+/// Prompt format of a completion-only model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatTemplate {
+    /// Text at the very start of the prompt (e.g. a BOS token).
+    pub bos: String,
+    /// Format of the system turn. When `None`, the system prompt is prepended to the
+    /// first user turn instead.
+    pub system: Option<RoleFormat>,
+    pub user: RoleFormat,
+    pub assistant: RoleFormat,
+    /// Sequences that end the assistant turn. They are sent as stop sequences and
+    /// stripped from completions.
+    pub stop: Vec<String>,
+}
+
+impl Default for ChatTemplate {
+    fn default() -> Self {
+        Self::chatml()
+    }
+}
+
+impl ChatTemplate {
+    /// ChatML, used by Qwen, Yi and many fine-tunes.
+    pub fn chatml() -> Self {
+        Self {
+            bos: String::new(),
+            system: Some(RoleFormat::new("<|im_start|>system\n", "<|im_end|>\n")),
+            user: RoleFormat::new("<|im_start|>user\n", "<|im_end|>\n"),
+            assistant: RoleFormat::new("<|im_start|>assistant\n", "<|im_end|>\n"),
+            stop: vec!["<|im_end|>".to_string()],
+        }
+    }
+
+    /// Llama 3 instruct format.
+    pub fn llama3() -> Self {
+        let turn = |role: &str| {
+            RoleFormat::new(
+                format!("<|start_header_id|>{}<|end_header_id|>\n\n", role),
+                "<|eot_id|>",
+            )
+        };
+        Self {
+            bos: "<|begin_of_text|>".to_string(),
+            system: Some(turn("system")),
+            user: turn("user"),
+            assistant: turn("assistant"),
+            stop: vec!["<|eot_id|>".to_string()],
+        }
+    }
+
+    /// Mistral instruct format, which has no system turn.
+    pub fn mistral() -> Self {
+        Self {
+            bos: "<s>".to_string(),
+            system: None,
+            user: RoleFormat::new("[INST] ", " [/INST]"),
+            assistant: RoleFormat::new("", "</s>"),
+            stop: vec!["</s>".to_string()],
+        }
+    }
+
+    /// Render a conversation into a prompt ending with an open assistant turn.
+    ///
+    /// Only text is supported; reasoning parts are skipped and any other part is
+    /// rejected with [`ClientError::Unsupported`].
+    pub fn render(
+        &self,
+        system: Option<&str>,
+        messages: &[Message],
+    ) -> Result<String, ClientError> {
+        let mut prompt = self.bos.clone();
+        let mut pending_system = system.filter(|system| !system.is_empty());
+
+        if let (Some(format), Some(system)) = (&self.system, pending_system) {
+            prompt.push_str(&format.render(system));
+            pending_system = None;
+        }
+
+        for message in messages {
+            let mut content = Self::text(message)?;
+            match message {
+                Message::User(_) => {
+                    if let Some(system) = pending_system.take() {
+                        content = format!("{}\n\n{}", system, content);
+                    }
+                    prompt.push_str(&self.user.render(&content));
+                }
+                Message::Assistant(_) => prompt.push_str(&self.assistant.render(&content)),
+            }
+        }
+
+        prompt.push_str(&self.assistant.prefix);
+        Ok(prompt)
+    }
+
+    /// Assistant text of a completion, without stop sequences or surrounding whitespace.
+    pub fn parse(&self, completion: &str) -> String {
+        let end = self
+            .stop
+            .iter()
+            .filter(|stop| !stop.is_empty())
+            .filter_map(|stop| completion.find(stop.as_str()))
+            .min()
+            .unwrap_or(completion.len());
+        completion[..end].trim().to_string()
+    }
+
+    fn text(message: &Message) -> Result<String, ClientError> {
+        let mut text = String::new();
+        for part in message.parts() {
+            match part {
+                Part::Text { content, .. } => text.push_str(content),
+                Part::Reasoning { .. } => {}
+                Part::FunctionCall { .. } | Part::FunctionResponse { .. } => {
+                    return Err(Self::unsupported("tool calls"))
+                }
+                Part::Media { .. } => return Err(Self::unsupported("media input")),
+            }
+        }
+        Ok(text)
+    }
+
+    fn unsupported(capability: &str) -> ClientError {
+        ClientError::Unsupported {
+            provider: "Chat templates".to_string(),
+            capability: capability.to_string(),
+        }
+    }
+}
+
+/// How a model expects fill-in-the-middle (FIM) requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum FimFormat {
+    /// The prefix is sent as the prompt and the suffix through the `suffix` parameter
+    /// of the completions endpoint (OpenAI, DeepSeek beta, llama.cpp).
+    #[default]
+    Suffix,
+    /// The prefix and suffix are joined into a single prompt with special tokens.
+    Tokens(FimTokens),
+    /// The request is sent to a dedicated `/fim/completions` endpoint (Codestral).
+    Endpoint,
+}
+
+/// Special tokens of a fill-in-the-middle prompt, in prefix-suffix-middle order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FimTokens {
+    pub prefix: String,
+    pub suffix: String,
+    pub middle: String,
+}
+
+impl FimTokens {
+    pub fn new(
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        middle: impl Into<String>,
+    ) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+            middle: middle.into(),
+        }
+    }
+
+    /// StarCoder and most models trained on The Stack.
+    pub fn starcoder() -> Self {
+        Self::new("<fim_prefix>", "<fim_suffix>", "<fim_middle>")
+    }
+
+    /// DeepSeek Coder.
+    pub fn deepseek() -> Self {
+        Self::new("<｜fim▁begin｜>", "<｜fim▁hole｜>", "<｜fim▁end｜>")
+    }
+
+    /// Code Llama.
+    pub fn codellama() -> Self {
+        Self::new("<PRE> ", " <SUF>", " <MID>")
+    }
+
+    /// Render the prompt asking for the code between `prefix` and `suffix`.
+    pub fn render(&self, prefix: &str, suffix: &str) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.prefix, prefix, self.suffix, suffix, self.middle
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Extensions, TextAnnotations};
+
+    fn text(content: &str) -> Vec<Part> {
+        vec![Part::Text {
+            content: content.into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }]
+    }
+
+    #[test]
+    fn test_render_presets() {
+        let messages = vec![
+            Message::User(text("Hi")),
+            Message::Assistant(text("Hello!")),
+            Message::User(text("Bye")),
+        ];
+
+        assert_eq!(
+            ChatTemplate::chatml()
+                .render(Some("Be brief."), &messages)
+                .unwrap(),
+            "<|im_start|>system\nBe brief.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\nHello!<|im_end|>\n\
+             <|im_start|>user\nBye<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+        assert_eq!(
+            ChatTemplate::mistral()
+                .render(Some("Be brief."), &messages)
+                .unwrap(),
+            "<s>[INST] Be brief.\n\nHi [/INST]Hello!</s>[INST] Bye [/INST]"
+        );
+        assert!(ChatTemplate::llama3()
+            .render(None, &messages)
+            .unwrap()
+            .ends_with("<|start_header_id|>assistant<|end_header_id|>\n\n"));
+    }
+
+    #[test]
+    fn test_fim_tokens() {
+        assert_eq!(
+            FimTokens::starcoder().render("def f():\n    ", "\n"),
+            "<fim_prefix>def f():\n    <fim_suffix>\n<fim_middle>"
+        );
+    }
+
+    #[test]
+    fn test_parse_strips_stop_sequences() {
+        let template = ChatTemplate::chatml();
+
+        assert_eq!(
+            template.parse(" Hello!<|im_end|>\n<|im_start|>user"),
+            "Hello!"
+        );
+        assert_eq!(template.parse("Hello!"), "Hello!");
+    }
+}
@@ -0,0 +1,31 @@
+//! Builders shared by the unit tests.
+
+use crate::model::{FinishReason, Message, Part, Response};
+
+/// PNG image part named `{name}.png`, with the name as its data.
+#[cfg(any(feature = "openai", feature = "anthropic", feature = "gemini"))]
+pub(crate) fn image(name: &str) -> Part {
+    Part::Media {
+        media_type: crate::model::MediaType::Image,
+        data: bytes::Bytes::copy_from_slice(name.as_bytes()),
+        mime_type: "image/png".to_string(),
+        uri: Some(format!("{}.png", name)),
+        finished: true,
+    }
+}
+
+/// Response snapshot with a single assistant text, finished unless `finish` is
+/// [`FinishReason::Unfinished`].
+pub(crate) fn snapshot(text: &str, finish: FinishReason) -> Response {
+    let mut part = Part::text(text);
+    if let Part::Text { finished, .. } = &mut part {
+        *finished = finish != FinishReason::Unfinished;
+    }
+    Response {
+        data: vec![Message::Assistant(vec![part])],
+        usage: Default::default(),
+        finish,
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
@@ -0,0 +1,343 @@
+//! Local emulation of provider APIs for tests.
+//!
+//! Enabled with the `testing` feature. A [`MockProvider`] answers requests from a
+//! script of [`MockReply`]s, and [`MockProviderServer`] serves it on a local port in
+//! the OpenAI, Anthropic and Gemini wire formats (see [`crate::server`]), so the real
+//! clients can be tested end to end, streaming included, without mocking HTTP:
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! use unia::providers::OpenAIClient;
+//! use unia::options::{ModelOptions, TransportOptions};
+//! use unia::testing::{MockProvider, MockProviderServer, MockReply};
+//!
+//! let server = MockProviderServer::start(
+//!     MockProvider::new()
+//!         .with_reply(MockReply::text("Hello there"))
+//!         .with_reply(MockReply::error(429, "Slow down")),
+//! )
+//! .await?;
+//! let client = OpenAIClient::new(
+//!     "test-key".to_string(),
+//!     server.openai_url(),
+//!     ModelOptions::new("gpt-5"),
+//!     TransportOptions::default(),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use futures::Stream;
+use rmcp::model::Tool;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::client::{Client, ClientError, StreamingClient};
+use crate::compress::estimate_tokens;
+use crate::model::{Extensions, FinishReason, Message, Part, Response, TextAnnotations, Usage};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Scripted answer of a [`MockProvider`].
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    /// Assistant text, streamed word by word.
+    Text(String),
+    /// A single tool call, finishing with [`FinishReason::ToolCalls`].
+    ToolCall { name: String, arguments: Value },
+    /// A complete response, streamed as a single snapshot.
+    Response(Response),
+    /// Failure answered with an HTTP status, as [`ClientError::Api`].
+    Error { status: u16, message: String },
+}
+
+impl MockReply {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn tool_call(name: impl Into<String>, arguments: Value) -> Self {
+        Self::ToolCall {
+            name: name.into(),
+            arguments,
+        }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::Error {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Script {
+    replies: VecDeque<MockReply>,
+    received: Vec<(Vec<Message>, Vec<Tool>)>,
+    calls: usize,
+}
+
+/// Client answering requests with scripted replies, in order.
+///
+/// Every request consumes one reply; once the script is exhausted, requests fail with
+/// [`ClientError::Config`]. Clones share the script and the recorded requests.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    script: Arc<Mutex<Script>>,
+    latency: Duration,
+    chunk_delay: Duration,
+    model_options: ModelOptions<()>,
+    transport_options: TransportOptions,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            script: Arc::default(),
+            latency: Duration::ZERO,
+            chunk_delay: Duration::ZERO,
+            model_options: ModelOptions::new("mock-model"),
+            transport_options: TransportOptions::default(),
+        }
+    }
+
+    /// Append a reply to the script.
+    pub fn with_reply(self, reply: MockReply) -> Self {
+        self.push(reply);
+        self
+    }
+
+    /// Append a reply to the script, e.g. while a test is running.
+    pub fn push(&self, reply: MockReply) {
+        self.script.lock().unwrap().replies.push_back(reply);
+    }
+
+    /// Wait before answering every request, streamed or not.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Wait between the snapshots of streamed replies.
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    /// Set the model name reported in responses.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model_options.model = model.into();
+        self
+    }
+
+    /// Messages and tools of every request received so far.
+    pub fn requests(&self) -> Vec<(Vec<Message>, Vec<Tool>)> {
+        self.script.lock().unwrap().received.clone()
+    }
+
+    /// Number of scripted replies not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().replies.len()
+    }
+
+    /// Record the request and take the next reply, as cumulative snapshots.
+    async fn answer(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Vec<Response>, ClientError> {
+        let (reply, call) = {
+            let mut script = self.script.lock().unwrap();
+            script.received.push((messages.clone(), tools));
+            script.calls += 1;
+            (script.replies.pop_front(), script.calls)
+        };
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let reply = reply.ok_or_else(|| {
+            ClientError::Config(format!(
+                "The mock provider has no reply for request {}",
+                call
+            ))
+        })?;
+        let prompt_tokens = estimate_tokens(&messages) as u32;
+        match reply {
+            MockReply::Text(text) => {
+                let mut snapshots = Vec::new();
+                let mut content = String::new();
+                for word in text.split_inclusive(' ') {
+                    content.push_str(word);
+                    snapshots.push(snapshot(
+                        Part::Text {
+                            content: content.clone().into(),
+                            signature: None,
+                            extensions: Extensions::new(),
+                            annotations: TextAnnotations::default(),
+                            finished: false,
+                        },
+                        FinishReason::Unfinished,
+                    ));
+                }
+                snapshots.push(snapshot(Part::text(text), FinishReason::Stop));
+                Ok(with_usage(snapshots, prompt_tokens))
+            }
+            MockReply::ToolCall { name, arguments } => {
+                let call = Part::FunctionCall {
+                    id: Some(format!("call_{}", call)),
+                    name,
+                    arguments,
+                    signature: None,
+                    repaired: false,
+                    extensions: Extensions::new(),
+                    finished: true,
+                };
+                let snapshots = vec![snapshot(call, FinishReason::ToolCalls)];
+                Ok(with_usage(snapshots, prompt_tokens))
+            }
+            MockReply::Response(response) => Ok(vec![response]),
+            MockReply::Error { status, message } => Err(ClientError::Api {
+                status: Some(status),
+                request_id: None,
+                message,
+            }),
+        }
+    }
+}
+
+fn snapshot(part: Part, finish: FinishReason) -> Response {
+    Response {
+        data: vec![Message::Assistant(vec![part])],
+        usage: Usage::default(),
+        finish,
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
+
+/// Report usage on the final snapshot.
+fn with_usage(mut snapshots: Vec<Response>, prompt_tokens: u32) -> Vec<Response> {
+    if let Some(last) = snapshots.last_mut() {
+        last.usage = Usage {
+            prompt_tokens: Some(prompt_tokens),
+            completion_tokens: Some(estimate_tokens(&last.data) as u32),
+            ..Usage::default()
+        };
+    }
+    snapshots
+}
+
+#[async_trait]
+impl Client for MockProvider {
+    type ModelProvider = ();
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let mut snapshots = self.answer(messages, tools).await?;
+        snapshots
+            .pop()
+            .ok_or_else(|| ClientError::ProviderError("Empty scripted reply".to_string()))
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        &self.model_options
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        &self.transport_options
+    }
+}
+
+#[async_trait]
+impl StreamingClient for MockProvider {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let snapshots = self.answer(messages, tools).await?;
+        let delay = self.chunk_delay;
+        Ok(Box::pin(async_stream::stream! {
+            for (index, snapshot) in snapshots.into_iter().enumerate() {
+                if index > 0 && !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                yield Ok(snapshot);
+            }
+        }))
+    }
+}
+
+/// A [`MockProvider`] served over HTTP on a local port.
+///
+/// The server stops when dropped.
+pub struct MockProviderServer {
+    address: SocketAddr,
+    provider: MockProvider,
+    handle: JoinHandle<()>,
+}
+
+impl MockProviderServer {
+    /// Serve `provider` on a free port of the loopback interface.
+    pub async fn start(provider: MockProvider) -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = crate::server::router(provider.clone());
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        Ok(Self {
+            address,
+            provider,
+            handle,
+        })
+    }
+
+    /// Root URL of the server, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Base URL for OpenAI-compatible clients.
+    pub fn openai_url(&self) -> String {
+        format!("{}/v1", self.url())
+    }
+
+    /// Base URL for Anthropic clients.
+    pub fn anthropic_url(&self) -> String {
+        format!("{}/v1", self.url())
+    }
+
+    /// Base URL for Gemini clients.
+    pub fn gemini_url(&self) -> String {
+        format!("{}/v1beta", self.url())
+    }
+
+    /// The served provider, to script more replies or inspect requests.
+    pub fn provider(&self) -> &MockProvider {
+        &self.provider
+    }
+}
+
+impl Drop for MockProviderServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
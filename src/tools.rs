@@ -2,13 +2,179 @@
 
 use async_trait::async_trait;
 pub use rmcp::model::Tool;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::mcp::MCPError;
+
+/// Category of a tool failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorKind {
+    /// The arguments did not match what the tool expects.
+    InvalidArguments,
+    /// No tool with the requested name exists.
+    NotFound,
+    /// The tool ran and reported a failure.
+    Execution,
+    /// The tool did not answer in time.
+    Timeout,
+    /// The tool or the server hosting it could not be reached.
+    Unavailable,
+    /// The call was aborted before the tool answered.
+    Cancelled,
+}
 
 /// Error type for tool execution.
-#[derive(Debug, thiserror::Error)]
-pub enum ToolError {
-    #[error("Tool error: {0}")]
-    Error(String),
+///
+/// Tool errors are reported back to the model rather than aborting the conversation.
+/// They are carried by [`Part::FunctionResponse`](crate::model::Part::FunctionResponse)
+/// and rendered in each provider's convention (`is_error` tool results for Anthropic,
+/// textual error content for OpenAI, an `error` response object for Gemini).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[error("Tool error: {message}")]
+pub struct ToolError {
+    pub kind: ToolErrorKind,
+    pub message: String,
+    /// Whether calling the tool again with the same arguments may succeed.
+    pub retryable: bool,
+}
+
+impl ToolError {
+    /// Create an error. Timeouts and unavailable tools are retryable by default.
+    pub fn new(kind: ToolErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retryable: matches!(kind, ToolErrorKind::Timeout | ToolErrorKind::Unavailable),
+        }
+    }
+
+    /// The arguments did not match what the tool expects.
+    pub fn invalid_arguments(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::InvalidArguments, message)
+    }
+
+    /// The tool ran and reported a failure.
+    pub fn execution(message: impl Into<String>) -> Self {
+        Self::new(ToolErrorKind::Execution, message)
+    }
+
+    /// The call was aborted before the tool answered.
+    pub fn cancelled() -> Self {
+        Self::new(ToolErrorKind::Cancelled, "Tool call was cancelled")
+    }
+
+    /// Override whether the error is retryable.
+    pub fn with_retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Function response payload for providers without a dedicated error convention.
+    pub fn to_response(&self) -> Value {
+        json!({ "error": { "kind": self.kind, "message": self.message } })
+    }
+}
+
+impl From<MCPError> for ToolError {
+    fn from(error: MCPError) -> Self {
+        let kind = match error {
+            MCPError::ToolNotFound(_)
+            | MCPError::ServerNotFound(_)
+            | MCPError::ServerIdMismatch => ToolErrorKind::NotFound,
+            _ => ToolErrorKind::Unavailable,
+        };
+        Self::new(kind, error.to_string())
+    }
+}
+
+/// When the agent calls a failed tool again before reporting the failure to the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToolRetryPolicy {
+    /// Report every failure to the model.
+    #[default]
+    Never,
+    /// Retry once if the error is retryable.
+    Transient,
+    /// Retry every failed call once.
+    Always,
+}
+
+impl ToolRetryPolicy {
+    /// Whether a call that failed with `error` should be retried.
+    pub fn should_retry(&self, error: &ToolError) -> bool {
+        match self {
+            ToolRetryPolicy::Never => false,
+            ToolRetryPolicy::Transient => error.retryable,
+            ToolRetryPolicy::Always => true,
+        }
+    }
+}
+
+/// Execution settings of a single tool, see [`Agent::with_tool_config`](crate::agent::Agent::with_tool_config).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolConfig {
+    /// Maximum time a call may take before it fails with [`ToolErrorKind::Timeout`].
+    pub timeout: Option<Duration>,
+    /// Maximum number of retries of a failed call, for failures the agent's
+    /// [`ToolRetryPolicy`] retries. Defaults to 1.
+    pub max_retries: Option<u32>,
+    /// Concurrency class whose limit applies to calls of this tool,
+    /// see [`Agent::with_concurrency_limit`](crate::agent::Agent::with_concurrency_limit).
+    pub concurrency_class: Option<String>,
+}
+
+impl ToolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the call timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum number of retries.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Set the concurrency class.
+    pub fn with_concurrency_class(mut self, class: impl Into<String>) -> Self {
+        self.concurrency_class = Some(class.into());
+        self
+    }
+}
+
+/// `_meta` key of a [`Tool`] holding its strict mode, see [`ToolExt::with_strict`].
+pub const STRICT_META_KEY: &str = "unia/strict";
+
+/// Provider settings attached to [`Tool`] definitions.
+pub trait ToolExt {
+    /// Ask the provider to enforce the input schema of this tool exactly (OpenAI strict
+    /// function calling), overriding [`ModelOptions::strict_tools`](crate::options::ModelOptions::strict_tools).
+    /// The schema is sanitized for the strict dialect when the request is built.
+    fn with_strict(self, strict: bool) -> Self;
+
+    /// Strict mode set with [`with_strict`](Self::with_strict), if any.
+    fn strict(&self) -> Option<bool>;
+}
+
+impl ToolExt for Tool {
+    fn with_strict(mut self, strict: bool) -> Self {
+        self.meta
+            .get_or_insert_with(Default::default)
+            .insert(STRICT_META_KEY.to_string(), Value::Bool(strict));
+        self
+    }
+
+    fn strict(&self) -> Option<bool> {
+        self.meta.as_ref()?.get(STRICT_META_KEY)?.as_bool()
+    }
 }
 
 /// Trait for tools that can be called by LLMs.
@@ -0,0 +1,332 @@
+//! Recorded traces of agent runs, replayable to reproduce them.
+//!
+//! Pass a [`TraceRecorder`] to [`Agent::with_trace`](crate::agent::Agent::with_trace)
+//! to record every request sent to the model, every response, tool call and result,
+//! and the decisions of the tool loop, in order and with timestamps. The resulting
+//! [`AgentTrace`] serializes to JSON for later inspection.
+//!
+//! To reproduce a run, build an agent on [`AgentTrace::replay_client`] and
+//! [`AgentTrace::replay_server`]: they answer with the recorded responses and tool
+//! results, and the client fails as soon as a request diverges from the recorded one.
+
+use async_trait::async_trait;
+use futures::Stream;
+use rmcp::model::{GetPromptResult, Prompt, ReadResourceResult, Resource, Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::{Client, ClientError, StreamingClient};
+use crate::mcp::{MCPError, MCPServer, Servable, Served};
+use crate::model::{Message, Part, Response, Usage};
+use crate::options::{ModelOptions, TransportOptions};
+
+/// Decision taken by the tool loop at the end of an iteration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum AgentDecision {
+    /// Execute the tool calls of the response.
+    CallTools { calls: usize },
+    /// Ask again because the answer failed validation.
+    Retry { reason: String },
+    /// Return the answer.
+    Finish,
+    /// Continue with messages interjected by the user, see
+    /// [`Agent::chat_stream_steered`](crate::agent::Agent::chat_stream_steered).
+    Interjection { messages: usize },
+    /// Give up after the maximum number of iterations.
+    MaxIterations,
+}
+
+/// A recorded step of an agent run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// Request sent to the model, after compression, instructions and hooks.
+    Request {
+        iteration: usize,
+        model: String,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    },
+    /// Complete response of the model, with its token counts.
+    Response {
+        iteration: usize,
+        response: Response,
+    },
+    /// Failed request.
+    Error {
+        iteration: usize,
+        message: String,
+    },
+    ToolCall {
+        id: Option<String>,
+        name: String,
+        arguments: Value,
+    },
+    ToolResult {
+        result: Part,
+    },
+    Decision {
+        iteration: usize,
+        #[serde(flatten)]
+        decision: AgentDecision,
+    },
+}
+
+/// An event with the time it was recorded at, in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+/// Events of an agent run, in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentTrace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl AgentTrace {
+    /// Events without their timestamps.
+    pub fn events(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.entries.iter().map(|entry| &entry.event)
+    }
+
+    /// Usage summed over the recorded responses.
+    pub fn usage(&self) -> Usage {
+        let mut usage = Usage::default();
+        for event in self.events() {
+            if let TraceEvent::Response { response, .. } = event {
+                usage += response.usage.clone();
+            }
+        }
+        usage
+    }
+
+    /// Client answering the recorded requests with the recorded responses, in order.
+    ///
+    /// A request whose messages differ from the recorded ones fails with
+    /// [`ClientError::Config`], which pinpoints where a replay stops reproducing the run.
+    pub fn replay_client(&self) -> ReplayClient {
+        let mut model = String::new();
+        let mut exchanges = VecDeque::new();
+        let mut pending = None;
+        for event in self.events() {
+            match event {
+                TraceEvent::Request {
+                    model: m, messages, ..
+                } => {
+                    if model.is_empty() {
+                        model = m.clone();
+                    }
+                    pending = Some(messages.clone());
+                }
+                TraceEvent::Response { response, .. } => {
+                    if let Some(messages) = pending.take() {
+                        exchanges.push_back((messages, Ok(response.clone())));
+                    }
+                }
+                TraceEvent::Error { message, .. } => {
+                    if let Some(messages) = pending.take() {
+                        exchanges.push_back((messages, Err(message.clone())));
+                    }
+                }
+                _ => {}
+            }
+        }
+        ReplayClient {
+            exchanges: Mutex::new(exchanges),
+            served: Mutex::new(0),
+            model_options: ModelOptions::new(model),
+            transport_options: TransportOptions::default(),
+        }
+    }
+
+    /// MCP server offering the recorded tools and answering calls with the recorded
+    /// results, in order.
+    pub fn replay_server(&self) -> ReplayServer {
+        let mut tools: Vec<Tool> = Vec::new();
+        let mut results = VecDeque::new();
+        for event in self.events() {
+            match event {
+                TraceEvent::Request { tools: offered, .. } => {
+                    for tool in offered {
+                        if !tools.iter().any(|t| t.name == tool.name) {
+                            tools.push(tool.clone());
+                        }
+                    }
+                }
+                TraceEvent::ToolResult { result } => results.push_back(result.clone()),
+                _ => {}
+            }
+        }
+        ReplayServer {
+            tools,
+            results: Mutex::new(results),
+        }
+    }
+}
+
+/// Shared handle recording an [`AgentTrace`].
+///
+/// Clones record into the same trace.
+#[derive(Debug, Clone, Default)]
+pub struct TraceRecorder {
+    trace: Arc<Mutex<AgentTrace>>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `event`, timestamped now.
+    pub fn record(&self, event: TraceEvent) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let mut trace = self.trace.lock().unwrap();
+        trace.entries.push(TraceEntry { timestamp, event });
+    }
+
+    /// Copy of the trace recorded so far.
+    pub fn trace(&self) -> AgentTrace {
+        self.trace.lock().unwrap().clone()
+    }
+
+    /// Take the trace recorded so far, leaving an empty one.
+    pub fn take(&self) -> AgentTrace {
+        std::mem::take(&mut *self.trace.lock().unwrap())
+    }
+}
+
+/// A recorded request with its response or error message.
+type Exchange = (Vec<Message>, Result<Response, String>);
+
+/// Client replaying the responses of an [`AgentTrace`].
+#[derive(Debug)]
+pub struct ReplayClient {
+    exchanges: Mutex<VecDeque<Exchange>>,
+    served: Mutex<usize>,
+    model_options: ModelOptions<()>,
+    transport_options: TransportOptions,
+}
+
+impl ReplayClient {
+    /// Number of recorded responses not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().unwrap().len()
+    }
+
+    fn next(&self, messages: &[Message]) -> Result<Response, ClientError> {
+        let mut served = self.served.lock().unwrap();
+        let (recorded, result) = self.exchanges.lock().unwrap().pop_front().ok_or_else(|| {
+            ClientError::Config(format!("The trace has no request {}", *served + 1))
+        })?;
+        *served += 1;
+        if recorded != messages {
+            return Err(ClientError::Config(format!(
+                "Request {} diverges from the trace",
+                *served
+            )));
+        }
+        result.map_err(ClientError::ProviderError)
+    }
+}
+
+#[async_trait]
+impl Client for ReplayClient {
+    type ModelProvider = ();
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        self.next(&messages)
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        &self.model_options
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        &self.transport_options
+    }
+}
+
+#[async_trait]
+impl StreamingClient for ReplayClient {
+    /// Replay the recorded response as a single snapshot.
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let response = self.next(&messages)?;
+        Ok(Box::pin(futures::stream::iter([Ok(response)])))
+    }
+}
+
+/// MCP server replaying the tool results of an [`AgentTrace`].
+#[derive(Debug)]
+pub struct ReplayServer {
+    tools: Vec<Tool>,
+    results: Mutex<VecDeque<Part>>,
+}
+
+#[async_trait]
+impl MCPServer for ReplayServer {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        Ok(self.tools.iter().cloned().map(|t| t.served(None)).collect())
+    }
+
+    /// Answer with the next recorded result, which must be for the tool `name`.
+    async fn call_tool(
+        &self,
+        name: String,
+        _args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        let mut results = self.results.lock().unwrap();
+        match results.front() {
+            Some(Part::FunctionResponse { name: recorded, .. }) if *recorded == name => {
+                Ok(results.pop_front().unwrap())
+            }
+            _ => Err(MCPError::Mcp(format!(
+                "Call of {} diverges from the trace",
+                name
+            ))),
+        }
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn get_prompt(
+        &self,
+        prompt: &Served<Prompt>,
+        _args: Option<Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        Err(MCPError::PromptNotFound(prompt.value.name.clone()))
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn read_resource(
+        &self,
+        resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        Err(MCPError::ResourceNotFound(resource.value.uri.clone()))
+    }
+}
@@ -0,0 +1,376 @@
+//! Markdown and HTML transcripts of conversations.
+//!
+//! A [`Transcript`] renders messages for humans, e.g. to log or share an agent run:
+//! reasoning goes into collapsible `<details>` sections, tool calls and results are
+//! shown as JSON blocks, and images are embedded inline as `data:` URIs. Render a
+//! [`Response`](crate::model::Response) by passing its `data`.
+
+use base64::prelude::*;
+use serde_json::Value;
+
+use crate::model::{MediaType, Message, Part};
+
+/// Renderer of conversation transcripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transcript {
+    reasoning: bool,
+    embed_media: bool,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self {
+            reasoning: true,
+            embed_media: true,
+        }
+    }
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include reasoning parts, collapsed. Defaults to `true`.
+    pub fn with_reasoning(mut self, reasoning: bool) -> Self {
+        self.reasoning = reasoning;
+        self
+    }
+
+    /// Embed inline media as `data:` URIs. Defaults to `true`; when disabled, inline
+    /// media is described by its MIME type and size. Remote media is always linked.
+    pub fn with_embedded_media(mut self, embed_media: bool) -> Self {
+        self.embed_media = embed_media;
+        self
+    }
+
+    /// Render `messages` as Markdown, with a heading per message.
+    pub fn markdown(&self, messages: &[Message]) -> String {
+        let mut blocks = Vec::new();
+        for message in messages {
+            blocks.push(format!("### {}", role_name(message)));
+            for part in message.parts() {
+                if let Some(block) = self.markdown_part(part) {
+                    blocks.push(block);
+                }
+            }
+        }
+        let mut markdown = blocks.join("\n\n");
+        markdown.push('\n');
+        markdown
+    }
+
+    /// Render `messages` as a standalone HTML document.
+    ///
+    /// Text is escaped and shown with its line breaks, not rendered as Markdown.
+    pub fn html(&self, messages: &[Message]) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Transcript</title>\n\
+             <style>\n\
+             body { font-family: sans-serif; max-width: 50rem; margin: auto; }\n\
+             .text { white-space: pre-wrap; }\n\
+             .user { background: #f4f4f4; }\n\
+             section { padding: 0.5rem 1rem; border-radius: 0.5rem; }\n\
+             img, video { max-width: 100%; }\n\
+             </style>\n</head>\n<body>\n",
+        );
+        for message in messages {
+            let role = role_name(message);
+            html.push_str(&format!(
+                "<section class=\"{}\">\n<h3>{}</h3>\n",
+                role.to_lowercase(),
+                role
+            ));
+            for part in message.parts() {
+                if let Some(element) = self.html_part(part) {
+                    html.push_str(&element);
+                    html.push('\n');
+                }
+            }
+            html.push_str("</section>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn markdown_part(&self, part: &Part) -> Option<String> {
+        match part {
+            Part::Text { content, .. } => Some(content.to_string()),
+            Part::Reasoning {
+                content, summary, ..
+            } => {
+                let text = summary.as_deref().unwrap_or(content);
+                (self.reasoning && !text.is_empty()).then(|| {
+                    format!(
+                        "<details>\n<summary>Reasoning</summary>\n\n{}\n\n</details>",
+                        text
+                    )
+                })
+            }
+            Part::FunctionCall {
+                name, arguments, ..
+            } => Some(format!(
+                "**Tool call** `{}`\n\n{}",
+                name,
+                json_block(arguments)
+            )),
+            Part::FunctionResponse {
+                name,
+                response,
+                parts,
+                error,
+                ..
+            } => {
+                let mut block = match error {
+                    Some(error) => format!("**Tool error** `{}`: {}", name, error.message),
+                    None => format!("**Tool result** `{}`\n\n{}", name, json_block(response)),
+                };
+                for part in parts {
+                    if let Some(part) = self.markdown_part(part) {
+                        block.push_str("\n\n");
+                        block.push_str(&part);
+                    }
+                }
+                Some(block)
+            }
+            Part::Media {
+                media_type,
+                mime_type,
+                ..
+            } => Some(match self.media_source(part) {
+                Some(source) if *media_type == MediaType::Image => {
+                    format!("![{}]({})", media_name(part), source)
+                }
+                Some(source) => format!("[{}]({})", media_name(part), source),
+                None => format!("*{}*", media_description(part, mime_type)),
+            }),
+        }
+    }
+
+    fn html_part(&self, part: &Part) -> Option<String> {
+        match part {
+            Part::Text { content, .. } => {
+                Some(format!("<div class=\"text\">{}</div>", escape(content)))
+            }
+            Part::Reasoning {
+                content, summary, ..
+            } => {
+                let text = summary.as_deref().unwrap_or(content);
+                (self.reasoning && !text.is_empty()).then(|| {
+                    format!(
+                        "<details class=\"reasoning\"><summary>Reasoning</summary><div class=\"text\">{}</div></details>",
+                        escape(text)
+                    )
+                })
+            }
+            Part::FunctionCall {
+                name, arguments, ..
+            } => Some(format!(
+                "<details class=\"tool-call\"><summary>Tool call <code>{}</code></summary><pre>{}</pre></details>",
+                escape(name),
+                escape(&pretty(arguments))
+            )),
+            Part::FunctionResponse {
+                name,
+                response,
+                parts,
+                error,
+                ..
+            } => {
+                let mut element = match error {
+                    Some(error) => format!(
+                        "<details class=\"tool-error\"><summary>Tool error <code>{}</code></summary><div class=\"text\">{}</div>",
+                        escape(name),
+                        escape(&error.message)
+                    ),
+                    None => format!(
+                        "<details class=\"tool-result\"><summary>Tool result <code>{}</code></summary><pre>{}</pre>",
+                        escape(name),
+                        escape(&pretty(response))
+                    ),
+                };
+                for part in parts {
+                    if let Some(part) = self.html_part(part) {
+                        element.push_str(&part);
+                    }
+                }
+                element.push_str("</details>");
+                Some(element)
+            }
+            Part::Media {
+                media_type,
+                mime_type,
+                ..
+            } => Some(match self.media_source(part) {
+                Some(source) => {
+                    let source = escape(&source);
+                    let name = escape(&media_name(part));
+                    match media_type {
+                        MediaType::Image => format!("<img src=\"{}\" alt=\"{}\">", source, name),
+                        MediaType::Video => format!("<video controls src=\"{}\"></video>", source),
+                        _ => format!("<a href=\"{}\">{}</a>", source, name),
+                    }
+                }
+                None => format!("<p><em>{}</em></p>", escape(&media_description(part, mime_type))),
+            }),
+        }
+    }
+
+    /// URL of a media part: its URI if remote, a `data:` URI if embedded.
+    fn media_source(&self, part: &Part) -> Option<String> {
+        let Part::Media {
+            data,
+            mime_type,
+            uri,
+            ..
+        } = part
+        else {
+            return None;
+        };
+        if part.is_remote_media() {
+            return uri.clone();
+        }
+        self.embed_media
+            .then(|| format!("data:{};base64,{}", mime_type, BASE64_STANDARD.encode(data)))
+    }
+}
+
+fn role_name(message: &Message) -> &'static str {
+    match message {
+        Message::User(_) => "User",
+        Message::Assistant(_) => "Assistant",
+    }
+}
+
+fn media_name(part: &Part) -> String {
+    match part {
+        Part::Media {
+            uri: Some(uri),
+            mime_type,
+            ..
+        } => uri
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(mime_type)
+            .to_string(),
+        Part::Media { mime_type, .. } => mime_type.clone(),
+        _ => String::new(),
+    }
+}
+
+fn media_description(part: &Part, mime_type: &str) -> String {
+    match part {
+        Part::Media { data, .. } => format!("Attached {} ({} bytes)", mime_type, data.len()),
+        _ => String::new(),
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+/// Fenced JSON block, with a fence longer than any backtick run in the JSON.
+fn json_block(value: &Value) -> String {
+    let json = pretty(value);
+    let longest = json.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}json\n{}\n{}", fence, json, fence)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Extensions;
+    use crate::tools::ToolError;
+    use bytes::Bytes;
+    use serde_json::json;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::User(vec![
+                Part::text("Weather in <Paris>?"),
+                Part::Media {
+                    media_type: MediaType::Image,
+                    data: Bytes::from_static(b"PNG"),
+                    mime_type: "image/png".to_string(),
+                    uri: Some("photos/paris.png".to_string()),
+                    finished: true,
+                },
+            ]),
+            Message::Assistant(vec![
+                Part::Reasoning {
+                    content: "Need the weather tool.".into(),
+                    summary: None,
+                    signature: None,
+                    extensions: Extensions::new(),
+                    finished: true,
+                },
+                Part::FunctionCall {
+                    id: Some("call_1".to_string()),
+                    name: "get_weather".to_string(),
+                    arguments: json!({ "city": "Paris" }),
+                    signature: None,
+                    repaired: false,
+                    extensions: Extensions::new(),
+                    finished: true,
+                },
+            ]),
+            Message::User(vec![Part::function_error(
+                Some("call_1".to_string()),
+                "get_weather",
+                ToolError::cancelled(),
+            )]),
+        ]
+    }
+
+    #[test]
+    fn test_markdown_transcript() {
+        let markdown = Transcript::new().markdown(&conversation());
+        assert!(markdown.starts_with(
+            "### User\n\nWeather in <Paris>?\n\n![paris.png](data:image/png;base64,UE5H)"
+        ));
+        assert!(markdown.contains(
+            "<details>\n<summary>Reasoning</summary>\n\nNeed the weather tool.\n\n</details>"
+        ));
+        assert!(markdown
+            .contains("**Tool call** `get_weather`\n\n```json\n{\n  \"city\": \"Paris\"\n}\n```"));
+        assert!(markdown.contains("**Tool error** `get_weather`: "));
+
+        let markdown = Transcript::new()
+            .with_reasoning(false)
+            .with_embedded_media(false)
+            .markdown(&conversation());
+        assert!(!markdown.contains("Reasoning"));
+        assert!(markdown.contains("*Attached image/png (3 bytes)*"));
+    }
+
+    #[test]
+    fn test_html_transcript() {
+        let html = Transcript::new().html(&conversation());
+        assert!(html.contains("<div class=\"text\">Weather in &lt;Paris&gt;?</div>"));
+        assert!(html.contains("<img src=\"data:image/png;base64,UE5H\" alt=\"paris.png\">"));
+        assert!(html.contains("<summary>Tool call <code>get_weather</code></summary>"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn test_json_block_fence() {
+        assert_eq!(json_block(&json!("```")), "````json\n\"```\"\n````");
+    }
+}
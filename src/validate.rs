@@ -0,0 +1,446 @@
+//! Output validation with automatic re-asking.
+//!
+//! A [`Validator`] checks the final text of a response: against a regular expression
+//! ([`RegexValidator`]), a JSON Schema ([`SchemaValidator`]) or any closure. When a
+//! response is rejected, [`Validated`] and
+//! [`Agent::with_validator`](crate::agent::Agent::with_validator) send the validation
+//! error back to the model and ask again, failing with
+//! [`ClientError::ValidationFailed`] once the attempts are exhausted.
+
+use async_trait::async_trait;
+use regex::Regex;
+use rmcp::model::Tool;
+use schemars::JsonSchema;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::client::{Client, ClientError, RequestPreview};
+use crate::model::{Message, Part, Response};
+use crate::options::{ModelOptions, TransportOptions};
+use crate::structured::{parse_complete, response_text};
+
+/// Number of attempts, including the first, unless configured otherwise.
+pub const DEFAULT_VALIDATION_ATTEMPTS: usize = 3;
+
+/// Check of a response, returning a description of the problem on failure.
+///
+/// The description is shown to the model when asking again, so it should say what
+/// to fix.
+pub trait Validator: Send + Sync {
+    fn validate(&self, response: &Response) -> Result<(), String>;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&Response) -> Result<(), String> + Send + Sync,
+{
+    fn validate(&self, response: &Response) -> Result<(), String> {
+        self(response)
+    }
+}
+
+/// Validator requiring the response text to match a regular expression.
+///
+/// Anchor the pattern with `^` and `$` to match the whole text.
+#[derive(Debug, Clone)]
+pub struct RegexValidator {
+    regex: Regex,
+}
+
+impl RegexValidator {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+        })
+    }
+}
+
+impl Validator for RegexValidator {
+    fn validate(&self, response: &Response) -> Result<(), String> {
+        let text = response_text(response);
+        if self.regex.is_match(text.trim()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "The answer must match the regular expression `{}`.",
+                self.regex
+            ))
+        }
+    }
+}
+
+/// Validator requiring the response to be JSON valid against a schema.
+///
+/// Prose and code fences around the document are ignored. The common subset of JSON
+/// Schema is checked: `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, length and range bounds, `anyOf`/`oneOf` and local
+/// `$ref`s. Other keywords, and references that cannot be resolved locally, are
+/// ignored, so values are only rejected for problems the validator is sure about.
+#[derive(Debug, Clone)]
+pub struct SchemaValidator {
+    schema: Value,
+}
+
+impl SchemaValidator {
+    pub fn new(schema: Value) -> Self {
+        Self { schema }
+    }
+
+    /// Validate against the schema of `T`.
+    pub fn for_type<T: JsonSchema>() -> Self {
+        let schema = schemars::schema_for!(T);
+        Self::new(serde_json::to_value(schema).unwrap_or_default())
+    }
+
+    /// Check a JSON value against the schema.
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        check(&self.schema, &self.schema, value, "$")
+    }
+}
+
+impl Validator for SchemaValidator {
+    fn validate(&self, response: &Response) -> Result<(), String> {
+        let value: Value = parse_complete(&response_text(response))
+            .map_err(|e| format!("The answer must be a JSON document: {}.", e))?;
+        self.check(&value)
+            .map_err(|e| format!("The JSON document is invalid: {}.", e))
+    }
+}
+
+/// Check `value` at `path` against `schema`, resolving references in `root`.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let Value::Object(schema) = schema else {
+        // `true`, `false` and malformed schemas.
+        return match schema {
+            Value::Bool(false) => Err(format!("{} is not allowed", path)),
+            _ => Ok(()),
+        };
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer));
+        match target {
+            Some(target) => check(root, target, value, path)?,
+            None => debug!("Not checking {} against unresolved {}", path, reference),
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            return Err(format!("{} must be of type {}", path, types.join(" or ")));
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!(
+                "{} must be one of {}",
+                path,
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(format!("{} must be {}", path, expected));
+        }
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+            let errors: Vec<String> = options
+                .iter()
+                .filter_map(|option| check(root, option, value, path).err())
+                .collect();
+            if errors.len() == options.len() && !options.is_empty() {
+                return Err(errors.join("; or "));
+            }
+        }
+    }
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for option in all {
+            check(root, option, value, path)?;
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        return Err(format!("{} is missing the required field `{}`", path, name));
+                    }
+                }
+            }
+            for (name, field) in object {
+                let field_path = format!("{}.{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(field_schema) => check(root, field_schema, field, &field_path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{} has the unexpected field `{}`", path, name))
+                        }
+                        Some(additional) => check(root, additional, field, &field_path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bounds(schema, "minItems", "maxItems", items.len(), path, "items")?;
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count();
+            check_bounds(schema, "minLength", "maxLength", len, path, "characters")?;
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    return Err(format!("{} must be at least {}", path, minimum));
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    return Err(format!("{} must be at most {}", path, maximum));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn check_bounds(
+    schema: &serde_json::Map<String, Value>,
+    min_keyword: &str,
+    max_keyword: &str,
+    len: usize,
+    path: &str,
+    unit: &str,
+) -> Result<(), String> {
+    let bound = |keyword| schema.get(keyword).and_then(Value::as_u64);
+    if let Some(min) = bound(min_keyword).filter(|min| (len as u64) < *min) {
+        return Err(format!("{} must have at least {} {}", path, min, unit));
+    }
+    if let Some(max) = bound(max_keyword).filter(|max| (len as u64) > *max) {
+        return Err(format!("{} must have at most {} {}", path, max, unit));
+    }
+    Ok(())
+}
+
+/// Run every validator, returning the first failure.
+pub(crate) fn validate_all(
+    validators: &[Arc<dyn Validator>],
+    response: &Response,
+) -> Result<(), String> {
+    validators
+        .iter()
+        .try_for_each(|validator| validator.validate(response))
+}
+
+/// User message asking the model to fix a rejected answer.
+pub(crate) fn correction(error: &str) -> Message {
+    Message::user(format!(
+        "Your answer was rejected: {}\nPlease answer again, fixing the problem.",
+        error
+    ))
+}
+
+/// Client wrapper re-asking the model until its response passes validation.
+///
+/// Rejected responses and the correction requests are added to the conversation for
+/// the next attempt. Only the accepted response is returned, with the usage of every
+/// attempt. Responses stopping for tool calls are returned without validation.
+pub struct Validated<C> {
+    client: C,
+    validators: Vec<Arc<dyn Validator>>,
+    max_attempts: usize,
+}
+
+impl<C> Validated<C> {
+    pub fn new<V: Validator + 'static>(client: C, validator: V) -> Self {
+        Self {
+            client,
+            validators: vec![Arc::new(validator)],
+            max_attempts: DEFAULT_VALIDATION_ATTEMPTS,
+        }
+    }
+
+    /// Additionally require `validator` to pass.
+    pub fn with_validator<V: Validator + 'static>(mut self, validator: V) -> Self {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    /// Number of attempts, including the first. Defaults to 3.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Get a reference to the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Validated<C> {
+    type ModelProvider = C::ModelProvider;
+
+    async fn request(
+        &self,
+        mut messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let mut usage = Default::default();
+        let mut attempt = 1;
+        loop {
+            let mut response = self.client.request(messages.clone(), tools.clone()).await?;
+            usage += response.usage.clone();
+
+            let has_calls = response
+                .data
+                .iter()
+                .flat_map(Message::parts)
+                .any(|part| matches!(part, Part::FunctionCall { .. }));
+            let error = match validate_all(&self.validators, &response) {
+                Ok(()) => None,
+                Err(_) if has_calls => None,
+                Err(error) => Some(error),
+            };
+            let Some(error) = error else {
+                response.usage = usage;
+                return Ok(response);
+            };
+
+            if attempt >= self.max_attempts {
+                return Err(ClientError::ValidationFailed {
+                    attempts: attempt,
+                    message: error,
+                });
+            }
+            debug!(
+                "Response failed validation (attempt {}): {}",
+                attempt, error
+            );
+            messages.extend(response.data);
+            messages.push(correction(&error));
+            attempt += 1;
+        }
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        self.client.model_options()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        self.client.transport_options()
+    }
+
+    fn dry_run(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<RequestPreview, ClientError> {
+        self.client.dry_run(messages, tools)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(text: &str) -> Response {
+        Response {
+            data: vec![Message::assistant(text)],
+            usage: Default::default(),
+            finish: crate::model::FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        }
+    }
+
+    #[test]
+    fn test_regex_validator() {
+        let validator = RegexValidator::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+        assert!(validator.validate(&response("2025-01-31\n")).is_ok());
+        assert!(validator.validate(&response("January 31st")).is_err());
+    }
+
+    #[test]
+    fn test_schema_validator() {
+        let validator = SchemaValidator::new(json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "additionalProperties": false,
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "tags": { "type": "array", "items": { "$ref": "#/definitions/Tag" } }
+            },
+            "definitions": { "Tag": { "enum": ["a", "b"] } }
+        }));
+
+        assert!(validator
+            .validate(&response(r#"```json\n{"name": "x", "tags": ["a"]}\n```"#))
+            .is_ok());
+        assert_eq!(
+            validator.check(&json!({ "name": "x", "tags": ["c"] })),
+            Err(r#"$.tags[0] must be one of ["a","b"]"#.to_string())
+        );
+        assert_eq!(
+            validator.check(&json!({ "name": "x" })),
+            Err("$ is missing the required field `tags`".to_string())
+        );
+        assert!(validator.validate(&response("no json here")).is_err());
+    }
+
+    #[test]
+    fn test_schema_validator_ignores_unsupported_constructs() {
+        let validator = SchemaValidator::new(json!({
+            "type": "object",
+            "properties": {
+                "count": { "type": "integer" },
+                "owner": { "$ref": "https://example.com/person.json" },
+                "tag": { "$ref": "#/$defs/Missing" }
+            }
+        }));
+
+        assert!(validator
+            .check(&json!({ "count": 1.0, "owner": 3, "tag": "x" }))
+            .is_ok());
+        assert_eq!(
+            validator.check(&json!({ "count": 1.5 })),
+            Err("$.count must be of type integer".to_string())
+        );
+    }
+}
@@ -1,15 +1,29 @@
 use async_trait::async_trait;
-use rmcp::model::Tool;
+use futures::{Stream, StreamExt};
+use rmcp::model::{GetPromptResult, Prompt, ReadResourceResult, Resource, Tool};
+use serde_json::{json, Value};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-use unia::agent::Agent;
-use unia::client::{Client, ClientError};
-use unia::model::{FinishReason, Message, Part, Response, Usage};
+use std::time::Duration;
+use unia::agent::{Agent, Interjections, IterationHooks, StopSignal};
+use unia::client::{Client, ClientError, StreamingClient};
+use unia::conversation::Conversation;
+use unia::instructions::{InstructionLayer, Instructions};
+use unia::mcp::{MCPError, MCPServer, Served};
+use unia::model::{Extensions, FinishReason, Message, Part, Response, TextAnnotations, Usage};
 use unia::options::{ModelOptions, TransportOptions};
+use unia::scratchpad::Scratchpad;
+use unia::tools::{ToolConfig, ToolErrorKind, ToolRetryPolicy};
+use unia::trace::{AgentDecision, AgentTrace, TraceEvent, TraceRecorder};
+use unia::validate::RegexValidator;
 
 #[derive(Clone)]
 struct MockClient {
     responses: Arc<Mutex<Vec<Response>>>,
     requests: Arc<Mutex<Vec<Vec<Message>>>>,
+    /// Names of the tools offered in each request.
+    tools: Arc<Mutex<Vec<Vec<String>>>>,
+    model_options: ModelOptions<()>,
 }
 
 impl MockClient {
@@ -17,6 +31,8 @@ impl MockClient {
         Self {
             responses: Arc::new(Mutex::new(responses)),
             requests: Arc::new(Mutex::new(Vec::new())),
+            tools: Arc::new(Mutex::new(Vec::new())),
+            model_options: ModelOptions::new("mock"),
         }
     }
 }
@@ -28,9 +44,13 @@ impl Client for MockClient {
     async fn request(
         &self,
         messages: Vec<Message>,
-        _tools: Vec<Tool>,
+        tools: Vec<Tool>,
     ) -> Result<Response, ClientError> {
         self.requests.lock().unwrap().push(messages);
+        self.tools
+            .lock()
+            .unwrap()
+            .push(tools.iter().map(|tool| tool.name.to_string()).collect());
         let mut responses = self.responses.lock().unwrap();
         if !responses.is_empty() {
             Ok(responses.remove(0))
@@ -42,7 +62,7 @@ impl Client for MockClient {
     }
 
     fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
-        unimplemented!()
+        &self.model_options
     }
 
     fn transport_options(&self) -> &TransportOptions {
@@ -54,18 +74,26 @@ impl Client for MockClient {
 async fn test_agent_simple_chat() {
     let expected_response = Response {
         data: vec![Message::Assistant(vec![Part::Text {
-            content: "Hello".to_string(),
+            content: "Hello".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
             finished: true,
         }])],
         usage: Usage::default(),
         finish: FinishReason::Stop,
+        stop_sequence: None,
+        service_tier: None,
     };
 
     let client = MockClient::new(vec![expected_response]);
     let agent = Agent::new(client);
 
     let messages = vec![Message::User(vec![Part::Text {
-        content: "Hi".to_string(),
+        content: "Hi".into(),
+        signature: None,
+        extensions: Extensions::new(),
+        annotations: TextAnnotations::default(),
         finished: true,
     }])];
 
@@ -82,3 +110,740 @@ async fn test_agent_simple_chat() {
         panic!("Expected assistant message");
     }
 }
+
+#[async_trait]
+impl StreamingClient for MockClient {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        // Queued responses are streamed as snapshots up to and including the next
+        // finished one, which ends the turn.
+        self.requests.lock().unwrap().push(messages);
+        let mut responses = self.responses.lock().unwrap();
+        let mut snapshots = Vec::new();
+        while !responses.is_empty() {
+            let response = responses.remove(0);
+            let finished = response.finish != FinishReason::Unfinished;
+            snapshots.push(response);
+            if finished {
+                break;
+            }
+        }
+        Ok(Box::pin(futures::stream::iter(
+            snapshots.into_iter().map(Ok),
+        )))
+    }
+}
+
+/// Local server exposing one tool that opts into streamed arguments.
+#[derive(Default)]
+struct StreamingToolServer {
+    partials: Arc<Mutex<Vec<Value>>>,
+    calls: Arc<Mutex<Vec<Value>>>,
+    /// Number of upcoming calls that fail as if the server was unreachable.
+    failures: Arc<Mutex<usize>>,
+    /// Time each call takes before answering.
+    delay: Duration,
+    /// Input schema of the tool, `{"type": "object"}` if None.
+    schema: Option<Value>,
+}
+
+#[async_trait]
+impl MCPServer for StreamingToolServer {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        let schema = self
+            .schema
+            .clone()
+            .unwrap_or_else(|| json!({ "type": "object" }));
+        let schema = Arc::new(schema.as_object().unwrap().clone());
+        Ok(vec![Served::new(
+            Tool::new("write_note", "Write a note", schema),
+            None,
+        )])
+    }
+
+    async fn call_tool(
+        &self,
+        name: String,
+        args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        self.calls.lock().unwrap().push(args);
+        tokio::time::sleep(self.delay).await;
+        let mut failures = self.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return Err(MCPError::Mcp("connection reset".to_string()));
+        }
+        Ok(Part::FunctionResponse {
+            id: None,
+            name,
+            response: json!({ "ok": true }),
+            parts: vec![],
+            error: None,
+            finished: true,
+        })
+    }
+
+    fn streams_arguments(&self, name: &str, _server_id: Option<&str>) -> bool {
+        name == "write_note"
+    }
+
+    async fn partial_arguments(
+        &self,
+        _name: String,
+        _call_id: Option<String>,
+        args: Value,
+        _server_id: Option<String>,
+    ) {
+        self.partials.lock().unwrap().push(args);
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn get_prompt(
+        &self,
+        _prompt: &Served<Prompt>,
+        _args: Option<serde_json::Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        unimplemented!()
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn read_resource(
+        &self,
+        _resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        unimplemented!()
+    }
+}
+
+fn call_snapshot(arguments: Value, finished: bool) -> Response {
+    Response {
+        data: vec![Message::Assistant(vec![Part::FunctionCall {
+            id: Some("call_1".to_string()),
+            name: "write_note".to_string(),
+            arguments,
+            signature: None,
+            repaired: false,
+            extensions: Extensions::new(),
+            finished,
+        }])],
+        usage: Usage::default(),
+        finish: if finished {
+            FinishReason::ToolCalls
+        } else {
+            FinishReason::Unfinished
+        },
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
+
+#[tokio::test]
+async fn test_agent_streams_partial_arguments() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!(r#"{"text": "Hel"#), false),
+        call_snapshot(json!(r#"{"text": "Hello wo"#), false),
+        call_snapshot(json!(r#"{"text": "Hello wo"#), false),
+        call_snapshot(json!({ "text": "Hello world" }), true),
+        Response {
+            data: vec![Message::Assistant(vec![Part::Text {
+                content: "Done".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }])],
+            usage: Usage::default(),
+            finish: FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        },
+    ]);
+
+    let server = StreamingToolServer::default();
+    let partials = server.partials.clone();
+    let calls = server.calls.clone();
+    let agent = Agent::new(client).with_server(server);
+
+    let responses: Vec<_> = agent.chat_stream(vec![]).collect().await;
+    assert!(responses.iter().all(|r| r.is_ok()));
+
+    assert_eq!(
+        *partials.lock().unwrap(),
+        vec![json!({ "text": "Hel" }), json!({ "text": "Hello wo" })]
+    );
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![json!({ "text": "Hello world" })]
+    );
+}
+
+async fn run_failing_tool(policy: ToolRetryPolicy, failures: usize) -> (usize, Part) {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "Hello" }), true),
+        Response {
+            data: vec![Message::Assistant(vec![Part::Text {
+                content: "Done".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }])],
+            usage: Usage::default(),
+            finish: FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        },
+    ]);
+
+    let server = StreamingToolServer::default();
+    *server.failures.lock().unwrap() = failures;
+    let calls = server.calls.clone();
+    let agent = Agent::new(client)
+        .with_server(server)
+        .with_tool_retry(policy);
+
+    let response = agent.chat(vec![]).await.unwrap();
+    let calls = calls.lock().unwrap().len();
+    (calls, response.data[1].parts()[0].clone())
+}
+
+#[tokio::test]
+async fn test_agent_reports_tool_errors() {
+    let (calls, part) = run_failing_tool(ToolRetryPolicy::Never, 1).await;
+
+    assert_eq!(calls, 1);
+    match part {
+        Part::FunctionResponse {
+            id,
+            error: Some(error),
+            ..
+        } => {
+            assert_eq!(id.as_deref(), Some("call_1"));
+            assert_eq!(error.kind, ToolErrorKind::Unavailable);
+            assert!(error.retryable);
+        }
+        other => panic!("Expected failed function response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_agent_retries_transient_tool_errors_once() {
+    let (calls, part) = run_failing_tool(ToolRetryPolicy::Transient, 1).await;
+    assert_eq!(calls, 2);
+    assert!(matches!(part, Part::FunctionResponse { error: None, .. }));
+
+    let (calls, part) = run_failing_tool(ToolRetryPolicy::Transient, 2).await;
+    assert_eq!(calls, 2);
+    assert!(matches!(
+        part,
+        Part::FunctionResponse { error: Some(_), .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_stop_aborts_running_tool() {
+    let client = MockClient::new(vec![call_snapshot(json!({ "text": "Hello" }), true)]);
+    let server = StreamingToolServer {
+        delay: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let agent = Agent::new(client).with_server(server);
+
+    let stop = StopSignal::new();
+    let mut stream = agent.chat_stream_until(vec![], stop.clone());
+
+    // The first snapshot carries the finished call; the tool starts on the next poll.
+    stream.next().await.unwrap().unwrap();
+    let trigger = stop.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        trigger.stop();
+    });
+
+    let last = tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("stop should abort the tool")
+        .unwrap()
+        .unwrap();
+    match &last.data[1].parts()[0] {
+        Part::FunctionResponse {
+            id,
+            error: Some(error),
+            ..
+        } => {
+            assert_eq!(id.as_deref(), Some("call_1"));
+            assert_eq!(error.kind, ToolErrorKind::Cancelled);
+        }
+        other => panic!("Expected cancelled function response, got {:?}", other),
+    }
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_agent_tool_timeout() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "Hello" }), true),
+        call_snapshot(json!({ "text": "Hello" }), true),
+        call_snapshot(json!({ "text": "Hello" }), true),
+    ]);
+    let server = StreamingToolServer {
+        delay: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let calls = server.calls.clone();
+    let agent = Agent::new(client)
+        .with_server(server)
+        .with_max_iterations(1)
+        .with_tool_retry(ToolRetryPolicy::Transient)
+        .with_tool_config(
+            "write_note",
+            ToolConfig::new()
+                .with_timeout(Duration::from_millis(10))
+                .with_max_retries(2),
+        );
+
+    let responses: Vec<_> = agent.chat_stream(vec![]).collect().await;
+    let response = responses[responses.len() - 2].as_ref().unwrap();
+
+    assert_eq!(calls.lock().unwrap().len(), 3);
+    match &response.data[1].parts()[0] {
+        Part::FunctionResponse {
+            error: Some(error), ..
+        } => assert_eq!(error.kind, ToolErrorKind::Timeout),
+        other => panic!("Expected timed out function response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_agent_rejects_repaired_arguments() {
+    let mut truncated = call_snapshot(json!({ "text": "Hel" }), true);
+    if let Part::FunctionCall { repaired, .. } = &mut truncated.data[0].parts_mut()[0] {
+        *repaired = true;
+    }
+    let client = MockClient::new(vec![
+        truncated,
+        Response {
+            data: vec![Message::Assistant(vec![Part::Text {
+                content: "Done".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }])],
+            usage: Usage::default(),
+            finish: FinishReason::Stop,
+            stop_sequence: None,
+            service_tier: None,
+        },
+    ]);
+    let server = StreamingToolServer::default();
+    let calls = server.calls.clone();
+    let agent = Agent::new(client).with_server(server);
+
+    let response = agent.chat(vec![]).await.unwrap();
+
+    assert!(calls.lock().unwrap().is_empty());
+    match &response.data[1].parts()[0] {
+        Part::FunctionResponse {
+            error: Some(error), ..
+        } => assert_eq!(error.kind, ToolErrorKind::InvalidArguments),
+        other => panic!("Expected rejected function response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_agent_validates_arguments_against_schema() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": 42 }), true),
+        answer("Done"),
+    ]);
+    let server = StreamingToolServer {
+        schema: Some(json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+            "required": ["text"]
+        })),
+        ..Default::default()
+    };
+    let calls = server.calls.clone();
+    let agent = Agent::new(client).with_server(server);
+
+    let response = agent.chat(vec![Message::user("Note 42")]).await.unwrap();
+
+    assert!(calls.lock().unwrap().is_empty());
+    match &response.data[1].parts()[0] {
+        Part::FunctionResponse {
+            error: Some(error), ..
+        } => {
+            assert_eq!(error.kind, ToolErrorKind::InvalidArguments);
+            assert!(error.message.contains("$.text"), "{}", error.message);
+        }
+        other => panic!("Expected rejected function response, got {:?}", other),
+    }
+}
+
+fn answer(text: &str) -> Response {
+    Response {
+        data: vec![Message::assistant(text)],
+        usage: Usage::default(),
+        finish: FinishReason::Stop,
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
+
+#[tokio::test]
+async fn test_agent_reasks_until_answer_is_valid() {
+    let client = MockClient::new(vec![answer("Tomorrow"), answer("2025-01-31")]);
+    let requests = client.requests.clone();
+    let agent =
+        Agent::new(client).with_validator(RegexValidator::new(r"^\d{4}-\d{2}-\d{2}$").unwrap());
+
+    let response = agent.chat(vec![Message::user("When?")]).await.unwrap();
+
+    assert_eq!(response.data.len(), 3);
+    assert_eq!(response.data[2].content().as_deref(), Some("2025-01-31"));
+    let retry = &requests.lock().unwrap()[1];
+    assert!(retry[2].content().unwrap().contains("regular expression"));
+}
+
+#[tokio::test]
+async fn test_agent_validation_gives_up() {
+    let client = MockClient::new(vec![answer("a"), answer("b")]);
+    let agent = Agent::new(client)
+        .with_validator(|_: &Response| Err("never valid".to_string()))
+        .with_validation_attempts(2);
+
+    match agent.chat(vec![Message::user("?")]).await {
+        Err(ClientError::ValidationFailed { attempts, message }) => {
+            assert_eq!(attempts, 2);
+            assert_eq!(message, "never valid");
+        }
+        other => panic!("Expected validation failure, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_agent_layers_instructions() {
+    let mut client = MockClient::new(vec![answer("Bonjour")]);
+    client.model_options = ModelOptions::new("mock").with_system("Be brief.");
+    let requests = client.requests.clone();
+    let agent = Agent::new(client).with_instructions(
+        Instructions::new()
+            .with(InstructionLayer::App, "Be brief.")
+            .with(InstructionLayer::App, "You are a travel assistant."),
+    );
+    let mut conversation = Conversation::new(vec![Message::user("Hi")]).with_instructions(
+        Instructions::new().with(InstructionLayer::Conversation, "Call the user Sam."),
+    );
+
+    agent
+        .chat_conversation(
+            &mut conversation,
+            &Instructions::new().with(InstructionLayer::Call, "Answer in French."),
+        )
+        .await
+        .unwrap();
+
+    let request = &requests.lock().unwrap()[0];
+    assert_eq!(
+        request[0].content().as_deref(),
+        Some("You are a travel assistant.\n\nCall the user Sam.\n\nAnswer in French.\nHi")
+    );
+    assert_eq!(
+        conversation.messages(),
+        &[Message::user("Hi"), Message::assistant("Bonjour")]
+    );
+}
+
+#[tokio::test]
+async fn test_agent_trace_replays() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "milk" }), true),
+        answer("Noted."),
+    ]);
+    let recorder = TraceRecorder::new();
+    let agent = Agent::new(client)
+        .with_server(StreamingToolServer::default())
+        .with_trace(recorder.clone());
+    let history = vec![Message::user("Remember milk")];
+    let recorded = agent.chat(history.clone()).await.unwrap();
+
+    let trace: AgentTrace =
+        serde_json::from_str(&serde_json::to_string(&recorder.take()).unwrap()).unwrap();
+    let events: Vec<&str> = trace
+        .events()
+        .map(|event| match event {
+            TraceEvent::Request { .. } => "request",
+            TraceEvent::Response { .. } => "response",
+            TraceEvent::Error { .. } => "error",
+            TraceEvent::ToolCall { .. } => "tool_call",
+            TraceEvent::ToolResult { .. } => "tool_result",
+            TraceEvent::Decision { .. } => "decision",
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "request",
+            "response",
+            "decision",
+            "tool_call",
+            "tool_result",
+            "request",
+            "response",
+            "decision"
+        ]
+    );
+    assert!(matches!(
+        trace.events().last(),
+        Some(TraceEvent::Decision {
+            iteration: 1,
+            decision: AgentDecision::Finish
+        })
+    ));
+
+    let replay = Agent::new(trace.replay_client()).with_server(trace.replay_server());
+    assert_eq!(replay.chat(history).await.unwrap(), recorded);
+    assert_eq!(replay.client().remaining(), 0);
+
+    let replay = Agent::new(trace.replay_client()).with_server(trace.replay_server());
+    assert!(matches!(
+        replay.chat(vec![Message::user("Remember eggs")]).await,
+        Err(ClientError::Config(_))
+    ));
+}
+
+/// Hooks injecting the iteration number and stopping after a usage limit.
+///
+/// The usage at the end of each iteration is recorded in `ends`.
+#[derive(Default)]
+struct Tracking {
+    ends: Arc<Mutex<Vec<Option<u32>>>>,
+}
+
+#[async_trait]
+impl IterationHooks for Tracking {
+    async fn on_iteration_start(
+        &self,
+        iteration: usize,
+        request: &mut Vec<Message>,
+    ) -> Result<(), ClientError> {
+        request.push(Message::user(format!("Iteration {}", iteration)));
+        Ok(())
+    }
+
+    async fn on_iteration_end(&self, iteration: usize, usage: &Usage) -> Result<(), ClientError> {
+        assert_eq!(self.ends.lock().unwrap().len(), iteration);
+        self.ends.lock().unwrap().push(usage.completion_tokens);
+        if usage.completion_tokens > Some(5) {
+            return Err(ClientError::BudgetExhausted("too many tokens".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_agent_runs_iteration_hooks() {
+    let mut call = call_snapshot(json!({ "text": "Hello" }), true);
+    call.usage.completion_tokens = Some(4);
+    let mut done = answer("Done");
+    done.usage.completion_tokens = Some(4);
+    let client = MockClient::new(vec![call, done]);
+    let requests = client.requests.clone();
+
+    let hooks = Tracking::default();
+    let ends = hooks.ends.clone();
+    let agent = Agent::new(client)
+        .with_server(StreamingToolServer::default())
+        .with_hooks(hooks);
+
+    let result = agent.chat(vec![Message::user("Hi")]).await;
+    assert!(matches!(result, Err(ClientError::BudgetExhausted(_))));
+    assert_eq!(*ends.lock().unwrap(), vec![Some(4), Some(8)]);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0][1].content().as_deref(), Some("Iteration 0"));
+    // Injected context is not kept in the conversation.
+    assert_eq!(requests[1].len(), 4);
+    assert_eq!(requests[1][3].content().as_deref(), Some("Iteration 1"));
+}
+
+#[tokio::test]
+async fn test_agent_tools_are_provided_per_iteration() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "Plan" }), true),
+        answer("Done"),
+    ]);
+    let tools = client.tools.clone();
+
+    let agent = Agent::new(client)
+        .with_server(StreamingToolServer::default())
+        .with_tool_provider(|history: Vec<Message>| async move {
+            let schema = Arc::new(serde_json::Map::new());
+            let planned = history
+                .iter()
+                .flat_map(|m| m.parts())
+                .any(|p| matches!(p, Part::FunctionResponse { error: None, .. }));
+            let mut tools = vec![Tool::new("write_note", "Write a note", schema.clone())];
+            if planned {
+                tools.push(Tool::new("deploy", "Deploy", schema));
+            }
+            Ok(tools)
+        });
+
+    agent.chat(vec![Message::user("Go")]).await.unwrap();
+    assert_eq!(
+        *tools.lock().unwrap(),
+        vec![vec!["write_note"], vec!["write_note", "deploy"]]
+    );
+}
+
+/// Local server whose tool records every call in a scratchpad.
+struct ScratchpadToolServer {
+    scratchpad: Scratchpad,
+}
+
+#[async_trait]
+impl MCPServer for ScratchpadToolServer {
+    async fn list_tools(&self) -> Result<Vec<Served<Tool>>, MCPError> {
+        let schema = Arc::new(json!({ "type": "object" }).as_object().unwrap().clone());
+        Ok(vec![Served::new(
+            Tool::new("write_note", "Write a note", schema),
+            None,
+        )])
+    }
+
+    async fn call_tool(
+        &self,
+        name: String,
+        args: Value,
+        _server_id: Option<String>,
+    ) -> Result<Part, MCPError> {
+        self.scratchpad
+            .update("notes", |notes: &mut Vec<Value>| {
+                notes.push(args["text"].clone())
+            })
+            .map_err(|e| MCPError::Mcp(e.to_string()))?;
+        Ok(Part::FunctionResponse {
+            id: None,
+            name,
+            response: json!({ "ok": true }),
+            parts: vec![],
+            error: None,
+            finished: true,
+        })
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Served<Prompt>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn get_prompt(
+        &self,
+        _prompt: &Served<Prompt>,
+        _args: Option<serde_json::Map<String, Value>>,
+    ) -> Result<Served<GetPromptResult>, MCPError> {
+        unimplemented!()
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Served<Resource>>, MCPError> {
+        Ok(vec![])
+    }
+
+    async fn read_resource(
+        &self,
+        _resource: &Served<Resource>,
+    ) -> Result<Served<ReadResourceResult>, MCPError> {
+        unimplemented!()
+    }
+}
+
+#[tokio::test]
+async fn test_agent_summarizes_scratchpad_into_requests() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "Hello" }), true),
+        answer("Done"),
+    ]);
+    let requests = client.requests.clone();
+
+    let scratchpad = Scratchpad::new();
+    let agent = Agent::new(client)
+        .with_server(ScratchpadToolServer {
+            scratchpad: scratchpad.clone(),
+        })
+        .with_scratchpad(scratchpad.clone());
+
+    let response = agent.chat(vec![Message::user("Hi")]).await.unwrap();
+    assert_eq!(
+        scratchpad.get::<Vec<String>>("notes").unwrap(),
+        Some(vec!["Hello".to_string()])
+    );
+
+    let requests = requests.lock().unwrap();
+    // Nothing to summarize before the first tool call.
+    assert_eq!(requests[0], vec![Message::user("Hi")]);
+    let results = requests[1].last().unwrap().parts();
+    assert!(matches!(results[0], Part::FunctionResponse { .. }));
+    assert_eq!(
+        results[1],
+        Part::text("Current scratchpad of the tools:\n- notes: [\"Hello\"]")
+    );
+    // The summary is not kept in the conversation.
+    assert_eq!(response.data[1].parts().len(), 1);
+}
+
+#[tokio::test]
+async fn test_agent_applies_interjections_between_iterations() {
+    let client = MockClient::new(vec![
+        call_snapshot(json!({ "text": "Hello" }), true),
+        answer("Bonjour"),
+        answer("Salut"),
+    ]);
+    let requests = client.requests.clone();
+    let agent = Agent::new(client).with_server(StreamingToolServer::default());
+
+    let interjections = Interjections::new();
+    let mut stream = agent.chat_stream_steered(
+        vec![Message::user("Greet me")],
+        StopSignal::new(),
+        interjections.clone(),
+    );
+
+    // Pushed while the call is pending: applied after its result.
+    stream.next().await.unwrap().unwrap();
+    interjections.push(Message::user("In French"));
+    let snapshot = stream.next().await.unwrap().unwrap();
+    assert_eq!(interjections.pending(), 0);
+    let results = snapshot.data[1].parts();
+    assert!(matches!(results[0], Part::FunctionResponse { .. }));
+    assert_eq!(results[1], Part::text("In French"));
+
+    // Pushed after a final answer: the run continues instead of finishing.
+    let snapshot = stream.next().await.unwrap().unwrap();
+    assert_eq!(snapshot.data[2].content().as_deref(), Some("Bonjour"));
+    interjections.push(Message::user("Shorter"));
+    let mut last = None;
+    while let Some(snapshot) = stream.next().await {
+        last = Some(snapshot.unwrap());
+    }
+    let data = last.unwrap().data;
+    assert_eq!(data.len(), 5);
+    assert_eq!(data[3], Message::user("Shorter"));
+    assert_eq!(data[4].content().as_deref(), Some("Salut"));
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].last(), Some(&Message::user("Shorter")));
+}
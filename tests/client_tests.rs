@@ -0,0 +1,291 @@
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use rmcp::model::Tool;
+use serde::Deserialize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use unia::client::{Client, ClientError, ClientExt, StreamingClient, StreamingClientExt};
+use unia::model::{FinishReason, Message, Response};
+use unia::options::{ModelOptions, TransportOptions};
+
+mod common;
+use common::snapshot;
+
+/// Streaming mock that yields its snapshots with a delay between each one.
+struct SlowStreamingClient {
+    snapshots: Vec<Response>,
+    delay: Duration,
+}
+
+#[async_trait]
+impl Client for SlowStreamingClient {
+    type ModelProvider = ();
+
+    async fn request(
+        &self,
+        _messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        unimplemented!()
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        unimplemented!()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        unimplemented!()
+    }
+}
+
+#[async_trait]
+impl StreamingClient for SlowStreamingClient {
+    async fn request_stream(
+        &self,
+        _messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        let snapshots = self.snapshots.clone();
+        let delay = self.delay;
+        Ok(Box::pin(async_stream::stream! {
+            for snapshot in snapshots {
+                tokio::time::sleep(delay).await;
+                yield Ok(snapshot);
+            }
+        }))
+    }
+}
+
+#[tokio::test]
+async fn test_request_with_progress_reports_heartbeats() {
+    let client = SlowStreamingClient {
+        snapshots: vec![
+            snapshot("Hel", FinishReason::Unfinished),
+            snapshot("Hello", FinishReason::Stop),
+        ],
+        delay: Duration::from_millis(60),
+    };
+
+    let ticks = Arc::new(Mutex::new(Vec::new()));
+    let recorded = ticks.clone();
+
+    let response = client
+        .request_with_progress(vec![], vec![], Duration::from_millis(20), move |p| {
+            recorded.lock().unwrap().push(p)
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.finish, FinishReason::Stop);
+    assert_eq!(response.data[0].content().as_deref(), Some("Hello"));
+
+    let ticks = ticks.lock().unwrap();
+    assert!(!ticks.is_empty());
+    assert!(ticks.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+}
+
+#[tokio::test]
+async fn test_request_with_progress_rejects_zero_interval() {
+    let client = SlowStreamingClient {
+        snapshots: vec![snapshot("Hello", FinishReason::Stop)],
+        delay: Duration::ZERO,
+    };
+
+    let result = client
+        .request_with_progress(vec![], vec![], Duration::ZERO, |_| {})
+        .await;
+
+    assert!(matches!(result, Err(ClientError::Config(_))));
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Person {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    hobbies: Vec<String>,
+}
+
+#[tokio::test]
+async fn test_chat_structured_stream_yields_partials() {
+    let chunks = [
+        r#"{"na"#,
+        r#"{"name": "Ad"#,
+        r#"{"name": "Ada", "hobbies": ["ch"#,
+        r#"{"name": "Ada", "hobbies": ["chess"]}"#,
+    ];
+    let mut snapshots: Vec<Response> = chunks
+        .iter()
+        .map(|c| snapshot(c, FinishReason::Unfinished))
+        .collect();
+    snapshots.last_mut().unwrap().finish = FinishReason::Stop;
+
+    let client = SlowStreamingClient {
+        snapshots,
+        delay: Duration::ZERO,
+    };
+
+    let partials: Vec<_> = client
+        .chat_structured_stream::<Person>(vec![])
+        .map(|p| p.unwrap())
+        .collect()
+        .await;
+
+    let names: Vec<&str> = partials.iter().map(|p| p.value.name.as_str()).collect();
+    assert_eq!(names, vec!["", "Ad", "Ada", "Ada", "Ada"]);
+    assert_eq!(partials[2].value.hobbies, vec!["ch"]);
+
+    let last = partials.last().unwrap();
+    assert!(last.complete);
+    assert_eq!(last.value.hobbies, vec!["chess"]);
+    assert!(partials[..partials.len() - 1].iter().all(|p| !p.complete));
+}
+
+#[test]
+fn test_api_error_retryability() {
+    let error = |status| ClientError::Api {
+        status,
+        request_id: Some("req_123".to_string()),
+        message: "boom".to_string(),
+    };
+
+    assert!(error(Some(429)).is_retryable());
+    assert!(error(Some(529)).is_retryable());
+    assert!(!error(Some(400)).is_retryable());
+    assert!(!error(None).is_retryable());
+    assert!(!ClientError::StreamCancelled.is_retryable());
+
+    let error = error(Some(503));
+    assert_eq!(error.status(), Some(503));
+    assert_eq!(error.request_id(), Some("req_123"));
+    assert_eq!(
+        error.to_string(),
+        "Provider error: boom (request id: req_123)"
+    );
+}
+
+#[test]
+fn test_error_codes_and_predicates() {
+    let rate_limited = ClientError::Api {
+        status: Some(429),
+        request_id: None,
+        message: "slow down".to_string(),
+    };
+    assert_eq!(rate_limited.code(), "api");
+    assert!(rate_limited.is_rate_limited());
+    assert!(!rate_limited.is_timeout());
+
+    let timeout = ClientError::Timeout("no first token".to_string());
+    assert_eq!(timeout.code(), "timeout");
+    assert!(timeout.is_timeout());
+
+    assert_eq!(ClientError::StreamCancelled.code(), "stream_cancelled");
+    assert!(ClientError::StreamCancelled.is_cancelled());
+    assert!(ClientError::Config("no key".to_string()).is_config());
+}
+
+/// Mock echoing the prompt after a delay, failing prompts that say "fail".
+#[derive(Default)]
+struct EchoClient {
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+}
+
+#[async_trait]
+impl Client for EchoClient {
+    type ModelProvider = ();
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+
+        let prompt = messages[0].content().unwrap_or_default();
+        if prompt == "fail" {
+            return Err(ClientError::ProviderError("failed".to_string()));
+        }
+        let mut response = snapshot(&prompt, FinishReason::Stop);
+        response.usage.completion_tokens = Some(2);
+        Ok(response)
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        unimplemented!()
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        unimplemented!()
+    }
+}
+
+#[tokio::test]
+async fn test_chat_many_isolates_errors() {
+    let client = EchoClient::default();
+    let chats = ["a", "fail", "c", "d", "e"]
+        .iter()
+        .map(|prompt| vec![Message::user(*prompt)])
+        .collect();
+
+    let results = client.chat_many(chats, 2).await;
+
+    assert_eq!(results.succeeded(), 4);
+    assert_eq!(results.failed(), 1);
+    assert!(results.results[1].is_err());
+    assert_eq!(
+        results.results[4].as_ref().unwrap().data[0]
+            .content()
+            .as_deref(),
+        Some("e")
+    );
+    assert_eq!(results.usage.completion_tokens, Some(8));
+    assert_eq!(client.max_running.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(all(feature = "openai", feature = "gemini"))]
+#[test]
+fn test_dry_run_redacts_credentials() {
+    use unia::providers::{GeminiClient, OpenAIClient};
+
+    let messages = vec![Message::user("What is the capital of France?")];
+    let openai = OpenAIClient::new(
+        "sk-proj-0123456789abcdefghijklmnop".to_string(),
+        "https://api.openai.com/v1".to_string(),
+        ModelOptions::new("gpt-5").with_system("Answer briefly."),
+        TransportOptions::default(),
+    );
+    let preview = openai.dry_run(messages.clone(), vec![]).unwrap();
+    assert_eq!(preview.method, "POST");
+    assert_eq!(preview.url, "https://api.openai.com/v1/chat/completions");
+    assert_eq!(preview.body["model"], "gpt-5");
+    assert!(preview.estimated_tokens > 0);
+    let authorization = preview
+        .headers
+        .iter()
+        .find(|(name, _)| name == "authorization")
+        .unwrap();
+    assert_eq!(authorization.1, "[REDACTED]");
+    assert!(!serde_json::to_string(&preview)
+        .unwrap()
+        .contains("0123456789abcdefghijklmnop"));
+
+    let gemini = GeminiClient::new(
+        "AIzaSecretKey0123456789".to_string(),
+        "https://generativelanguage.googleapis.com/v1beta".to_string(),
+        ModelOptions::new("gemini-2.5-flash"),
+        TransportOptions::default(),
+    );
+    let preview = gemini.dry_run(messages, vec![]).unwrap();
+    assert!(preview.url.ends_with(":generateContent?key=%5BREDACTED%5D"));
+    assert_eq!(
+        preview.body["contents"][0]["parts"][0]["text"],
+        "What is the capital of France?"
+    );
+}
@@ -0,0 +1,19 @@
+//! Builders shared by the integration tests.
+
+use unia::model::{FinishReason, Message, Part, Response};
+
+/// Response snapshot with a single assistant text, finished unless `finish` is
+/// [`FinishReason::Unfinished`].
+pub fn snapshot(text: &str, finish: FinishReason) -> Response {
+    let mut part = Part::text(text);
+    if let Part::Text { finished, .. } = &mut part {
+        *finished = finish != FinishReason::Unfinished;
+    }
+    Response {
+        data: vec![Message::Assistant(vec![part])],
+        usage: Default::default(),
+        finish,
+        stop_sequence: None,
+        service_tier: None,
+    }
+}
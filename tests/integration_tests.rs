@@ -1,5 +1,7 @@
+#![cfg(feature = "openai")]
+
 use unia::client::Client;
-use unia::model::{Message, Part, Role};
+use unia::model::{Extensions, Message, Part, Role, TextAnnotations};
 use unia::providers::{OpenAI, Provider};
 
 #[test]
@@ -11,7 +13,10 @@ fn test_client_creation() {
 #[test]
 fn test_message_construction() {
     let msg = Message::User(vec![Part::Text {
-        content: "Hello".to_string(),
+        content: "Hello".into(),
+        signature: None,
+        extensions: Extensions::new(),
+        annotations: TextAnnotations::default(),
         finished: true,
     }]);
 
@@ -27,3 +32,19 @@ fn test_message_construction() {
         panic!("Expected User message");
     }
 }
+
+#[test]
+fn test_message_shorthands() {
+    assert_eq!(
+        Message::user("Hello"),
+        Message::User(vec![Part::Text {
+            content: "Hello".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        }])
+    );
+    assert_eq!(Message::assistant("Hi").role(), Role::Assistant);
+    assert_eq!(Message::assistant("Hi").content().as_deref(), Some("Hi"));
+}
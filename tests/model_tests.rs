@@ -0,0 +1,172 @@
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use unia::model::{
+    Extensions, FinishReason, MediaType, Message, Part, Response, TextAnnotations, Usage,
+};
+use unia::tools::{ToolError, ToolErrorKind};
+
+/// Serialize `value`, compare it against `expected` and check it deserializes back unchanged.
+fn assert_snapshot<T>(value: &T, expected: Value)
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    assert_eq!(serde_json::to_value(value).unwrap(), expected);
+    assert_eq!(&serde_json::from_value::<T>(expected).unwrap(), value);
+}
+
+#[test]
+fn test_part_serde_snapshots() {
+    assert_snapshot(
+        &Part::Text {
+            content: "Hello".into(),
+            signature: None,
+            extensions: Extensions::new(),
+            annotations: TextAnnotations::default(),
+            finished: true,
+        },
+        json!({ "type": "Text", "data": { "content": "Hello", "finished": true } }),
+    );
+    assert_snapshot(
+        &Part::Reasoning {
+            content: "Thinking".into(),
+            summary: None,
+            signature: Some("sig".to_string()),
+            extensions: Extensions::new(),
+            finished: true,
+        },
+        json!({
+            "type": "Reasoning",
+            "data": { "content": "Thinking", "summary": null, "signature": "sig", "finished": true }
+        }),
+    );
+    assert_snapshot(
+        &Part::FunctionCall {
+            id: Some("call_1".to_string()),
+            name: "get_weather".to_string(),
+            arguments: json!({ "city": "Paris" }),
+            signature: None,
+            repaired: false,
+            extensions: Extensions::new(),
+            finished: true,
+        },
+        json!({
+            "type": "FunctionCall",
+            "data": {
+                "id": "call_1",
+                "name": "get_weather",
+                "arguments": { "city": "Paris" },
+                "signature": null,
+                "finished": true
+            }
+        }),
+    );
+    assert_snapshot(
+        &Part::FunctionResponse {
+            id: Some("call_1".to_string()),
+            name: "get_weather".to_string(),
+            response: json!({ "error": { "kind": "timeout", "message": "slow" } }),
+            parts: vec![],
+            error: Some(ToolError::new(ToolErrorKind::Timeout, "slow")),
+            finished: true,
+        },
+        json!({
+            "type": "FunctionResponse",
+            "data": {
+                "id": "call_1",
+                "name": "get_weather",
+                "response": { "error": { "kind": "timeout", "message": "slow" } },
+                "parts": [],
+                "error": { "kind": "timeout", "message": "slow", "retryable": true },
+                "finished": true
+            }
+        }),
+    );
+    assert_snapshot(
+        &Part::media(
+            MediaType::Image,
+            "image/png",
+            Bytes::from_static(b"\x89PNG"),
+        ),
+        json!({
+            "type": "Media",
+            "data": {
+                "media_type": "image",
+                "data": "iVBORw==",
+                "mime_type": "image/png",
+                "uri": null,
+                "finished": true
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_response_serde_snapshot() {
+    let response = Response {
+        data: vec![
+            Message::User(vec![Part::Text {
+                content: "Hi".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }]),
+            Message::Assistant(vec![Part::Text {
+                content: "Hello".into(),
+                signature: None,
+                extensions: Extensions::new(),
+                annotations: TextAnnotations::default(),
+                finished: true,
+            }]),
+        ],
+        usage: Usage {
+            prompt_tokens: Some(3),
+            completion_tokens: None,
+            cached_prompt_tokens: None,
+            accepted_prediction_tokens: None,
+            rejected_prediction_tokens: None,
+        },
+        finish: FinishReason::Stop,
+        stop_sequence: None,
+        service_tier: None,
+    };
+
+    assert_snapshot(
+        &response,
+        json!({
+            "data": [
+                { "role": "user", "content": [
+                    { "type": "Text", "data": { "content": "Hi", "finished": true } }
+                ] },
+                { "role": "assistant", "content": [
+                    { "type": "Text", "data": { "content": "Hello", "finished": true } }
+                ] }
+            ],
+            "usage": { "prompt_tokens": 3 },
+            "finish": "Stop"
+        }),
+    );
+}
+
+#[test]
+fn test_finish_reason_and_usage_are_hashable() {
+    let reasons: HashSet<FinishReason> = [
+        FinishReason::Stop,
+        FinishReason::ToolCalls,
+        FinishReason::Stop,
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(reasons.len(), 2);
+
+    let usage = Usage {
+        prompt_tokens: Some(1),
+        completion_tokens: Some(2),
+        cached_prompt_tokens: None,
+        accepted_prediction_tokens: None,
+        rejected_prediction_tokens: None,
+    };
+    let usages: HashSet<Usage> = [usage.clone(), usage].into_iter().collect();
+    assert_eq!(usages.len(), 1);
+}
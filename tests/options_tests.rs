@@ -1,29 +1,88 @@
+#![cfg(all(feature = "openai", feature = "anthropic", feature = "gemini"))]
+
 use std::time::Duration;
-use unia::options::{ModelOptions, TransportOptions};
-use unia::providers::OpenAIModel;
+use unia::client::ClientError;
+use unia::constraints::{Constraints, PromptStyle};
+use unia::http::{check_request_size, identification_headers, with_idempotency_key};
+use unia::model::{MediaType, Message, Part, PartIndex};
+use unia::options::{
+    AppInfo, IdempotencyKey, ModelOptions, ReconnectPolicy, SafetyLevel, SamplingPreset,
+    ServiceTier, TransportOptions,
+};
+use unia::providers::{AnthropicModel, GeminiModel, GroqModel, OpenAIModel};
 
 #[test]
 fn test_transport_options_builder() {
     let options = TransportOptions::new()
         .with_timeout(Duration::from_secs(30))
+        .with_connect_timeout(Duration::from_secs(5))
+        .with_first_token_timeout(Duration::from_secs(10))
+        .with_idle_timeout(Duration::from_secs(60))
         .with_proxy("http://proxy.example.com".to_string())
-        .with_header("X-Custom-Header".to_string(), "Value".to_string());
+        .with_header("X-Custom-Header".to_string(), "Value".to_string())
+        .with_app(AppInfo::new("my-app", "1.0.0"))
+        .with_reconnect(ReconnectPolicy::new(2, Duration::from_millis(500)))
+        .with_max_stream_tokens(4096)
+        .with_recover_partial(true)
+        .with_organization("org-123")
+        .with_project("proj_456");
 
     match options {
         TransportOptions::Http {
             timeout,
+            connect_timeout,
+            first_token_timeout,
+            idle_timeout,
             proxy,
             headers,
+            app,
+            reconnect,
+            idempotency_key,
+            max_request_bytes,
+            max_stream_tokens,
+            recover_partial,
+            organization,
+            project,
         } => {
             assert_eq!(timeout, Some(Duration::from_secs(30)));
+            assert_eq!(connect_timeout, Some(Duration::from_secs(5)));
+            assert_eq!(first_token_timeout, Some(Duration::from_secs(10)));
+            assert_eq!(idle_timeout, Some(Duration::from_secs(60)));
             assert_eq!(proxy, Some("http://proxy.example.com".to_string()));
 
             let headers = headers.unwrap();
             assert_eq!(headers.get("X-Custom-Header"), Some(&"Value".to_string()));
+            assert_eq!(app, Some(AppInfo::new("my-app", "1.0.0")));
+            assert_eq!(
+                reconnect,
+                Some(ReconnectPolicy::new(2, Duration::from_millis(500)))
+            );
+            assert_eq!(idempotency_key, IdempotencyKey::Random);
+            assert_eq!(max_request_bytes, None);
+            assert_eq!(max_stream_tokens, Some(4096));
+            assert_eq!(recover_partial, Some(true));
+            assert_eq!(organization.as_deref(), Some("org-123"));
+            assert_eq!(project.as_deref(), Some("proj_456"));
         }
     }
 }
 
+#[tokio::test]
+async fn test_idempotency_keys() {
+    let options = TransportOptions::new();
+
+    // Identical calls get distinct keys.
+    let first = options.idempotency_key().unwrap();
+    assert_eq!(first.len(), 36);
+    assert_ne!(Some(first), options.idempotency_key());
+
+    let disabled = options.with_idempotency_key(IdempotencyKey::Disabled);
+    assert_eq!(disabled.idempotency_key(), None);
+    let key = with_idempotency_key("job-42", async { disabled.idempotency_key() }).await;
+    assert_eq!(key.as_deref(), Some("job-42"));
+    assert_eq!(disabled.idempotency_key(), None);
+}
+
 #[test]
 fn test_model_options_new() {
     let options: ModelOptions<OpenAIModel> = ModelOptions::new("gpt-5");
@@ -43,3 +102,210 @@ fn test_model_options_custom() {
     assert_eq!(options.temperature, Some(0.7));
     assert_eq!(options.max_tokens, Some(100));
 }
+
+#[test]
+fn test_identification_headers() {
+    let app = AppInfo::new("my-app", "1.2.0").with_url("https://example.com");
+    let headers = identification_headers(Some(&app));
+
+    let user_agent = headers.get("user-agent").unwrap().to_str().unwrap();
+    assert_eq!(
+        user_agent,
+        format!(
+            "my-app/1.2.0 (+https://example.com) unia/{}",
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+    assert_eq!(headers.get("x-unia-lang").unwrap(), "rust");
+
+    let anonymous = identification_headers(None);
+    assert!(anonymous
+        .get("user-agent")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("unia/"));
+}
+
+#[test]
+fn test_model_options_validate_reports_all_violations() {
+    let options = ModelOptions::<OpenAIModel>::new("gpt-5")
+        .with_temperature(3.0)
+        .with_top_p(-0.5)
+        .with_max_tokens(0);
+
+    let error = options.validate().unwrap_err();
+    let fields: Vec<_> = error.violations.iter().map(|v| v.field).collect();
+    assert_eq!(fields, vec!["temperature", "top_p", "max_tokens"]);
+
+    assert!(ModelOptions::<OpenAIModel>::new("gpt-5")
+        .with_temperature(0.7)
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn test_model_options_validate_provider_constraints() {
+    let error = ModelOptions::<OpenAIModel>::new("o3-mini")
+        .with_temperature(0.5)
+        .validate()
+        .unwrap_err();
+    assert_eq!(error.violations[0].field, "temperature");
+
+    let error = ModelOptions::<AnthropicModel>::new("claude")
+        .with_reasoning(true)
+        .with_temperature(1.5)
+        .with_max_tokens(1024)
+        .validate()
+        .unwrap_err();
+    let fields: Vec<_> = error.violations.iter().map(|v| v.field).collect();
+    assert_eq!(fields, vec!["temperature", "max_tokens", "temperature"]);
+    assert!(ModelOptions::<AnthropicModel>::new("claude")
+        .with_reasoning(true)
+        .validate()
+        .is_ok());
+
+    let mut gemini = ModelOptions::<GeminiModel>::new("gemini");
+    gemini.provider.response_json_schema = Some(serde_json::json!({ "type": "object" }));
+    let error = gemini.validate().unwrap_err();
+    assert_eq!(error.violations[0].field, "provider.response_json_schema");
+    assert!(error.to_string().contains("application/json"));
+}
+
+#[test]
+fn test_service_tier_support_is_validated() {
+    let tier = |tier| ModelOptions::<OpenAIModel>::new("gpt-5").with_service_tier(tier);
+    assert!(tier(ServiceTier::Flex).validate().is_ok());
+    assert!(tier(ServiceTier::Priority).validate().is_ok());
+    assert!(tier(ServiceTier::Batch).validate().is_err());
+
+    assert!(ModelOptions::<GroqModel>::new("llama")
+        .with_service_tier(ServiceTier::Flex)
+        .validate()
+        .is_ok());
+    assert!(ModelOptions::<AnthropicModel>::new("claude")
+        .with_service_tier(ServiceTier::Flex)
+        .validate()
+        .is_err());
+    let error = ModelOptions::<GeminiModel>::new("gemini")
+        .with_service_tier(ServiceTier::Standard)
+        .validate()
+        .unwrap_err();
+    assert_eq!(error.violations[0].field, "service_tier");
+}
+
+#[test]
+fn test_safety_level_support_is_validated() {
+    assert!(ModelOptions::<GeminiModel>::new("gemini")
+        .with_safety(SafetyLevel::Permissive)
+        .validate()
+        .is_ok());
+    assert!(ModelOptions::<OpenAIModel>::new("gpt-5")
+        .with_safety(SafetyLevel::Default)
+        .validate()
+        .is_ok());
+    let error = ModelOptions::<AnthropicModel>::new("claude")
+        .with_safety(SafetyLevel::Strict)
+        .validate()
+        .unwrap_err();
+    assert_eq!(error.violations[0].field, "safety");
+}
+
+#[test]
+fn test_sampling_presets_follow_provider_guidance() {
+    let openai = ModelOptions::<OpenAIModel>::new("gpt-4.1")
+        .with_top_p(0.5)
+        .with_preset(SamplingPreset::Precise);
+    assert_eq!(openai.temperature, Some(0.2));
+    assert_eq!(openai.top_p, None);
+
+    let anthropic =
+        ModelOptions::<AnthropicModel>::new("claude").with_preset(SamplingPreset::Creative);
+    assert_eq!(anthropic.temperature, Some(1.0));
+    assert!(anthropic.validate().is_ok());
+
+    let gemini = ModelOptions::<GeminiModel>::new("gemini").with_preset(SamplingPreset::Balanced);
+    assert_eq!(gemini.temperature, Some(0.7));
+    assert_eq!(gemini.top_p, Some(0.95));
+    assert_eq!(gemini.provider.top_k, Some(40));
+
+    for preset in [
+        SamplingPreset::Precise,
+        SamplingPreset::Balanced,
+        SamplingPreset::Creative,
+    ] {
+        assert!(ModelOptions::<OpenAIModel>::new("gpt-4.1")
+            .with_preset(preset)
+            .validate()
+            .is_ok());
+        assert!(ModelOptions::<GeminiModel>::new("gemini")
+            .with_preset(preset)
+            .validate()
+            .is_ok());
+    }
+}
+
+#[test]
+fn test_constraints_extend_the_system_prompt() {
+    let constraints = Constraints::new()
+        .with_language("German")
+        .with_max_words(50);
+    let options = ModelOptions::<AnthropicModel>::new("claude")
+        .with_system("You are a tour guide.")
+        .with_constraints(constraints.clone());
+    assert_eq!(
+        options.system_prompt(PromptStyle::Xml).as_deref(),
+        Some(
+            "You are a tour guide.\n\n<output_constraints>\n- Answer in German.\n\
+             - Use at most 50 words.\n</output_constraints>"
+        )
+    );
+
+    let options = ModelOptions::<GeminiModel>::new("gemini").with_constraints(constraints);
+    assert_eq!(
+        options.system_prompt(PromptStyle::Plain).as_deref(),
+        Some("Your answer must follow these rules:\n- Answer in German.\n- Use at most 50 words.")
+    );
+    assert_eq!(
+        ModelOptions::<GeminiModel>::new("gemini")
+            .with_constraints(Constraints::new())
+            .system_prompt(PromptStyle::Plain),
+        None
+    );
+}
+
+#[test]
+fn test_request_size_is_checked() {
+    let image = Part::Media {
+        media_type: MediaType::Image,
+        data: vec![0; 3000].into(),
+        mime_type: "image/png".to_string(),
+        uri: None,
+        finished: true,
+    };
+    let messages = vec![
+        Message::user("Compare these"),
+        Message::User(vec![image.clone(), Part::text("and"), image]),
+    ];
+    assert_eq!(messages[1].parts()[0].estimated_bytes(), 4000);
+
+    let transport = TransportOptions::new().with_max_request_bytes(5000);
+    match check_request_size(&transport, &messages) {
+        Err(ClientError::RequestTooLarge { size, limit, parts }) => {
+            assert_eq!(size, 8016);
+            assert_eq!(limit, 5000);
+            assert_eq!(
+                parts,
+                vec![PartIndex {
+                    message: 1,
+                    part: 0
+                }]
+            );
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let transport = TransportOptions::new().with_max_request_bytes(10_000);
+    assert!(check_request_size(&transport, &messages).is_ok());
+    assert!(check_request_size(&TransportOptions::new(), &messages).is_ok());
+}
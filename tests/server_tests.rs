@@ -0,0 +1,366 @@
+#![cfg(feature = "server")]
+
+use async_trait::async_trait;
+use futures::Stream;
+use rmcp::model::Tool;
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use unia::client::{Client, ClientError, StreamingClient};
+use unia::model::{Extensions, FinishReason, Message, Part, Response, Usage};
+use unia::options::{ModelOptions, TransportOptions};
+
+mod common;
+use common::snapshot;
+
+type Received = Arc<Mutex<Vec<(Vec<Message>, Vec<Tool>)>>>;
+
+/// Client answering every request with the same snapshots and recording what it received.
+struct ScriptedClient {
+    snapshots: Vec<Response>,
+    received: Received,
+    model_options: ModelOptions<()>,
+    transport_options: TransportOptions,
+}
+
+impl ScriptedClient {
+    fn new(snapshots: Vec<Response>) -> Self {
+        Self {
+            snapshots,
+            received: Arc::new(Mutex::new(Vec::new())),
+            model_options: ModelOptions::new("scripted-model".to_string()),
+            transport_options: TransportOptions::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Client for ScriptedClient {
+    type ModelProvider = ();
+
+    async fn request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Response, ClientError> {
+        self.received.lock().unwrap().push((messages, tools));
+        Ok(self.snapshots.last().cloned().unwrap())
+    }
+
+    fn model_options(&self) -> &ModelOptions<Self::ModelProvider> {
+        &self.model_options
+    }
+
+    fn transport_options(&self) -> &TransportOptions {
+        &self.transport_options
+    }
+}
+
+#[async_trait]
+impl StreamingClient for ScriptedClient {
+    async fn request_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Response, ClientError>> + Send>>, ClientError>
+    {
+        self.received.lock().unwrap().push((messages, tools));
+        Ok(Box::pin(futures::stream::iter(
+            self.snapshots.clone().into_iter().map(Ok),
+        )))
+    }
+}
+
+async fn serve(client: ScriptedClient) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, unia::server::router(client))
+            .await
+            .unwrap();
+    });
+    format!("http://{}", address)
+}
+
+#[tokio::test]
+async fn test_chat_completions() {
+    let client = ScriptedClient::new(vec![Response {
+        usage: Usage {
+            prompt_tokens: Some(5),
+            completion_tokens: Some(2),
+            ..Usage::default()
+        },
+        ..snapshot("Hi there", FinishReason::Stop)
+    }]);
+    let received = client.received.clone();
+    let base = serve(client).await;
+
+    let response: Value = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&json!({
+            "messages": [
+                { "role": "system", "content": "Be brief." },
+                { "role": "developer", "content": "Answer in English." },
+                { "role": "user", "content": "Hello" }
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the weather",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(response["object"], "chat.completion");
+    assert_eq!(response["model"], "scripted-model");
+    assert_eq!(response["choices"][0]["message"]["content"], "Hi there");
+    assert_eq!(response["choices"][0]["finish_reason"], "stop");
+    assert_eq!(response["usage"]["total_tokens"], 7);
+
+    let received = received.lock().unwrap();
+    let (messages, tools) = &received[0];
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        messages[0].parts(),
+        &[
+            Part::text("Be brief.\n\nAnswer in English."),
+            Part::text("Hello")
+        ]
+    );
+    assert_eq!(tools[0].name, "get_weather");
+}
+
+#[tokio::test]
+async fn test_chat_completions_stream() {
+    let client = ScriptedClient::new(vec![
+        snapshot("Hi", FinishReason::Unfinished),
+        snapshot("Hi there", FinishReason::Stop),
+    ]);
+    let base = serve(client).await;
+
+    let body = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&json!({
+            "model": "gateway",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "Hello" }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let events: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    assert_eq!(events.last(), Some(&"[DONE]"));
+
+    let chunks: Vec<Value> = events[..events.len() - 1]
+        .iter()
+        .map(|event| serde_json::from_str(event).unwrap())
+        .collect();
+    let content: String = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    assert_eq!(content, "Hi there");
+    assert_eq!(chunks[0]["model"], "gateway");
+    assert_eq!(
+        chunks.last().unwrap()["choices"][0]["finish_reason"],
+        "stop"
+    );
+}
+
+#[tokio::test]
+async fn test_anthropic_messages_with_tool_use() {
+    let client = ScriptedClient::new(vec![Response {
+        data: vec![Message::Assistant(vec![
+            Part::text("Checking."),
+            Part::FunctionCall {
+                id: Some("toolu_1".to_string()),
+                name: "get_weather".to_string(),
+                arguments: json!({ "city": "Paris" }),
+                signature: None,
+                repaired: false,
+                extensions: Extensions::new(),
+                finished: true,
+            },
+        ])],
+        usage: Usage::default(),
+        finish: FinishReason::ToolCalls,
+        stop_sequence: None,
+        service_tier: None,
+    }]);
+    let received = client.received.clone();
+    let base = serve(client).await;
+
+    let response: Value = reqwest::Client::new()
+        .post(format!("{}/v1/messages", base))
+        .json(&json!({
+            "model": "claude",
+            "max_tokens": 100,
+            "system": "Be brief.",
+            "messages": [{ "role": "user", "content": "Weather in Paris?" }],
+            "tools": [{
+                "name": "get_weather",
+                "input_schema": { "type": "object", "properties": {} }
+            }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(response["type"], "message");
+    assert_eq!(response["stop_reason"], "tool_use");
+    assert_eq!(response["content"][0]["text"], "Checking.");
+    assert_eq!(response["content"][1]["type"], "tool_use");
+    assert_eq!(response["content"][1]["input"], json!({ "city": "Paris" }));
+    let received = received.lock().unwrap();
+    let (messages, tools) = &received[0];
+    assert_eq!(messages[0].parts()[0], Part::text("Be brief."));
+    assert_eq!(tools[0].name, "get_weather");
+}
+
+#[tokio::test]
+async fn test_anthropic_messages_stream() {
+    let client = ScriptedClient::new(vec![
+        snapshot("Hi", FinishReason::Unfinished),
+        snapshot("Hi there", FinishReason::Stop),
+    ]);
+    let received = client.received.clone();
+    let base = serve(client).await;
+
+    let body = reqwest::Client::new()
+        .post(format!("{}/v1/messages", base))
+        .json(&json!({
+            "stream": true,
+            "system": [
+                { "type": "text", "text": "Be brief." },
+                { "type": "text", "text": "Answer in English." }
+            ],
+            "messages": [{ "role": "user", "content": "Hello" }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let events: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop"
+        ]
+    );
+    assert_eq!(
+        received.lock().unwrap()[0].0[0].parts()[0],
+        Part::text("Be brief.\n\nAnswer in English.")
+    );
+}
+
+#[tokio::test]
+async fn test_gemini_generate_content() {
+    let client = ScriptedClient::new(vec![snapshot("Hi there", FinishReason::Stop)]);
+    let received = client.received.clone();
+    let base = serve(client).await;
+
+    let response: Value = reqwest::Client::new()
+        .post(format!(
+            "{}/v1beta/models/gemini-2.5-flash:generateContent",
+            base
+        ))
+        .json(&json!({
+            "systemInstruction": { "parts": [{ "text": "Be brief." }] },
+            "contents": [{ "role": "user", "parts": [{ "text": "Hello" }] }],
+            "tools": [{
+                "functionDeclarations": [{
+                    "name": "get_weather",
+                    "parameters": { "type": "object", "properties": {} }
+                }]
+            }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response["candidates"][0]["content"]["parts"][0]["text"],
+        "Hi there"
+    );
+    assert_eq!(response["candidates"][0]["finishReason"], "STOP");
+    assert_eq!(response["modelVersion"], "gemini-2.5-flash");
+    let received = received.lock().unwrap();
+    let (messages, tools) = &received[0];
+    assert_eq!(
+        messages[0].parts(),
+        &[Part::text("Be brief."), Part::text("Hello")]
+    );
+    assert_eq!(tools[0].name, "get_weather");
+}
+
+#[tokio::test]
+async fn test_gemini_stream_generate_content() {
+    let client = ScriptedClient::new(vec![
+        snapshot("Hi", FinishReason::Unfinished),
+        snapshot("Hi there", FinishReason::Stop),
+    ]);
+    let base = serve(client).await;
+
+    let body = reqwest::Client::new()
+        .post(format!(
+            "{}/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse",
+            base
+        ))
+        .json(&json!({
+            "contents": [{ "role": "user", "parts": [{ "text": "Hello" }] }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let chunks: Vec<Value> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let content: String = chunks
+        .iter()
+        .filter_map(|chunk| chunk["candidates"][0]["content"]["parts"][0]["text"].as_str())
+        .collect();
+    assert_eq!(content, "Hi there");
+    assert_eq!(
+        chunks.last().unwrap()["candidates"][0]["finishReason"],
+        "STOP"
+    );
+}
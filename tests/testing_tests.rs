@@ -0,0 +1,113 @@
+#![cfg(feature = "testing")]
+
+use futures::StreamExt;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use unia::client::{Client, ClientError, StreamingClient};
+use unia::model::{FinishReason, Message, Part};
+use unia::options::{ModelOptions, TransportOptions};
+use unia::providers::{AnthropicClient, GeminiClient, OpenAIClient};
+use unia::testing::{MockProvider, MockProviderServer, MockReply};
+
+#[tokio::test]
+async fn test_mock_server_emulates_providers() {
+    let server = MockProviderServer::start(
+        MockProvider::new()
+            .with_reply(MockReply::text("Hello there"))
+            .with_reply(MockReply::tool_call(
+                "get_weather",
+                json!({ "city": "Paris" }),
+            ))
+            .with_reply(MockReply::text("Sunny")),
+    )
+    .await
+    .unwrap();
+
+    let openai = OpenAIClient::new(
+        "test-key".to_string(),
+        server.openai_url(),
+        ModelOptions::new("gpt-5"),
+        TransportOptions::default(),
+    );
+    let response = openai
+        .request(vec![Message::user("Hi")], vec![])
+        .await
+        .unwrap();
+    assert_eq!(response.data[0].content().as_deref(), Some("Hello there"));
+    assert_eq!(response.finish, FinishReason::Stop);
+
+    let anthropic = AnthropicClient::new(
+        "test-key".to_string(),
+        server.anthropic_url(),
+        ModelOptions::new("claude"),
+        TransportOptions::default(),
+    );
+    let response = anthropic
+        .request(vec![Message::user("Weather?")], vec![])
+        .await
+        .unwrap();
+    assert_eq!(response.finish, FinishReason::ToolCalls);
+    assert!(matches!(
+        &response.data[0].parts()[0],
+        Part::FunctionCall { name, .. } if name == "get_weather"
+    ));
+
+    let gemini = GeminiClient::new(
+        "test-key".to_string(),
+        server.gemini_url(),
+        ModelOptions::new("gemini"),
+        TransportOptions::default(),
+    );
+    let mut stream = gemini
+        .request_stream(vec![Message::user("And now?")], vec![])
+        .await
+        .unwrap();
+    let mut last = None;
+    while let Some(snapshot) = stream.next().await {
+        last = Some(snapshot.unwrap());
+    }
+    assert_eq!(last.unwrap().data[0].content().as_deref(), Some("Sunny"));
+
+    let requests = server.provider().requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].0, vec![Message::user("And now?")]);
+    assert_eq!(server.provider().remaining(), 0);
+}
+
+#[tokio::test]
+async fn test_mock_server_injects_errors_and_latency() {
+    let server = MockProviderServer::start(
+        MockProvider::new()
+            .with_latency(Duration::from_millis(50))
+            .with_reply(MockReply::error(429, "Slow down")),
+    )
+    .await
+    .unwrap();
+    let client = OpenAIClient::new(
+        "test-key".to_string(),
+        server.openai_url(),
+        ModelOptions::new("gpt-5"),
+        TransportOptions::default(),
+    );
+
+    let start = Instant::now();
+    let error = client
+        .request(vec![Message::user("Hi")], vec![])
+        .await
+        .unwrap_err();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(error.is_rate_limited(), "unexpected error: {:?}", error);
+
+    server.provider().push(MockReply::text("Better now"));
+    let response = client
+        .request(vec![Message::user("Hi")], vec![])
+        .await
+        .unwrap();
+    assert_eq!(response.data[0].content().as_deref(), Some("Better now"));
+
+    let error = client
+        .request(vec![Message::user("Hi")], vec![])
+        .await
+        .unwrap_err();
+    assert!(!matches!(error, ClientError::Config(_)));
+}